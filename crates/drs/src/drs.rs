@@ -29,6 +29,7 @@ use either::Either;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::prelude::*;
+use std::io::BufWriter;
 use std::path::Path;
 use std::io::Seek;
 use std::io::SeekFrom;
@@ -51,6 +52,10 @@ const SWBG_COPYRIGHT_EMPTY: SwbgCopyright = [0u8; SWBG_COPYRIGHT_LEN];
 
 type DrsCopyrightType = Either<AoeCopyright, SwbgCopyright>;
 
+const TABLE_HEADER_LEN: u32 = 12;
+const TABLE_ENTRY_LEN: u32 = 12;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum DrsGameType {
     /// Age of Empires
     AOE,
@@ -79,6 +84,29 @@ impl DrsHeader {
         }
     }
 
+    /// Creates the header that the original games put on their archives.
+    /// Table count and file offset are left at zero until the archive is built.
+    pub fn new(game_type: DrsGameType) -> DrsHeader {
+        let mut header = DrsHeader::empty();
+        match game_type {
+            DrsGameType::AOE => {
+                let mut copyright = AOE_COPYRIGHT_EMPTY;
+                copy_str(&mut copyright[..], EXPECTED_AOE_COPYRIGHT);
+                header.copyright_info = Either::Left(copyright);
+                copy_str(&mut header.file_version[..], EXPECTED_AOE_VERSION);
+                copy_str(&mut header.file_type[..], EXPECTED_AOE_TYPE);
+            }
+            DrsGameType::SWBG => {
+                let mut copyright = SWBG_COPYRIGHT_EMPTY;
+                copy_str(&mut copyright[..], EXPECTED_SWBG_COPYRIGHT);
+                header.copyright_info = Either::Right(copyright);
+                copy_str(&mut header.file_version[..], EXPECTED_SWBG_VERSION);
+                copy_str(&mut header.file_type[..], EXPECTED_SWBG_TYPE);
+            }
+        }
+        header
+    }
+
     pub fn game_type(&self) -> DrsGameType {
         match self.copyright_info {
             Either::Left(_) => DrsGameType::AOE,
@@ -86,9 +114,28 @@ impl DrsHeader {
         }
     }
 
-    // TODO: Implement writing
+    /// Size of the header in bytes (it differs between AOE and SWBG archives)
+    pub fn size(&self) -> u32 {
+        let copyright_len = match self.copyright_info {
+            Either::Left(_) => AOE_COPYRIGHT_LEN,
+            Either::Right(_) => SWBG_COPYRIGHT_LEN,
+        };
+        (copyright_len + self.file_version.len() + self.file_type.len() + 8) as u32
+    }
 
-    pub fn read_from_file(file: &mut File, file_name: &Path) -> Result<DrsHeader> {
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        match self.copyright_info {
+            Either::Left(ref copyright) => stream.write_all(&copyright[..])?,
+            Either::Right(ref copyright) => stream.write_all(&copyright[..])?,
+        }
+        stream.write_all(&self.file_version[..])?;
+        stream.write_all(&self.file_type[..])?;
+        write_u32(stream, self.table_count)?;
+        write_u32(stream, self.file_offset)?;
+        Ok(())
+    }
+
+    pub fn read_from_file<R: Read + Seek>(file: &mut R, file_name: &Path) -> Result<DrsHeader> {
         file.seek(SeekFrom::Start(64))?;
         let mut type_str_buf = [0u8; 4];
         file.read_exact(&mut type_str_buf)?;
@@ -183,6 +230,17 @@ impl From<u32> for DrsFileType {
     }
 }
 
+impl From<DrsFileType> for u32 {
+    fn from(file_type: DrsFileType) -> u32 {
+        match file_type {
+            DrsFileType::Binary => 0x62696E61,
+            DrsFileType::Slp => 0x736C7020,
+            DrsFileType::Shp => 0x73687020,
+            DrsFileType::Wav => 0x77617620,
+        }
+    }
+}
+
 pub struct DrsTableHeader {
     pub file_type: DrsFileType,
     pub table_offset: u32,
@@ -198,7 +256,12 @@ impl DrsTableHeader {
         }
     }
 
-    // TODO: Implement writing
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        write_u32(stream, self.file_type.into())?;
+        write_u32(stream, self.table_offset)?;
+        write_u32(stream, self.file_count)?;
+        Ok(())
+    }
 
     fn read_from_file<R: Read>(file: &mut R) -> Result<DrsTableHeader> {
         let mut header = DrsTableHeader::new();
@@ -234,7 +297,12 @@ impl DrsTableEntry {
         }
     }
 
-    // TODO: Implement writing
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        write_u32(stream, self.file_id)?;
+        write_u32(stream, self.file_offset)?;
+        write_u32(stream, self.file_size)?;
+        Ok(())
+    }
 
     fn read_from_file<R: Read>(file: &mut R) -> Result<DrsTableEntry> {
         let mut entry = DrsTableEntry::new();
@@ -276,7 +344,12 @@ impl DrsLogicalTable {
         }
     }
 
+    fn find_index(&self, file_id: u32) -> Option<usize> {
+        self.entries.iter().position(|e| e.file_id == file_id)
+    }
+
     fn populate_index_map(&mut self) {
+        self.index_map.clear();
         for i in 0..self.entries.len() {
             self.index_map.insert(self.entries[i].file_id, i);
         }
@@ -311,12 +384,16 @@ impl DrsFile {
     pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<DrsFile> {
         let file_name = file_name.as_ref();
         let mut file = File::open(file_name)?;
+        DrsFile::read_from(&mut file, file_name)
+    }
 
+    /// Loads a DRS archive from an arbitrary stream. The file name is only used for error reporting.
+    pub fn read_from<R: Read + Seek>(stream: &mut R, file_name: &Path) -> Result<DrsFile> {
         let mut drs_file = DrsFile::empty();
-        drs_file.header = DrsHeader::read_from_file(&mut file, file_name)?;
-        DrsFile::read_table_headers(&mut file, &mut drs_file)?;
-        DrsFile::read_file_entry_headers(&mut file, &mut drs_file)?;
-        DrsFile::read_file_contents(&mut file, &mut drs_file)?;
+        drs_file.header = DrsHeader::read_from_file(stream, file_name)?;
+        DrsFile::read_table_headers(stream, &mut drs_file)?;
+        DrsFile::read_file_entry_headers(stream, &mut drs_file)?;
+        DrsFile::read_file_contents(stream, &mut drs_file)?;
 
         for table in &mut drs_file.tables {
             table.populate_index_map();
@@ -325,6 +402,65 @@ impl DrsFile {
         Ok(drs_file)
     }
 
+    /// Writes the archive to the file system, replacing any existing file.
+    pub fn write_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let mut file = BufWriter::new(File::create(file_name.as_ref())?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Writes the archive exactly as described by its headers and entries: each table's entries
+    /// go at the table's offset and each file at its entry's offset, with any gaps between them
+    /// filled with zeros. Offsets are not recomputed here; use a `DrsBuilder` when the contents
+    /// have been changed.
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        if self.header.table_count as usize != self.tables.len() {
+            return Err(ErrorKind::InvalidLayout("table count doesn't match the tables").into());
+        }
+        self.header.write_to(stream)?;
+        for table in &self.tables {
+            table.header.write_to(stream)?;
+        }
+
+        let mut entry_blocks = Vec::new();
+        for table in &self.tables {
+            if table.header.file_count as usize != table.entries.len() ||
+               table.entries.len() != table.contents.len() {
+                return Err(ErrorKind::InvalidLayout("file count doesn't match the entries").into());
+            }
+            let mut bytes = Vec::new();
+            for entry in &table.entries {
+                entry.write_to(&mut bytes)?;
+            }
+            entry_blocks.push((table.header.table_offset, bytes));
+        }
+
+        let mut blocks: Vec<(u32, &[u8])> = entry_blocks.iter()
+            .map(|&(offset, ref bytes)| (offset, &bytes[..]))
+            .collect();
+        for table in &self.tables {
+            for (entry, contents) in table.entries.iter().zip(table.contents.iter()) {
+                if entry.file_size as usize != contents.len() {
+                    return Err(ErrorKind::InvalidLayout("file size doesn't match the contents").into());
+                }
+                blocks.push((entry.file_offset, &contents[..]));
+            }
+        }
+        blocks.sort_by_key(|&(offset, _)| offset);
+
+        let mut position = self.header.size() as u64 + (TABLE_HEADER_LEN * self.header.table_count) as u64;
+        for (offset, bytes) in blocks {
+            if (offset as u64) < position {
+                return Err(ErrorKind::InvalidLayout("overlapping tables or files").into());
+            }
+            stream.write_all(&vec![0u8; (offset as u64 - position) as usize])?;
+            stream.write_all(bytes)?;
+            position = offset as u64 + bytes.len() as u64;
+        }
+        Ok(())
+    }
+
     fn read_table_headers<R: Read>(file: &mut R, drs_file: &mut DrsFile) -> Result<()> {
         for table_index in 0..drs_file.header.table_count {
             drs_file.tables.push(DrsLogicalTable::new());
@@ -333,36 +469,259 @@ impl DrsFile {
         Ok(())
     }
 
-    fn read_file_entry_headers<R: Read>(file: &mut R, drs_file: &mut DrsFile) -> Result<()> {
-        for table_index in 0..drs_file.header.table_count {
-            for _file_index in 0..drs_file.tables[table_index as usize].header.file_count {
+    fn read_file_entry_headers<R: Read + Seek>(file: &mut R, drs_file: &mut DrsFile) -> Result<()> {
+        for table in &mut drs_file.tables {
+            file.seek(SeekFrom::Start(table.header.table_offset as u64))?;
+            for _file_index in 0..table.header.file_count {
                 let table_entry = DrsTableEntry::read_from_file(file)?;
-                drs_file.tables[table_index as usize].entries.push(table_entry);
+                table.entries.push(table_entry);
             }
         }
         Ok(())
     }
 
-    fn read_file_contents<R: Read>(file: &mut R, drs_file: &mut DrsFile) -> Result<()> {
-        for table_index in 0..drs_file.header.table_count {
-            let file_sizes: Vec<u32> = drs_file.tables[table_index as usize]
-                .entries
-                .iter()
-                .map(|e| e.file_size)
-                .collect();
-            for file_size in file_sizes {
-                let mut buffer = vec![0u8; file_size as usize];
+    fn read_file_contents<R: Read + Seek>(file: &mut R, drs_file: &mut DrsFile) -> Result<()> {
+        for table in &mut drs_file.tables {
+            for entry in &table.entries {
+                let mut buffer = vec![0u8; entry.file_size as usize];
+                file.seek(SeekFrom::Start(entry.file_offset as u64))?;
                 file.read_exact(&mut buffer[..])?;
-                drs_file.tables[table_index as usize].contents.push(buffer);
+                table.contents.push(buffer);
             }
         }
         Ok(())
     }
 }
 
+/// Assembles DRS archives, either from scratch or from an existing archive.
+/// Entries are kept sorted by file ID within their table (as they are in the original
+/// archives), and all offsets are recomputed when the archive is built.
+pub struct DrsBuilder {
+    header: DrsHeader,
+    tables: Vec<DrsLogicalTable>,
+}
+
+impl DrsBuilder {
+    pub fn new(game_type: DrsGameType) -> DrsBuilder {
+        DrsBuilder {
+            header: DrsHeader::new(game_type),
+            tables: Vec::new(),
+        }
+    }
+
+    /// Starts from an existing archive, keeping its header and table order.
+    pub fn from_drs(drs_file: DrsFile) -> DrsBuilder {
+        DrsBuilder {
+            header: drs_file.header,
+            tables: drs_file.tables,
+        }
+    }
+
+    /// Adds a new file to the table of the given type, creating the table if necessary.
+    pub fn add_file(&mut self, file_type: DrsFileType, file_id: u32, contents: DrsFileContents) -> Result<()> {
+        let table_index = match self.tables.iter().position(|t| t.header.file_type == file_type) {
+            Some(index) => index,
+            None => {
+                let mut table = DrsLogicalTable::new();
+                table.header.file_type = file_type;
+                self.tables.push(table);
+                self.tables.len() - 1
+            }
+        };
+
+        let table = &mut self.tables[table_index];
+        if table.find_index(file_id).is_some() {
+            return Err(ErrorKind::DuplicateFile(file_type, file_id).into());
+        }
+
+        let insert_at = table.entries.iter().position(|e| e.file_id > file_id).unwrap_or(table.entries.len());
+        let mut entry = DrsTableEntry::new();
+        entry.file_id = file_id;
+        table.entries.insert(insert_at, entry);
+        table.contents.insert(insert_at, contents);
+        Ok(())
+    }
+
+    /// Replaces the contents of an existing file, returning the previous contents.
+    pub fn replace_file(&mut self,
+                        file_type: DrsFileType,
+                        file_id: u32,
+                        contents: DrsFileContents)
+                        -> Result<DrsFileContents> {
+        let (table_index, index) = self.locate(file_type, file_id)?;
+        Ok(::std::mem::replace(&mut self.tables[table_index].contents[index], contents))
+    }
+
+    /// Removes a file, returning its contents. Tables left empty are removed as well.
+    pub fn remove_file(&mut self, file_type: DrsFileType, file_id: u32) -> Result<DrsFileContents> {
+        let (table_index, index) = self.locate(file_type, file_id)?;
+        let contents = {
+            let table = &mut self.tables[table_index];
+            table.entries.remove(index);
+            table.contents.remove(index)
+        };
+        if self.tables[table_index].entries.is_empty() {
+            self.tables.remove(table_index);
+        }
+        Ok(contents)
+    }
+
+    /// Lays out the archive: table headers, then every table's entries, then the contents
+    /// of every file in the same order as the entries.
+    pub fn build(self) -> Result<DrsFile> {
+        let DrsBuilder { mut header, mut tables } = self;
+
+        let entry_count: usize = tables.iter().map(|t| t.entries.len()).sum();
+        header.table_count = tables.len() as u32;
+        header.file_offset = header.size() + TABLE_HEADER_LEN * header.table_count +
+                             TABLE_ENTRY_LEN * entry_count as u32;
+
+        let mut table_offset = header.size() + TABLE_HEADER_LEN * header.table_count;
+        let mut file_offset = header.file_offset;
+        for table in &mut tables {
+            table.header.table_offset = table_offset;
+            table.header.file_count = table.entries.len() as u32;
+            table_offset += TABLE_ENTRY_LEN * table.header.file_count;
+
+            for (entry, contents) in table.entries.iter_mut().zip(table.contents.iter()) {
+                if contents.len() > (u32::max_value() - file_offset) as usize {
+                    return Err(ErrorKind::ArchiveTooLarge.into());
+                }
+                entry.file_offset = file_offset;
+                entry.file_size = contents.len() as u32;
+                file_offset += entry.file_size;
+            }
+            table.populate_index_map();
+        }
+
+        Ok(DrsFile {
+            header: header,
+            tables: tables,
+        })
+    }
+
+    fn locate(&self, file_type: DrsFileType, file_id: u32) -> Result<(usize, usize)> {
+        for (table_index, table) in self.tables.iter().enumerate() {
+            if table.header.file_type == file_type {
+                if let Some(index) = table.find_index(file_id) {
+                    return Ok((table_index, index));
+                }
+            }
+        }
+        Err(ErrorKind::FileNotFound(file_type, file_id).into())
+    }
+}
+
+fn write_u32<W: Write>(stream: &mut W, value: u32) -> Result<()> {
+    stream.write_all(&value.to_le_bytes())?;
+    Ok(())
+}
+
+fn copy_str(dest: &mut [u8], src: &'static str) {
+    dest[0..src.len()].copy_from_slice(src.as_bytes());
+}
+
 fn validate_str(file_name: &Path, bytes: &[u8], expected: &'static str) -> Result<()> {
     if bytes.len() < expected.len() || &bytes[0..expected.len()] != expected.as_bytes() {
         return Err(ErrorKind::InvalidDrs(file_name.into()).into());
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn build_archive(game_type: DrsGameType) -> DrsFile {
+        let mut builder = DrsBuilder::new(game_type);
+        builder.add_file(DrsFileType::Slp, 15001, vec![1, 2, 3]).unwrap();
+        builder.add_file(DrsFileType::Slp, 15000, vec![4, 5]).unwrap();
+        builder.add_file(DrsFileType::Wav, 5000, vec![6; 10]).unwrap();
+        builder.add_file(DrsFileType::Binary, 50500, vec![7; 4]).unwrap();
+        builder.build().unwrap()
+    }
+
+    fn round_trip(drs_file: &DrsFile) -> (Vec<u8>, DrsFile) {
+        let mut bytes = Vec::new();
+        drs_file.write_to(&mut bytes).unwrap();
+        let read = DrsFile::read_from(&mut Cursor::new(&bytes[..]), Path::new("test.drs")).unwrap();
+        (bytes, read)
+    }
+
+    #[test]
+    fn test_round_trip_is_byte_identical() {
+        for &game_type in &[DrsGameType::AOE, DrsGameType::SWBG] {
+            let (bytes, read) = round_trip(&build_archive(game_type));
+            assert_eq!(game_type, read.header.game_type());
+
+            let mut rewritten = Vec::new();
+            read.write_to(&mut rewritten).unwrap();
+            assert_eq!(bytes, rewritten);
+        }
+    }
+
+    #[test]
+    fn test_round_trip_keeps_offsets() {
+        // Pad the archive out so that every file sits a little further along than the builder put it
+        let mut drs_file = build_archive(DrsGameType::AOE);
+        drs_file.header.file_offset += 4;
+        let mut padding = 4;
+        for table in &mut drs_file.tables {
+            for entry in &mut table.entries {
+                entry.file_offset += padding;
+                padding += 2;
+            }
+        }
+
+        let (bytes, read) = round_trip(&drs_file);
+        let slp_table = read.find_table(DrsFileType::Slp).unwrap();
+        let offset = slp_table.entries[1].file_offset as usize;
+        assert_eq!(&[0, 0, 1, 2, 3][..], &bytes[offset - 2..offset + 3]);
+        assert_eq!(Some(&vec![1, 2, 3]), slp_table.find_file_contents(15001));
+        assert_eq!(Some(&vec![7; 4]), read.find_table(DrsFileType::Binary).unwrap().find_file_contents(50500));
+
+        let mut rewritten = Vec::new();
+        read.write_to(&mut rewritten).unwrap();
+        assert_eq!(bytes, rewritten);
+
+        // Files can't be written on top of each other
+        drs_file.tables[0].entries[1].file_offset -= 4;
+        assert!(drs_file.write_to(&mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_builder_layout() {
+        let drs_file = build_archive(DrsGameType::AOE);
+        assert_eq!(3, drs_file.header.table_count);
+        assert_eq!(64 + 3 * 12 + 4 * 12, drs_file.header.file_offset);
+
+        let slp_table = drs_file.find_table(DrsFileType::Slp).unwrap();
+        assert_eq!(64 + 3 * 12, slp_table.header.table_offset);
+        assert_eq!(vec![15000, 15001], slp_table.entries.iter().map(|e| e.file_id).collect::<Vec<_>>());
+        assert_eq!(drs_file.header.file_offset, slp_table.entries[0].file_offset);
+        assert_eq!(drs_file.header.file_offset + 2, slp_table.entries[1].file_offset);
+
+        let (_, read) = round_trip(&drs_file);
+        assert_eq!(Some(&vec![1, 2, 3]), read.find_table(DrsFileType::Slp).unwrap().find_file_contents(15001));
+        assert_eq!(Some(&vec![6; 10]), read.find_table(DrsFileType::Wav).unwrap().find_file_contents(5000));
+    }
+
+    #[test]
+    fn test_builder_replace_and_remove() {
+        let mut builder = DrsBuilder::from_drs(build_archive(DrsGameType::AOE));
+        assert!(builder.add_file(DrsFileType::Slp, 15000, vec![0]).is_err());
+        assert_eq!(vec![4, 5], builder.replace_file(DrsFileType::Slp, 15000, vec![9; 8]).unwrap());
+        assert_eq!(vec![7; 4], builder.remove_file(DrsFileType::Binary, 50500).unwrap());
+        assert!(builder.remove_file(DrsFileType::Binary, 50500).is_err());
+
+        let drs_file = builder.build().unwrap();
+        assert_eq!(2, drs_file.header.table_count);
+        assert!(drs_file.find_table(DrsFileType::Binary).is_none());
+
+        let (_, read) = round_trip(&drs_file);
+        let slp_table = read.find_table(DrsFileType::Slp).unwrap();
+        assert_eq!(Some(&vec![9; 8]), slp_table.find_file_contents(15000));
+        assert_eq!(8, slp_table.entries[0].file_size);
+    }
+}
@@ -20,6 +20,8 @@
 // SOFTWARE.
 //
 
+use crate::drs::DrsFileType;
+
 use std::path::PathBuf;
 
 error_chain! {
@@ -40,5 +42,25 @@ error_chain! {
             description("invalid DRS")
             display("Invalid DRS: {:?}", path)
         }
+
+        DuplicateFile(file_type: DrsFileType, file_id: u32) {
+            description("duplicate file in DRS table")
+            display("File {} already exists in the {:?} table", file_id, file_type)
+        }
+
+        FileNotFound(file_type: DrsFileType, file_id: u32) {
+            description("file not found in DRS table")
+            display("File {} does not exist in the {:?} table", file_id, file_type)
+        }
+
+        ArchiveTooLarge {
+            description("DRS archive too large")
+            display("DRS archive contents exceed the 4GiB limit of the format")
+        }
+
+        InvalidLayout(reason: &'static str) {
+            description("invalid DRS layout")
+            display("Invalid DRS layout: {}", reason)
+        }
     }
 }
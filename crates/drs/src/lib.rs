@@ -28,7 +28,9 @@ extern crate error_chain;
 mod drs;
 mod error;
 
+pub use drs::DrsBuilder;
 pub use drs::DrsFile;
 pub use drs::DrsFileType;
+pub use drs::DrsGameType;
 
 pub use error::{ChainErr, Error, ErrorKind, Result};
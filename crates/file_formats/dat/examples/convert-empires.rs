@@ -0,0 +1,89 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use chariot_dat as dat;
+use clap::{Arg, App};
+
+use std::fs;
+use std::path::Path;
+
+fn extension(file_name: &str) -> String {
+    Path::new(file_name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+fn read_db(file_name: &str) -> Result<dat::EmpiresDb, String> {
+    match extension(file_name).as_str() {
+        "ron" => {
+            let text = fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+            ron::de::from_str(&text).map_err(|e| e.to_string())
+        },
+        "json" => {
+            let text = fs::read_to_string(file_name).map_err(|e| e.to_string())?;
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        },
+        _ => dat::EmpiresDb::read_from_file(file_name).map_err(|e| e.to_string()),
+    }
+}
+
+fn write_db(db: &dat::EmpiresDb, file_name: &str) -> Result<(), String> {
+    match extension(file_name).as_str() {
+        "ron" => {
+            let text = ron::ser::to_string_pretty(db, Default::default()).map_err(|e| e.to_string())?;
+            fs::write(file_name, text).map_err(|e| e.to_string())
+        },
+        "json" => {
+            let text = serde_json::to_string_pretty(db).map_err(|e| e.to_string())?;
+            fs::write(file_name, text).map_err(|e| e.to_string())
+        },
+        _ => db.write_to_file(file_name).map_err(|e| e.to_string()),
+    }
+}
+
+fn main() {
+    let matches = App::new("convert-empires")
+        .version("1.0")
+        .author("Kevin Fuller <angered.ghandi@gmail.com>")
+        .about("Converts the empires.dat for Age of Empires (1997) to and from RON or JSON. \
+                The format is picked from the file extension (.ron, .json, or anything else for \
+                empires.dat). RON is recommended since JSON can't hold the NaN values \
+                some float fields may contain.")
+        .arg(Arg::with_name("INPUT")
+            .help("File to read")
+            .required(true)
+            .index(1))
+        .arg(Arg::with_name("OUTPUT")
+            .help("File to write")
+            .required(true)
+            .index(2))
+        .get_matches();
+
+    let input = matches.value_of("INPUT").unwrap();
+    let output = matches.value_of("OUTPUT").unwrap();
+    let result = read_db(input).and_then(|db| write_db(&db, output));
+    match result {
+        Ok(_) => println!("Converted {} to {}", input, output),
+        Err(err) => println!("Failed to convert {}: {}", input, err),
+    }
+}
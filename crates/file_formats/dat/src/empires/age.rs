@@ -21,14 +21,16 @@
 //
 
 use super::resource::ResourceType;
+use crate::empires::write_ext::WriteExt;
 use crate::error::Result;
 
 use identifier::{UnitClassId, AgeId, UnitId, ResearchId};
 use chariot_io_tools::{ReadExt, ReadArrayExt};
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnitAttributeId {
     HitPoints,
     LineOfSight,
@@ -74,14 +76,25 @@ impl UnitAttributeId {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResearchEffectValue {
     SetTo(f32),
     Add(f32),
     MultiplyBy(f32),
 }
 
-#[derive(Debug)]
+impl ResearchEffectValue {
+    /// The value after the effect is applied to it
+    pub fn apply(self, value: f32) -> f32 {
+        match self {
+            ResearchEffectValue::SetTo(amount) => amount,
+            ResearchEffectValue::Add(amount) => value + amount,
+            ResearchEffectValue::MultiplyBy(factor) => value * factor,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResearchEffect {
     UnitAttribute {
         target_unit_id: Option<UnitId>,
@@ -140,12 +153,36 @@ impl Default for ResearchEffect {
     }
 }
 
+/// An effect as it is stored in empires.dat. The meaning of the parameters
+/// depends on the type; use `decode` to interpret them.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ResearchEffectRecord {
+    pub type_id: i8,
+    pub param_a: i16,
+    pub param_b: i16,
+    pub param_c: i16,
+    pub param_d: f32,
+}
+
+impl ResearchEffectRecord {
+    pub fn decode(&self) -> ResearchEffect {
+        decode_effect(self)
+    }
+}
+
 // TODO: Rename to TechEffects, and then rename all children accordingly
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct ResearchEffectGroup {
     pub id: AgeId,
     pub name: String,
-    pub effects: Vec<ResearchEffect>,
+    pub effects: Vec<ResearchEffectRecord>,
+}
+
+impl ResearchEffectGroup {
+    /// Returns the interpreted effects
+    pub fn decoded_effects(&self) -> Vec<ResearchEffect> {
+        self.effects.iter().map(|e| e.decode()).collect()
+    }
 }
 
 pub fn read_ages<R: Read + Seek>(stream: &mut R) -> Result<Vec<ResearchEffectGroup>> {
@@ -166,16 +203,22 @@ pub fn read_age<R: Read + Seek>(stream: &mut R) -> Result<ResearchEffectGroup> {
     Ok(age)
 }
 
-fn read_age_effect<R: Read + Seek>(stream: &mut R) -> Result<ResearchEffect> {
-    let type_id = stream.read_i8()?;
-    let param_a = stream.read_i16()?;
-    let param_b = stream.read_i16()?;
-    let param_c = stream.read_i16()?;
-    let param_d = stream.read_f32()?;
+fn read_age_effect<R: Read + Seek>(stream: &mut R) -> Result<ResearchEffectRecord> {
+    let mut effect: ResearchEffectRecord = Default::default();
+    effect.type_id = stream.read_i8()?;
+    effect.param_a = stream.read_i16()?;
+    effect.param_b = stream.read_i16()?;
+    effect.param_c = stream.read_i16()?;
+    effect.param_d = stream.read_f32()?;
+    Ok(effect)
+}
+
+fn decode_effect(effect: &ResearchEffectRecord) -> ResearchEffect {
+    let ResearchEffectRecord { type_id, param_a, param_b, param_c, param_d } = *effect;
 
     use self::ResearchEffect::*;
     use self::ResearchEffectValue::*;
-    let result = match type_id {
+    match type_id {
         0 | 4 | 5 => {
             UnitAttribute {
                 target_unit_id: optional_id!(param_a),
@@ -244,7 +287,21 @@ fn read_age_effect<R: Read + Seek>(stream: &mut R) -> Result<ResearchEffect> {
                 param_d: param_d,
             }
         }
-    };
+    }
+}
 
-    Ok(result)
+pub fn write_ages<W: Write>(stream: &mut W, ages: &[ResearchEffectGroup]) -> Result<()> {
+    stream.write_u32(ages.len() as u32)?;
+    for age in ages {
+        stream.write_sized_str(&age.name, 31)?;
+        stream.write_u16(age.effects.len() as u16)?;
+        for effect in &age.effects {
+            stream.write_i8(effect.type_id)?;
+            stream.write_i16(effect.param_a)?;
+            stream.write_i16(effect.param_b)?;
+            stream.write_i16(effect.param_c)?;
+            stream.write_f32(effect.param_d)?;
+        }
+    }
+    Ok(())
 }
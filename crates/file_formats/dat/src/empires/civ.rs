@@ -21,44 +21,125 @@
 //
 
 use super::resource::ResourceType;
-use super::unit::{Unit, read_unit};
-use crate::error::Result;
+use super::unit::{Unit, read_unit, write_unit};
+use crate::empires::write_ext::WriteExt;
+use crate::error::{ErrorKind, Result};
 
 use identifier::{SoundGroupId, CivilizationId, AgeId, UnitId, ResearchId};
 use chariot_io_tools::{ReadArrayExt, ReadExt};
-use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
-#[derive(Default, Debug)]
+/// The four ages a civilization advances through
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Age {
+    Stone,
+    Tool,
+    Bronze,
+    Iron,
+}
+
+impl Age {
+    /// English name of the age, for when no localized name is available
+    pub fn default_name(self) -> &'static str {
+        match self {
+            Age::Stone => "Stone Age",
+            Age::Tool => "Tool Age",
+            Age::Bronze => "Bronze Age",
+            Age::Iron => "Iron Age",
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct CivilizationStartingValues {
+    /// Used to initialize unit attributes for the civ
+    age_id: Option<AgeId>,
+
+    /// As far as I can tell, AOE holds a massive blob of floating point data for each civ,
+    /// and it initializes that blob with whatever is in the file here. Several of the values
+    /// only make sense in the context of the game having been played for a while
+    /// (i.e., kill count). Others are useful for the start of the game, however, and
+    /// have accessors below.
+    values: Vec<f32>,
+}
+
+impl CivilizationStartingValues {
+    /// Used to initialize unit attributes for the civ
+    pub fn age_id(&self) -> Option<AgeId> {
+        self.age_id
+    }
+
+    /// Every starting value, in file order
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
     /// Starting resource values (for random map)
-    resources: BTreeMap<ResourceType, f32>,
+    pub fn resources(&self) -> BTreeMap<ResourceType, f32> {
+        let mut resources = BTreeMap::new();
+        resources.insert(ResourceType::Food, self.value(0));
+        resources.insert(ResourceType::Wood, self.value(1));
+        resources.insert(ResourceType::Stone, self.value(2));
+        resources.insert(ResourceType::Gold, self.value(3));
+        resources
+    }
 
     /// Multiplier for trade
-    trade_productivity: f32,
+    pub fn trade_productivity(&self) -> f32 {
+        self.value(10)
+    }
 
     /// Amount of food a farm provides; can increase with research
-    farm_food_capacity: f32,
+    pub fn farm_food_capacity(&self) -> f32 {
+        self.value(36)
+    }
 
     /// Amount of a tribute that is removed as a penalty; can decrease with research
-    tribute_penalty: f32,
+    pub fn tribute_penalty(&self) -> f32 {
+        self.value(46)
+    }
 
     /// Multiplier for gold mined; increases with research
-    gold_mine_productivity: f32,
-
-    /// Used to initialize unit attributes for the civ
-    age_id: Option<AgeId>,
+    pub fn gold_mine_productivity(&self) -> f32 {
+        self.value(47)
+    }
 
     /// If not starting in the default age, grant the given tech based on what the starting age is
-    tool_age_research_id: ResearchId,
-    bronze_age_research_id: ResearchId,
-    iron_age_research_id: ResearchId,
+    pub fn tool_age_research_id(&self) -> Option<ResearchId> {
+        optional_id!(self.value(25) as i32)
+    }
+
+    pub fn bronze_age_research_id(&self) -> Option<ResearchId> {
+        optional_id!(self.value(23) as i32)
+    }
+
+    pub fn iron_age_research_id(&self) -> Option<ResearchId> {
+        optional_id!(self.value(24) as i32)
+    }
+
+    /// The research that advances into the given age (the Stone Age has none)
+    pub fn age_research_id(&self, age: Age) -> Option<ResearchId> {
+        match age {
+            Age::Stone => None,
+            Age::Tool => self.tool_age_research_id(),
+            Age::Bronze => self.bronze_age_research_id(),
+            Age::Iron => self.iron_age_research_id(),
+        }
+    }
 
-    attack_warning_sound_id: SoundGroupId,
+    pub fn attack_warning_sound_id(&self) -> Option<SoundGroupId> {
+        optional_id!(self.value(26) as i32)
+    }
+
+    fn value(&self, index: usize) -> f32 {
+        self.values.get(index).cloned().unwrap_or(0f32)
+    }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Civilization {
     id: CivilizationId,
     enabled: bool,
@@ -69,15 +150,54 @@ pub struct Civilization {
     /// 0 => Egyption interface, 1 => Greek, 2 => Babylonian, 3 => Asiatic, 4 => Roman
     icon_set: i8,
 
-    units: HashMap<UnitId, Unit>,
+    units: BTreeMap<UnitId, Unit>,
+
+    /// Pointer table preceding the units; a zero means the unit slot is empty
+    unit_pointers: Vec<i32>,
 }
 
 impl Civilization {
+    #[inline]
+    pub fn id(&self) -> CivilizationId {
+        self.id
+    }
+
+    /// Internal (English) name of the civilization
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
     /// Retrieve unit data by ID
     #[inline]
     pub fn unit<'a>(&'a self, unit_id: UnitId) -> &'a Unit {
         return &self.units[&unit_id];
     }
+
+    /// Every unit the civilization has, in unit ID order
+    pub fn units(&self) -> impl Iterator<Item = &Unit> {
+        self.units.values()
+    }
+
+    #[inline]
+    pub fn starting_values<'a>(&'a self) -> &'a CivilizationStartingValues {
+        &self.starting_values
+    }
+
+    /// A civilization with the given units that advances through the ages with the given
+    /// (tool, bronze, iron) research IDs, where -1 means none. Meant for tests, along with
+    /// `EmpiresDb::sample`.
+    pub fn sample(id: CivilizationId, name: &str, units: Vec<Unit>, age_research_ids: [i32; 3]) -> Civilization {
+        let mut civ: Civilization = Default::default();
+        civ.id = id;
+        civ.name = name.into();
+        civ.starting_values.values = vec![0.0; 26];
+        civ.starting_values.values[25] = age_research_ids[0] as f32;
+        civ.starting_values.values[23] = age_research_ids[1] as f32;
+        civ.starting_values.values[24] = age_research_ids[2] as f32;
+        civ.units = units.into_iter().map(|unit| (unit.id, unit)).collect();
+        civ
+    }
 }
 
 pub fn read_civs<R: Read + Seek>(stream: &mut R) -> Result<Vec<Civilization>> {
@@ -97,37 +217,63 @@ fn read_civ<R: Read + Seek>(stream: &mut R) -> Result<Civilization> {
 
     let starting_value_count = stream.read_u16()? as usize;
     civ.starting_values.age_id = optional_id!(stream.read_i16()?);
-
-    // As far as I can tell, AOE holds a massive blob of floating point data for each civ,
-    // and it initializes that blob with whatever is in the file here. Several of the values
-    // only make sense in the context of the game having been played for a while
-    // (i.e., kill count). Others are useful for the start of the game, however.
-    // Only save the values that make sense in the context of starting the game.
-    let starting_values = stream.read_array(starting_value_count, |c| c.read_f32())?;
-    civ.starting_values.resources.insert(ResourceType::Food, starting_values[0]);
-    civ.starting_values.resources.insert(ResourceType::Wood, starting_values[1]);
-    civ.starting_values.resources.insert(ResourceType::Stone, starting_values[2]);
-    civ.starting_values.resources.insert(ResourceType::Gold, starting_values[3]);
-    civ.starting_values.trade_productivity = starting_values[10];
-    civ.starting_values.farm_food_capacity = starting_values[36];
-    civ.starting_values.tribute_penalty = starting_values[46];
-    civ.starting_values.gold_mine_productivity = starting_values[47];
-    civ.starting_values.tool_age_research_id = required_id!(starting_values[25] as i32);
-    civ.starting_values.bronze_age_research_id = required_id!(starting_values[23] as i32);
-    civ.starting_values.iron_age_research_id = required_id!(starting_values[24] as i32);
-    civ.starting_values.attack_warning_sound_id = required_id!(starting_values[26] as i32);
+    civ.starting_values.values = stream.read_array(starting_value_count, |c| c.read_f32())?;
 
     civ.icon_set = stream.read_i8()?;
 
     let unit_count = stream.read_u16()? as usize;
-    let unit_pointers = stream.read_array(unit_count, |c| c.read_i32())?;
+    civ.unit_pointers = stream.read_array(unit_count, |c| c.read_i32())?;
     for i in 0..unit_count {
         // Similarly with graphics, units have an array of pointers that are meaningless
         // except that if one of them is zero, that unit has to be skipped
-        if unit_pointers[i] != 0 {
+        if civ.unit_pointers[i] != 0 {
             let unit = read_unit(stream)?;
             civ.units.insert(unit.id, unit);
         }
     }
     Ok(civ)
 }
+
+pub fn write_civs<W: Write>(stream: &mut W, civs: &[Civilization]) -> Result<()> {
+    stream.write_u16(civs.len() as u16)?;
+    for civ in civs {
+        write_civ(stream, civ)?;
+    }
+    Ok(())
+}
+
+fn write_civ<W: Write>(stream: &mut W, civ: &Civilization) -> Result<()> {
+    stream.write_u8(civ.enabled as u8)?;
+    stream.write_sized_str(&civ.name, 20)?;
+
+    stream.write_u16(civ.starting_values.values.len() as u16)?;
+    stream.write_i16(raw_optional_id!(civ.starting_values.age_id, i16))?;
+    for value in &civ.starting_values.values {
+        stream.write_f32(*value)?;
+    }
+
+    stream.write_i8(civ.icon_set)?;
+
+    // Units are stored in the slot matching their ID. Slots for units that were added
+    // since the file was read get a non-zero pointer, and slots without a unit get zero.
+    let slot_count = civ.units.keys().last().map(|id| **id as usize + 1).unwrap_or(0);
+    let slot_count = slot_count.max(civ.unit_pointers.len());
+    if slot_count > u16::MAX as usize {
+        return Err(ErrorKind::InvalidDb("too many units").into());
+    }
+
+    stream.write_u16(slot_count as u16)?;
+    for slot in 0..slot_count {
+        let unit_id: UnitId = slot.into();
+        let pointer = if civ.units.contains_key(&unit_id) {
+            civ.unit_pointers.get(slot).cloned().filter(|p| *p != 0).unwrap_or(1)
+        } else {
+            0
+        };
+        stream.write_i32(pointer)?;
+    }
+    for unit in civ.units.values() {
+        write_unit(stream, unit)?;
+    }
+    Ok(())
+}
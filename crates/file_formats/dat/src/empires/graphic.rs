@@ -20,34 +20,39 @@
 // SOFTWARE.
 //
 
-use crate::error::Result;
+use crate::empires::write_ext::WriteExt;
+use crate::error::{ErrorKind, Result};
 
 use identifier::{SlpFileId, PlayerColorId, GraphicId, SoundGroupId};
 use chariot_io_tools::{ReadArrayExt, ReadExt};
-use std::io::SeekFrom;
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Read, Seek};
+use std::io::prelude::{Read, Seek, Write};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct GraphicAttackSound {
     pub sound_delay: i16,
-    pub sound_group_id: SoundGroupId,
+    pub sound_group_id: Option<SoundGroupId>,
 }
 
 /// Additional graphic to draw with a graphic
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct GraphicDelta {
     /// Graphic ID to draw
-    pub graphic_id: GraphicId,
+    pub graphic_id: Option<GraphicId>,
     /// X offset from parent graphic
     pub offset_x: i16,
     /// Y offset from parent graphic
     pub offset_y: i16,
     /// Appears to be unused in AOE 1
     display_angle: i16,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_bytes: [u8; 6],
+    unknown_i16: i16,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Graphic {
     pub id: GraphicId,
     pub name: String,
@@ -77,10 +82,13 @@ pub struct Graphic {
     /// that would be joined to this one
     pub deltas: Vec<GraphicDelta>,
 
+    /// Either empty, or three sounds per angle
     pub attack_sounds: Vec<GraphicAttackSound>,
+
+    unknown_i16: i16,
 }
 
-pub fn read_graphics<R: Read + Seek>(stream: &mut R) -> Result<Vec<Graphic>> {
+pub fn read_graphics<R: Read + Seek>(stream: &mut R) -> Result<(Vec<Graphic>, Vec<u32>)> {
     let mut graphics = Vec::new();
     let graphic_count = stream.read_u16()? as usize;
 
@@ -97,7 +105,7 @@ pub fn read_graphics<R: Read + Seek>(stream: &mut R) -> Result<Vec<Graphic>> {
         graphic.short_name = stream.read_sized_str(13)?;
         graphic.slp_id = optional_id!(stream.read_i32()?);
 
-        stream.seek(SeekFrom::Current(2))?; // skip 2 unknown bytes
+        graphic.unknown_i16 = stream.read_i16()?;
         graphic.layer = stream.read_u8()?;
 
         graphic.player_color_id = optional_id!(stream.read_i8()?);
@@ -116,44 +124,104 @@ pub fn read_graphics<R: Read + Seek>(stream: &mut R) -> Result<Vec<Graphic>> {
         graphic.sequence_type = stream.read_u8()?;
         graphic.id = required_id!(stream.read_i16()?);
         graphic.mirror_mode = stream.read_u8()?;
-        graphic.deltas = stream.read_array(delta_count, |c| read_delta(c))?
-            .into_iter()
-            .filter_map(|d| d)
-            .collect();
+        graphic.deltas = stream.read_array(delta_count, |c| read_delta(c))?;
 
         if attack_sound_used != 0 {
             // three sounds per angle
             let attack_sound_count = 3 * graphic.angle_count as usize;
-            graphic.attack_sounds = stream.read_array(attack_sound_count, |c| read_attack_sound(c))?
-                .into_iter()
-                .filter_map(|a| a)
-                .collect();
+            graphic.attack_sounds = stream.read_array(attack_sound_count, |c| read_attack_sound(c))?;
         }
         graphics.push(graphic);
     }
-    Ok(graphics)
+    Ok((graphics, graphic_pointers))
 }
 
-fn read_delta<R: Read + Seek>(stream: &mut R) -> Result<Option<GraphicDelta>> {
+fn read_delta<R: Read + Seek>(stream: &mut R) -> Result<GraphicDelta> {
     let mut delta: GraphicDelta = Default::default();
-    let graphic_id = optional_id!(stream.read_i16()?);
-    if graphic_id.is_some() {
-        delta.graphic_id = graphic_id.unwrap();
-    }
-    stream.seek(SeekFrom::Current(6))?; // skip unknown bytes
+    delta.graphic_id = optional_id!(stream.read_i16()?);
+    stream.read_exact(&mut delta.unknown_bytes)?;
     delta.offset_x = stream.read_i16()?;
     delta.offset_y = stream.read_i16()?;
     delta.display_angle = stream.read_i16()?;
-    stream.seek(SeekFrom::Current(2))?; // skip unknown bytes
-    Ok(if graphic_id.is_some() { Some(delta) } else { None })
+    delta.unknown_i16 = stream.read_i16()?;
+    Ok(delta)
 }
 
-fn read_attack_sound<R: Read>(stream: &mut R) -> Result<Option<GraphicAttackSound>> {
+fn read_attack_sound<R: Read>(stream: &mut R) -> Result<GraphicAttackSound> {
     let mut attack_sound: GraphicAttackSound = Default::default();
     attack_sound.sound_delay = stream.read_i16()?;
-    let sound_group_id = optional_id!(stream.read_i16()?);
-    if sound_group_id.is_some() {
-        attack_sound.sound_group_id = sound_group_id.unwrap();
+    attack_sound.sound_group_id = optional_id!(stream.read_i16()?);
+    Ok(attack_sound)
+}
+
+/// Writes the graphics along with the pointer table that precedes them. A graphic is
+/// expected for every non-zero pointer; graphics beyond those get a new pointer appended.
+pub fn write_graphics<W: Write>(stream: &mut W, graphics: &[Graphic], graphic_pointers: &[u32]) -> Result<()> {
+    let used_pointers = graphic_pointers.iter().filter(|p| **p != 0).count();
+    if used_pointers > graphics.len() {
+        return Err(ErrorKind::InvalidDb("fewer graphics than graphic pointers").into());
+    }
+
+    let mut pointers = graphic_pointers.to_vec();
+    pointers.extend((used_pointers..graphics.len()).map(|_| 1));
+    if pointers.len() > u16::MAX as usize {
+        return Err(ErrorKind::InvalidDb("too many graphics").into());
+    }
+
+    stream.write_u16(pointers.len() as u16)?;
+    for pointer in &pointers {
+        stream.write_u32(*pointer)?;
+    }
+    for graphic in graphics {
+        write_graphic(stream, graphic)?;
+    }
+    Ok(())
+}
+
+fn write_graphic<W: Write>(stream: &mut W, graphic: &Graphic) -> Result<()> {
+    if graphic.coordinates.len() != 4 {
+        return Err(ErrorKind::InvalidDb("graphic coordinates must have four values").into());
+    }
+    if !graphic.attack_sounds.is_empty() &&
+       graphic.attack_sounds.len() != 3 * graphic.angle_count as usize {
+        return Err(ErrorKind::InvalidDb("graphic needs three attack sounds per angle").into());
+    }
+
+    stream.write_sized_str(&graphic.name, 21)?;
+    stream.write_sized_str(&graphic.short_name, 13)?;
+    stream.write_i32(raw_optional_id!(graphic.slp_id, i32))?;
+    stream.write_i16(graphic.unknown_i16)?;
+    stream.write_u8(graphic.layer)?;
+    stream.write_i8(raw_optional_id!(graphic.player_color_id, i8))?;
+    stream.write_i8(raw_optional_id!(graphic.second_player_color_id, i8))?;
+    stream.write_u8(graphic.replay as u8)?;
+    for coordinate in &graphic.coordinates {
+        stream.write_u16(*coordinate)?;
+    }
+
+    stream.write_u16(graphic.deltas.len() as u16)?;
+    stream.write_i16(raw_optional_id!(graphic.sound_group_id, i16))?;
+    stream.write_u8(!graphic.attack_sounds.is_empty() as u8)?;
+    stream.write_u16(graphic.frame_count)?;
+    stream.write_u16(graphic.angle_count)?;
+    stream.write_f32(graphic.new_speed)?;
+    stream.write_f32(graphic.frame_rate)?;
+    stream.write_f32(graphic.replay_delay)?;
+    stream.write_u8(graphic.sequence_type)?;
+    stream.write_i16(*graphic.id as i16)?;
+    stream.write_u8(graphic.mirror_mode)?;
+
+    for delta in &graphic.deltas {
+        stream.write_i16(raw_optional_id!(delta.graphic_id, i16))?;
+        stream.write_all(&delta.unknown_bytes)?;
+        stream.write_i16(delta.offset_x)?;
+        stream.write_i16(delta.offset_y)?;
+        stream.write_i16(delta.display_angle)?;
+        stream.write_i16(delta.unknown_i16)?;
+    }
+    for attack_sound in &graphic.attack_sounds {
+        stream.write_i16(attack_sound.sound_delay)?;
+        stream.write_i16(raw_optional_id!(attack_sound.sound_group_id, i16))?;
     }
-    Ok(if sound_group_id.is_some() { Some(attack_sound) } else { None })
+    Ok(())
 }
@@ -0,0 +1,155 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use super::civ::Age;
+use super::EmpiresDb;
+
+use chariot_language::Language;
+use identifier::{CivilizationId, LocalizationId, ResearchId, UnitId};
+
+/// language.dll keeps the civilization names in a block of consecutive IDs, indexed by civilization ID
+const CIVILIZATION_NAME_BASE_ID: usize = 10230;
+
+/// Resolves the text that the player sees for game data, falling back to the internal
+/// (English) names from empires.dat when language.dll has no string for them
+#[derive(Copy, Clone)]
+pub struct LocalizedEmpires<'a> {
+    empires: &'a EmpiresDb,
+    language: &'a Language,
+}
+
+impl<'a> LocalizedEmpires<'a> {
+    pub fn new(empires: &'a EmpiresDb, language: &'a Language) -> LocalizedEmpires<'a> {
+        LocalizedEmpires {
+            empires,
+            language,
+        }
+    }
+
+    pub fn unit_name(&self, civilization_id: CivilizationId, unit_id: UnitId) -> &'a str {
+        let unit = self.empires.unit(civilization_id, unit_id);
+        self.text(unit.name_id()).unwrap_or(unit.name())
+    }
+
+    pub fn unit_help(&self, civilization_id: CivilizationId, unit_id: UnitId) -> Option<&'a str> {
+        self.text(self.empires.unit(civilization_id, unit_id).help_id())
+    }
+
+    pub fn research_name(&self, research_id: ResearchId) -> &'a str {
+        let research = self.empires.research(research_id);
+        self.text(Some(research.name_id)).unwrap_or(&research.name)
+    }
+
+    pub fn research_description(&self, research_id: ResearchId) -> Option<&'a str> {
+        self.text(Some(self.empires.research(research_id).description_id))
+    }
+
+    pub fn research_help(&self, research_id: ResearchId) -> Option<&'a str> {
+        self.text(self.empires.research(research_id).help_id)
+    }
+
+    pub fn civilization_name(&self, civilization_id: CivilizationId) -> &'a str {
+        let name_id = (CIVILIZATION_NAME_BASE_ID + *civilization_id as usize).into();
+        self.text(Some(name_id)).unwrap_or(self.empires.civilization(civilization_id).name())
+    }
+
+    /// Ages are named after the research that advances into them
+    pub fn age_name(&self, civilization_id: CivilizationId, age: Age) -> &'a str {
+        let civilization = self.empires.civilization(civilization_id);
+        match civilization.starting_values().age_research_id(age) {
+            Some(research_id) => self.research_name(research_id),
+            None => age.default_name(),
+        }
+    }
+
+    fn text(&self, id: Option<LocalizationId>) -> Option<&'a str> {
+        // Zero is used in place of a missing ID just as often as -1 is
+        id.filter(|id| **id != 0).and_then(|id| self.language.get(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::civ::Civilization;
+    use super::super::research::Research;
+    use super::super::unit::Unit;
+    use std::io;
+
+    fn sample_db() -> EmpiresDb {
+        let mut db = EmpiresDb::new();
+
+        let mut wheel: Research = Default::default();
+        wheel.name = "Wheel".into();
+        wheel.name_id = 4201.into();
+        wheel.description_id = 4202.into();
+        db.research.push(wheel);
+
+        let mut tool_age: Research = Default::default();
+        tool_age.name = "Tool Age".into();
+        tool_age.name_id = 4999.into();
+        db.research.push(tool_age);
+
+        let units = vec![
+            Unit::named(83.into(), "Villager", Some(5001.into()), Some(5002.into())),
+            Unit::named(299.into(), "Scout", None, None),
+        ];
+        db.civilizations.push(Civilization::sample(1.into(), "Egyptian", units, [1, -1, -1]));
+        db
+    }
+
+    fn sample_language() -> Language {
+        let table = "4201 \"La roue\"\n\
+                     5001 \"Villageois\"\n\
+                     5002 \"Construit et récolte\"\n\
+                     10231 \"Égyptiens\"\n";
+        Language::read_table_from_stream(&mut io::Cursor::new(table)).unwrap()
+    }
+
+    #[test]
+    fn test_localized_names() {
+        let db = sample_db();
+        let language = sample_language();
+        let localized = LocalizedEmpires::new(&db, &language);
+        let civ_id = 1.into();
+
+        assert_eq!("Villageois", localized.unit_name(civ_id, 83.into()));
+        assert_eq!(Some("Construit et récolte"), localized.unit_help(civ_id, 83.into()));
+        assert_eq!("La roue", localized.research_name(0.into()));
+        assert_eq!("Égyptiens", localized.civilization_name(civ_id));
+    }
+
+    #[test]
+    fn test_fallback_to_internal_names() {
+        let db = sample_db();
+        let language = sample_language();
+        let localized = LocalizedEmpires::new(&db, &language);
+        let civ_id = 1.into();
+
+        assert_eq!("Scout", localized.unit_name(civ_id, 299.into()));
+        assert_eq!(None, localized.unit_help(civ_id, 299.into()));
+        assert_eq!(None, localized.research_description(0.into()));
+        assert_eq!("Tool Age", localized.age_name(civ_id, Age::Tool));
+        assert_eq!("Stone Age", localized.age_name(civ_id, Age::Stone));
+        assert_eq!("Bronze Age", localized.age_name(civ_id, Age::Bronze));
+    }
+}
@@ -20,12 +20,15 @@
 // SOFTWARE.
 //
 
+#[macro_use]
+mod write_ext;
 #[macro_use]
 mod resource;
 
 mod age;
 mod civ;
 mod graphic;
+mod localized;
 mod player_color;
 mod random_map;
 mod research;
@@ -35,29 +38,40 @@ mod terrain_restrictions;
 mod unit;
 
 
-use age::{ResearchEffectGroup, read_ages};
-pub use civ::Civilization;
-use civ::read_civs;
-pub use graphic::Graphic;
-use graphic::read_graphics;
-use player_color::{PlayerColor, read_player_colors};
-use random_map::{RandomMap, read_random_maps};
-use research::{Research, read_research};
-use sound::{SoundEffectGroup, read_sound_effect_groups};
+pub use age::{ResearchEffect, ResearchEffectGroup, ResearchEffectRecord, ResearchEffectValue, UnitAttributeId};
+use age::{read_ages, write_ages};
+pub use civ::{Age, Civilization, CivilizationStartingValues};
+use civ::{read_civs, write_civs};
+pub use graphic::{Graphic, GraphicAttackSound};
+pub use localized::LocalizedEmpires;
+use graphic::{read_graphics, write_graphics};
+use player_color::{PlayerColor, read_player_colors, write_player_colors};
+use random_map::{RandomMap, read_random_maps, write_random_maps};
+pub use research::{Research, ResearchCost};
+use research::{read_research, write_research};
+pub use sound::{SoundEffect, SoundEffectGroup};
+use sound::{read_sound_effect_groups, write_sound_effect_groups};
 pub use terrain_block::Terrain;
 
 pub use terrain_block::TerrainBlock;
 pub use terrain_block::TerrainBorder;
-use terrain_block::read_terrain_block;
-use terrain_restrictions::{TerrainRestriction, read_terrain_restrictions};
-pub use unit::{InteractionMode, Unit};
+use terrain_block::{read_terrain_block, write_terrain_block};
+use terrain_restrictions::{TerrainRestriction, read_terrain_restrictions, write_terrain_restrictions};
+pub use resource::{ResourceCost, ResourceType};
+pub use unit::{BattleParams, BuildingParams, CommandableParams, DamageGraphic, InteractionMode, ProjectileParams,
+               TrainableParams, Unit, UnitCommand, UnitResourceCost};
+use write_ext::WriteExt;
 use crate::error::{ Result, ErrorKind };
 
 use identifier::{UnitTerrainRestrictionId, TerrainId, CivilizationId, ResearchId, TerrainBorderId, PlayerColorId, UnitId, GraphicId, SoundGroupId, AgeId};
 use chariot_io_tools::ReadExt;
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io;
-use std::io::prelude::{Read, Seek};
+use std::io::BufWriter;
+use std::io::prelude::{Read, Seek, Write};
 use std::path::Path;
 
 use std::sync::Arc;
@@ -66,7 +80,13 @@ const EXPECTED_FILE_VERSION: &'static str = "VER 3.7\0";
 
 /// Struct containing all of the game's information about terrain, civilizations,
 /// players, units, sounds, tech, and random map generation.
-#[derive(Default, Debug)]
+///
+/// The whole database can be serialized with serde for editing as text. Values the
+/// game keeps but Chariot doesn't understand yet are kept as well, so reading a file
+/// and writing it back out loses nothing. The one exception is filler in unused
+/// slots (resource costs, required techs, and terrain units), which is written with
+/// canonical values.
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct EmpiresDb {
     terrain_restrictions: Vec<TerrainRestriction>,
     player_colors: Vec<PlayerColor>,
@@ -77,6 +97,10 @@ pub struct EmpiresDb {
     ages: Vec<ResearchEffectGroup>,
     civilizations: Vec<Civilization>,
     research: Vec<Research>,
+
+    /// Pointer table preceding the graphics; a zero means the graphic slot is empty
+    graphic_pointers: Vec<u32>,
+    random_map_pointer: u32,
 }
 
 pub type EmpiresDbRef = Arc<EmpiresDb>;
@@ -86,6 +110,22 @@ impl EmpiresDb {
         Default::default()
     }
 
+    /// A database with nothing in it but the given civilizations, research and ages,
+    /// for testing game rules without the real data file. Units get the default terrain
+    /// restriction, which here lets them go anywhere on terrain 0.
+    pub fn sample(civilizations: Vec<Civilization>,
+                  research: Vec<Research>,
+                  ages: Vec<ResearchEffectGroup>)
+                  -> EmpiresDb {
+        EmpiresDb {
+            civilizations: civilizations,
+            research: research,
+            ages: ages,
+            terrain_restrictions: vec![TerrainRestriction::sample(Default::default(), 1)],
+            ..Default::default()
+        }
+    }
+
     /// Retrieve an age by ID
     #[inline]
     pub fn age<'a>(&'a self, age_id: AgeId) -> &'a ResearchEffectGroup {
@@ -154,6 +194,12 @@ impl EmpiresDb {
         &self.research[*research_id as usize]
     }
 
+    /// Every research, in research ID order
+    #[inline]
+    pub fn all_research(&self) -> &[Research] {
+        &self.research
+    }
+
     /// Retrieve a sound effect group by ID
     #[inline]
     pub fn sound_effect_group<'a>(&'a self, sound_group_id: SoundGroupId) -> &'a SoundEffectGroup {
@@ -162,8 +208,13 @@ impl EmpiresDb {
 
     /// Read all of the game data from the empires.dat file specified
     pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<EmpiresDb> {
-        let file = File::open(file_name.as_ref())?;
-        let mut stream = io::Cursor::new(file.read_and_decompress()?);
+        let mut file = File::open(file_name.as_ref())?;
+        EmpiresDb::read_from(&mut file)
+    }
+
+    /// Read all of the game data from a compressed empires.dat stream
+    pub fn read_from<R: Read>(stream: &mut R) -> Result<EmpiresDb> {
+        let mut stream = io::Cursor::new(stream.read_and_decompress()?);
 
         read_header(&mut stream)?;
         let terrain_restriction_count = stream.read_u16()? as usize;
@@ -175,15 +226,55 @@ impl EmpiresDb {
             read_terrain_restrictions(&mut stream, terrain_restriction_count, terrain_count)?;
         db.player_colors = read_player_colors(&mut stream)?;
         db.sound_effect_groups = read_sound_effect_groups(&mut stream)?;
-        db.graphics = read_graphics(&mut stream)?;
+        let (graphics, graphic_pointers) = read_graphics(&mut stream)?;
+        db.graphics = graphics;
+        db.graphic_pointers = graphic_pointers;
         db.terrain_block = read_terrain_block(&mut stream)?;
-        db.random_maps = read_random_maps(&mut stream)?;
+        let (random_maps, random_map_pointer) = read_random_maps(&mut stream)?;
+        db.random_maps = random_maps;
+        db.random_map_pointer = random_map_pointer;
         db.ages = read_ages(&mut stream)?;
         db.civilizations = read_civs(&mut stream)?;
         db.research = read_research(&mut stream)?;
 
         Ok(db)
     }
+
+    /// Write all of the game data to the empires.dat file specified
+    pub fn write_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let mut file = BufWriter::new(File::create(file_name.as_ref())?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Write all of the game data as a compressed empires.dat stream
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let mut data = Vec::new();
+        self.write_uncompressed(&mut data)?;
+
+        let mut encoder = DeflateEncoder::new(stream, Compression::default());
+        encoder.write_all(&data)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    fn write_uncompressed<W: Write>(&self, stream: &mut W) -> Result<()> {
+        stream.write_all(EXPECTED_FILE_VERSION.as_bytes())?;
+        stream.write_u16(self.terrain_restrictions.len() as u16)?;
+        stream.write_u16(terrain_restrictions::terrain_count(&self.terrain_restrictions)? as u16)?;
+
+        write_terrain_restrictions(stream, &self.terrain_restrictions)?;
+        write_player_colors(stream, &self.player_colors)?;
+        write_sound_effect_groups(stream, &self.sound_effect_groups)?;
+        write_graphics(stream, &self.graphics, &self.graphic_pointers)?;
+        write_terrain_block(stream, &self.terrain_block)?;
+        write_random_maps(stream, &self.random_maps, self.random_map_pointer)?;
+        write_ages(stream, &self.ages)?;
+        write_civs(stream, &self.civilizations)?;
+        write_research(stream, &self.research)?;
+        Ok(())
+    }
 }
 
 fn read_header<R: Read + Seek>(stream: &mut R) -> Result<()> {
@@ -195,3 +286,97 @@ fn read_header<R: Read + Seek>(stream: &mut R) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::age::ResearchEffectRecord;
+    use super::graphic::{GraphicAttackSound, GraphicDelta};
+    use super::sound::SoundEffect;
+
+    fn sample_db() -> EmpiresDb {
+        let mut db = EmpiresDb::new();
+        db.terrain_block = TerrainBlock::blank();
+
+        let mut color: PlayerColor = Default::default();
+        color.name = "Blue".into();
+        color.palette_index = 16;
+        db.player_colors.push(color);
+
+        let mut effect: SoundEffect = Default::default();
+        effect.file_name = "arrow.wav".into();
+        effect.resource_id = Some(5000.into());
+        effect.probability = 100;
+        let mut sound_group: SoundEffectGroup = Default::default();
+        sound_group.sound_effects.push(effect);
+        db.sound_effect_groups.push(sound_group);
+
+        let mut graphic: Graphic = Default::default();
+        graphic.name = "ARCHR_WALK".into();
+        graphic.slp_id = Some(418.into());
+        graphic.coordinates = vec![0; 4];
+        graphic.angle_count = 1;
+        graphic.frame_rate = 0.5;
+        let mut delta: GraphicDelta = Default::default();
+        delta.offset_x = -3;
+        graphic.deltas.push(delta);
+        graphic.attack_sounds = (0..3).map(|_| GraphicAttackSound::default()).collect();
+        db.graphics.push(graphic);
+
+        let mut age: ResearchEffectGroup = Default::default();
+        age.name = "Tool Age".into();
+        age.effects.push(ResearchEffectRecord {
+            type_id: 1,
+            param_a: 32,
+            param_b: 2,
+            param_c: 7,
+            param_d: 1.5,
+        });
+        db.ages.push(age);
+
+        let mut research: Research = Default::default();
+        research.name = "Wheel".into();
+        research.required_techs = vec![101];
+        db.research.push(research);
+        db.research.push(Default::default());
+
+        db.civilizations.push(Default::default());
+        db
+    }
+
+    fn to_bytes(db: &EmpiresDb) -> Vec<u8> {
+        let mut data = Vec::new();
+        db.write_to(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_empires_db_round_trip() {
+        let written = to_bytes(&sample_db());
+        let read = EmpiresDb::read_from(&mut io::Cursor::new(&written)).unwrap();
+        assert_eq!(1, read.graphics[0].deltas.len());
+        assert_eq!(3, read.graphics[0].attack_sounds.len());
+        assert_eq!(vec![101], read.research[0].required_techs);
+        assert_eq!(ResearchEffect::Unknown {
+                       type_id: 1,
+                       param_a: 32,
+                       param_b: 2,
+                       param_c: 7,
+                       param_d: 1.5,
+                   },
+                   read.ages[0].effects[0].decode());
+        assert_eq!(written, to_bytes(&read));
+    }
+
+    #[test]
+    fn test_empires_db_text_round_trip() {
+        let db = sample_db();
+        let text = ron::ser::to_string(&db).unwrap();
+        let from_ron: EmpiresDb = ron::de::from_str(&text).unwrap();
+        assert_eq!(to_bytes(&db), to_bytes(&from_ron));
+
+        let text = serde_json::to_string(&db).unwrap();
+        let from_json: EmpiresDb = serde_json::from_str(&text).unwrap();
+        assert_eq!(to_bytes(&db), to_bytes(&from_json));
+    }
+}
@@ -20,18 +20,24 @@
 // SOFTWARE.
 //
 
+use crate::empires::write_ext::WriteExt;
 use crate::error::Result;
 
 use identifier::PlayerColorId;
 use chariot_io_tools::ReadExt;
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct PlayerColor {
     pub id: PlayerColorId,
     pub name: String,
     pub palette_index: u8,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_u16: u16,
+    unknown_u8: u8,
 }
 
 pub fn read_player_colors<R: Read + Seek>(stream: &mut R) -> Result<Vec<PlayerColor>> {
@@ -42,13 +48,25 @@ pub fn read_player_colors<R: Read + Seek>(stream: &mut R) -> Result<Vec<PlayerCo
         let mut color: PlayerColor = Default::default();
         color.name = stream.read_sized_str(30)?;
         color.id = required_id!(stream.read_i16()?);
-        stream.read_u16()?; // unknown; skip
+        color.unknown_u16 = stream.read_u16()?;
 
         color.palette_index = stream.read_u8()?;
-        stream.read_u8()?; // unknown byte
+        color.unknown_u8 = stream.read_u8()?;
 
         player_colors.push(color);
     }
 
     Ok(player_colors)
 }
+
+pub fn write_player_colors<W: Write>(stream: &mut W, player_colors: &[PlayerColor]) -> Result<()> {
+    stream.write_u16(player_colors.len() as u16)?;
+    for color in player_colors {
+        stream.write_sized_str(&color.name, 30)?;
+        stream.write_i16(*color.id as i16)?;
+        stream.write_u16(color.unknown_u16)?;
+        stream.write_u8(color.palette_index)?;
+        stream.write_u8(color.unknown_u8)?;
+    }
+    Ok(())
+}
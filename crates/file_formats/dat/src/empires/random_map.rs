@@ -20,47 +20,41 @@
 // SOFTWARE.
 //
 
-use crate::error::Result;
+use crate::empires::write_ext::WriteExt;
+use crate::error::{ErrorKind, Result};
 
 use identifier::{RandomMapScriptId, TerrainId, UnitId};
 use chariot_io_tools::{ReadArrayExt, ReadExt};
-use std::io::SeekFrom;
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
-#[derive(Default, Debug)]
-pub struct RandomMapHeader {
-    script_id: RandomMapScriptId,
-    border_sw: i32,
-    border_nw: i32,
-    border_ne: i32,
-    border_se: i32,
-    border_usage: i32,
-    water_shape: i32,
-    non_base_terrain_id: TerrainId,
-    base_zone_coverage: i32,
-    base_zone_count: u32,
-    terrain_count: u32,
-    unit_count: u32,
-}
+/// Size of each entry in the unknown table at the end of a random map
+const UNKNOWN_ENTRY_LEN: usize = 24;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct BaseZone {
     base_terrain_id: TerrainId,
     space_between_players: i32,
     start_area_radius: i32,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_u32: u32,
+    unknown_bytes_a: [u8; 20],
+    unknown_bytes_b: [u8; 8],
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct MapTerrain {
     proportion: i32,
     terrain_id: TerrainId,
     clump_count: i32,
     spacing_to_other_terrains: i32,
     placement_zone: i32,
+    unknown_i32: i32,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct MapUnit {
     unit_id: UnitId,
     host_terrain_id: Option<TerrainId>,
@@ -72,10 +66,12 @@ pub struct MapUnit {
     set_place_for_all_players: i32,
     min_distance_to_players: i32,
     max_distance_to_players: i32,
+    unknown_i32: i32,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct RandomMap {
+    script_id: RandomMapScriptId,
     border_sw: i32,
     border_nw: i32,
     border_ne: i32,
@@ -87,30 +83,42 @@ pub struct RandomMap {
     base_zones: Vec<BaseZone>,
     terrains: Vec<MapTerrain>,
     units: Vec<MapUnit>,
+
+    // Pointers and unknown values from both the header and the map data,
+    // kept so that they can be written back out
+    unknown_i32: i32,
+    pointers: [u32; 4],
+    unknown_data: Vec<u8>,
+    header_unknown_i32: i32,
+    header_pointers: [i32; 4],
 }
 
-pub fn read_random_maps<R: Read + Seek>(stream: &mut R) -> Result<Vec<RandomMap>> {
+/// Returns the random maps and the random map pointer that precedes them
+pub fn read_random_maps<R: Read + Seek>(stream: &mut R) -> Result<(Vec<RandomMap>, u32)> {
     let mut random_maps = Vec::new();
 
     let random_map_count = stream.read_u32()? as usize;
-    stream.read_u32()?; // Unused: random map pointer
-    for _ in 0..random_map_count {
-        // Not certain how useful the header is since most of its information is
-        // repeated in the actual random map data; just drop it for now
-        read_random_map_header(stream)?;
-    }
-    for _ in 0..random_map_count {
-        random_maps.push(read_random_map(stream)?);
+    let random_map_pointer = stream.read_u32()?;
+
+    // Most of the header's information is repeated in the actual random map data,
+    // so only the parts that aren't get merged into the maps
+    let headers = stream.read_array(random_map_count, |c| read_random_map_header(c))?;
+    for (script_id, header_unknown_i32, header_pointers) in headers {
+        let mut map = read_random_map(stream)?;
+        map.script_id = script_id;
+        map.header_unknown_i32 = header_unknown_i32;
+        map.header_pointers = header_pointers;
+        random_maps.push(map);
     }
 
-    Ok(random_maps)
+    Ok((random_maps, random_map_pointer))
 }
 
 fn read_map_unit<R: Read>(stream: &mut R) -> Result<MapUnit> {
     let mut unit: MapUnit = Default::default();
     unit.unit_id = required_id!(stream.read_i32()?);
     unit.host_terrain_id = optional_id!(stream.read_i32()?);
-    stream.read_i32()?; // Unknown
+    unit.unknown_i32 = stream.read_i32()?;
     unit.objects_per_group = stream.read_i32()?;
     unit.fluctuation = stream.read_i32()?;
     unit.groups_per_player = stream.read_i32()?;
@@ -129,18 +137,18 @@ fn read_map_terrain<R: Read>(stream: &mut R) -> Result<MapTerrain> {
     terrain.clump_count = stream.read_i32()?;
     terrain.spacing_to_other_terrains = stream.read_i32()?;
     terrain.placement_zone = stream.read_i32()?;
-    stream.read_i32()?; // Unknown
+    terrain.unknown_i32 = stream.read_i32()?;
     Ok(terrain)
 }
 
 fn read_base_zone<R: Read + Seek>(stream: &mut R) -> Result<BaseZone> {
     let mut zone: BaseZone = Default::default();
-    stream.read_u32()?; // Unknown
+    zone.unknown_u32 = stream.read_u32()?;
     zone.base_terrain_id = required_id!(stream.read_i32()?);
     zone.space_between_players = stream.read_i32()?;
-    stream.seek(SeekFrom::Current(20))?; // 20 unknown bytes
+    stream.read_exact(&mut zone.unknown_bytes_a)?;
     zone.start_area_radius = stream.read_i32()?;
-    stream.seek(SeekFrom::Current(8))?; // 8 unknown bytes
+    stream.read_exact(&mut zone.unknown_bytes_b)?;
     Ok(zone)
 }
 
@@ -154,50 +162,136 @@ fn read_random_map<R: Read + Seek>(stream: &mut R) -> Result<RandomMap> {
     map.water_shape = stream.read_i32()?;
     map.non_base_terrain_id = required_id!(stream.read_i32()?);
     map.base_zone_coverage = stream.read_i32()?;
-    stream.read_i32()?; // Unknown
+    map.unknown_i32 = stream.read_i32()?;
 
     let base_zone_count = stream.read_u32()? as usize;
-    stream.read_u32()?; // Unused: Base zone pointer
+    map.pointers[0] = stream.read_u32()?;
     map.base_zones = stream.read_array(base_zone_count, |c| read_base_zone(c))?;
 
     let terrain_count = stream.read_u32()? as usize;
-    stream.read_u32()?; // Unused: Terrain pointer
+    map.pointers[1] = stream.read_u32()?;
     map.terrains = stream.read_array(terrain_count, |c| read_map_terrain(c))?;
 
     let unit_count = stream.read_u32()? as usize;
-    stream.read_u32()?; // Unused: Unit pointer
+    map.pointers[2] = stream.read_u32()?;
     map.units = stream.read_array(unit_count, |c| read_map_unit(c))?;
 
-    let unknown_count = stream.read_u32()? as i64;
-    stream.read_u32()?; // Unused: Unknown pointer
-    stream.seek(SeekFrom::Current(24 * unknown_count))?; // Skip unknown data
+    let unknown_count = stream.read_u32()? as usize;
+    map.pointers[3] = stream.read_u32()?;
+    map.unknown_data = vec![0u8; UNKNOWN_ENTRY_LEN * unknown_count];
+    stream.read_exact(&mut map.unknown_data)?;
 
     Ok(map)
 }
 
-fn read_random_map_header<R: Read + Seek>(stream: &mut R) -> Result<RandomMapHeader> {
-    let mut header: RandomMapHeader = Default::default();
-    header.script_id = required_id!(stream.read_i32()?);
-    header.border_sw = stream.read_i32()?;
-    header.border_nw = stream.read_i32()?;
-    header.border_ne = stream.read_i32()?;
-    header.border_se = stream.read_i32()?;
-    header.border_usage = stream.read_i32()?;
-    header.water_shape = stream.read_i32()?;
-    header.non_base_terrain_id = required_id!(stream.read_i32()?);
-    header.base_zone_coverage = stream.read_i32()?;
-    stream.read_i32()?; // Unknown
-
-    header.base_zone_count = stream.read_u32()?;
-    stream.read_i32()?; // Unused: Base zone pointer
-
-    header.terrain_count = stream.read_u32()?;
-    stream.read_i32()?; // Unused: Terrain pointer
-
-    header.unit_count = stream.read_u32()?;
-    stream.read_i32()?; // Unused: Unit pointer
-
-    stream.read_i32()?; // Unknown count
-    stream.read_i32()?; // Unused: unknown pointer
-    Ok(header)
+/// Returns the script ID, unknown value, and pointers from a random map header
+fn read_random_map_header<R: Read + Seek>(stream: &mut R) -> Result<(RandomMapScriptId, i32, [i32; 4])> {
+    let script_id = required_id!(stream.read_i32()?);
+    stream.read_array(8, |c| c.read_i32())?; // Repeated in the map data
+    let unknown = stream.read_i32()?;
+
+    let mut pointers = [0i32; 4];
+    for pointer in pointers.iter_mut() {
+        stream.read_u32()?; // Count; repeated in the map data
+        *pointer = stream.read_i32()?;
+    }
+    Ok((script_id, unknown, pointers))
+}
+
+pub fn write_random_maps<W: Write>(stream: &mut W,
+                                   random_maps: &[RandomMap],
+                                   random_map_pointer: u32)
+                                   -> Result<()> {
+    stream.write_u32(random_maps.len() as u32)?;
+    stream.write_u32(random_map_pointer)?;
+    for map in random_maps {
+        write_random_map_header(stream, map)?;
+    }
+    for map in random_maps {
+        write_random_map(stream, map)?;
+    }
+    Ok(())
+}
+
+fn write_map_settings<W: Write>(stream: &mut W, map: &RandomMap) -> Result<()> {
+    stream.write_i32(map.border_sw)?;
+    stream.write_i32(map.border_nw)?;
+    stream.write_i32(map.border_ne)?;
+    stream.write_i32(map.border_se)?;
+    stream.write_i32(map.border_usage)?;
+    stream.write_i32(map.water_shape)?;
+    stream.write_i32(*map.non_base_terrain_id as i32)?;
+    stream.write_i32(map.base_zone_coverage)?;
+    Ok(())
+}
+
+fn unknown_count(map: &RandomMap) -> Result<u32> {
+    if map.unknown_data.len() % UNKNOWN_ENTRY_LEN != 0 {
+        return Err(ErrorKind::InvalidDb("random map unknown data has a partial entry").into());
+    }
+    Ok((map.unknown_data.len() / UNKNOWN_ENTRY_LEN) as u32)
+}
+
+fn write_random_map_header<W: Write>(stream: &mut W, map: &RandomMap) -> Result<()> {
+    stream.write_i32(*map.script_id as i32)?;
+    write_map_settings(stream, map)?;
+    stream.write_i32(map.header_unknown_i32)?;
+
+    let counts = [map.base_zones.len() as u32,
+                  map.terrains.len() as u32,
+                  map.units.len() as u32,
+                  unknown_count(map)?];
+    for (count, pointer) in counts.iter().zip(map.header_pointers.iter()) {
+        stream.write_u32(*count)?;
+        stream.write_i32(*pointer)?;
+    }
+    Ok(())
+}
+
+fn write_random_map<W: Write>(stream: &mut W, map: &RandomMap) -> Result<()> {
+    write_map_settings(stream, map)?;
+    stream.write_i32(map.unknown_i32)?;
+
+    stream.write_u32(map.base_zones.len() as u32)?;
+    stream.write_u32(map.pointers[0])?;
+    for zone in &map.base_zones {
+        stream.write_u32(zone.unknown_u32)?;
+        stream.write_i32(*zone.base_terrain_id as i32)?;
+        stream.write_i32(zone.space_between_players)?;
+        stream.write_all(&zone.unknown_bytes_a)?;
+        stream.write_i32(zone.start_area_radius)?;
+        stream.write_all(&zone.unknown_bytes_b)?;
+    }
+
+    stream.write_u32(map.terrains.len() as u32)?;
+    stream.write_u32(map.pointers[1])?;
+    for terrain in &map.terrains {
+        stream.write_i32(terrain.proportion)?;
+        stream.write_i32(*terrain.terrain_id as i32)?;
+        stream.write_i32(terrain.clump_count)?;
+        stream.write_i32(terrain.spacing_to_other_terrains)?;
+        stream.write_i32(terrain.placement_zone)?;
+        stream.write_i32(terrain.unknown_i32)?;
+    }
+
+    stream.write_u32(map.units.len() as u32)?;
+    stream.write_u32(map.pointers[2])?;
+    for unit in &map.units {
+        stream.write_i32(*unit.unit_id as i32)?;
+        stream.write_i32(raw_optional_id!(unit.host_terrain_id, i32))?;
+        stream.write_i32(unit.unknown_i32)?;
+        stream.write_i32(unit.objects_per_group)?;
+        stream.write_i32(unit.fluctuation)?;
+        stream.write_i32(unit.groups_per_player)?;
+        stream.write_i32(unit.group_radius)?;
+        stream.write_i32(unit.own_at_start)?;
+        stream.write_i32(unit.set_place_for_all_players)?;
+        stream.write_i32(unit.min_distance_to_players)?;
+        stream.write_i32(unit.max_distance_to_players)?;
+    }
+
+    stream.write_u32(unknown_count(map)?)?;
+    stream.write_u32(map.pointers[3])?;
+    stream.write_all(&map.unknown_data)?;
+    Ok(())
 }
@@ -21,19 +21,21 @@
 
 use super::resource::{ResourceCost};
 
+use crate::empires::write_ext::WriteExt;
 use crate::error::{Result, ErrorKind};
 
 use identifier::{LocalizationId, AgeId, UnitId, ResearchId};
 use chariot_io_tools::{ReadExt, ReadArrayExt};
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
 const MAX_REQUIRED_TECHS: usize = 4;
 const RESOURCE_COST_COUNT: usize = 3;
 
 pub type ResearchCost = ResourceCost<i16, u8>;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Research {
     pub id: ResearchId,
     pub required_techs: Vec<i16>,
@@ -59,6 +61,8 @@ pub struct Research {
     pub help_id: Option<LocalizationId>,
     pub tech_tree_id: Option<LocalizationId>,
     pub name: String,
+
+    unknown_i32: i32,
 }
 
 pub fn read_research<R: Read + Seek>(stream: &mut R) -> Result<Vec<Research>> {
@@ -92,7 +96,7 @@ fn read_single_research<R: Read + Seek>(stream: &mut R) -> Result<Research> {
     research.button_id = stream.read_i8()?;
     research.help_id = optional_id!(stream.read_i32()?);
     research.tech_tree_id = optional_id!(stream.read_i32()?);
-    stream.read_i32()?; // Unknown
+    research.unknown_i32 = stream.read_i32()?;
 
     let name_length = stream.read_u16()? as usize;
     if name_length > 0 {
@@ -100,3 +104,45 @@ fn read_single_research<R: Read + Seek>(stream: &mut R) -> Result<Research> {
     }
     Ok(research)
 }
+
+pub fn write_research<W: Write>(stream: &mut W, research: &[Research]) -> Result<()> {
+    stream.write_u16(research.len() as u16)?;
+    for single_research in research {
+        write_single_research(stream, single_research)?;
+    }
+    Ok(())
+}
+
+fn write_single_research<W: Write>(stream: &mut W, research: &Research) -> Result<()> {
+    if research.required_techs.len() > MAX_REQUIRED_TECHS {
+        return Err(ErrorKind::InvalidDb("more required techs than possible").into());
+    }
+
+    // Unused required tech slots are filled with -1
+    for i in 0..MAX_REQUIRED_TECHS {
+        stream.write_i16(research.required_techs.get(i).cloned().unwrap_or(-1))?;
+    }
+    write_resource_costs!(i16, u8, &research.resource_costs, stream, RESOURCE_COST_COUNT);
+    stream.write_u16(research.required_techs.len() as u16)?;
+
+    stream.write_i16(raw_optional_id!(research.location, i16))?;
+    stream.write_i16(*research.name_id as i16)?;
+    stream.write_i16(*research.description_id as i16)?;
+    stream.write_i16(research.time_seconds)?;
+    stream.write_i16(raw_optional_id!(research.age_id, i16))?;
+    stream.write_i16(research.type_id)?;
+    stream.write_i16(research.icon_id)?;
+    stream.write_i8(research.button_id)?;
+    stream.write_i32(raw_optional_id!(research.help_id, i32))?;
+    stream.write_i32(raw_optional_id!(research.tech_tree_id, i32))?;
+    stream.write_i32(research.unknown_i32)?;
+
+    // Names are written null terminated, with the terminator counted in the length
+    if research.name.is_empty() {
+        stream.write_u16(0)?;
+    } else {
+        stream.write_u16(research.name.len() as u16 + 1)?;
+        stream.write_sized_str(&research.name, research.name.len() + 1)?;
+    }
+    Ok(())
+}
@@ -20,14 +20,18 @@
 // SOFTWARE.
 //
 
+use crate::empires::write_ext::WriteExt;
+use crate::error;
+
 use chariot_io_tools::ReadExt;
+use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::io::Error;
 
-use std::io::prelude::{Read};
+use std::io::prelude::{Read, Write};
 use std::marker::PhantomData;
 
-#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ResourceType {
     Food,
     Wood,
@@ -47,6 +51,17 @@ impl ResourceType {
             _ => Unknown(val),
         }
     }
+
+    pub fn to_i16(self) -> i16 {
+        use self::ResourceType::*;
+        match self {
+            Food => 0,
+            Wood => 1,
+            Stone => 2,
+            Gold => 3,
+            Unknown(val) => val,
+        }
+    }
 }
 
 impl Default for ResourceType {
@@ -59,14 +74,34 @@ pub trait ReadResourceCost {
     fn read_resource_cost(&mut self, stream: &mut dyn Read) -> Result<(), Error>;
 }
 
-#[derive(Default, Clone, Copy)]
+pub trait WriteResourceCost {
+    fn write_resource_cost(&self, stream: &mut dyn Write) -> error::Result<()>;
+
+    /// Writes the filler used for a cost slot that isn't in use
+    fn write_unused_resource_cost(stream: &mut dyn Write) -> error::Result<()>;
+}
+
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
 pub struct ResourceCost<T: Copy, E: Copy> {
     pub resource_type: ResourceType,
     pub amount: T,
     pub enabled: bool,
+    #[serde(skip)]
     phantom: PhantomData<E>,
 }
 
+impl<T: Copy, E: Copy> ResourceCost<T, E> {
+    /// An enabled cost of the given amount
+    pub fn new(resource_type: ResourceType, amount: T) -> ResourceCost<T, E> {
+        ResourceCost {
+            resource_type: resource_type,
+            amount: amount,
+            enabled: true,
+            phantom: PhantomData,
+        }
+    }
+}
+
 impl<T: Copy + fmt::Display, E: Copy> fmt::Debug for ResourceCost<T, E> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f,
@@ -104,6 +139,48 @@ impl ReadResourceCost for ResourceCost<f32, u8> {
     }
 }
 
+impl WriteResourceCost for ResourceCost<i16, u8> {
+    fn write_resource_cost(&self, stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(self.resource_type.to_i16())?;
+        stream.write_i16(self.amount)?;
+        stream.write_u8(self.enabled as u8)
+    }
+
+    fn write_unused_resource_cost(stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(-1)?;
+        stream.write_i16(0)?;
+        stream.write_u8(0)
+    }
+}
+
+impl WriteResourceCost for ResourceCost<i16, i16> {
+    fn write_resource_cost(&self, stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(self.resource_type.to_i16())?;
+        stream.write_i16(self.amount)?;
+        stream.write_i16(self.enabled as i16)
+    }
+
+    fn write_unused_resource_cost(stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(-1)?;
+        stream.write_i16(0)?;
+        stream.write_i16(0)
+    }
+}
+
+impl WriteResourceCost for ResourceCost<f32, u8> {
+    fn write_resource_cost(&self, stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(self.resource_type.to_i16())?;
+        stream.write_f32(self.amount)?;
+        stream.write_u8(self.enabled as u8)
+    }
+
+    fn write_unused_resource_cost(stream: &mut dyn Write) -> error::Result<()> {
+        stream.write_i16(-1)?;
+        stream.write_f32(0.0)?;
+        stream.write_u8(0)
+    }
+}
+
 #[macro_export]
 macro_rules! read_resource_costs {
     ($t:ty, $e:ty, $stream:expr, $num:expr) => {
@@ -121,3 +198,24 @@ macro_rules! read_resource_costs {
         }
     }
 }
+
+/// Writes the costs read by `read_resource_costs!` back out. Only enabled costs are
+/// kept when reading, so the remaining slots are filled with disabled costs.
+#[macro_export]
+macro_rules! write_resource_costs {
+    ($t:ty, $e:ty, $costs:expr, $stream:expr, $num:expr) => {
+        {
+            use crate::empires::resource::{ ResourceCost, WriteResourceCost };
+            let costs: &Vec<ResourceCost<$t, $e>> = $costs;
+            if costs.len() > $num {
+                return Err(crate::error::ErrorKind::InvalidDb("too many resource costs").into());
+            }
+            for cost in costs {
+                cost.write_resource_cost($stream)?;
+            }
+            for _ in costs.len()..$num {
+                <ResourceCost<$t, $e>>::write_unused_resource_cost($stream)?;
+            }
+        }
+    }
+}
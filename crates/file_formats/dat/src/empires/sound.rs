@@ -20,14 +20,16 @@
 // SOFTWARE.
 //
 
+use crate::empires::write_ext::WriteExt;
 use crate::error::Result;
 
 use identifier::{WavFileId, SoundGroupId};
 use chariot_io_tools::ReadExt;
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Read, Seek};
+use std::io::prelude::{Read, Seek, Write};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct SoundEffect {
     pub file_name: String,
 
@@ -37,7 +39,7 @@ pub struct SoundEffect {
     pub probability: u16,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct SoundEffectGroup {
     pub id: SoundGroupId,
     pub play_at_update_count: u16,
@@ -45,6 +47,26 @@ pub struct SoundEffectGroup {
     pub sound_effects: Vec<SoundEffect>,
 }
 
+impl SoundEffectGroup {
+    /// Picks one of the effects, weighted by probability. `roll` can be any random number;
+    /// it's reduced to the total probability of the group (usually 100).
+    pub fn choose_effect(&self, roll: u32) -> Option<&SoundEffect> {
+        let total: u32 = self.sound_effects.iter().map(|effect| effect.probability as u32).sum();
+        if total == 0 {
+            return self.sound_effects.first();
+        }
+
+        let mut remaining = roll % total;
+        for effect in &self.sound_effects {
+            if remaining < effect.probability as u32 {
+                return Some(effect);
+            }
+            remaining -= effect.probability as u32;
+        }
+        None
+    }
+}
+
 pub fn read_sound_effect_groups<R: Read + Seek>(stream: &mut R) -> Result<Vec<SoundEffectGroup>> {
     let mut sound_effect_groups = Vec::new();
 
@@ -69,3 +91,55 @@ pub fn read_sound_effect_groups<R: Read + Seek>(stream: &mut R) -> Result<Vec<So
 
     Ok(sound_effect_groups)
 }
+
+pub fn write_sound_effect_groups<W: Write>(stream: &mut W,
+                                           sound_effect_groups: &[SoundEffectGroup])
+                                           -> Result<()> {
+    stream.write_u16(sound_effect_groups.len() as u16)?;
+    for sound_group in sound_effect_groups {
+        stream.write_i16(*sound_group.id as i16)?;
+        stream.write_u16(sound_group.play_at_update_count)?;
+        stream.write_u16(sound_group.sound_effects.len() as u16)?;
+        stream.write_u32(sound_group.cache_time)?;
+
+        for effect in &sound_group.sound_effects {
+            stream.write_sized_str(&effect.file_name, 13)?;
+            stream.write_i32(raw_optional_id!(effect.resource_id, i32))?;
+            stream.write_u16(effect.probability)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(file_name: &str, probability: u16) -> SoundEffect {
+        SoundEffect {
+            file_name: file_name.into(),
+            resource_id: None,
+            probability: probability,
+        }
+    }
+
+    #[test]
+    fn test_choose_effect() {
+        let mut group: SoundEffectGroup = Default::default();
+        assert!(group.choose_effect(5).is_none());
+
+        group.sound_effects = vec![effect("a.wav", 25), effect("b.wav", 0), effect("c.wav", 75)];
+        assert_eq!("a.wav", group.choose_effect(0).unwrap().file_name);
+        assert_eq!("a.wav", group.choose_effect(24).unwrap().file_name);
+        assert_eq!("c.wav", group.choose_effect(25).unwrap().file_name);
+        assert_eq!("c.wav", group.choose_effect(99).unwrap().file_name);
+        assert_eq!("a.wav", group.choose_effect(100).unwrap().file_name);
+    }
+
+    #[test]
+    fn test_choose_effect_without_probabilities() {
+        let mut group: SoundEffectGroup = Default::default();
+        group.sound_effects = vec![effect("a.wav", 0), effect("b.wav", 0)];
+        assert_eq!("a.wav", group.choose_effect(7).unwrap().file_name);
+    }
+}
@@ -20,25 +20,29 @@
 // SOFTWARE.
 //
 
+use crate::empires::write_ext::WriteExt;
 use crate::error::{Result, ErrorKind};
 
 use identifier::{TerrainId, TerrainBorderId, SlpFrameId, SlpFileId, UnitId, SoundGroupId};
 use chariot_io_tools::{ReadArrayExt, ReadExt};
-use std::io::SeekFrom;
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Read, Seek};
+use std::io::prelude::{Read, Seek, Write};
 
 const TILE_TYPE_COUNT: usize = 19;
 const MAX_TERRAIN_UNITS: usize = 30;
+const TERRAIN_COUNT: usize = 32;
+const TERRAIN_BORDER_COUNT: usize = 16;
+const BORDER_FRAME_COUNT: usize = 12;
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TerrainFrameData {
     pub frame_count: i16,
     pub angle_count: i16,
     pub frame_id: SlpFrameId,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TerrainBorder {
     pub id: TerrainBorderId,
     pub enabled: bool,
@@ -62,16 +66,18 @@ pub struct TerrainBorder {
 
     pub border_style: i16,
     pub borders: Vec<Vec<TerrainFrameData>>,
+
+    unknown_u32: u32,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TerrainUnit {
     unit_id: UnitId,
     density: i16,
     priority: i8,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct Terrain {
     pub id: TerrainId,
     enabled: bool,
@@ -111,6 +117,10 @@ pub struct Terrain {
 
     /// Units that speckle this terrain (randomly)
     terrain_units: Vec<TerrainUnit>,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_u32: u32,
+    unknown_u16: u16,
 }
 
 impl Terrain {
@@ -121,14 +131,14 @@ impl Terrain {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TileSize {
     width: i16,
     height: i16,
     delta_y: i16,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TerrainBlock {
     map_width: i32,
     map_height: i32,
@@ -154,6 +164,14 @@ pub struct TerrainBlock {
     any_frame_change: i8,
     map_visible: bool,
     fog: bool,
+
+    // Pointers and unknown values, kept so that they can be written back out
+    map_pointer: i32,
+    unknown_i32: i32,
+    unknown_u16: u16,
+    unknown_pointer: i32,
+    unknown_pointers: [u32; 2],
+    unknown_bytes: [u8; 25],
 }
 
 impl TerrainBlock {
@@ -174,26 +192,49 @@ impl TerrainBlock {
     pub fn terrain_border<'a>(&'a self, terrain_border_id: TerrainBorderId) -> &'a TerrainBorder {
         &self.terrain_borders[*terrain_border_id as usize]
     }
+
+    /// A terrain block with every fixed size table filled in, so that it can be written
+    #[cfg(test)]
+    pub fn blank() -> TerrainBlock {
+        let mut terrain_block: TerrainBlock = Default::default();
+        terrain_block.tile_sizes = (0..TILE_TYPE_COUNT).map(|_| Default::default()).collect();
+        for i in 0..TERRAIN_COUNT {
+            let mut terrain: Terrain = Default::default();
+            terrain.id = i.into();
+            terrain.elevation_graphics = (0..TILE_TYPE_COUNT).map(|_| Default::default()).collect();
+            terrain.terrain_borders = (0..TERRAIN_COUNT).map(|_| Default::default()).collect();
+            terrain_block.terrains.push(terrain);
+        }
+        for i in 0..TERRAIN_BORDER_COUNT {
+            let mut border: TerrainBorder = Default::default();
+            border.id = i.into();
+            border.borders = (0..TILE_TYPE_COUNT)
+                .map(|_| (0..BORDER_FRAME_COUNT).map(|_| Default::default()).collect())
+                .collect();
+            terrain_block.terrain_borders.push(border);
+        }
+        terrain_block
+    }
 }
 
 pub fn read_terrain_block<R: Read + Seek>(stream: &mut R) -> Result<TerrainBlock> {
     let mut terrain_block: TerrainBlock = Default::default();
 
-    stream.read_i32()?; // Map pointer; not needed
-    stream.read_i32()?; // Unknown
+    terrain_block.map_pointer = stream.read_i32()?;
+    terrain_block.unknown_i32 = stream.read_i32()?;
     terrain_block.map_width = stream.read_i32()?;
     terrain_block.map_height = stream.read_i32()?;
     terrain_block.world_width = stream.read_i32()?;
     terrain_block.world_height = stream.read_i32()?;
 
     read_tile_sizes(&mut terrain_block, stream)?;
-    stream.read_u16()?; // Unknown
+    terrain_block.unknown_u16 = stream.read_u16()?;
 
     terrain_block.terrains = read_terrains(stream)?;
 
     read_terrain_borders(&mut terrain_block, stream)?;
 
-    stream.read_i32()?; // Unknown pointer
+    terrain_block.unknown_pointer = stream.read_i32()?;
     terrain_block.terrains_used = stream.read_u16()?;
     terrain_block.borders_used = stream.read_u16()?;
     terrain_block.max_terrain = stream.read_i16()?;
@@ -209,13 +250,13 @@ pub fn read_terrain_block<R: Read + Seek>(stream: &mut R) -> Result<TerrainBlock
     terrain_block.block_begin_col = stream.read_i16()?;
     terrain_block.block_end_col = stream.read_i16()?;
 
-    stream.read_u32()?; // Unknown pointer
-    stream.read_u32()?; // Unknown pointer
+    terrain_block.unknown_pointers[0] = stream.read_u32()?;
+    terrain_block.unknown_pointers[1] = stream.read_u32()?;
     terrain_block.any_frame_change = stream.read_i8()?;
     terrain_block.map_visible = stream.read_u8()? != 0;
     terrain_block.fog = stream.read_u8()? != 0;
 
-    stream.seek(SeekFrom::Current(25))?; // Skip 25 unknown bytes
+    stream.read_exact(&mut terrain_block.unknown_bytes)?;
     Ok(terrain_block)
 }
 
@@ -233,8 +274,7 @@ fn read_tile_sizes<R: Read + Seek>(terrain_block: &mut TerrainBlock, stream: &mu
 fn read_terrains<R: Read + Seek>(stream: &mut R) -> Result<Vec<Terrain>> {
     let mut terrains = Vec::new();
 
-    let terrain_count = 32;
-    for i in 0..terrain_count {
+    for i in 0..TERRAIN_COUNT {
         let mut terrain: Terrain = Default::default();
 
         terrain.id = i.into();
@@ -243,7 +283,7 @@ fn read_terrains<R: Read + Seek>(stream: &mut R) -> Result<Vec<Terrain>> {
         terrain.name = stream.read_sized_str(13)?;
         terrain.short_name = stream.read_sized_str(13)?;
         terrain.slp_id = optional_id!(stream.read_i32()?);
-        stream.read_u32()?; // Unknown
+        terrain.unknown_u32 = stream.read_u32()?;
         terrain.sound_group_id = optional_id!(stream.read_i32()?);
 
         for i in 0..3 {
@@ -272,12 +312,12 @@ fn read_terrains<R: Read + Seek>(stream: &mut R) -> Result<Vec<Terrain>> {
         terrain.terrain_width = stream.read_i16()?;
         terrain.terrain_height = stream.read_i16()?;
 
-        terrain.terrain_borders = stream.read_array(terrain_count, |c| -> Result<TerrainBorderId> {
+        terrain.terrain_borders = stream.read_array(TERRAIN_COUNT, |c| -> Result<TerrainBorderId> {
             Ok(required_id!(c.read_i16()?))
         })?;
 
         read_terrain_units(&mut terrain.terrain_units, stream)?;
-        stream.read_u16()?; // Unknown
+        terrain.unknown_u16 = stream.read_u16()?;
 
         terrains.push(terrain);
     }
@@ -313,8 +353,7 @@ fn read_frame_data<R: Read>(stream: &mut R) -> Result<TerrainFrameData> {
 }
 
 fn read_terrain_borders<R: Read + Seek>(terrain_block: &mut TerrainBlock, stream: &mut R) -> Result<()> {
-    let terrain_border_count = 16;
-    for i in 0..terrain_border_count {
+    for i in 0..TERRAIN_BORDER_COUNT {
         let mut border: TerrainBorder = Default::default();
 
         border.id = i.into();
@@ -323,7 +362,7 @@ fn read_terrain_borders<R: Read + Seek>(terrain_block: &mut TerrainBlock, stream
         border.name = stream.read_sized_str(13)?;
         border.short_name = stream.read_sized_str(13)?;
         border.slp_id = required_id!(stream.read_i32()?);
-        stream.read_u32()?; // Unknown
+        border.unknown_u32 = stream.read_u32()?;
         border.sound_group_id = optional_id!(stream.read_i32()?);
 
         for i in 0..3 {
@@ -342,12 +381,8 @@ fn read_terrain_borders<R: Read + Seek>(terrain_block: &mut TerrainBlock, stream
         stream.read_i8()?; // Unused; always zero
 
         border.borders = stream.read_array(TILE_TYPE_COUNT, |outer_stream| {
-            outer_stream.read_array(12, |inner_stream| read_frame_data(inner_stream))
+            outer_stream.read_array(BORDER_FRAME_COUNT, |inner_stream| read_frame_data(inner_stream))
         })?;
-        if !border.enabled {
-            // Don't bother saving all of this data if we're not enabled
-            border.borders.clear();
-        }
 
         stream.read_i16()?; // Unused; always zero
         border.underlay_terrain_id = optional_id!(stream.read_i16()?);
@@ -357,3 +392,181 @@ fn read_terrain_borders<R: Read + Seek>(terrain_block: &mut TerrainBlock, stream
     }
     Ok(())
 }
+
+pub fn write_terrain_block<W: Write>(stream: &mut W, terrain_block: &TerrainBlock) -> Result<()> {
+    if terrain_block.tile_sizes.len() != TILE_TYPE_COUNT {
+        return Err(ErrorKind::InvalidDb("unexpected number of tile sizes").into());
+    }
+    if terrain_block.terrains.len() != TERRAIN_COUNT {
+        return Err(ErrorKind::InvalidDb("unexpected number of terrains").into());
+    }
+    if terrain_block.terrain_borders.len() != TERRAIN_BORDER_COUNT {
+        return Err(ErrorKind::InvalidDb("unexpected number of terrain borders").into());
+    }
+
+    stream.write_i32(terrain_block.map_pointer)?;
+    stream.write_i32(terrain_block.unknown_i32)?;
+    stream.write_i32(terrain_block.map_width)?;
+    stream.write_i32(terrain_block.map_height)?;
+    stream.write_i32(terrain_block.world_width)?;
+    stream.write_i32(terrain_block.world_height)?;
+
+    for tile_size in &terrain_block.tile_sizes {
+        stream.write_i16(tile_size.width)?;
+        stream.write_i16(tile_size.height)?;
+        stream.write_i16(tile_size.delta_y)?;
+    }
+    stream.write_u16(terrain_block.unknown_u16)?;
+
+    for terrain in &terrain_block.terrains {
+        write_terrain(stream, terrain)?;
+    }
+    for border in &terrain_block.terrain_borders {
+        write_terrain_border(stream, border)?;
+    }
+
+    stream.write_i32(terrain_block.unknown_pointer)?;
+    stream.write_u16(terrain_block.terrains_used)?;
+    stream.write_u16(terrain_block.borders_used)?;
+    stream.write_i16(terrain_block.max_terrain)?;
+    stream.write_i16(terrain_block.tile_width)?;
+    stream.write_i16(terrain_block.tile_height)?;
+    stream.write_i16(terrain_block.tile_half_height)?;
+    stream.write_i16(terrain_block.tile_half_width)?;
+    stream.write_i16(terrain_block.elevation_height)?;
+    stream.write_i16(terrain_block.current_row)?;
+    stream.write_i16(terrain_block.current_col)?;
+    stream.write_i16(terrain_block.block_begin_row)?;
+    stream.write_i16(terrain_block.block_end_row)?;
+    stream.write_i16(terrain_block.block_begin_col)?;
+    stream.write_i16(terrain_block.block_end_col)?;
+
+    stream.write_u32(terrain_block.unknown_pointers[0])?;
+    stream.write_u32(terrain_block.unknown_pointers[1])?;
+    stream.write_i8(terrain_block.any_frame_change)?;
+    stream.write_u8(terrain_block.map_visible as u8)?;
+    stream.write_u8(terrain_block.fog as u8)?;
+    stream.write_all(&terrain_block.unknown_bytes)?;
+    Ok(())
+}
+
+fn write_terrain<W: Write>(stream: &mut W, terrain: &Terrain) -> Result<()> {
+    if terrain.elevation_graphics.len() != TILE_TYPE_COUNT {
+        return Err(ErrorKind::InvalidDb("unexpected number of terrain elevation graphics").into());
+    }
+    if terrain.terrain_borders.len() != TERRAIN_COUNT {
+        return Err(ErrorKind::InvalidDb("unexpected number of terrain border IDs").into());
+    }
+
+    stream.write_u8(terrain.enabled as u8)?;
+    stream.write_i8(0)?;
+    stream.write_sized_str(&terrain.name, 13)?;
+    stream.write_sized_str(&terrain.short_name, 13)?;
+    stream.write_i32(raw_optional_id!(terrain.slp_id, i32))?;
+    stream.write_u32(terrain.unknown_u32)?;
+    stream.write_i32(raw_optional_id!(terrain.sound_group_id, i32))?;
+    stream.write_all(&terrain.colors)?;
+    stream.write_all(&terrain.cliff_colors)?;
+    stream.write_i8(raw_optional_id!(terrain.pass_terrain_id, i8))?;
+    stream.write_i8(raw_optional_id!(terrain.impass_terrain_id, i8))?;
+
+    stream.write_u8(terrain.animated as u8)?;
+    stream.write_i16(terrain.animation_frames)?;
+    stream.write_i16(terrain.pause_frames)?;
+    stream.write_f32(terrain.frame_interval)?;
+    stream.write_f32(terrain.pause_between_loops)?;
+    stream.write_i16(terrain.frame)?;
+    stream.write_i16(terrain.draw_frame)?;
+    stream.write_f32(terrain.animate_last)?;
+    stream.write_i8(terrain.frame_changed)?;
+    stream.write_i8(0)?;
+
+    for frame_data in &terrain.elevation_graphics {
+        write_frame_data(stream, frame_data)?;
+    }
+
+    stream.write_i16(raw_optional_id!(terrain.terrain_to_draw, i16))?;
+    stream.write_i16(terrain.terrain_width)?;
+    stream.write_i16(terrain.terrain_height)?;
+    for terrain_border_id in &terrain.terrain_borders {
+        stream.write_i16(**terrain_border_id as i16)?;
+    }
+
+    write_terrain_units(stream, &terrain.terrain_units)?;
+    stream.write_u16(terrain.unknown_u16)?;
+    Ok(())
+}
+
+/// Unused slots are filled with -1 unit IDs and zeroed densities and priorities
+fn write_terrain_units<W: Write>(stream: &mut W, terrain_units: &[TerrainUnit]) -> Result<()> {
+    if terrain_units.len() > MAX_TERRAIN_UNITS {
+        return Err(ErrorKind::InvalidDb("invalid number of terrain units used").into());
+    }
+    let unused = MAX_TERRAIN_UNITS - terrain_units.len();
+
+    for unit in terrain_units {
+        stream.write_i16(*unit.unit_id as i16)?;
+    }
+    for _ in 0..unused {
+        stream.write_i16(-1)?;
+    }
+    for unit in terrain_units {
+        stream.write_i16(unit.density)?;
+    }
+    for _ in 0..unused {
+        stream.write_i16(0)?;
+    }
+    for unit in terrain_units {
+        stream.write_i8(unit.priority)?;
+    }
+    for _ in 0..unused {
+        stream.write_i8(0)?;
+    }
+    stream.write_i16(terrain_units.len() as i16)?;
+    Ok(())
+}
+
+fn write_frame_data<W: Write>(stream: &mut W, frame_data: &TerrainFrameData) -> Result<()> {
+    stream.write_i16(frame_data.frame_count)?;
+    stream.write_i16(frame_data.angle_count)?;
+    stream.write_i16(*frame_data.frame_id as i16)?;
+    Ok(())
+}
+
+fn write_terrain_border<W: Write>(stream: &mut W, border: &TerrainBorder) -> Result<()> {
+    if border.borders.len() != TILE_TYPE_COUNT ||
+       border.borders.iter().any(|frames| frames.len() != BORDER_FRAME_COUNT) {
+        return Err(ErrorKind::InvalidDb("unexpected number of terrain border frames").into());
+    }
+
+    stream.write_u8(border.enabled as u8)?;
+    stream.write_i8(0)?;
+    stream.write_sized_str(&border.name, 13)?;
+    stream.write_sized_str(&border.short_name, 13)?;
+    stream.write_i32(*border.slp_id as i32)?;
+    stream.write_u32(border.unknown_u32)?;
+    stream.write_i32(raw_optional_id!(border.sound_group_id, i32))?;
+    stream.write_all(&border.colors)?;
+
+    stream.write_u8(border.animated as u8)?;
+    stream.write_i16(border.animation_frames)?;
+    stream.write_i16(border.pause_frames)?;
+    stream.write_f32(border.frame_interval)?;
+    stream.write_f32(border.pause_between_loops)?;
+    stream.write_i16(border.frame)?;
+    stream.write_i16(border.draw_frame)?;
+    stream.write_f32(border.animate_last)?;
+    stream.write_i8(border.frame_changed)?;
+    stream.write_i8(0)?;
+
+    for frames in &border.borders {
+        for frame_data in frames {
+            write_frame_data(stream, frame_data)?;
+        }
+    }
+
+    stream.write_i16(0)?;
+    stream.write_i16(raw_optional_id!(border.underlay_terrain_id, i16))?;
+    stream.write_i16(border.border_style)?;
+    Ok(())
+}
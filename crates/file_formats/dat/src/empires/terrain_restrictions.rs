@@ -20,17 +20,22 @@
 // SOFTWARE.
 //
 
-use crate::error::Result;
+use crate::empires::write_ext::WriteExt;
+use crate::error::{ErrorKind, Result};
 
 use identifier::{TerrainId, UnitTerrainRestrictionId};
 use chariot_io_tools::{ReadExt, ReadArrayExt};
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct TerrainRestriction {
     pub id: UnitTerrainRestrictionId,
     passability_map: Vec<f32>,
+
+    /// Value from the pointer table preceding the restrictions
+    pointer: u32,
 }
 
 impl TerrainRestriction {
@@ -45,19 +50,29 @@ impl TerrainRestriction {
     pub fn passability(&self, terrain_id: TerrainId) -> f32 {
         self.passability_map[*terrain_id as usize]
     }
+
+    /// A restriction that lets units cross every one of the first `terrain_count` terrains,
+    /// for `EmpiresDb::sample`
+    pub fn sample(id: UnitTerrainRestrictionId, terrain_count: usize) -> TerrainRestriction {
+        TerrainRestriction {
+            id: id,
+            passability_map: vec![1.0; terrain_count],
+            pointer: 0,
+        }
+    }
 }
 
 pub fn read_terrain_restrictions<R: Read + Seek>(stream: &mut R,
                                                  terrain_restriction_count: usize,
                                                  terrain_count: usize)
                                                  -> Result<Vec<TerrainRestriction>> {
-    // Skip terrain restriction pointers
-    stream.read_array(terrain_restriction_count, |c| c.read_u32())?;
+    let pointers = stream.read_array(terrain_restriction_count, |c| c.read_u32())?;
 
     let mut restrictions: Vec<TerrainRestriction> = stream.read_array(terrain_restriction_count,
                                                   |c| read_terrain_restriction(c, terrain_count))?;
     for (index, terrain_restriction) in restrictions.iter_mut().enumerate() {
         terrain_restriction.id = UnitTerrainRestrictionId::from_index(index);
+        terrain_restriction.pointer = pointers[index];
     }
     Ok(restrictions)
 }
@@ -69,3 +84,27 @@ fn read_terrain_restriction<R: Read>(stream: &mut R, terrain_count: usize) -> Re
 
     Ok(restriction)
 }
+
+/// Returns the number of terrains the restrictions were written for, which the
+/// file header records alongside the restriction count
+pub fn terrain_count(restrictions: &[TerrainRestriction]) -> Result<usize> {
+    let terrain_count = restrictions.first().map(|r| r.passability_map.len()).unwrap_or(0);
+    if restrictions.iter().any(|r| r.passability_map.len() != terrain_count) {
+        return Err(ErrorKind::InvalidDb("terrain restrictions cover different terrain counts").into());
+    }
+    Ok(terrain_count)
+}
+
+pub fn write_terrain_restrictions<W: Write>(stream: &mut W,
+                                            restrictions: &[TerrainRestriction])
+                                            -> Result<()> {
+    for restriction in restrictions {
+        stream.write_u32(restriction.pointer)?;
+    }
+    for restriction in restrictions {
+        for passability in &restriction.passability_map {
+            stream.write_f32(*passability)?;
+        }
+    }
+    Ok(())
+}
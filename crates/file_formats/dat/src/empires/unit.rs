@@ -20,26 +20,37 @@
 // SOFTWARE.
 //
 
+use super::age::{ResearchEffectValue, UnitAttributeId};
 use super::resource::{ResourceCost};
+use crate::empires::write_ext::WriteExt;
 use crate::error::{Result, ErrorKind};
 
 use identifier::{LocalizationId, UnitCommandId, SoundGroupId, GraphicId, TerrainId, UnitId, ResearchId, UnitTerrainRestrictionId};
 use chariot_io_tools::{ReadExt, ReadArrayExt};
+use serde::{Deserialize, Serialize};
 
-use std::io::prelude::{Seek, Read};
+use std::io::prelude::{Seek, Read, Write};
 
 type UnitResourceStorage = ResourceCost<f32, u8>;
-type UnitResourceCost = ResourceCost<i16, i16>;
+pub type UnitResourceCost = ResourceCost<i16, i16>;
 
-#[derive(Default, Debug)]
+const RESOURCE_STORAGE_COUNT: usize = 3;
+const RESOURCE_COST_COUNT: usize = 3;
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct DamageGraphic {
-    graphic_id: GraphicId,
-    damage_percent: u8,
+    pub graphic_id: GraphicId,
+
+    /// The graphic is used once the unit's hit points are at or below this percent
+    pub damage_percent: u8,
+
     old_apply_mode: u8,
-    apply_mode: u8,
+
+    /// 0 and 1 draw the graphic over the unit's own; 2 replaces it
+    pub apply_mode: u8,
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum InteractionMode {
     NonInteracting,
     Flag,
@@ -66,9 +77,20 @@ impl InteractionMode {
             _ => Err(ErrorKind::InvalidInteractionMode(val).into()),
         }
     }
+
+    pub fn to_u8(self) -> u8 {
+        use self::InteractionMode::*;
+        match self {
+            NonInteracting => 0,
+            Flag => 1,
+            Resource => 2,
+            Building => 3,
+            Movable => 4,
+        }
+    }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum UnitType {
     GraphicEffect,
     Flag,
@@ -106,6 +128,22 @@ impl UnitType {
         }
     }
 
+    pub fn to_u8(self) -> u8 {
+        use self::UnitType::*;
+        match self {
+            GraphicEffect => 10,
+            Flag => 20,
+            Unknown25 => 25,
+            Moveable => 30,
+            Commandable => 40,
+            BattleReady => 50,
+            Projectile => 60,
+            Trainable => 70,
+            Building => 80,
+            Tree => 90,
+        }
+    }
+
     pub fn has_motion_params(&self) -> bool {
         use self::UnitType::*;
         match *self {
@@ -155,34 +193,55 @@ impl UnitType {
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct UnitCommand {
     id: UnitCommandId,
     enabled: bool,
-    type_id: i16,
-    class_id: i16,
-    unit_id: Option<UnitId>,
+
+    /// What kind of task this is; 5 is gathering
+    pub type_id: i16,
+
+    /// Class of units the command can be used on, if `unit_id` isn't set
+    pub class_id: i16,
+    pub unit_id: Option<UnitId>,
     terrain_id: Option<TerrainId>,
-    resource_in: i16,
+
+    /// Resource taken from the target
+    pub resource_in: i16,
     resource_productivity_multiplier: i16,
-    resource_out: i16,
+
+    /// Resource the unit ends up with
+    pub resource_out: i16,
     resource: i16,
-    quantity: f32,
-    execution_radius: f32,
+    pub quantity: f32,
+
+    /// How close the unit has to be to the target to carry out the command
+    pub execution_radius: f32,
     extra_range: f32,
     selection_enabler: i8,
     plunder_source: i16,
     selection_mode: i8,
     right_click_mode: i8,
     tool_graphic_id: Option<GraphicId>,
-    proceeding_graphic_id: Option<GraphicId>,
-    action_graphic_id: Option<GraphicId>,
-    carrying_graphic_id: Option<GraphicId>,
+
+    /// Graphic for walking to the target
+    pub proceeding_graphic_id: Option<GraphicId>,
+
+    /// Graphic for carrying out the command, like chopping a tree
+    pub action_graphic_id: Option<GraphicId>,
+
+    /// Graphic for walking around with what was gathered
+    pub carrying_graphic_id: Option<GraphicId>,
     execution_sound_id: Option<SoundGroupId>,
-    resource_deposit_sound_id: Option<SoundGroupId>,
+    pub resource_deposit_sound_id: Option<SoundGroupId>,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_bytes: [u8; 4],
+    unknown_f32: f32,
+    unknown_i16: i16,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct MotionParams {
     pub speed: f32,
     pub walking_graphics: [Option<GraphicId>; 2],
@@ -190,76 +249,144 @@ pub struct MotionParams {
     tracking_unit: Option<UnitId>,
     tracking_unit_used: bool,
     tracking_unit_density: f32,
+    unknown_bytes: [u8; 2],
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct CommandableParams {
     action_when_discovered_id: i16,
     search_radius: f32,
-    work_rate: f32,
-    drop_sites: [i16; 2],
+
+    /// Resources gathered per second
+    pub work_rate: f32,
+
+    /// Units that resources can be dropped off at, or -1
+    pub drop_sites: [i16; 2],
     task_swap_id: i8,
     attack_sound: i16,
     move_sound: i16,
     animal_mode: i8,
-    commands: Vec<UnitCommand>,
+    pub commands: Vec<UnitCommand>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct BattleParams {
-    default_armor: u8,
-    attacks: Vec<(i16, i16)>, // class, amount
-    armors: Vec<(i16, i16)>, // class, amount
+    pub default_armor: u8,
+    pub attacks: Vec<(i16, i16)>, // class, amount
+    pub armors: Vec<(i16, i16)>, // class, amount
     terrain_restriction_for_damage_multiplier: i16,
-    max_range: f32,
-    blast_width: f32,
-    reload_time: f32,
-    projectile_unit_id: Option<UnitId>,
-    accuracy_percent: i16,
+    pub max_range: f32,
+    pub blast_width: f32,
+    pub reload_time: f32,
+    pub projectile_unit_id: Option<UnitId>,
+    pub accuracy_percent: i16,
     tower_mode: i8,
     frame_delay: i16,
-    graphic_displacements: [f32; 3],
+    pub graphic_displacements: [f32; 3],
     blast_attack_level: i8,
-    min_range: f32,
-    attack_graphic_id: Option<GraphicId>,
+    pub min_range: f32,
+    pub attack_graphic_id: Option<GraphicId>,
     displayed_melee_armour: i16,
     displayed_attack: i16,
     displayed_range: f32,
     displayed_reload_time: f32,
 }
 
-#[derive(Default, Debug)]
+impl BattleParams {
+    fn apply_attribute(&mut self, attribute_id: UnitAttributeId, effect: ResearchEffectValue) {
+        match attribute_id {
+            UnitAttributeId::ArmorStrength => apply_to_class(&mut self.armors, effect),
+            UnitAttributeId::AttackStrength => apply_to_class(&mut self.attacks, effect),
+            UnitAttributeId::ReloadTime => self.reload_time = effect.apply(self.reload_time),
+            UnitAttributeId::AttackAccuracy => self.accuracy_percent = apply_rounded(effect, self.accuracy_percent),
+            UnitAttributeId::AttackRange => self.max_range = effect.apply(self.max_range),
+            UnitAttributeId::MissileUnitId => {
+                if let ResearchEffectValue::SetTo(unit_id) = effect {
+                    self.projectile_unit_id = optional_id!(unit_id as i16);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Applies an attack or armor effect to a list of (class, amount) pairs. The effect's value
+/// has the class in its high byte and the amount, which can be negative, in its low byte.
+fn apply_to_class(amounts: &mut [(i16, i16)], effect: ResearchEffectValue) {
+    let encoded = match effect {
+        ResearchEffectValue::SetTo(value) | ResearchEffectValue::Add(value) => value as i32,
+        ResearchEffectValue::MultiplyBy(_) => {
+            for amount in amounts.iter_mut() {
+                amount.1 = apply_rounded(effect, amount.1);
+            }
+            return;
+        }
+    };
+    let (class, amount) = ((encoded >> 8) as i16, (encoded as i8) as f32);
+    let effect = match effect {
+        ResearchEffectValue::SetTo(_) => ResearchEffectValue::SetTo(amount),
+        _ => ResearchEffectValue::Add(amount),
+    };
+    for entry in amounts.iter_mut().filter(|entry| entry.0 == class) {
+        entry.1 = apply_rounded(effect, entry.1);
+    }
+}
+
+fn apply_rounded(effect: ResearchEffectValue, value: i16) -> i16 {
+    effect.apply(value as f32).round() as i16
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct BuildingParams {
-    construction_graphic_id: GraphicId,
-    adjacent_mode: i8,
+    /// Shown while the building is under construction
+    pub construction_graphic_id: GraphicId,
+
+    /// Non-zero for buildings like walls that change their graphic to match the ones beside them
+    pub adjacent_mode: i8,
     graphics_angle: i16,
     disappears_when_built: bool,
-    stack_unit_id: Option<UnitId>,
-    foundation_terrain_id: Option<TerrainId>,
+
+    /// A second unit placed along with this one, like the roof of a town center
+    pub stack_unit_id: Option<UnitId>,
+
+    /// Terrain put down under the building when it's placed
+    pub foundation_terrain_id: Option<TerrainId>,
     old_terrain_id: Option<TerrainId>,
-    research_id: Option<ResearchId>,
-    construction_sound: i16,
+
+    /// Research the player gets when the building is finished, which later research can require
+    pub research_id: Option<ResearchId>,
+
+    /// Sound played when the building is placed
+    pub construction_sound_id: Option<SoundGroupId>,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct ProjectileParams {
     stretch_mode: i8,
     smart_mode: i8,
     drop_animation_mode: i8,
     penetration_mode: i8,
-    projectile_arc: f32,
+    pub projectile_arc: f32,
+    unknown_u8: u8,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct TrainableParams {
-    resource_costs: Vec<UnitResourceCost>,
-    train_time: i16,
-    train_location_id: Option<UnitId>,
-    button_id: i8,
+    /// What it costs to train (or build) the unit
+    pub resource_costs: Vec<UnitResourceCost>,
+
+    /// Seconds it takes to train the unit, or for one villager to build it
+    pub train_time: i16,
+
+    /// The unit that trains (or builds) this one
+    pub train_location_id: Option<UnitId>,
+
+    /// Where the unit's button goes in the trainer's command panel
+    pub button_id: i8,
     displayed_pierce_armor: i16,
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct Unit {
     pub id: UnitId,
 
@@ -270,7 +397,7 @@ pub struct Unit {
     name: String,
     name_id: Option<LocalizationId>,
     creation_id: LocalizationId,
-    class_id: i16,
+    pub class_id: i16,
     pub standing_graphic: Option<GraphicId>,
 
     /// Graphic IDs for when unit is dying; second one is never used
@@ -279,7 +406,7 @@ pub struct Unit {
     /// Always zero; use unknown
     death_mode: i8,
 
-    hit_points: i16,
+    pub hit_points: i16,
     line_of_sight: f32,
     garrison_capability: i8,
     pub collision_size_x: f32,
@@ -287,10 +414,10 @@ pub struct Unit {
     pub collision_size_z: f32,
 
     /// Sound played when unit is trained
-    train_sound_id: Option<SoundGroupId>,
+    pub train_sound_id: Option<SoundGroupId>,
 
     /// Replacement unit id for when the unit is dead and dying animation is completed
-    dead_unit_id: Option<UnitId>,
+    pub dead_unit_id: Option<UnitId>,
 
     /// 0 = unit can be placed on other units in the map editor, 5 = it can't
     placement_mode: i8,
@@ -323,8 +450,8 @@ pub struct Unit {
     pub terrain_restriction: UnitTerrainRestrictionId,
 
     fly_mode: bool,
-    resource_capacity: i16,
-    resource_decay: f32,
+    pub resource_capacity: i16,
+    pub resource_decay: f32,
 
     /// Unit would only be affected by a blast attack with the same or lower level
     blast_defense_level: i8,
@@ -348,21 +475,102 @@ pub struct Unit {
     pub selection_shape_size_y: f32,
     pub selection_shape_size_z: f32,
 
-    resource_storage: Vec<UnitResourceStorage>,
-    damage_graphics: Vec<DamageGraphic>,
+    pub resource_storage: Vec<UnitResourceStorage>,
+    pub damage_graphics: Vec<DamageGraphic>,
 
     selection_sound: i16,
-    dying_sound: i16,
+    pub dying_sound: i16,
     attack_mode: i8,
 
     id2: i16,
 
     pub motion_params: Option<MotionParams>,
-    commandable_params: Option<CommandableParams>,
-    battle_params: Option<BattleParams>,
-    projectile_params: Option<ProjectileParams>,
-    trainable_params: Option<TrainableParams>,
-    building_params: Option<BuildingParams>,
+    pub commandable_params: Option<CommandableParams>,
+    pub battle_params: Option<BattleParams>,
+    pub projectile_params: Option<ProjectileParams>,
+    pub trainable_params: Option<TrainableParams>,
+    pub building_params: Option<BuildingParams>,
+
+    // Unknown values, kept so that they can be written back out
+    unused_dying_graphic: i16,
+    unknown_u16: u16,
+    unknown_f32: f32,
+    unknown_u8: u8,
+
+    /// What may be the speed of flags and type 25 units, despite them not moving
+    unused_speed: f32,
+}
+
+impl Unit {
+    /// Internal (English) name of the unit
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn name_id(&self) -> Option<LocalizationId> {
+        self.name_id
+    }
+
+    #[inline]
+    pub fn help_id(&self) -> Option<LocalizationId> {
+        self.help_id
+    }
+
+    #[inline]
+    pub fn line_of_sight(&self) -> f32 {
+        self.line_of_sight
+    }
+
+    /// Applies a research effect to one of the unit's attributes. Attributes the unit doesn't
+    /// have (like the speed of a building) and the ones Chariot doesn't use are left alone.
+    ///
+    /// Attack and armor effects carry the class they change in the high byte of the amount,
+    /// and only change classes the unit already has. Multipliers change every class.
+    pub fn apply_attribute(&mut self, attribute_id: UnitAttributeId, effect: ResearchEffectValue) {
+        use self::UnitAttributeId::*;
+        match attribute_id {
+            HitPoints => self.hit_points = apply_rounded(effect, self.hit_points),
+            LineOfSight => self.line_of_sight = effect.apply(self.line_of_sight),
+            SizeRadius1 => self.collision_size_x = effect.apply(self.collision_size_x),
+            SizeRadius2 => self.collision_size_y = effect.apply(self.collision_size_y),
+            Speed => {
+                if let Some(ref mut params) = self.motion_params {
+                    params.speed = effect.apply(params.speed);
+                }
+            }
+            WorkRate => {
+                if let Some(ref mut params) = self.commandable_params {
+                    params.work_rate = effect.apply(params.work_rate);
+                }
+            }
+            ResourceCarryCapacity => self.resource_capacity = apply_rounded(effect, self.resource_capacity),
+            ResourceCost => {
+                if let Some(ref mut params) = self.trainable_params {
+                    for cost in params.resource_costs.iter_mut().filter(|cost| cost.enabled) {
+                        cost.amount = apply_rounded(effect, cost.amount);
+                    }
+                }
+            }
+            ArmorStrength | AttackStrength | ReloadTime | AttackAccuracy | AttackRange | MissileUnitId => {
+                if let Some(ref mut params) = self.battle_params {
+                    params.apply_attribute(attribute_id, effect);
+                }
+            }
+            BuildingUpgradeLevel | MissileAccuracyMode | Unknown(_) => {}
+        }
+    }
+
+    #[cfg(test)]
+    pub fn named(id: UnitId, name: &str, name_id: Option<LocalizationId>, help_id: Option<LocalizationId>) -> Unit {
+        let mut unit: Unit = Default::default();
+        unit.id = id;
+        unit.name = name.into();
+        unit.name_id = name_id;
+        unit.help_id = help_id;
+        unit
+    }
 }
 
 pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
@@ -376,7 +584,7 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
     unit.class_id = stream.read_i16()?;
     unit.standing_graphic = optional_id!(stream.read_i16()?);
     unit.dying_graphic = optional_id!(stream.read_i16()?);
-    stream.read_i16()?; // unused (dying graphic 2)
+    unit.unused_dying_graphic = stream.read_i16()?; // dying graphic 2
     unit.death_mode = stream.read_i8()?;
     unit.hit_points = stream.read_i16()?;
     unit.line_of_sight = stream.read_f32()?;
@@ -390,7 +598,7 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
     unit.air_mode = stream.read_u8()? != 0;
     unit.icon_id = stream.read_i16()?;
     unit.hide_in_editor = stream.read_u8()? != 0;
-    stream.read_u16()?; // unknown
+    unit.unknown_u16 = stream.read_u16()?;
     unit.enabled = stream.read_u8()? != 0;
 
     unit.placement_side_terrain_ids[0] = optional_id!(stream.read_i16()?);
@@ -410,7 +618,7 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
     unit.interaction_mode = InteractionMode::from_u8(stream.read_u8()?)?;
     unit.minimap_mode = stream.read_i8()?;
     unit.command_attribute = stream.read_i8()?;
-    stream.read_f32()?; // unknown
+    unit.unknown_f32 = stream.read_f32()?;
     unit.minimap_color = stream.read_u8()?;
     unit.help_id = optional_id!(stream.read_i32()?);
     unit.hotkey_text_id = optional_id!(stream.read_i32()?);
@@ -426,7 +634,7 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
     unit.selection_shape_size_y = stream.read_f32()?;
     unit.selection_shape_size_z = stream.read_f32()?;
 
-    unit.resource_storage = read_resource_costs!(f32, u8, stream, 3);
+    unit.resource_storage = read_resource_costs!(f32, u8, stream, RESOURCE_STORAGE_COUNT);
 
     let damage_graphic_count = stream.read_u8()? as usize;
     unit.damage_graphics = stream.read_array(damage_graphic_count, |c| read_damage_graphic(c))?;
@@ -434,7 +642,7 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
     unit.selection_sound = stream.read_i16()?;
     unit.dying_sound = stream.read_i16()?;
     unit.attack_mode = stream.read_i8()?;
-    stream.read_u8()?; // Unknown
+    unit.unknown_u8 = stream.read_u8()?;
 
     unit.name = stream.read_sized_str(name_length)?;
     unit.id2 = stream.read_i16()?;
@@ -446,8 +654,8 @@ pub fn read_unit<R: Read + Seek>(stream: &mut R) -> Result<Unit> {
         },
         UnitType::Flag |
         UnitType::Unknown25 => {
-            // Skip what may be the speed; but on a non-moveable
-            stream.read_f32()?;
+            // What may be the speed; but on a non-moveable
+            unit.unused_speed = stream.read_f32()?;
         },
         _ => (),
     }
@@ -489,11 +697,11 @@ fn read_motion_params<R: Read>(stream: &mut R) -> Result<MotionParams> {
     params.walking_graphics[0] = optional_id!(stream.read_i16()?);
     params.walking_graphics[1] = optional_id!(stream.read_i16()?);
     params.rotation_speed = stream.read_f32()?;
-    stream.read_u8()?; // unknown
+    params.unknown_bytes[0] = stream.read_u8()?;
     params.tracking_unit = optional_id!(stream.read_i16()?);
     params.tracking_unit_used = stream.read_u8()? != 0;
     params.tracking_unit_density = stream.read_f32()?;
-    stream.read_u8()?; // unknown
+    params.unknown_bytes[1] = stream.read_u8()?;
     Ok(params)
 }
 
@@ -518,7 +726,7 @@ fn read_unit_command<R: Read>(stream: &mut R) -> Result<UnitCommand> {
     let mut command: UnitCommand = Default::default();
     command.enabled = stream.read_u16()? != 0;
     command.id = required_id!(stream.read_i16()?);
-    stream.read_u8()?; // unknown
+    command.unknown_bytes[0] = stream.read_u8()?;
     command.type_id = stream.read_i16()?;
     command.class_id = stream.read_i16()?;
     command.unit_id = optional_id!(stream.read_i16()?);
@@ -530,15 +738,15 @@ fn read_unit_command<R: Read>(stream: &mut R) -> Result<UnitCommand> {
     command.quantity = stream.read_f32()?;
     command.execution_radius = stream.read_f32()?;
     command.extra_range = stream.read_f32()?;
-    stream.read_u8()?; // unknown
-    stream.read_f32()?; // unknown
+    command.unknown_bytes[1] = stream.read_u8()?;
+    command.unknown_f32 = stream.read_f32()?;
     command.selection_enabler = stream.read_i8()?;
-    stream.read_u8()?; // unknown
+    command.unknown_bytes[2] = stream.read_u8()?;
     command.plunder_source = stream.read_i16()?;
-    stream.read_i16()?; // unknown
+    command.unknown_i16 = stream.read_i16()?;
     command.selection_mode = stream.read_i8()?;
     command.right_click_mode = stream.read_i8()?;
-    stream.read_u8()?; // unknown
+    command.unknown_bytes[3] = stream.read_u8()?;
     command.tool_graphic_id = optional_id!(stream.read_i16()?);
     command.proceeding_graphic_id = optional_id!(stream.read_i16()?);
     command.action_graphic_id = optional_id!(stream.read_i16()?);
@@ -589,14 +797,14 @@ fn read_projectile_params<R: Read>(stream: &mut R) -> Result<ProjectileParams> {
     params.smart_mode = stream.read_i8()?;
     params.drop_animation_mode = stream.read_i8()?;
     params.penetration_mode = stream.read_i8()?;
-    stream.read_u8()?; // unknown
+    params.unknown_u8 = stream.read_u8()?;
     params.projectile_arc = stream.read_f32()?;
     Ok(params)
 }
 
 fn read_trainable_params<R: Read>(stream: &mut R) -> Result<TrainableParams> {
     let mut params: TrainableParams = Default::default();
-    params.resource_costs = read_resource_costs!(i16, i16, stream, 3);
+    params.resource_costs = read_resource_costs!(i16, i16, stream, RESOURCE_COST_COUNT);
     params.train_time = stream.read_i16()?;
     params.train_location_id = optional_id!(stream.read_i16()?);
     params.button_id = stream.read_i8()?;
@@ -614,6 +822,347 @@ fn read_building_params<R: Read>(stream: &mut R) -> Result<BuildingParams> {
     params.foundation_terrain_id = optional_id!(stream.read_i16()?);
     params.old_terrain_id = optional_id!(stream.read_i16()?);
     params.research_id = optional_id!(stream.read_i16()?);
-    params.construction_sound = stream.read_i16()?;
+    params.construction_sound_id = optional_id!(stream.read_i16()?);
     Ok(params)
 }
+
+pub fn write_unit<W: Write>(stream: &mut W, unit: &Unit) -> Result<()> {
+    if unit.damage_graphics.len() > u8::MAX as usize {
+        return Err(ErrorKind::InvalidDb("too many damage graphics").into());
+    }
+
+    // Names are written null terminated, with the terminator counted in the length
+    let name_length = if unit.name.is_empty() { 0 } else { unit.name.len() + 1 };
+
+    stream.write_u8(unit.unit_type.to_u8())?;
+    stream.write_u16(name_length as u16)?;
+    stream.write_i16(*unit.id as i16)?;
+    stream.write_i16(raw_optional_id!(unit.name_id, i16))?;
+    stream.write_i16(*unit.creation_id as i16)?;
+    stream.write_i16(unit.class_id)?;
+    stream.write_i16(raw_optional_id!(unit.standing_graphic, i16))?;
+    stream.write_i16(raw_optional_id!(unit.dying_graphic, i16))?;
+    stream.write_i16(unit.unused_dying_graphic)?;
+    stream.write_i8(unit.death_mode)?;
+    stream.write_i16(unit.hit_points)?;
+    stream.write_f32(unit.line_of_sight)?;
+    stream.write_i8(unit.garrison_capability)?;
+    stream.write_f32(unit.collision_size_x)?;
+    stream.write_f32(unit.collision_size_y)?;
+    stream.write_f32(unit.collision_size_z)?;
+    stream.write_i16(raw_optional_id!(unit.train_sound_id, i16))?;
+    stream.write_i16(raw_optional_id!(unit.dead_unit_id, i16))?;
+    stream.write_i8(unit.placement_mode)?;
+    stream.write_u8(unit.air_mode as u8)?;
+    stream.write_i16(unit.icon_id)?;
+    stream.write_u8(unit.hide_in_editor as u8)?;
+    stream.write_u16(unit.unknown_u16)?;
+    stream.write_u8(unit.enabled as u8)?;
+
+    for terrain_id in unit.placement_side_terrain_ids.iter().chain(unit.placement_terrain_ids.iter()) {
+        stream.write_i16(raw_optional_id!(*terrain_id, i16))?;
+    }
+    stream.write_f32(unit.clearance_size_x)?;
+    stream.write_f32(unit.clearance_size_y)?;
+    stream.write_i8(unit.hill_mode)?;
+    stream.write_u8(unit.visible_in_fog as u8)?;
+    stream.write_i16(unit.terrain_restriction.as_index() as i16)?;
+    stream.write_i8(unit.fly_mode as i8)?;
+    stream.write_i16(unit.resource_capacity)?;
+    stream.write_f32(unit.resource_decay)?;
+    stream.write_i8(unit.blast_defense_level)?;
+    stream.write_i8(unit.sub_type)?;
+    stream.write_u8(unit.interaction_mode.to_u8())?;
+    stream.write_i8(unit.minimap_mode)?;
+    stream.write_i8(unit.command_attribute)?;
+    stream.write_f32(unit.unknown_f32)?;
+    stream.write_u8(unit.minimap_color)?;
+    stream.write_i32(raw_optional_id!(unit.help_id, i32))?;
+    stream.write_i32(raw_optional_id!(unit.hotkey_text_id, i32))?;
+    stream.write_i32(unit.hotkey)?;
+    stream.write_u8(unit.unselectable as u8)?;
+    stream.write_u8(unit.enable_auto_gather as u8)?;
+    stream.write_i8(unit.auto_gather_mode)?;
+    stream.write_i8(unit.auto_gather_id)?;
+
+    stream.write_i8(unit.selection_effect)?;
+    stream.write_u8(unit.editor_selection_color)?;
+    stream.write_f32(unit.selection_shape_size_x)?;
+    stream.write_f32(unit.selection_shape_size_y)?;
+    stream.write_f32(unit.selection_shape_size_z)?;
+
+    write_resource_costs!(f32, u8, &unit.resource_storage, stream, RESOURCE_STORAGE_COUNT);
+
+    stream.write_u8(unit.damage_graphics.len() as u8)?;
+    for damage_graphic in &unit.damage_graphics {
+        stream.write_i16(*damage_graphic.graphic_id as i16)?;
+        stream.write_u8(damage_graphic.damage_percent)?;
+        stream.write_u8(damage_graphic.old_apply_mode)?;
+        stream.write_u8(damage_graphic.apply_mode)?;
+    }
+
+    stream.write_i16(unit.selection_sound)?;
+    stream.write_i16(unit.dying_sound)?;
+    stream.write_i8(unit.attack_mode)?;
+    stream.write_u8(unit.unknown_u8)?;
+
+    stream.write_sized_str(&unit.name, name_length)?;
+    stream.write_i16(unit.id2)?;
+
+    match unit.unit_type {
+        UnitType::Tree | UnitType::GraphicEffect => return Ok(()),
+        UnitType::Flag |
+        UnitType::Unknown25 => stream.write_f32(unit.unused_speed)?,
+        _ => (),
+    }
+
+    if unit.unit_type.has_motion_params() {
+        write_motion_params(stream, params_for(&unit.motion_params)?)?;
+    }
+    if unit.unit_type.has_commandable_params() {
+        write_commandable_params(stream, params_for(&unit.commandable_params)?)?;
+    }
+    if unit.unit_type.has_battle_params() {
+        write_battle_params(stream, params_for(&unit.battle_params)?)?;
+    }
+    if unit.unit_type.has_projectile_params() {
+        write_projectile_params(stream, params_for(&unit.projectile_params)?)?;
+    }
+    if unit.unit_type.has_trainable_params() {
+        write_trainable_params(stream, params_for(&unit.trainable_params)?)?;
+    }
+    if unit.unit_type.has_building_params() {
+        write_building_params(stream, params_for(&unit.building_params)?)?;
+    }
+    Ok(())
+}
+
+/// Every param group the unit type calls for has to be present to write the unit
+fn params_for<T>(params: &Option<T>) -> Result<&T> {
+    params.as_ref().ok_or_else(|| ErrorKind::InvalidDb("unit is missing params for its type").into())
+}
+
+fn write_motion_params<W: Write>(stream: &mut W, params: &MotionParams) -> Result<()> {
+    stream.write_f32(params.speed)?;
+    stream.write_i16(raw_optional_id!(params.walking_graphics[0], i16))?;
+    stream.write_i16(raw_optional_id!(params.walking_graphics[1], i16))?;
+    stream.write_f32(params.rotation_speed)?;
+    stream.write_u8(params.unknown_bytes[0])?;
+    stream.write_i16(raw_optional_id!(params.tracking_unit, i16))?;
+    stream.write_u8(params.tracking_unit_used as u8)?;
+    stream.write_f32(params.tracking_unit_density)?;
+    stream.write_u8(params.unknown_bytes[1])?;
+    Ok(())
+}
+
+fn write_commandable_params<W: Write>(stream: &mut W, params: &CommandableParams) -> Result<()> {
+    stream.write_i16(params.action_when_discovered_id)?;
+    stream.write_f32(params.search_radius)?;
+    stream.write_f32(params.work_rate)?;
+    stream.write_i16(params.drop_sites[0])?;
+    stream.write_i16(params.drop_sites[1])?;
+    stream.write_i8(params.task_swap_id)?;
+    stream.write_i16(params.attack_sound)?;
+    stream.write_i16(params.move_sound)?;
+    stream.write_i8(params.animal_mode)?;
+
+    stream.write_u16(params.commands.len() as u16)?;
+    for command in &params.commands {
+        write_unit_command(stream, command)?;
+    }
+    Ok(())
+}
+
+fn write_unit_command<W: Write>(stream: &mut W, command: &UnitCommand) -> Result<()> {
+    stream.write_u16(command.enabled as u16)?;
+    stream.write_i16(*command.id as i16)?;
+    stream.write_u8(command.unknown_bytes[0])?;
+    stream.write_i16(command.type_id)?;
+    stream.write_i16(command.class_id)?;
+    stream.write_i16(raw_optional_id!(command.unit_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.terrain_id, i16))?;
+    stream.write_i16(command.resource_in)?;
+    stream.write_i16(command.resource_productivity_multiplier)?;
+    stream.write_i16(command.resource_out)?;
+    stream.write_i16(command.resource)?;
+    stream.write_f32(command.quantity)?;
+    stream.write_f32(command.execution_radius)?;
+    stream.write_f32(command.extra_range)?;
+    stream.write_u8(command.unknown_bytes[1])?;
+    stream.write_f32(command.unknown_f32)?;
+    stream.write_i8(command.selection_enabler)?;
+    stream.write_u8(command.unknown_bytes[2])?;
+    stream.write_i16(command.plunder_source)?;
+    stream.write_i16(command.unknown_i16)?;
+    stream.write_i8(command.selection_mode)?;
+    stream.write_i8(command.right_click_mode)?;
+    stream.write_u8(command.unknown_bytes[3])?;
+    stream.write_i16(raw_optional_id!(command.tool_graphic_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.proceeding_graphic_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.action_graphic_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.carrying_graphic_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.execution_sound_id, i16))?;
+    stream.write_i16(raw_optional_id!(command.resource_deposit_sound_id, i16))?;
+    Ok(())
+}
+
+fn write_battle_params<W: Write>(stream: &mut W, params: &BattleParams) -> Result<()> {
+    stream.write_u8(params.default_armor)?;
+
+    stream.write_u16(params.attacks.len() as u16)?;
+    for &(class, amount) in &params.attacks {
+        stream.write_i16(class)?;
+        stream.write_i16(amount)?;
+    }
+
+    stream.write_u16(params.armors.len() as u16)?;
+    for &(class, amount) in &params.armors {
+        stream.write_i16(class)?;
+        stream.write_i16(amount)?;
+    }
+
+    stream.write_i16(params.terrain_restriction_for_damage_multiplier)?;
+    stream.write_f32(params.max_range)?;
+    stream.write_f32(params.blast_width)?;
+    stream.write_f32(params.reload_time)?;
+    stream.write_i16(raw_optional_id!(params.projectile_unit_id, i16))?;
+    stream.write_i16(params.accuracy_percent)?;
+    stream.write_i8(params.tower_mode)?;
+    stream.write_i16(params.frame_delay)?;
+    for displacement in &params.graphic_displacements {
+        stream.write_f32(*displacement)?;
+    }
+    stream.write_i8(params.blast_attack_level)?;
+    stream.write_f32(params.min_range)?;
+    stream.write_i16(raw_optional_id!(params.attack_graphic_id, i16))?;
+    stream.write_i16(params.displayed_melee_armour)?;
+    stream.write_i16(params.displayed_attack)?;
+    stream.write_f32(params.displayed_range)?;
+    stream.write_f32(params.displayed_reload_time)?;
+    Ok(())
+}
+
+fn write_projectile_params<W: Write>(stream: &mut W, params: &ProjectileParams) -> Result<()> {
+    stream.write_i8(params.stretch_mode)?;
+    stream.write_i8(params.smart_mode)?;
+    stream.write_i8(params.drop_animation_mode)?;
+    stream.write_i8(params.penetration_mode)?;
+    stream.write_u8(params.unknown_u8)?;
+    stream.write_f32(params.projectile_arc)?;
+    Ok(())
+}
+
+fn write_trainable_params<W: Write>(stream: &mut W, params: &TrainableParams) -> Result<()> {
+    write_resource_costs!(i16, i16, &params.resource_costs, stream, RESOURCE_COST_COUNT);
+    stream.write_i16(params.train_time)?;
+    stream.write_i16(raw_optional_id!(params.train_location_id, i16))?;
+    stream.write_i8(params.button_id)?;
+    stream.write_i16(params.displayed_pierce_armor)?;
+    Ok(())
+}
+
+fn write_building_params<W: Write>(stream: &mut W, params: &BuildingParams) -> Result<()> {
+    stream.write_i16(*params.construction_graphic_id as i16)?;
+    stream.write_i8(params.adjacent_mode)?;
+    stream.write_i16(params.graphics_angle)?;
+    stream.write_u8(params.disappears_when_built as u8)?;
+    stream.write_i16(raw_optional_id!(params.stack_unit_id, i16))?;
+    stream.write_i16(raw_optional_id!(params.foundation_terrain_id, i16))?;
+    stream.write_i16(raw_optional_id!(params.old_terrain_id, i16))?;
+    stream.write_i16(raw_optional_id!(params.research_id, i16))?;
+    stream.write_i16(raw_optional_id!(params.construction_sound_id, i16))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::empires::resource::ResourceType;
+    use std::io::Cursor;
+
+    fn to_bytes(unit: &Unit) -> Vec<u8> {
+        let mut data = Vec::new();
+        write_unit(&mut data, unit).unwrap();
+        data
+    }
+
+    #[test]
+    fn test_unit_round_trip() {
+        let mut unit: Unit = Default::default();
+        unit.unit_type = UnitType::Building;
+        unit.id = 12.into();
+        unit.name = "Barracks".into();
+        unit.hit_points = 350;
+        unit.interaction_mode = InteractionMode::Building;
+        let mut storage: UnitResourceStorage = Default::default();
+        storage.resource_type = ResourceType::Food;
+        storage.amount = 5.0;
+        storage.enabled = true;
+        unit.resource_storage.push(storage);
+        unit.motion_params = Some(Default::default());
+        let mut commandable: CommandableParams = Default::default();
+        commandable.commands.push(Default::default());
+        unit.commandable_params = Some(commandable);
+        let mut battle: BattleParams = Default::default();
+        battle.attacks.push((4, 3));
+        unit.battle_params = Some(battle);
+        let mut trainable: TrainableParams = Default::default();
+        trainable.train_time = 50;
+        let mut cost: UnitResourceCost = Default::default();
+        cost.resource_type = ResourceType::Wood;
+        cost.amount = 125;
+        cost.enabled = true;
+        trainable.resource_costs.push(cost);
+        unit.trainable_params = Some(trainable);
+        unit.building_params = Some(Default::default());
+
+        let written = to_bytes(&unit);
+        let read = read_unit(&mut Cursor::new(&written)).unwrap();
+        assert_eq!("Barracks", read.name);
+        assert_eq!(1, read.resource_storage.len());
+        assert_eq!(125, read.trainable_params.as_ref().unwrap().resource_costs[0].amount);
+        assert_eq!(written, to_bytes(&read));
+    }
+
+    #[test]
+    fn test_apply_attribute() {
+        let mut unit: Unit = Default::default();
+        unit.hit_points = 25;
+        let mut battle: BattleParams = Default::default();
+        battle.attacks = vec![(4, 3), (3, 1)];
+        battle.armors = vec![(4, 1)];
+        unit.battle_params = Some(battle);
+        let mut trainable: TrainableParams = Default::default();
+        trainable.resource_costs.push(UnitResourceCost::new(ResourceType::Food, 50));
+        unit.trainable_params = Some(trainable);
+
+        unit.apply_attribute(UnitAttributeId::HitPoints, ResearchEffectValue::MultiplyBy(1.15));
+        assert_eq!(29, unit.hit_points);
+
+        // Class 4 (melee) attack goes up by 2, and class 3 (pierce) armor is left alone since the unit has none
+        unit.apply_attribute(UnitAttributeId::AttackStrength, ResearchEffectValue::Add((4 * 256 + 2) as f32));
+        unit.apply_attribute(UnitAttributeId::ArmorStrength, ResearchEffectValue::SetTo((3 * 256 + 2) as f32));
+        let battle = unit.battle_params.as_ref().unwrap();
+        assert_eq!(vec![(4, 5), (3, 1)], battle.attacks);
+        assert_eq!(vec![(4, 1)], battle.armors);
+
+        // Amounts are signed, so a low byte of 0xff takes 1 away
+        unit.apply_attribute(UnitAttributeId::ArmorStrength, ResearchEffectValue::Add((4 * 256 + 0xff) as f32));
+        assert_eq!(vec![(4, 0)], unit.battle_params.as_ref().unwrap().armors);
+
+        unit.apply_attribute(UnitAttributeId::ResourceCost, ResearchEffectValue::MultiplyBy(0.75));
+        assert_eq!(38, unit.trainable_params.as_ref().unwrap().resource_costs[0].amount);
+
+        // The unit doesn't move, so it has no speed to change
+        unit.apply_attribute(UnitAttributeId::Speed, ResearchEffectValue::Add(1.0));
+        assert!(unit.motion_params.is_none());
+    }
+
+    #[test]
+    fn test_write_unit_missing_params() {
+        let mut unit: Unit = Default::default();
+        unit.unit_type = UnitType::Moveable;
+        let mut data = Vec::new();
+        assert!(write_unit(&mut data, &unit).is_err());
+    }
+}
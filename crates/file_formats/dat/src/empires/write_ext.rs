@@ -0,0 +1,85 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+
+use std::io::Write;
+
+/// Little-endian writes mirroring the reads that `chariot_io_tools::ReadExt` provides
+pub trait WriteExt: Write {
+    fn write_u8(&mut self, value: u8) -> Result<()> {
+        self.write_all(&[value])?;
+        Ok(())
+    }
+
+    fn write_i8(&mut self, value: i8) -> Result<()> {
+        self.write_u8(value as u8)
+    }
+
+    fn write_u16(&mut self, value: u16) -> Result<()> {
+        self.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_i16(&mut self, value: i16) -> Result<()> {
+        self.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_u32(&mut self, value: u32) -> Result<()> {
+        self.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_i32(&mut self, value: i32) -> Result<()> {
+        self.write_all(&value.to_le_bytes())?;
+        Ok(())
+    }
+
+    fn write_f32(&mut self, value: f32) -> Result<()> {
+        self.write_all(&value.to_bits().to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Writes a string into a fixed size, zero padded field
+    fn write_sized_str(&mut self, value: &str, len: usize) -> Result<()> {
+        let bytes = value.as_bytes();
+        if bytes.len() > len {
+            return Err(ErrorKind::InvalidDb("string is too long for its field").into());
+        }
+        self.write_all(bytes)?;
+        self.write_all(&vec![0u8; len - bytes.len()])?;
+        Ok(())
+    }
+}
+
+impl<W: Write + ?Sized> WriteExt for W {}
+
+/// Converts an optional ID back into its raw value, using -1 for `None`
+macro_rules! raw_optional_id {
+    ($id:expr, $t:ty) => {
+        match $id {
+            Some(id) => *id as $t,
+            None => -1,
+        }
+    }
+}
@@ -33,6 +33,10 @@ error_chain! {
             description("bad empires.dat")
             display("Bad empires.dat: {:?}", reason)
         }
+        InvalidDb(reason: &'static str) {
+            description("invalid empires database")
+            display("Invalid empires database: {:?}", reason)
+        }
         InvalidInteractionMode(interaction_mode: u8) {
             description("invalid interaction mode")
             display("Invalid interaction mode: {}", interaction_mode)
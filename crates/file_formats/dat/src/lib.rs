@@ -31,9 +31,14 @@ mod tileedge;
 mod error;
 
 pub use empires::{EmpiresDb, EmpiresDbRef};
-pub use empires::{InteractionMode, Unit};
-pub use empires::Civilization;
-pub use empires::Graphic;
+pub use empires::{BattleParams, BuildingParams, CommandableParams, DamageGraphic, InteractionMode, ProjectileParams,
+                  ResourceCost, ResourceType, TrainableParams, Unit, UnitCommand, UnitResourceCost};
+pub use empires::{Age, Civilization, CivilizationStartingValues};
+pub use empires::LocalizedEmpires;
+pub use empires::{Graphic, GraphicAttackSound};
+pub use empires::{Research, ResearchCost};
+pub use empires::{SoundEffect, SoundEffectGroup};
+pub use empires::{ResearchEffect, ResearchEffectGroup, ResearchEffectRecord, ResearchEffectValue, UnitAttributeId};
 pub use empires::Terrain;
 pub use empires::TerrainBlock;
 pub use empires::TerrainBorder;
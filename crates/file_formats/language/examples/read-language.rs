@@ -35,12 +35,33 @@ fn main() {
             .help("Sets the input dll to use")
             .required(true)
             .index(1))
+        .arg(Arg::with_name("translation")
+            .long("translation")
+            .value_name("FILE")
+            .help("Overlays the strings from a translation string table")
+            .takes_value(true))
+        .arg(Arg::with_name("export")
+            .long("export")
+            .value_name("FILE")
+            .help("Writes the strings out as a string table instead of printing them")
+            .takes_value(true))
         .get_matches();
 
     let file_name = matches.value_of("INPUT").unwrap();
-    match language::Language::read_from_file(file_name) {
+    let result = match matches.value_of("translation") {
+        Some(translation) => language::Language::read_with_translation(file_name, translation),
+        None => language::Language::read_from_file(file_name),
+    };
+    match result {
         Ok(lang) => {
-            println!("{:#?}", lang.strings);
+            match matches.value_of("export") {
+                Some(export) => {
+                    if let Err(err) = lang.write_table_to_file(export) {
+                        println!("Failed to write the string table: {}", err);
+                    }
+                },
+                None => println!("{:#?}", lang.strings),
+            }
         },
         Err(err) => {
             println!("Failed to read the language file: {}", err);
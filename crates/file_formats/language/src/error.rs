@@ -42,5 +42,9 @@ error_chain! {
             display("failed to find string resources")
             description("failed to find string resources")
         }
+        InvalidStringTable(line: usize, reason: &'static str) {
+            display("invalid string table on line {}: {}", line, reason)
+            description("invalid string table")
+        }
     }
 }
@@ -20,18 +20,20 @@
 // SOFTWARE.
 
 use crate::error::{Result, ErrorKind};
+use crate::string_table::{read_string_table, write_string_table};
+use identifier::LocalizationId;
 
 use chariot_io_tools::{ReadExt, ReadArrayExt};
 
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::SeekFrom;
-use std::io::prelude::{Read, Seek};
+use std::io::{self, SeekFrom};
+use std::io::prelude::{BufRead, Read, Seek, Write};
 use std::path::Path;
 
 #[derive(Debug)]
 pub struct Language {
-    pub strings: BTreeMap<usize, String>,
+    pub strings: BTreeMap<LocalizationId, String>,
 }
 
 impl Language {
@@ -39,6 +41,49 @@ impl Language {
         Language { strings: BTreeMap::new() }
     }
 
+    /// Looks up a localized string
+    pub fn get(&self, id: LocalizationId) -> Option<&str> {
+        self.strings.get(&id).map(|text| text.as_str())
+    }
+
+    /// Replaces this language's strings with any that the given translation defines.
+    /// Strings the translation doesn't define are left alone.
+    pub fn overlay(&mut self, translation: Language) {
+        self.strings.extend(translation.strings);
+    }
+
+    /// Reads the strings out of language.dll, then overlays the given translation string table
+    pub fn read_with_translation<P: AsRef<Path>, T: AsRef<Path>>(dll_file_name: P,
+                                                                 translation_file_name: T)
+                                                                 -> Result<Language> {
+        let mut language = Language::read_from_file(dll_file_name)?;
+        language.overlay(Language::read_table_from_file(translation_file_name)?);
+        Ok(language)
+    }
+
+    /// Reads a plain text string table (see the `string_table` module for the format)
+    pub fn read_table_from_file<P: AsRef<Path>>(file_name: P) -> Result<Language> {
+        let file = File::open(file_name.as_ref())?;
+        Language::read_table_from_stream(&mut io::BufReader::new(file))
+    }
+
+    pub fn read_table_from_stream<S: BufRead>(stream: &mut S) -> Result<Language> {
+        Ok(Language { strings: read_string_table(stream)? })
+    }
+
+    /// Writes all of the strings out as a plain text string table
+    pub fn write_table_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let mut file = io::BufWriter::new(File::create(file_name.as_ref())?);
+        self.write_table_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    pub fn write_table_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        write_string_table(&self.strings, stream)
+    }
+
+    /// Reads the string resources out of a language.dll file
     pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<Language> {
         let file_name = file_name.as_ref();
         let mut file = File::open(file_name)?;
@@ -85,7 +130,7 @@ impl Language {
                             words.push(stream.read_u16()?);
                         }
                         let text = String::from_utf16(&words)?;
-                        language.strings.insert(string_id, text);
+                        language.strings.insert(string_id.into(), text);
                     }
                     string_id += 1;
                 }
@@ -325,3 +370,23 @@ fn read_pe_resource_data_entry<S: Read + Seek>(stream: &mut S,
         reserved: stream.read_u32()?,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlay() {
+        let mut base = Language::new();
+        base.strings.insert(4201.into(), "Stone Age".into());
+        base.strings.insert(4202.into(), "Tool Age".into());
+
+        let table = "4202 \"Âge des outils\"\n4203 \"Âge du bronze\"\n";
+        base.overlay(Language::read_table_from_stream(&mut io::Cursor::new(table)).unwrap());
+
+        assert_eq!(Some("Stone Age"), base.get(4201.into()));
+        assert_eq!(Some("Âge des outils"), base.get(4202.into()));
+        assert_eq!(Some("Âge du bronze"), base.get(4203.into()));
+        assert_eq!(None, base.get(4204.into()));
+    }
+}
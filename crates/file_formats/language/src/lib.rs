@@ -25,9 +25,11 @@
 extern crate error_chain;
 
 extern crate chariot_io_tools;
+extern crate chariot_identifier as identifier;
 
 mod error;
 mod language;
+mod string_table;
 
 pub use error::ChainErr;
 pub use error::Error;
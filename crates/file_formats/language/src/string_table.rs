@@ -0,0 +1,142 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//! Plain text string tables, one string per line:
+//!
+//! ```text
+//! # Comments and blank lines are ignored
+//! 4201 "Stone Age"
+//! 4202 "Two\nlines with a \"quote\""
+//! ```
+//!
+//! Strings are double quoted; backslash, double quote, newline, carriage return
+//! and tab are escaped with a backslash.
+
+use crate::error::{ErrorKind, Result};
+use identifier::LocalizationId;
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+
+pub fn read_string_table<S: BufRead>(stream: &mut S) -> Result<BTreeMap<LocalizationId, String>> {
+    let mut strings = BTreeMap::new();
+    for (index, line) in stream.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line_number = index + 1;
+        let invalid = |reason| ErrorKind::InvalidStringTable(line_number, reason);
+        let separator = line.find(char::is_whitespace).ok_or_else(|| invalid("missing string"))?;
+        let id: u32 = line[..separator].parse().map_err(|_| invalid("invalid string ID"))?;
+
+        let quoted = line[separator..].trim_start();
+        if quoted.len() < 2 || !quoted.starts_with('"') || !quoted.ends_with('"') {
+            return Err(invalid("string must be double quoted").into());
+        }
+        let text = unescape(&quoted[1..quoted.len() - 1]).ok_or_else(|| invalid("invalid escape sequence"))?;
+        strings.insert((id as usize).into(), text);
+    }
+    Ok(strings)
+}
+
+pub fn write_string_table<W: Write>(strings: &BTreeMap<LocalizationId, String>, stream: &mut W) -> Result<()> {
+    for (id, text) in strings {
+        writeln!(stream, "{} \"{}\"", **id, escape(text))?;
+    }
+    Ok(())
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(match chars.next()? {
+                '\\' => '\\',
+                '"' => '"',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                _ => return None,
+            }),
+            '"' => return None,
+            _ => unescaped.push(c),
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    #[test]
+    fn test_round_trip() {
+        let mut strings = BTreeMap::new();
+        strings.insert(4201.into(), "Stone Age".to_string());
+        strings.insert(4202.into(), "Two\nlines with a \"quote\" and a \\ backslash".to_string());
+        strings.insert(9999.into(), String::new());
+
+        let mut written = Vec::new();
+        write_string_table(&strings, &mut written).unwrap();
+        let read = read_string_table(&mut io::Cursor::new(written)).unwrap();
+        assert_eq!(strings, read);
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let table = "# Translated by the community\n\n  4201   \"Âge de pierre\"  \n";
+        let strings = read_string_table(&mut io::Cursor::new(table)).unwrap();
+        assert_eq!(1, strings.len());
+        assert_eq!("Âge de pierre", strings[&4201.into()]);
+    }
+
+    #[test]
+    fn test_invalid_lines() {
+        for table in &["4201\n", "abc \"Stone Age\"\n", "4201 Stone Age\n", "4201 \"Stone\\q\"\n",
+                       "4201 \"Stone\" Age\"\n"] {
+            match read_string_table(&mut io::Cursor::new(*table)) {
+                Err(crate::Error(ErrorKind::InvalidStringTable(1, _), _)) => {},
+                other => panic!("unexpected result for {:?}: {:?}", table, other),
+            }
+        }
+    }
+}
@@ -38,5 +38,17 @@ error_chain! {
             display("scenario instructions too large")
             description("scenario instructions too large")
         }
+        InvalidScenario(reason: &'static str) {
+            display("invalid scenario: {}", reason)
+            description("invalid scenario")
+        }
+        StringTooLong(text: String) {
+            display("string too long for its field: {:?}", text)
+            description("string too long for its field")
+        }
+        UnencodableCharacter(character: char) {
+            display("character can't be stored in a scenario: {:?}", character)
+            description("character can't be stored in a scenario")
+        }
     }
 }
@@ -27,18 +27,26 @@ extern crate error_chain;
 
 #[macro_use]
 extern crate chariot_identifier as identifier;
+extern crate chariot_dat as dat;
 
 mod error;
 mod scn;
 mod player_data;
 mod player_resources;
+mod player_settings;
 mod player_unit;
 mod map;
+mod text;
+mod version;
+mod write_ext;
 
 pub use error::ChainErr;
 pub use error::Error;
 pub use error::ErrorKind;
 pub use error::Result;
 pub use map::{Map, MapTile};
+pub use player_resources::PlayerResources;
+pub use player_settings::PlayerSettings;
+pub use version::ScenarioVersion;
 
 pub use scn::Scenario;
@@ -20,12 +20,14 @@
 // SOFTWARE.
 //
 
-use crate::error::Result;
+use crate::error::{ErrorKind, Result};
+use crate::version::ScenarioVersion;
+use crate::write_ext::WriteExt;
 
 use identifier::TerrainId;
 use chariot_io_tools::{ReadArrayExt, ReadExt};
 
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[derive(Default, Debug)]
 pub struct Map {
@@ -42,21 +44,38 @@ pub struct MapTile {
 }
 
 impl Map {
-    pub fn read_from_stream<S: Read>(stream: &mut S) -> Result<Map> {
+    pub fn read_from_stream<S: Read>(stream: &mut S, version: ScenarioVersion) -> Result<Map> {
         let mut map = Map {
             width: stream.read_u32()?,
             height: stream.read_u32()?,
             tiles: Default::default(),
         };
-        map.tiles = stream.read_array((map.width * map.height) as usize, |s| read_map_tile(s))?;
+        map.tiles = stream.read_array((map.width * map.height) as usize, |s| read_map_tile(s, version))?;
         Ok(map)
     }
+
+    pub fn write_to<W: Write>(&self, stream: &mut W, version: ScenarioVersion) -> Result<()> {
+        if self.tiles.len() != (self.width * self.height) as usize {
+            return Err(ErrorKind::InvalidScenario("map tile count doesn't match its size").into());
+        }
+
+        stream.write_u32(self.width)?;
+        stream.write_u32(self.height)?;
+        for tile in &self.tiles {
+            stream.write_i8(*tile.terrain_id as i8)?;
+            stream.write_u8(tile.elevation)?;
+            if version.has_tile_padding() {
+                stream.write_u8(tile.unused)?;
+            }
+        }
+        Ok(())
+    }
 }
 
-fn read_map_tile<S: Read>(stream: &mut S) -> Result<MapTile> {
+fn read_map_tile<S: Read>(stream: &mut S, version: ScenarioVersion) -> Result<MapTile> {
     Ok(MapTile {
         terrain_id: required_id!(stream.read_i8()?),
         elevation: stream.read_u8()?,
-        unused: stream.read_u8()?,
+        unused: if version.has_tile_padding() { stream.read_u8()? } else { 0 },
     })
 }
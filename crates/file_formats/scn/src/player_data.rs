@@ -19,12 +19,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::error::Result;
+use crate::error::{ErrorKind, Result};
+use crate::text::{OriginalStrings, StringWriter};
+use crate::version::ScenarioVersion;
+use crate::write_ext::WriteExt;
 use identifier::CivilizationId;
 
 use chariot_io_tools::{ReadArrayExt, ReadExt};
 
-use std::io::{Read, Seek};
+use std::io::{Read, Seek, Write};
 
 #[derive(Default, Debug)]
 pub struct PlayerData {
@@ -66,6 +69,8 @@ pub struct PlayerData {
 
     pub all_techs: bool,
     pub starting_ages: Vec<u32>,
+
+    originals: OriginalStrings,
 }
 
 #[derive(Default, Debug)]
@@ -122,62 +127,205 @@ pub struct Diplomacy {
     individual_victory: Vec<Vec<u32>>,
 }
 
+const PLAYER_SLOT_COUNT: usize = 16;
+const AI_TYPE_COUNT: usize = 4;
+const DISABLED_RESEARCH_COUNT: usize = 20;
+const INDIVIDUAL_VICTORY_LENGTH: usize = 180;
+const PLAYER_NAME_LENGTH: usize = 256;
+const SEPARATOR: i32 = -1;
 const PLAYER_DATA_UNKNOWN_1_LENGTH: usize = 8;
 const THUMBNAIL_UNKNOWN_1_LENGTH: usize = 22;
 const THUMBNAIL_UNKNOWN_2_LENGTH: usize = 16;
 
 impl PlayerData {
-    // TODO: Implement writing
-
-    pub fn read_from_stream<S: Read + Seek>(stream: &mut S) -> Result<PlayerData> {
+    pub fn read_from_stream<S: Read + Seek>(stream: &mut S, version: ScenarioVersion) -> Result<PlayerData> {
         let mut data: PlayerData = Default::default();
+        let mut originals = OriginalStrings::default();
         data.version = stream.read_f32()?;
-        data.player_names = stream.read_array(16, |s| s.read_sized_str(256))?;
-        data.player_civs = stream.read_array(16, |s| read_civilization(s))?;
+        data.player_names = (0..PLAYER_SLOT_COUNT)
+            .map(|_| originals.read(stream, PLAYER_NAME_LENGTH))
+            .collect::<Result<_>>()?;
+        data.player_civs = stream.read_array(PLAYER_SLOT_COUNT, |s| read_civilization(s))?;
         data.conquest_victory = (stream.read_u8()?) != 0;
 
         data.unknown1 = vec![0u8; PLAYER_DATA_UNKNOWN_1_LENGTH];
         stream.read_exact(&mut data.unknown1)?;
 
-        data.original_file_name = read_pascal_string(stream)?;
-        data.instructions = read_pascal_string(stream)?;
-        data.hints = read_pascal_string(stream)?;
-        data.victory = read_pascal_string(stream)?;
-        data.loss = read_pascal_string(stream)?;
-        data.history = read_pascal_string(stream)?;
+        data.original_file_name = read_pascal_string(stream, &mut originals)?;
+        data.instructions = read_pascal_string(stream, &mut originals)?;
+        data.hints = read_pascal_string(stream, &mut originals)?;
+        data.victory = read_pascal_string(stream, &mut originals)?;
+        data.loss = read_pascal_string(stream, &mut originals)?;
+        if version.has_history() {
+            data.history = read_pascal_string(stream, &mut originals)?;
+        }
 
-        data.pre_game_cinematic_file_name = read_pascal_string(stream)?;
-        data.victory_cinematic_file_name = read_pascal_string(stream)?;
-        data.loss_cinematic_file_name = read_pascal_string(stream)?;
-        data.background_file_name = read_pascal_string(stream)?;
+        data.pre_game_cinematic_file_name = read_pascal_string(stream, &mut originals)?;
+        data.victory_cinematic_file_name = read_pascal_string(stream, &mut originals)?;
+        data.loss_cinematic_file_name = read_pascal_string(stream, &mut originals)?;
+        data.background_file_name = read_pascal_string(stream, &mut originals)?;
 
         data.preview_thumbnail = read_preview_thumbnail(stream)?;
 
-        data.ai_names = stream.read_array(16, |s| read_pascal_string(s))?;
-        data.city_names = stream.read_array(16, |s| read_pascal_string(s))?;
-        data.personality_names = stream.read_array(16, |s| read_pascal_string(s))?;
-        data.ai_script_configs = stream.read_array(16, |s| read_ai_script_config(s))?;
-        data.ai_types = stream.read_array(4, |s| s.read_u8())?;
+        data.ai_names = read_pascal_strings(stream, &mut originals)?;
+        data.city_names = read_pascal_strings(stream, &mut originals)?;
+        data.personality_names = read_pascal_strings(stream, &mut originals)?;
+        data.ai_script_configs = (0..PLAYER_SLOT_COUNT)
+            .map(|_| read_ai_script_config(stream, &mut originals))
+            .collect::<Result<_>>()?;
+        data.ai_types = stream.read_array(AI_TYPE_COUNT, |s| s.read_u8())?;
 
-        data.player_starting_resources = stream.read_array(16, |s| read_player_starting_resources(s))?;
+        data.player_starting_resources = stream.read_array(PLAYER_SLOT_COUNT,
+                                                           |s| read_player_starting_resources(s))?;
         stream.read_i32()?; // separator (-1)
 
         data.victory_conditions = read_victory_conditions(stream)?;
         data.diplomacy = read_diplomacy(stream)?;
         stream.read_i32()?; // separator (-1)
 
-        data.allied_victory = stream.read_array(16, |s| s.read_u32())?;
-        data.disabled_research_ids = stream.read_array(16, |s| s.read_array(20, |s2| s2.read_u32()))?;
+        data.allied_victory = stream.read_array(PLAYER_SLOT_COUNT, |s| s.read_u32())?;
+        data.disabled_research_ids = stream.read_array(PLAYER_SLOT_COUNT,
+                                                       |s| s.read_array(DISABLED_RESEARCH_COUNT, |s2| s2.read_u32()))?;
 
         data.unused1 = stream.read_u32()?;
         data.unused2 = stream.read_u32()?;
 
-        data.all_techs = stream.read_u32()? != 0;
-        data.starting_ages = stream.read_array(16, |s| s.read_u32())?;
-        stream.read_i32()?; // separator (-1)
+        if version.has_starting_ages() {
+            data.all_techs = stream.read_u32()? != 0;
+            data.starting_ages = stream.read_array(PLAYER_SLOT_COUNT, |s| s.read_u32())?;
+            stream.read_i32()?; // separator (-1)
+        }
 
+        data.originals = originals;
         Ok(data)
     }
+
+    /// Player data with every fixed size list filled in, for building scenarios in tests
+    #[cfg(test)]
+    pub fn blank() -> PlayerData {
+        let mut data: PlayerData = Default::default();
+        data.version = 1.0;
+        data.player_names = vec![String::new(); PLAYER_SLOT_COUNT];
+        data.player_civs = (0..PLAYER_SLOT_COUNT).map(|_| Default::default()).collect();
+        data.unknown1 = vec![0u8; PLAYER_DATA_UNKNOWN_1_LENGTH];
+        data.preview_thumbnail.unknown1 = vec![0u8; 2];
+        data.ai_names = vec![String::new(); PLAYER_SLOT_COUNT];
+        data.city_names = vec![String::new(); PLAYER_SLOT_COUNT];
+        data.personality_names = vec![String::new(); PLAYER_SLOT_COUNT];
+        data.ai_script_configs = (0..PLAYER_SLOT_COUNT).map(|_| Default::default()).collect();
+        data.ai_types = vec![0u8; AI_TYPE_COUNT];
+        data.player_starting_resources = (0..PLAYER_SLOT_COUNT).map(|_| Default::default()).collect();
+        data.diplomacy.stances = vec![vec![0u32; PLAYER_SLOT_COUNT]; PLAYER_SLOT_COUNT];
+        data.diplomacy.individual_victory = vec![vec![0u32; INDIVIDUAL_VICTORY_LENGTH]; PLAYER_SLOT_COUNT];
+        data.allied_victory = vec![0u32; PLAYER_SLOT_COUNT];
+        data.disabled_research_ids = vec![vec![0u32; DISABLED_RESEARCH_COUNT]; PLAYER_SLOT_COUNT];
+        data.starting_ages = vec![0u32; PLAYER_SLOT_COUNT];
+        data
+    }
+
+    pub fn write_to<W: Write>(&self, stream: &mut W, version: ScenarioVersion) -> Result<()> {
+        check_count(&self.player_names, "player names")?;
+        check_count(&self.player_civs, "player civilizations")?;
+        check_count(&self.ai_names, "AI names")?;
+        check_count(&self.city_names, "city names")?;
+        check_count(&self.personality_names, "personality names")?;
+        check_count(&self.ai_script_configs, "AI script configs")?;
+        check_count(&self.player_starting_resources, "player starting resources")?;
+        check_count(&self.allied_victory, "allied victory flags")?;
+        check_count(&self.disabled_research_ids, "disabled research lists")?;
+        if version.has_starting_ages() {
+            check_count(&self.starting_ages, "starting ages")?;
+        }
+        if self.ai_types.len() != AI_TYPE_COUNT {
+            return Err(ErrorKind::InvalidScenario("unexpected number of AI types").into());
+        }
+        if self.disabled_research_ids.iter().any(|ids| ids.len() != DISABLED_RESEARCH_COUNT) {
+            return Err(ErrorKind::InvalidScenario("unexpected number of disabled research IDs").into());
+        }
+
+        let mut strings = self.originals.writer();
+        stream.write_f32(self.version)?;
+        for name in &self.player_names {
+            strings.write_fixed(stream, name, PLAYER_NAME_LENGTH)?;
+        }
+        for civ in &self.player_civs {
+            stream.write_u32(civ.state)?;
+            stream.write_u32(civ.type_id)?;
+            stream.write_i32(*civ.civilization_id as i32)?;
+            stream.write_u32(civ.unknown1)?;
+        }
+        stream.write_u8(self.conquest_victory as u8)?;
+        stream.write_all(&self.unknown1)?;
+
+        for text in &[&self.original_file_name, &self.instructions, &self.hints, &self.victory, &self.loss] {
+            write_pascal_string(stream, &mut strings, text)?;
+        }
+        if version.has_history() {
+            write_pascal_string(stream, &mut strings, &self.history)?;
+        }
+        for text in &[&self.pre_game_cinematic_file_name, &self.victory_cinematic_file_name,
+                      &self.loss_cinematic_file_name, &self.background_file_name] {
+            write_pascal_string(stream, &mut strings, text)?;
+        }
+
+        write_preview_thumbnail(stream, &self.preview_thumbnail)?;
+
+        for text in self.ai_names.iter().chain(self.city_names.iter()).chain(self.personality_names.iter()) {
+            write_pascal_string(stream, &mut strings, text)?;
+        }
+        for config in &self.ai_script_configs {
+            let ai = strings.bytes(&config.ai_file_name)?;
+            let city = strings.bytes(&config.city_file_name)?;
+            let personality = strings.bytes(&config.personality_file_name)?;
+            stream.write_u32(ai.len() as u32)?;
+            stream.write_u32(city.len() as u32)?;
+            stream.write_u32(personality.len() as u32)?;
+            stream.write_all(&ai)?;
+            stream.write_all(&city)?;
+            stream.write_all(&personality)?;
+        }
+        stream.write_all(&self.ai_types)?;
+
+        for resources in &self.player_starting_resources {
+            stream.write_u32(resources.gold)?;
+            stream.write_u32(resources.wood)?;
+            stream.write_u32(resources.food)?;
+            stream.write_u32(resources.stone)?;
+        }
+        stream.write_i32(SEPARATOR)?;
+
+        write_victory_conditions(stream, &self.victory_conditions)?;
+        write_diplomacy(stream, &self.diplomacy)?;
+        stream.write_i32(SEPARATOR)?;
+
+        for value in &self.allied_victory {
+            stream.write_u32(*value)?;
+        }
+        for ids in &self.disabled_research_ids {
+            for id in ids {
+                stream.write_u32(*id)?;
+            }
+        }
+
+        stream.write_u32(self.unused1)?;
+        stream.write_u32(self.unused2)?;
+
+        if version.has_starting_ages() {
+            stream.write_u32(self.all_techs as u32)?;
+            for age in &self.starting_ages {
+                stream.write_u32(*age)?;
+            }
+            stream.write_i32(SEPARATOR)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_count<T>(items: &[T], what: &'static str) -> Result<()> {
+    if items.len() != PLAYER_SLOT_COUNT {
+        return Err(ErrorKind::InvalidScenario(what).into());
+    }
+    Ok(())
 }
 
 fn read_civilization<S: Read + Seek>(stream: &mut S) -> Result<PlayerCivilization> {
@@ -214,15 +362,15 @@ fn read_preview_thumbnail<S: Read + Seek>(stream: &mut S) -> Result<PreviewThumb
     Ok(thumb)
 }
 
-fn read_ai_script_config<S: Read>(stream: &mut S) -> Result<AiScriptConfig> {
+fn read_ai_script_config<S: Read>(stream: &mut S, originals: &mut OriginalStrings) -> Result<AiScriptConfig> {
     let ai_len = stream.read_u32()? as usize;
     let city_len = stream.read_u32()? as usize;
     let per_len = stream.read_u32()? as usize;
 
     Ok(AiScriptConfig {
-        ai_file_name: stream.read_sized_str(ai_len)?,
-        city_file_name: stream.read_sized_str(city_len)?,
-        personality_file_name: stream.read_sized_str(per_len)?,
+        ai_file_name: originals.read(stream, ai_len)?,
+        city_file_name: originals.read(stream, city_len)?,
+        personality_file_name: originals.read(stream, per_len)?,
     })
 }
 
@@ -252,12 +400,68 @@ fn read_victory_conditions<S: Read>(stream: &mut S) -> Result<VictoryConditions>
 
 fn read_diplomacy<S: Read>(stream: &mut S) -> Result<Diplomacy> {
     Ok(Diplomacy {
-        stances: stream.read_array(16, |s| s.read_array(16, |s2| s2.read_u32()))?,
-        individual_victory: stream.read_array(16, |s| s.read_array(180, |s2| s2.read_u32()))?,
+        stances: stream.read_array(PLAYER_SLOT_COUNT, |s| s.read_array(PLAYER_SLOT_COUNT, |s2| s2.read_u32()))?,
+        individual_victory: stream.read_array(PLAYER_SLOT_COUNT,
+                                              |s| s.read_array(INDIVIDUAL_VICTORY_LENGTH, |s2| s2.read_u32()))?,
     })
 }
 
-fn read_pascal_string<S: Read>(stream: &mut S) -> Result<String> {
+pub fn read_pascal_string<S: Read>(stream: &mut S, originals: &mut OriginalStrings) -> Result<String> {
     let length = stream.read_u16()? as usize;
-    Ok(stream.read_sized_str(length)?)
+    originals.read(stream, length)
+}
+
+fn read_pascal_strings<S: Read>(stream: &mut S, originals: &mut OriginalStrings) -> Result<Vec<String>> {
+    (0..PLAYER_SLOT_COUNT).map(|_| read_pascal_string(stream, originals)).collect()
+}
+
+pub fn write_pascal_string<W: Write>(stream: &mut W, strings: &mut StringWriter, text: &str) -> Result<()> {
+    let bytes = strings.bytes(text)?;
+    if bytes.len() > u16::MAX as usize {
+        return Err(ErrorKind::StringTooLong(text.into()).into());
+    }
+    stream.write_u16(bytes.len() as u16)?;
+    stream.write_all(&bytes)?;
+    Ok(())
+}
+
+fn write_preview_thumbnail<W: Write>(stream: &mut W, thumb: &PreviewThumbnail) -> Result<()> {
+    stream.write_u32(thumb.included as u32)?;
+    stream.write_u32(thumb.width)?;
+    stream.write_u32(thumb.height)?;
+    stream.write_all(&thumb.unknown1)?;
+    if thumb.included {
+        stream.write_u32(thumb.pixel_data.len() as u32 + 40)?;
+        stream.write_all(&thumb.unknown2)?;
+        stream.write_all(&thumb.pixel_data)?;
+    }
+    Ok(())
+}
+
+fn write_victory_conditions<W: Write>(stream: &mut W, conditions: &VictoryConditions) -> Result<()> {
+    stream.write_u32(conditions.conquest_required as u32)?;
+    stream.write_u32(conditions.unused1)?;
+    stream.write_u32(conditions.required_relic_count)?;
+    stream.write_u32(conditions.unused2)?;
+    stream.write_u32(conditions.required_exploration_percent)?;
+    stream.write_u32(conditions.unused3)?;
+    stream.write_u32(conditions.all_conditions_required as u32)?;
+    stream.write_u32(conditions.victory_mode)?;
+    stream.write_u32(conditions.score_required)?;
+    stream.write_u32(conditions.timed_game_time)?;
+    Ok(())
+}
+
+fn write_diplomacy<W: Write>(stream: &mut W, diplomacy: &Diplomacy) -> Result<()> {
+    check_count(&diplomacy.stances, "diplomacy stances")?;
+    check_count(&diplomacy.individual_victory, "individual victory conditions")?;
+    if diplomacy.stances.iter().any(|s| s.len() != PLAYER_SLOT_COUNT) ||
+       diplomacy.individual_victory.iter().any(|v| v.len() != INDIVIDUAL_VICTORY_LENGTH) {
+        return Err(ErrorKind::InvalidScenario("unexpected diplomacy size").into());
+    }
+
+    for value in diplomacy.stances.iter().chain(diplomacy.individual_victory.iter()).flatten() {
+        stream.write_u32(*value)?;
+    }
+    Ok(())
 }
@@ -19,12 +19,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::error::Result;
+use crate::error::{ErrorKind, Result};
+use crate::write_ext::WriteExt;
 use identifier::PlayerId;
 
 use chariot_io_tools::{ReadArrayExt, ReadExt};
 
-use std::io::Read;
+use std::io::{Read, Write};
+
+const PLAYER_RESOURCES_COUNT: usize = 8;
 
 #[derive(Default, Debug)]
 pub struct PlayerResources {
@@ -36,15 +39,27 @@ pub struct PlayerResources {
 }
 
 impl PlayerResources {
-    // TODO: Implement writing
-
     pub fn read_from_stream<S: Read>(stream: &mut S) -> Result<Vec<PlayerResources>> {
-        let mut resources = stream.read_array(8, |s| read_single_from_stream(s))?;
+        let mut resources = stream.read_array(PLAYER_RESOURCES_COUNT, |s| read_single_from_stream(s))?;
         for (index, mut resource) in resources.iter_mut().enumerate() {
-            resource.player_id = index.into();
+            // There are no resources for gaia, so the first entry is player 1's
+            resource.player_id = (index + 1).into();
         }
         Ok(resources)
     }
+
+    pub fn write_to<W: Write>(resources: &[PlayerResources], stream: &mut W) -> Result<()> {
+        if resources.len() != PLAYER_RESOURCES_COUNT {
+            return Err(ErrorKind::InvalidScenario("unexpected number of player resources").into());
+        }
+        for resource in resources {
+            stream.write_f32(resource.food)?;
+            stream.write_f32(resource.wood)?;
+            stream.write_f32(resource.gold)?;
+            stream.write_f32(resource.stone)?;
+        }
+        Ok(())
+    }
 }
 
 fn read_single_from_stream<S: Read>(stream: &mut S) -> Result<PlayerResources> {
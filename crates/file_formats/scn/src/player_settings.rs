@@ -0,0 +1,152 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+use crate::player_data::{read_pascal_string, write_pascal_string};
+use crate::text::OriginalStrings;
+use crate::write_ext::WriteExt;
+
+use chariot_io_tools::{ReadArrayExt, ReadExt};
+
+use std::io::{Read, Write};
+
+const AI_DIPLOMACY_COUNT: usize = 9;
+const UNKNOWN_ENTRY_LENGTH: usize = 44;
+const VERSION_2_UNKNOWN_LENGTH: usize = 8;
+const TRAILER_LENGTH: usize = 7;
+
+/// Per player settings stored after the units (players 1 through 8; gaia has none)
+#[derive(Default, Debug)]
+pub struct PlayerSettings {
+    pub name: String,
+    pub editor_camera_x: f32,
+    pub editor_camera_y: f32,
+    pub initial_camera_x: i16,
+    pub initial_camera_y: i16,
+    pub allied_victory: u8,
+
+    /// Stance towards each player, gaia included
+    pub diplomacy: Vec<u8>,
+    pub ai_diplomacy: Vec<u32>,
+    pub victory_version: f32,
+
+    // Unknown values, kept so that they can be written back out
+    unknown_version_2: Vec<u8>,
+    unknown_entries: Vec<u8>,
+    unknown_trailer: Vec<u8>,
+    unknown_i32: i32,
+
+    originals: OriginalStrings,
+}
+
+impl PlayerSettings {
+    /// Settings with the fixed size lists filled in, for building scenarios in tests
+    #[cfg(test)]
+    pub fn blank() -> PlayerSettings {
+        let mut settings: PlayerSettings = Default::default();
+        settings.diplomacy = vec![0u8; 9];
+        settings.ai_diplomacy = vec![0u32; AI_DIPLOMACY_COUNT];
+        settings.victory_version = 2.0;
+        settings
+    }
+
+    /// Reads the settings for every player, preceded by the player count (which includes gaia)
+    pub fn read_from_stream<S: Read>(stream: &mut S) -> Result<Vec<PlayerSettings>> {
+        let player_count = stream.read_u32()? as usize;
+        if player_count == 0 {
+            return Err(ErrorKind::InvalidScenario("player settings without gaia").into());
+        }
+        (1..player_count).map(|_| read_single_from_stream(stream)).collect()
+    }
+
+    pub fn write_to<W: Write>(settings: &[PlayerSettings], stream: &mut W) -> Result<()> {
+        stream.write_u32(settings.len() as u32 + 1)?;
+        for player in settings {
+            player.write_single_to(stream)?;
+        }
+        Ok(())
+    }
+
+    fn write_single_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        if self.ai_diplomacy.len() != AI_DIPLOMACY_COUNT {
+            return Err(ErrorKind::InvalidScenario("unexpected number of AI diplomacy stances").into());
+        }
+        if self.unknown_entries.len() % UNKNOWN_ENTRY_LENGTH != 0 {
+            return Err(ErrorKind::InvalidScenario("player settings have a partial entry").into());
+        }
+
+        write_pascal_string(stream, &mut self.originals.writer(), &self.name)?;
+        stream.write_f32(self.editor_camera_x)?;
+        stream.write_f32(self.editor_camera_y)?;
+        stream.write_i16(self.initial_camera_x)?;
+        stream.write_i16(self.initial_camera_y)?;
+        stream.write_u8(self.allied_victory)?;
+        stream.write_u16(self.diplomacy.len() as u16)?;
+        stream.write_all(&self.diplomacy)?;
+        for stance in &self.ai_diplomacy {
+            stream.write_u32(*stance)?;
+        }
+        stream.write_f32(self.victory_version)?;
+        stream.write_u16((self.unknown_entries.len() / UNKNOWN_ENTRY_LENGTH) as u16)?;
+        if self.victory_version == 2.0 {
+            let mut unknown = self.unknown_version_2.clone();
+            unknown.resize(VERSION_2_UNKNOWN_LENGTH, 0);
+            stream.write_all(&unknown)?;
+        }
+        stream.write_all(&self.unknown_entries)?;
+
+        let mut trailer = self.unknown_trailer.clone();
+        trailer.resize(TRAILER_LENGTH, 0);
+        stream.write_all(&trailer)?;
+        stream.write_i32(self.unknown_i32)?;
+        Ok(())
+    }
+}
+
+fn read_single_from_stream<S: Read>(stream: &mut S) -> Result<PlayerSettings> {
+    let mut settings: PlayerSettings = Default::default();
+    settings.name = read_pascal_string(stream, &mut settings.originals)?;
+    settings.editor_camera_x = stream.read_f32()?;
+    settings.editor_camera_y = stream.read_f32()?;
+    settings.initial_camera_x = stream.read_i16()?;
+    settings.initial_camera_y = stream.read_i16()?;
+    settings.allied_victory = stream.read_u8()?;
+
+    let diplomacy_count = stream.read_u16()? as usize;
+    settings.diplomacy = vec![0u8; diplomacy_count];
+    stream.read_exact(&mut settings.diplomacy)?;
+    settings.ai_diplomacy = stream.read_array(AI_DIPLOMACY_COUNT, |s| s.read_u32())?;
+    settings.victory_version = stream.read_f32()?;
+
+    let unknown_count = stream.read_u16()? as usize;
+    if settings.victory_version == 2.0 {
+        settings.unknown_version_2 = vec![0u8; VERSION_2_UNKNOWN_LENGTH];
+        stream.read_exact(&mut settings.unknown_version_2)?;
+    }
+    settings.unknown_entries = vec![0u8; UNKNOWN_ENTRY_LENGTH * unknown_count];
+    stream.read_exact(&mut settings.unknown_entries)?;
+
+    settings.unknown_trailer = vec![0u8; TRAILER_LENGTH];
+    stream.read_exact(&mut settings.unknown_trailer)?;
+    settings.unknown_i32 = stream.read_i32()?;
+    Ok(settings)
+}
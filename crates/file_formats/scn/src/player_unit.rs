@@ -20,11 +20,13 @@
 // SOFTWARE.
 
 use crate::error::Result;
+use crate::version::ScenarioVersion;
+use crate::write_ext::WriteExt;
 use identifier::{SpawnId, UnitId};
 
 use chariot_io_tools::ReadExt;
 
-use std::io::Read;
+use std::io::{Read, Write};
 
 #[derive(Default, Debug)]
 pub struct PlayerUnit {
@@ -38,9 +40,7 @@ pub struct PlayerUnit {
 }
 
 impl PlayerUnit {
-    // TODO: Implement writing
-
-    pub fn read_from_stream<S: Read>(stream: &mut S) -> Result<PlayerUnit> {
+    pub fn read_from_stream<S: Read>(stream: &mut S, version: ScenarioVersion) -> Result<PlayerUnit> {
         let mut data: PlayerUnit = Default::default();
         data.position_x = stream.read_f32()?;
         data.position_y = stream.read_f32()?;
@@ -48,7 +48,22 @@ impl PlayerUnit {
         data.spawn_id = optional_id!(stream.read_i32()?);
         data.unit_id = required_id!(stream.read_i16()?);
         data.state = stream.read_u8()?;
-        data.rotation = stream.read_f32()?;
+        if version.has_unit_rotation() {
+            data.rotation = stream.read_f32()?;
+        }
         Ok(data)
     }
+
+    pub fn write_to<W: Write>(&self, stream: &mut W, version: ScenarioVersion) -> Result<()> {
+        stream.write_f32(self.position_x)?;
+        stream.write_f32(self.position_y)?;
+        stream.write_f32(self.position_z)?;
+        stream.write_i32(self.spawn_id.map(|id| *id as i32).unwrap_or(-1))?;
+        stream.write_i16(*self.unit_id as i16)?;
+        stream.write_u8(self.state)?;
+        if version.has_unit_rotation() {
+            stream.write_f32(self.rotation)?;
+        }
+        Ok(())
+    }
 }
@@ -21,32 +21,49 @@
 
 use crate::error::{ErrorKind, Result};
 
-use identifier::{CivilizationId, PlayerId};
+use dat::Age;
+use identifier::{CivilizationId, PlayerId, ResearchId};
 use chariot_io_tools::{ReadExt, ReadArrayExt};
 use crate::map::Map;
 use crate::player_data::PlayerData;
 use crate::player_resources::PlayerResources;
+use crate::player_settings::PlayerSettings;
 use crate::player_unit::PlayerUnit;
+use crate::text::OriginalStrings;
+use crate::version::ScenarioVersion;
+use crate::write_ext::WriteExt;
 use std::fs::File;
 
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+
 use std::io;
-use std::io::prelude::{Read, Seek};
+use std::io::prelude::{Read, Seek, Write};
 use std::path::Path;
 
 #[derive(Default, Debug)]
 pub struct Scenario {
     header: ScenarioHeader,
+    next_unit_id: u32,
     pub player_data: PlayerData,
     player_resources: Vec<PlayerResources>,
     player_units: Vec<Vec<PlayerUnit>>,
     pub map: Map,
+
+    /// Settings for players 1 through 8, if they could be parsed
+    pub player_settings: Vec<PlayerSettings>,
+
+    /// Anything after the units that couldn't be parsed, kept as is. Scenarios from before Age
+    /// of Kings have no trigger section; victory is driven by the per-player conditions in
+    /// `player_data`.
+    unparsed_data: Vec<u8>,
 }
 
 impl Scenario {
-    /// Retrieves player resources by player ID
+    /// Retrieves player resources by player ID. Gaia (player 0) doesn't have any.
     #[inline]
-    pub fn player_resources<'a>(&'a self, player_id: PlayerId) -> &'a PlayerResources {
-        &self.player_resources[*player_id as usize]
+    pub fn player_resources<'a>(&'a self, player_id: PlayerId) -> Option<&'a PlayerResources> {
+        self.player_resources.iter().find(|resources| resources.player_id == player_id)
     }
 
     /// Retrieves a list of units by player ID
@@ -61,13 +78,37 @@ impl Scenario {
         self.player_data.player_civs[*player_id as usize].civilization_id
     }
 
+    /// Returns the research the given player isn't allowed to do; empty slots are -1
+    pub fn disabled_research_ids(&self, player_id: PlayerId) -> Vec<ResearchId> {
+        self.player_data.disabled_research_ids[*player_id as usize]
+            .iter()
+            .filter(|&&research_id| research_id as i32 >= 0)
+            .map(|&research_id| (research_id as usize).into())
+            .collect()
+    }
+
+    /// Returns the age the given player starts the game in. Scenarios without starting ages start
+    /// everyone in the Stone Age, and the post-Iron Age starts them in the Iron Age.
+    pub fn starting_age(&self, player_id: PlayerId) -> Age {
+        match self.player_data.starting_ages.get(*player_id as usize) {
+            Some(1) => Age::Tool,
+            Some(2) => Age::Bronze,
+            Some(3) | Some(4) => Age::Iron,
+            _ => Age::Stone,
+        }
+    }
+
+    /// Returns the format version the scenario was read with (and will be written with)
+    #[inline]
+    pub fn version(&self) -> ScenarioVersion {
+        self.header.version
+    }
+
     /// Returns all of the player IDs the scenario contains data for
     pub fn player_ids(&self) -> Vec<PlayerId> {
         (0..self.player_units.len()).map(|i| i.into()).collect()
     }
 
-    // TODO: Implement writing
-
     pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<Scenario> {
         Scenario::read_from_stream(File::open(file_name.as_ref())?)
     }
@@ -75,51 +116,97 @@ impl Scenario {
     pub fn read_from_stream<S: Read + Seek>(mut stream: S) -> Result<Scenario> {
         let mut scenario: Scenario = Default::default();
         scenario.header = ScenarioHeader::read_from_stream(&mut stream)?;
+        let version = scenario.header.version;
 
         let mut stream = io::Cursor::new(stream.read_and_decompress()?);
 
-        let _next_unit_id = stream.read_u32()?; // not sure what this is for yet
-        scenario.player_data = PlayerData::read_from_stream(&mut stream)?;
-        scenario.map = Map::read_from_stream(&mut stream)?;
+        scenario.next_unit_id = stream.read_u32()?;
+        scenario.player_data = PlayerData::read_from_stream(&mut stream, version)?;
+        scenario.map = Map::read_from_stream(&mut stream, version)?;
 
         let player_unit_group_count = stream.read_u32()? as isize;
         scenario.player_resources = PlayerResources::read_from_stream(&mut stream)?;
 
         for _player_index in 0..player_unit_group_count {
             let unit_count = stream.read_u32()? as usize;
-            let units = stream.read_array(unit_count, |s| PlayerUnit::read_from_stream(s))?;
+            let units = stream.read_array(unit_count, |s| PlayerUnit::read_from_stream(s, version))?;
             scenario.player_units.push(units);
         }
 
-        // TODO: Read other player data
-        // TODO: Read triggers
+        let settings_position = stream.position();
+        match PlayerSettings::read_from_stream(&mut stream) {
+            Ok(settings) => scenario.player_settings = settings,
+            Err(_) => stream.set_position(settings_position),
+        }
+        stream.read_to_end(&mut scenario.unparsed_data)?;
 
         Ok(scenario)
     }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let mut file = io::BufWriter::new(File::create(file_name.as_ref())?);
+        self.write_to(&mut file)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Writes the scenario with its body compressed the way the game expects. Reading a
+    /// scenario and writing it back reproduces the original decompressed data exactly, but
+    /// the compressed bytes may differ from those written by the original compressor.
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        self.header.write_to(stream)?;
+
+        let mut encoder = DeflateEncoder::new(stream, Compression::default());
+        self.write_body_to(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+
+    /// Writes everything after the header, uncompressed
+    fn write_body_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let version = self.header.version;
+        stream.write_u32(self.next_unit_id)?;
+        self.player_data.write_to(stream, version)?;
+        self.map.write_to(stream, version)?;
+
+        stream.write_u32(self.player_units.len() as u32)?;
+        PlayerResources::write_to(&self.player_resources, stream)?;
+        for units in &self.player_units {
+            stream.write_u32(units.len() as u32)?;
+            for unit in units {
+                unit.write_to(stream, version)?;
+            }
+        }
+
+        if !self.player_settings.is_empty() {
+            PlayerSettings::write_to(&self.player_settings, stream)?;
+        }
+        stream.write_all(&self.unparsed_data)?;
+        Ok(())
+    }
 }
 
 const REASONABLE_INSTRUCTION_LIMIT: usize = 512 * 1024; // 0.5 mibibytes
 
 #[derive(Default, Debug)]
 struct ScenarioHeader {
-    version: String,
+    version: ScenarioVersion,
     length: u32,
     save_type: i32,
     last_save_time: u32,
     instructions: String,
     victory_type: u32,
     player_count: u32,
+    originals: OriginalStrings,
 }
 
-impl ScenarioHeader {
-    // TODO: Implement writing
+/// Size of the header fields that follow `length` and are counted by it
+const HEADER_FIXED_LENGTH: u32 = 20;
 
+impl ScenarioHeader {
     fn read_from_stream<S: Read + Seek>(stream: &mut S) -> Result<ScenarioHeader> {
         let mut header: ScenarioHeader = Default::default();
-        header.version = stream.read_sized_str(4)?;
-        if header.version != "1.11" {
-            return Err(ErrorKind::UnrecognizedScenarioVersion.into());
-        }
+        header.version = ScenarioVersion::parse(&stream.read_sized_str(4)?)?;
 
         header.length = stream.read_u32()?;
         header.save_type = stream.read_i32()?;
@@ -130,10 +217,150 @@ impl ScenarioHeader {
                 // Refuse to load too many instructions
                 return Err(ErrorKind::InstructionsTooLarge.into());
             }
-            stream.read_sized_str(length)?
+            header.originals.read(stream, length)?
         };
         header.victory_type = stream.read_u32()?;
         header.player_count = stream.read_u32()?;
         Ok(header)
     }
+
+    fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let instructions = self.originals.writer().bytes(&self.instructions)?;
+        if instructions.len() > REASONABLE_INSTRUCTION_LIMIT {
+            return Err(ErrorKind::InstructionsTooLarge.into());
+        }
+
+        stream.write_all(self.version.to_header_string().as_bytes())?;
+        stream.write_u32(HEADER_FIXED_LENGTH + instructions.len() as u32)?;
+        stream.write_i32(self.save_type)?;
+        stream.write_u32(self.last_save_time)?;
+        stream.write_u32(instructions.len() as u32)?;
+        stream.write_all(&instructions)?;
+        stream.write_u32(self.victory_type)?;
+        stream.write_u32(self.player_count)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_scenario() -> Scenario {
+        let mut scenario: Scenario = Default::default();
+        scenario.header.instructions = "Defend the city".into();
+        scenario.header.player_count = 2;
+        scenario.next_unit_id = 3;
+
+        scenario.player_data = PlayerData::blank();
+        scenario.player_data.player_names[1] = "Ramses".into();
+        scenario.player_data.history = "Long ago...".into();
+        scenario.player_data.disabled_research_ids[1] = vec![!0u32; 20];
+        scenario.player_data.disabled_research_ids[1][0] = 12;
+        scenario.player_data.starting_ages[1] = 2;
+
+        scenario.map.width = 2;
+        scenario.map.height = 2;
+        scenario.map.tiles = (0..4).map(|_| Default::default()).collect();
+
+        scenario.player_resources = (0..8usize)
+            .map(|index| {
+                let mut resources: PlayerResources = Default::default();
+                resources.player_id = (index + 1).into();
+                resources
+            })
+            .collect();
+        scenario.player_resources[0].wood = 200.0;
+        scenario.player_units = (0..9).map(|_| Vec::new()).collect();
+        let mut unit: PlayerUnit = Default::default();
+        unit.position_x = 1.5;
+        unit.position_y = 0.5;
+        scenario.player_units[1].push(unit);
+
+        scenario.player_settings = (0..8).map(|_| PlayerSettings::blank()).collect();
+        scenario.player_settings[0].name = "Ramses".into();
+        scenario
+    }
+
+    fn body_bytes(scenario: &Scenario) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        scenario.write_body_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let scenario = test_scenario();
+        let mut written = Vec::new();
+        scenario.write_to(&mut written).unwrap();
+
+        let read = Scenario::read_from_stream(io::Cursor::new(&written)).unwrap();
+        assert_eq!("Defend the city", read.header.instructions);
+        assert_eq!("Ramses", read.player_data.player_names[1]);
+        assert_eq!("Long ago...", read.player_data.history);
+        assert_eq!(1, read.player_units(1.into()).len());
+        assert_eq!(200.0, read.player_resources(1.into()).unwrap().wood);
+        assert!(read.player_resources(0.into()).is_none());
+        assert_eq!(8, read.player_settings.len());
+        let disabled: ResearchId = 12usize.into();
+        assert_eq!(vec![disabled], read.disabled_research_ids(1.into()));
+        assert_eq!(Age::Bronze, read.starting_age(1.into()));
+        assert_eq!(Age::Stone, read.starting_age(2.into()));
+        assert!(read.unparsed_data.is_empty());
+        assert_eq!(body_bytes(&scenario), body_bytes(&read));
+    }
+
+    #[test]
+    fn test_round_trip_other_versions() {
+        for version in &[ScenarioVersion::Early(2), ScenarioVersion::Early(5), ScenarioVersion::RiseOfRome(12)] {
+            let mut scenario = test_scenario();
+            scenario.header.version = *version;
+            scenario.player_data.history.clear();
+            let mut written = Vec::new();
+            scenario.write_to(&mut written).unwrap();
+
+            let read = Scenario::read_from_stream(io::Cursor::new(&written)).unwrap();
+            assert_eq!(*version, read.version());
+            assert_eq!("Ramses", read.player_data.player_names[1]);
+            assert_eq!(1.5, read.player_units(1.into())[0].position_x);
+            assert_eq!(8, read.player_settings.len());
+            assert_eq!(body_bytes(&scenario), body_bytes(&read));
+        }
+    }
+
+    #[test]
+    fn test_unparsed_data_is_kept() {
+        let mut scenario = test_scenario();
+        scenario.player_settings.clear();
+        scenario.unparsed_data = vec![1, 2, 3];
+        let mut written = Vec::new();
+        scenario.write_to(&mut written).unwrap();
+
+        let read = Scenario::read_from_stream(io::Cursor::new(&written)).unwrap();
+        assert!(read.player_settings.is_empty());
+        assert_eq!(vec![1, 2, 3], read.unparsed_data);
+    }
+
+    #[test]
+    fn test_original_string_bytes_are_kept() {
+        let mut body = body_bytes(&test_scenario());
+        // Put some garbage after the terminator of the first player name
+        let name_start = 4 + 4 + 256;
+        body[name_start + 7] = b'x';
+
+        let mut header = Vec::new();
+        test_scenario().header.write_to(&mut header).unwrap();
+        let mut file = header;
+        let mut encoder = DeflateEncoder::new(&mut file, Compression::default());
+        encoder.write_all(&body).unwrap();
+        encoder.finish().unwrap();
+
+        let mut read = Scenario::read_from_stream(io::Cursor::new(&file)).unwrap();
+        assert_eq!("Ramses", read.player_data.player_names[1]);
+        assert_eq!(body, body_bytes(&read));
+
+        read.player_data.player_names[1] = "Hatshepsut".into();
+        let changed = body_bytes(&read);
+        assert_eq!(b"Hatshepsut\0\0", &changed[name_start..name_start + 12]);
+    }
 }
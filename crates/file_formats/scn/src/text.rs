@@ -0,0 +1,103 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+
+use std::io::{Read, Write};
+
+/// Scenario text is stored one byte per character (the game uses the Windows-1252 code page,
+/// which we treat as Latin-1), usually null terminated, and sometimes with leftover bytes
+/// after the terminator. Only the text before the terminator is exposed, so the bytes as
+/// they were read are kept here in order. When a string is written back unchanged, its
+/// original bytes are used so that the file comes out exactly as it went in.
+#[derive(Default, Debug, Clone)]
+pub struct OriginalStrings {
+    strings: Vec<Vec<u8>>,
+}
+
+impl OriginalStrings {
+    /// Reads a string of the given byte length
+    pub fn read<S: Read>(&mut self, stream: &mut S, length: usize) -> Result<String> {
+        let mut bytes = vec![0u8; length];
+        stream.read_exact(&mut bytes)?;
+        let text = decode(&bytes);
+        self.strings.push(bytes);
+        Ok(text)
+    }
+
+    /// Returns a writer that hands out the original bytes in the order they were read
+    pub fn writer(&self) -> StringWriter<'_> {
+        StringWriter {
+            originals: self,
+            next: 0,
+        }
+    }
+}
+
+pub struct StringWriter<'a> {
+    originals: &'a OriginalStrings,
+    next: usize,
+}
+
+impl<'a> StringWriter<'a> {
+    /// Returns the bytes to write for the next string. New or changed strings are
+    /// null terminated unless they are empty.
+    pub fn bytes(&mut self, text: &str) -> Result<Vec<u8>> {
+        let original = self.originals.strings.get(self.next);
+        self.next += 1;
+        match original {
+            Some(bytes) if decode(bytes) == text => Ok(bytes.clone()),
+            _ => {
+                let mut bytes = encode(text)?;
+                if !bytes.is_empty() {
+                    bytes.push(0);
+                }
+                Ok(bytes)
+            },
+        }
+    }
+
+    /// Writes the next string into a fixed size, zero padded field
+    pub fn write_fixed<W: Write>(&mut self, stream: &mut W, text: &str, length: usize) -> Result<()> {
+        let mut bytes = self.bytes(text)?;
+        if bytes.len() > length {
+            return Err(ErrorKind::StringTooLong(text.into()).into());
+        }
+        bytes.resize(length, 0);
+        stream.write_all(&bytes)?;
+        Ok(())
+    }
+}
+
+fn decode(bytes: &[u8]) -> String {
+    bytes.iter().take_while(|b| **b != 0).map(|b| *b as char).collect()
+}
+
+fn encode(text: &str) -> Result<Vec<u8>> {
+    text.chars()
+        .map(|c| if (c as u32) < 0x100 && c != '\0' {
+            Ok(c as u8)
+        } else {
+            Err(ErrorKind::UnencodableCharacter(c).into())
+        })
+        .collect()
+}
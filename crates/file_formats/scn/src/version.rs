@@ -0,0 +1,122 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+
+/// Scenario format version, as stored in the first four bytes of the file ("1.xx")
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScenarioVersion {
+    /// "1.00" through "1.09", saved by the early releases of Age of Empires
+    Early(u8),
+
+    /// "1.10" and "1.11", saved by Age of Empires
+    AgeOfEmpires(u8),
+
+    /// "1.12" through "1.17", saved by the Rise of Rome expansion
+    RiseOfRome(u8),
+}
+
+// Versions from 1.18 onward belong to Age of Kings, which has a different layout
+const FIRST_AOE_MINOR: u8 = 10;
+const FIRST_ROR_MINOR: u8 = 12;
+const LAST_ROR_MINOR: u8 = 17;
+
+// Sections that were added to the format over time
+const HISTORY_MINOR: u8 = 3;
+const UNIT_ROTATION_MINOR: u8 = 5;
+const TILE_PADDING_MINOR: u8 = 6;
+const STARTING_AGES_MINOR: u8 = 7;
+
+impl ScenarioVersion {
+    pub fn from_minor(minor: u8) -> Result<ScenarioVersion> {
+        match minor {
+            0..=9 => Ok(ScenarioVersion::Early(minor)),
+            FIRST_AOE_MINOR..=11 => Ok(ScenarioVersion::AgeOfEmpires(minor)),
+            FIRST_ROR_MINOR..=LAST_ROR_MINOR => Ok(ScenarioVersion::RiseOfRome(minor)),
+            _ => Err(ErrorKind::UnrecognizedScenarioVersion.into()),
+        }
+    }
+
+    /// Parses the version string from the scenario header
+    pub fn parse(version: &str) -> Result<ScenarioVersion> {
+        let bytes = version.as_bytes();
+        if bytes.len() != 4 || &bytes[0..2] != b"1." || !bytes[2..].iter().all(|b| b.is_ascii_digit()) {
+            return Err(ErrorKind::UnrecognizedScenarioVersion.into());
+        }
+        ScenarioVersion::from_minor((bytes[2] - b'0') * 10 + (bytes[3] - b'0'))
+    }
+
+    pub fn minor(self) -> u8 {
+        match self {
+            ScenarioVersion::Early(minor) |
+            ScenarioVersion::AgeOfEmpires(minor) |
+            ScenarioVersion::RiseOfRome(minor) => minor,
+        }
+    }
+
+    /// The version string as it's stored in the scenario header
+    pub fn to_header_string(self) -> String {
+        format!("1.{:02}", self.minor())
+    }
+
+    /// Whether the scenario messages include the history text
+    pub fn has_history(self) -> bool {
+        self.minor() >= HISTORY_MINOR
+    }
+
+    /// Whether placed units store their rotation
+    pub fn has_unit_rotation(self) -> bool {
+        self.minor() >= UNIT_ROTATION_MINOR
+    }
+
+    /// Whether each map tile is followed by an unused byte
+    pub fn has_tile_padding(self) -> bool {
+        self.minor() >= TILE_PADDING_MINOR
+    }
+
+    /// Whether the player data ends with the "all techs" flag and starting ages
+    pub fn has_starting_ages(self) -> bool {
+        self.minor() >= STARTING_AGES_MINOR
+    }
+}
+
+impl Default for ScenarioVersion {
+    fn default() -> ScenarioVersion {
+        ScenarioVersion::AgeOfEmpires(11)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(ScenarioVersion::Early(2), ScenarioVersion::parse("1.02").unwrap());
+        assert_eq!(ScenarioVersion::AgeOfEmpires(11), ScenarioVersion::parse("1.11").unwrap());
+        assert_eq!(ScenarioVersion::RiseOfRome(15), ScenarioVersion::parse("1.15").unwrap());
+        assert_eq!("1.07", ScenarioVersion::parse("1.07").unwrap().to_header_string());
+        assert!(ScenarioVersion::parse("1.18").is_err());
+        assert!(ScenarioVersion::parse("2.00").is_err());
+        assert!(ScenarioVersion::parse("1.1").is_err());
+    }
+}
@@ -19,12 +19,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use serde::{Deserialize, Serialize};
+
 use std::fmt;
 use std::ops::Deref;
 
 macro_rules! create_id_type {
     ($name:ident, $underlying_type:ty) => {
-        #[derive(Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        #[derive(Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+        #[serde(transparent)]
         pub struct $name($underlying_type);
 
         // Implement Debug instead of deriving it so that we can keep it all
@@ -111,7 +114,7 @@ create_id_type!(TerrainId, u8);
 create_id_type!(TerrainBorderId, u8);
 
 /// Different classes of terrain restriction for a unit
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum UnitTerrainRestrictionId {
     /// Units that fly or are in the air (dying units and missiles)
     Flying,
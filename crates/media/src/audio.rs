@@ -0,0 +1,207 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::Result;
+use crate::sound::Sound;
+
+use sdl2;
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+
+use std::sync::Arc;
+
+/// Plays sound effects
+pub trait Audio {
+    /// Starts playing a sound. `volume` ranges from 0 (silent) to 1 (full), and `pan`
+    /// from -1 (left speaker only) through 0 (centered) to 1 (right speaker only).
+    fn play(&mut self, sound: &Arc<Sound>, volume: f32, pan: f32);
+}
+
+/// A sound that was handed to `NullAudio`
+#[derive(Clone, Debug)]
+pub struct PlayedSound {
+    pub sound: Arc<Sound>,
+    pub volume: f32,
+    pub pan: f32,
+}
+
+/// Audio that doesn't make any noise, for running without a sound device (and in tests).
+/// Everything it's asked to play is recorded.
+#[derive(Default)]
+pub struct NullAudio {
+    played: Vec<PlayedSound>,
+}
+
+impl NullAudio {
+    pub fn new() -> NullAudio {
+        Default::default()
+    }
+
+    pub fn played(&self) -> &[PlayedSound] {
+        &self.played
+    }
+
+    /// Removes and returns the sounds played so far
+    pub fn take_played(&mut self) -> Vec<PlayedSound> {
+        std::mem::replace(&mut self.played, Vec::new())
+    }
+}
+
+impl Audio for NullAudio {
+    fn play(&mut self, sound: &Arc<Sound>, volume: f32, pan: f32) {
+        self.played.push(PlayedSound {
+            sound: sound.clone(),
+            volume: volume,
+            pan: pan,
+        });
+    }
+}
+
+/// Converts a volume and pan into left and right speaker gains
+pub fn stereo_gains(volume: f32, pan: f32) -> (f32, f32) {
+    let volume = volume.max(0.0).min(1.0);
+    let pan = pan.max(-1.0).min(1.0);
+    (volume * (1.0 - pan).min(1.0), volume * (1.0 + pan).min(1.0))
+}
+
+const OUTPUT_FREQUENCY: i32 = 22050;
+const OUTPUT_CHANNELS: u8 = 2;
+
+pub struct SdlAudio {
+    _subsystem: sdl2::AudioSubsystem,
+    device: AudioDevice<Mixer>,
+}
+
+impl SdlAudio {
+    pub fn new(context: &sdl2::Sdl) -> Result<SdlAudio> {
+        let subsystem = context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(OUTPUT_FREQUENCY),
+            channels: Some(OUTPUT_CHANNELS),
+            samples: None,
+        };
+        let device = subsystem.open_playback(None, &desired, |spec| {
+                Mixer {
+                    output_frequency: spec.freq as f64,
+                    output_channels: spec.channels as usize,
+                    voices: Vec::new(),
+                }
+            })?;
+        device.resume();
+
+        Ok(SdlAudio {
+            _subsystem: subsystem,
+            device: device,
+        })
+    }
+}
+
+impl Audio for SdlAudio {
+    fn play(&mut self, sound: &Arc<Sound>, volume: f32, pan: f32) {
+        let (left, right) = stereo_gains(volume, pan);
+        self.device.lock().voices.push(Voice {
+            sound: sound.clone(),
+            position: 0.0,
+            left: left,
+            right: right,
+        });
+    }
+}
+
+/// A sound that is currently playing
+struct Voice {
+    sound: Arc<Sound>,
+
+    /// Current frame in the sound; fractional since the sound may not match the output frequency
+    position: f64,
+    left: f32,
+    right: f32,
+}
+
+impl Voice {
+    fn is_finished(&self) -> bool {
+        self.position as usize >= self.sound.frame_count()
+    }
+}
+
+/// Mixes all of the playing sounds together on SDL's audio thread
+struct Mixer {
+    output_frequency: f64,
+    output_channels: usize,
+    voices: Vec<Voice>,
+}
+
+impl AudioCallback for Mixer {
+    type Channel = i16;
+
+    fn callback(&mut self, out: &mut [i16]) {
+        for sample in out.iter_mut() {
+            *sample = 0;
+        }
+
+        for voice in &mut self.voices {
+            let step = voice.sound.sample_rate as f64 / self.output_frequency;
+            for frame in out.chunks_mut(self.output_channels) {
+                if voice.is_finished() {
+                    break;
+                }
+                let (left, right) = voice.sound.frame(voice.position as usize);
+                let left = (left as f32 * voice.left) as i16;
+                let right = (right as f32 * voice.right) as i16;
+                if frame.len() >= 2 {
+                    frame[0] = frame[0].saturating_add(left);
+                    frame[1] = frame[1].saturating_add(right);
+                } else {
+                    frame[0] = frame[0].saturating_add(left / 2 + right / 2);
+                }
+                voice.position += step;
+            }
+        }
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sound::encode_wav;
+
+    #[test]
+    fn test_stereo_gains() {
+        assert_eq!((1.0, 1.0), stereo_gains(1.0, 0.0));
+        assert_eq!((1.0, 0.0), stereo_gains(1.0, -1.0));
+        assert_eq!((0.25, 0.5), stereo_gains(0.5, 0.5));
+        assert_eq!((0.0, 1.0), stereo_gains(2.0, 3.0));
+    }
+
+    #[test]
+    fn test_null_audio_records_sounds() {
+        let sound = Arc::new(Sound::from_wav(7.into(), &encode_wav(1, 11025, 8, &[128])).unwrap());
+        let mut audio = NullAudio::new();
+        audio.play(&sound, 0.5, -0.25);
+
+        let played = audio.take_played();
+        assert_eq!(1, played.len());
+        assert_eq!(7, *played[0].sound.id);
+        assert_eq!((0.5, -0.25), (played[0].volume, played[0].pan));
+        assert!(audio.played().is_empty());
+    }
+}
@@ -30,5 +30,22 @@ error_chain! {
         WindowBuildError(::sdl2::video::WindowBuildError);
         IntegerOrSdlError(::sdl2::IntegerOrSdlError);
         TextureValueError(::sdl2::render::TextureValueError);
+        PngEncodingError(::png::EncodingError);
+        PngDecodingError(::png::DecodingError);
+    }
+
+    errors {
+        InvalidWav(reason: &'static str) {
+            description("invalid WAV file")
+            display("invalid WAV file: {}", reason)
+        }
+        UnsupportedPng(reason: &'static str) {
+            description("unsupported PNG image")
+            display("unsupported PNG image: {}", reason)
+        }
+        InvalidInputScript(line: usize, reason: &'static str) {
+            description("invalid input script")
+            display("invalid input script on line {}: {}", line, reason)
+        }
     }
 }
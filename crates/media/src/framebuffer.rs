@@ -0,0 +1,331 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+use crate::types::{Color, Rect};
+
+use nalgebra::Vector2;
+
+use std::cmp;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::io::prelude::{Read, Write};
+use std::path::Path;
+
+/// An image in memory that can be drawn into without a window or a graphics card.
+/// Pixels are stored as RGBA8888 `u32`s (red in the most significant byte), the same
+/// format palettes are converted into.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Framebuffer {
+        Framebuffer {
+            width: width,
+            height: height,
+            pixels: vec![0u32; (width * height) as usize],
+        }
+    }
+
+    /// Creates a framebuffer out of existing RGBA8888 pixels, row by row from the top left
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u32>) -> Framebuffer {
+        assert_eq!((width * height) as usize, pixels.len(), "pixel count doesn't match the size");
+        Framebuffer {
+            width: width,
+            height: height,
+            pixels: pixels,
+        }
+    }
+
+    pub fn read_png_from_file<P: AsRef<Path>>(file_name: P) -> Result<Framebuffer> {
+        let file = File::open(file_name.as_ref())?;
+        Framebuffer::read_png_from(&mut BufReader::new(file))
+    }
+
+    /// Reads an 8-bit RGB or RGBA PNG image (paletted images are expanded to RGB)
+    pub fn read_png_from<R: Read>(stream: &mut R) -> Result<Framebuffer> {
+        let (info, mut reader) = png::Decoder::new(stream).read_info()?;
+        if info.bit_depth != png::BitDepth::Eight {
+            return Err(ErrorKind::UnsupportedPng("only 8 bits per channel are supported").into());
+        }
+        let channels = match info.color_type {
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            _ => return Err(ErrorKind::UnsupportedPng("only RGB and RGBA images are supported").into()),
+        };
+
+        let mut bytes = vec![0u8; reader.output_buffer_size()];
+        reader.next_frame(&mut bytes)?;
+
+        let mut pixels = Vec::with_capacity((info.width * info.height) as usize);
+        for y in 0..info.height as usize {
+            let row = &bytes[(y * info.line_size)..];
+            for x in 0..info.width as usize {
+                let p = &row[(x * channels)..(x * channels + channels)];
+                let alpha = if channels == 4 { p[3] } else { 255 };
+                pixels.push(from_color(Color::rgba(p[0], p[1], p[2], alpha)));
+            }
+        }
+        Ok(Framebuffer::from_pixels(info.width, info.height, pixels))
+    }
+
+    pub fn write_png_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let file = File::create(file_name.as_ref())?;
+        self.write_png_to(&mut BufWriter::new(file))
+    }
+
+    pub fn write_png_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let mut encoder = png::Encoder::new(stream, self.width, self.height);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.to_rgba_bytes())?;
+        Ok(())
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn size(&self) -> Vector2<u32> {
+        Vector2::new(self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[u32] {
+        &self.pixels
+    }
+
+    /// Returns the color at the given pixel, or `None` if it's out of bounds
+    pub fn pixel(&self, x: u32, y: u32) -> Option<Color> {
+        if x < self.width && y < self.height {
+            Some(to_color(self.pixels[(y * self.width + x) as usize]))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pixels as bytes, four per pixel in R, G, B, A order
+    pub fn to_rgba_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            let color = to_color(*pixel);
+            bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        bytes
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        let value = from_color(color);
+        for pixel in self.pixels.iter_mut() {
+            *pixel = value;
+        }
+    }
+
+    /// Sets a pixel without blending; pixels outside of the framebuffer are ignored
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
+        if let Some(index) = self.index(x, y) {
+            self.pixels[index] = from_color(color);
+        }
+    }
+
+    pub fn draw_rect(&mut self, rect: Rect, color: Color) {
+        if rect.w <= 0 || rect.h <= 0 {
+            return;
+        }
+        let (right, bottom) = (rect.x + rect.w - 1, rect.y + rect.h - 1);
+        for x in rect.x..(right + 1) {
+            self.set_pixel(x, rect.y, color);
+            self.set_pixel(x, bottom, color);
+        }
+        for y in rect.y..(bottom + 1) {
+            self.set_pixel(rect.x, y, color);
+            self.set_pixel(right, y, color);
+        }
+    }
+
+    /// Draws a line between (and including) both points using Bresenham's algorithm
+    pub fn draw_line(&mut self, first: Vector2<i32>, second: Vector2<i32>, color: Color) {
+        let (mut x, mut y) = (first.x, first.y);
+        let (dx, dy) = ((second.x - first.x).abs(), -(second.y - first.y).abs());
+        let step_x = if first.x < second.x { 1 } else { -1 };
+        let step_y = if first.y < second.y { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x, y, color);
+            if x == second.x && y == second.y {
+                break;
+            }
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Copies `src_rect` out of an RGBA8888 image that is `src_width` pixels wide into `dst_rect`,
+    /// scaling to fit if the rects aren't the same size. Pixels are alpha blended.
+    pub fn blit(&mut self,
+                src_pixels: &[u32],
+                src_width: u32,
+                src_rect: Rect,
+                dst_rect: Rect,
+                flip_horizontal: bool,
+                flip_vertical: bool) {
+        if src_rect.w <= 0 || src_rect.h <= 0 || dst_rect.w <= 0 || dst_rect.h <= 0 {
+            return;
+        }
+
+        let min_x = cmp::max(dst_rect.x, 0);
+        let min_y = cmp::max(dst_rect.y, 0);
+        let max_x = cmp::min(dst_rect.x + dst_rect.w, self.width as i32);
+        let max_y = cmp::min(dst_rect.y + dst_rect.h, self.height as i32);
+        for y in min_y..max_y {
+            let mut v = (y - dst_rect.y) * src_rect.h / dst_rect.h;
+            if flip_vertical {
+                v = src_rect.h - 1 - v;
+            }
+            for x in min_x..max_x {
+                let mut u = (x - dst_rect.x) * src_rect.w / dst_rect.w;
+                if flip_horizontal {
+                    u = src_rect.w - 1 - u;
+                }
+
+                let src_index = ((src_rect.y + v) * src_width as i32 + src_rect.x + u) as usize;
+                if let Some(src) = src_pixels.get(src_index) {
+                    let dst_index = (y as u32 * self.width + x as u32) as usize;
+                    self.pixels[dst_index] = blend(*src, self.pixels[dst_index]);
+                }
+            }
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+            Some((y as u32 * self.width + x as u32) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+pub fn from_color(color: Color) -> u32 {
+    ((color.r as u32) << 24) | ((color.g as u32) << 16) | ((color.b as u32) << 8) | color.a as u32
+}
+
+pub fn to_color(pixel: u32) -> Color {
+    Color::rgba((pixel >> 24) as u8, (pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8)
+}
+
+/// Blends `src` over `dst`
+fn blend(src: u32, dst: u32) -> u32 {
+    let alpha = src & 0xFF;
+    match alpha {
+        0 => dst,
+        0xFF => src,
+        _ => {
+            let mix = |shift: u32| {
+                let (s, d) = ((src >> shift) & 0xFF, (dst >> shift) & 0xFF);
+                ((s * alpha + d * (0xFF - alpha)) / 0xFF) << shift
+            };
+            let dst_alpha = dst & 0xFF;
+            let out_alpha = alpha + dst_alpha * (0xFF - alpha) / 0xFF;
+            mix(24) | mix(16) | mix(8) | out_alpha
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Framebuffer;
+    use crate::types::{Color, Rect};
+
+    use nalgebra::Vector2;
+
+    use std::io;
+
+    const RED: u32 = 0xFF0000FF;
+    const BLUE: u32 = 0x0000FFFF;
+
+    #[test]
+    fn test_draw_line_and_rect() {
+        let white = Color::rgb(255, 255, 255);
+        let mut framebuffer = Framebuffer::new(4, 4);
+        framebuffer.draw_line(Vector2::new(0, 0), Vector2::new(3, 3), white);
+        for i in 0..4 {
+            assert_eq!(Some(white), framebuffer.pixel(i, i));
+        }
+        assert_eq!(Some(Color::rgba(0, 0, 0, 0)), framebuffer.pixel(1, 0));
+
+        framebuffer.clear(Color::rgb(0, 0, 0));
+        framebuffer.draw_rect(Rect::of(1, 1, 3, 2), white);
+        assert_eq!(Some(white), framebuffer.pixel(1, 1));
+        assert_eq!(Some(white), framebuffer.pixel(3, 2));
+        assert_eq!(Some(Color::rgb(0, 0, 0)), framebuffer.pixel(0, 0));
+        assert_eq!(None, framebuffer.pixel(4, 0));
+    }
+
+    #[test]
+    fn test_blit() {
+        // 2x1 image: red, transparent
+        let src = [RED, 0];
+        let mut framebuffer = Framebuffer::new(3, 2);
+        framebuffer.clear(Color::rgb(0, 0, 255));
+
+        framebuffer.blit(&src, 2, Rect::of(0, 0, 2, 1), Rect::of(1, 0, 2, 1), false, false);
+        assert_eq!(&[BLUE, RED, BLUE, BLUE, BLUE, BLUE], framebuffer.pixels());
+
+        framebuffer.blit(&src, 2, Rect::of(0, 0, 2, 1), Rect::of(1, 1, 2, 1), true, false);
+        assert_eq!(&[BLUE, RED, BLUE, BLUE, BLUE, RED], framebuffer.pixels());
+
+        // Clipped against the left side, and scaled up vertically
+        framebuffer.clear(Color::rgb(0, 0, 255));
+        framebuffer.blit(&src, 2, Rect::of(0, 0, 2, 1), Rect::of(-1, 0, 2, 2), true, false);
+        assert_eq!(&[RED, BLUE, BLUE, RED, BLUE, BLUE], framebuffer.pixels());
+
+        assert_eq!(vec![255, 0, 0, 255], framebuffer.to_rgba_bytes()[0..4].to_vec());
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let framebuffer = Framebuffer::from_pixels(3, 2, vec![RED, BLUE, 0, 0x12345678, BLUE, RED]);
+
+        let mut png = Vec::new();
+        framebuffer.write_png_to(&mut png).unwrap();
+        assert_eq!(b"\x89PNG", &png[0..4]);
+
+        let read = Framebuffer::read_png_from(&mut io::Cursor::new(png)).unwrap();
+        assert_eq!(framebuffer, read);
+    }
+}
@@ -0,0 +1,172 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::audio::{Audio, NullAudio};
+use crate::input_script::{InputEvent, InputScript};
+use crate::key::{Key, KeyStates, MouseButton};
+use crate::media::{Media, update_key_states};
+use crate::renderer::Renderer;
+
+use nalgebra::Vector2;
+
+use std::collections::{HashMap, HashSet};
+
+/// Media that doesn't need a window, display or sound device. Everything is rendered into
+/// a framebuffer in memory (see `Renderer::framebuffer`), and input comes from a script.
+pub struct HeadlessMedia {
+    renderer: Renderer,
+    audio: NullAudio,
+    script: InputScript,
+    next_event: usize,
+    frame: u32,
+    open: bool,
+    keys_pressed: HashSet<Key>,
+    key_states: KeyStates<Key>,
+    mouse_position: Vector2<i32>,
+    mouse_buttons_pressed: HashSet<MouseButton>,
+    mouse_button_states: KeyStates<MouseButton>,
+}
+
+impl HeadlessMedia {
+    pub fn new(width: u32, height: u32, script: InputScript) -> HeadlessMedia {
+        HeadlessMedia {
+            renderer: Renderer::software(width, height),
+            audio: NullAudio::new(),
+            script: script,
+            next_event: 0,
+            frame: 0,
+            open: true,
+            keys_pressed: HashSet::new(),
+            key_states: KeyStates::new(HashMap::new()),
+            mouse_position: Vector2::new(0, 0),
+            mouse_buttons_pressed: HashSet::new(),
+            mouse_button_states: KeyStates::new(HashMap::new()),
+        }
+    }
+
+    /// Number of times `update` has been called
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    fn apply_event(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::KeyDown(key) => {
+                self.keys_pressed.insert(key);
+            }
+            InputEvent::KeyUp(key) => {
+                self.keys_pressed.remove(&key);
+            }
+            InputEvent::MouseMove(position) => self.mouse_position = position,
+            InputEvent::MouseDown(button) => {
+                self.mouse_buttons_pressed.insert(button);
+            }
+            InputEvent::MouseUp(button) => {
+                self.mouse_buttons_pressed.remove(&button);
+            }
+            InputEvent::Quit => self.open = false,
+        }
+    }
+}
+
+impl Media for HeadlessMedia {
+    fn is_open(&self) -> bool {
+        self.open
+    }
+
+    fn update(&mut self) {
+        while let Some(&(frame, event)) = self.script.events().get(self.next_event) {
+            if frame > self.frame {
+                break;
+            }
+            self.apply_event(event);
+            self.next_event += 1;
+        }
+        self.frame += 1;
+
+        self.key_states = update_key_states(&self.key_states, &self.keys_pressed);
+        self.mouse_button_states = update_key_states(&self.mouse_button_states, &self.mouse_buttons_pressed);
+    }
+
+    fn key_states(&self) -> &KeyStates<Key> {
+        &self.key_states
+    }
+
+    fn mouse_position(&self) -> Vector2<i32> {
+        self.mouse_position
+    }
+
+    fn mouse_button_states<'a>(&'a self) -> &'a KeyStates<MouseButton> {
+        &self.mouse_button_states
+    }
+
+    fn renderer<'a>(&'a mut self) -> &'a mut Renderer {
+        &mut self.renderer
+    }
+
+    fn viewport_size(&self) -> Vector2<u32> {
+        self.renderer.viewport_size()
+    }
+
+    fn audio<'a>(&'a mut self) -> &'a mut dyn Audio {
+        &mut self.audio
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeadlessMedia;
+    use crate::input_script::{InputEvent, InputScript};
+    use crate::key::{Key, KeyState, MouseButton};
+    use crate::media::Media;
+
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_scripted_input() {
+        let mut script = InputScript::new();
+        script.push(1, InputEvent::KeyDown(Key::Up));
+        script.push(3, InputEvent::KeyUp(Key::Up));
+        script.push(3, InputEvent::MouseMove(Vector2::new(10, 20)));
+        script.push(3, InputEvent::MouseDown(MouseButton::Left));
+        script.push(4, InputEvent::Quit);
+
+        let mut media = HeadlessMedia::new(64, 48, script);
+        assert_eq!(Vector2::new(64, 48), media.viewport_size());
+
+        media.update();
+        assert_eq!(KeyState::Up, media.key_states().key_state(Key::Up));
+        media.update();
+        assert_eq!(KeyState::TransitionDown, media.key_states().key_state(Key::Up));
+        media.update();
+        assert_eq!(KeyState::Down, media.key_states().key_state(Key::Up));
+        media.update();
+        assert_eq!(KeyState::TransitionUp, media.key_states().key_state(Key::Up));
+        assert_eq!(KeyState::TransitionDown, media.mouse_button_states().key_state(MouseButton::Left));
+        assert_eq!(Vector2::new(10, 20), media.mouse_position());
+        assert!(media.is_open());
+
+        media.update();
+        assert!(!media.is_open());
+        assert_eq!(5, media.frame());
+    }
+}
@@ -0,0 +1,154 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+use crate::key::{Key, MouseButton};
+
+use nalgebra::Vector2;
+
+use std::fs::File;
+use std::io::prelude::Read;
+use std::path::Path;
+
+/// Input that gets fed to the headless media
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    KeyDown(Key),
+    KeyUp(Key),
+    MouseMove(Vector2<i32>),
+    MouseDown(MouseButton),
+    MouseUp(MouseButton),
+    Quit,
+}
+
+/// A list of input events, each tagged with the frame (number of media updates) it happens on.
+///
+/// Scripts can be read from text files, with one event per line:
+///
+/// ```text
+/// # Scroll the camera right for a second, then click somewhere and quit
+/// 0 key_down right
+/// 60 key_up right
+/// 61 mouse_move 512 384
+/// 61 mouse_down left
+/// 62 mouse_up left
+/// 90 quit
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    events: Vec<(u32, InputEvent)>,
+}
+
+impl InputScript {
+    pub fn new() -> InputScript {
+        Default::default()
+    }
+
+    /// Adds an event that happens on the given frame, after any events already added for that frame
+    pub fn push(&mut self, frame: u32, event: InputEvent) {
+        let index = self.events.iter().position(|&(f, _)| f > frame).unwrap_or(self.events.len());
+        self.events.insert(index, (frame, event));
+    }
+
+    /// All events, sorted by frame
+    pub fn events(&self) -> &[(u32, InputEvent)] {
+        &self.events
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<InputScript> {
+        let mut file = File::open(file_name.as_ref())?;
+        InputScript::read_from(&mut file)
+    }
+
+    pub fn read_from<R: Read>(stream: &mut R) -> Result<InputScript> {
+        let mut text = String::new();
+        stream.read_to_string(&mut text)?;
+
+        let mut script = InputScript::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (frame, event) = parse_line(line).map_err(|reason| ErrorKind::InvalidInputScript(index + 1, reason))?;
+            script.push(frame, event);
+        }
+        Ok(script)
+    }
+}
+
+fn parse_line(line: &str) -> ::std::result::Result<(u32, InputEvent), &'static str> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let frame = parts[0].parse::<u32>().map_err(|_| "expected a frame number")?;
+    let arg = |index: usize| parts.get(index).cloned().ok_or("missing argument");
+    let key = |index: usize| arg(index).and_then(|name| Key::from_name(name).ok_or("unknown key"));
+    let button = |index: usize| {
+        arg(index).and_then(|name| MouseButton::from_name(name).ok_or("unknown mouse button"))
+    };
+    let coordinate = |index: usize| arg(index).and_then(|c| c.parse::<i32>().map_err(|_| "invalid coordinate"));
+
+    let event = match arg(1)? {
+        "key_down" => InputEvent::KeyDown(key(2)?),
+        "key_up" => InputEvent::KeyUp(key(2)?),
+        "mouse_move" => InputEvent::MouseMove(Vector2::new(coordinate(2)?, coordinate(3)?)),
+        "mouse_down" => InputEvent::MouseDown(button(2)?),
+        "mouse_up" => InputEvent::MouseUp(button(2)?),
+        "quit" => InputEvent::Quit,
+        _ => return Err("unknown event"),
+    };
+    Ok((frame, event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputEvent, InputScript};
+    use crate::error::ErrorKind;
+    use crate::key::{Key, MouseButton};
+
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_read_input_script() {
+        let text = "# comment\n\
+                    10 quit\n\
+                    0 key_down shift_left\n\
+                    \n\
+                    5 mouse_move 12 -3\n\
+                    5 mouse_down right\n";
+        let script = InputScript::read_from(&mut text.as_bytes()).unwrap();
+        assert_eq!(&[(0, InputEvent::KeyDown(Key::ShiftLeft)),
+                     (5, InputEvent::MouseMove(Vector2::new(12, -3))),
+                     (5, InputEvent::MouseDown(MouseButton::Right)),
+                     (10, InputEvent::Quit)],
+                   script.events());
+
+        match InputScript::read_from(&mut "0 quit\n1 key_down caps_lock\n".as_bytes()) {
+            Err(err) => {
+                match *err.kind() {
+                    ErrorKind::InvalidInputScript(line, reason) => assert_eq!((2, "unknown key"), (line, reason)),
+                    _ => panic!("unexpected error: {}", err),
+                }
+            }
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+}
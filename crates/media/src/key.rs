@@ -34,6 +34,24 @@ pub enum Key {
     Space,
     ShiftLeft,
     CtrlLeft,
+    F5,
+    F9,
+    F12,
+    F,
+    P,
+    PageUp,
+    PageDown,
+    Escape,
+    Backspace,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
     // Add keys as necessary
 }
 
@@ -49,10 +67,60 @@ impl Key {
                 K::Space => Key::Space,
                 K::LShift => Key::ShiftLeft,
                 K::LCtrl => Key::CtrlLeft,
+                K::F5 => Key::F5,
+                K::F9 => Key::F9,
+                K::F12 => Key::F12,
+                K::F => Key::F,
+                K::P => Key::P,
+                K::PageUp => Key::PageUp,
+                K::PageDown => Key::PageDown,
+                K::Escape => Key::Escape,
+                K::Backspace => Key::Backspace,
+                K::Num1 => Key::Num1,
+                K::Num2 => Key::Num2,
+                K::Num3 => Key::Num3,
+                K::Num4 => Key::Num4,
+                K::Num5 => Key::Num5,
+                K::Num6 => Key::Num6,
+                K::Num7 => Key::Num7,
+                K::Num8 => Key::Num8,
+                K::Num9 => Key::Num9,
                 _ => return None,
             })
         })
     }
+
+    /// Looks up a key by its name in lower snake case (such as "shift_left")
+    pub fn from_name(name: &str) -> Option<Key> {
+        Some(match name {
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "space" => Key::Space,
+            "shift_left" => Key::ShiftLeft,
+            "ctrl_left" => Key::CtrlLeft,
+            "f5" => Key::F5,
+            "f9" => Key::F9,
+            "f12" => Key::F12,
+            "f" => Key::F,
+            "p" => Key::P,
+            "page_up" => Key::PageUp,
+            "page_down" => Key::PageDown,
+            "escape" => Key::Escape,
+            "backspace" => Key::Backspace,
+            "1" => Key::Num1,
+            "2" => Key::Num2,
+            "3" => Key::Num3,
+            "4" => Key::Num4,
+            "5" => Key::Num5,
+            "6" => Key::Num6,
+            "7" => Key::Num7,
+            "8" => Key::Num8,
+            "9" => Key::Num9,
+            _ => return None,
+        })
+    }
 }
 
 #[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
@@ -62,6 +130,18 @@ pub enum MouseButton {
     Right,
 }
 
+impl MouseButton {
+    /// Looks up a mouse button by its name in lower case (such as "left")
+    pub fn from_name(name: &str) -> Option<MouseButton> {
+        Some(match name {
+            "left" => MouseButton::Left,
+            "middle" => MouseButton::Middle,
+            "right" => MouseButton::Right,
+            _ => return None,
+        })
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum KeyState {
     TransitionDown,
@@ -24,13 +24,19 @@
 #[macro_use]
 extern crate error_chain;
 
+mod audio;
 mod error;
+mod framebuffer;
+mod headless_media;
+mod input_script;
 mod key;
 mod media;
 mod renderer;
+mod sound;
 mod texture;
 mod texture_builder;
 
+use chariot_identifier as identifier;
 use chariot_types as types;
 
 pub use error::ChainErr;
@@ -38,12 +44,18 @@ pub use error::Error;
 pub use error::ErrorKind;
 pub use error::Result;
 
+pub use audio::{stereo_gains, Audio, NullAudio, PlayedSound, SdlAudio};
+pub use framebuffer::Framebuffer;
+pub use headless_media::HeadlessMedia;
+pub use input_script::{InputEvent, InputScript};
 pub use key::{Key, KeyState, KeyStates, MouseButton};
 pub use media::{Media, MediaRef};
 
-pub use media::create_media;
+pub use media::{create_headless_media, create_media};
 
 pub use renderer::Renderer;
 
+pub use sound::{encode_wav, Sound};
+
 pub use texture::Texture;
 pub use texture_builder::TextureBuilder;
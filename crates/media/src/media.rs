@@ -19,7 +19,10 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::audio::{Audio, NullAudio, SdlAudio};
 use crate::error::Result;
+use crate::headless_media::HeadlessMedia;
+use crate::input_script::InputScript;
 use crate::key::{Key, KeyState, KeyStates, MouseButton};
 
 use nalgebra::Vector2;
@@ -43,6 +46,8 @@ pub trait Media {
 
     fn renderer<'a>(&'a mut self) -> &'a mut Renderer;
     fn viewport_size(&self) -> Vector2<u32>;
+
+    fn audio<'a>(&'a mut self) -> &'a mut dyn Audio;
 }
 
 pub type MediaRef = Rc<RefCell<Box<dyn Media>>>;
@@ -51,9 +56,16 @@ pub fn create_media(width: u32, height: u32, title: &str) -> Result<MediaRef> {
     SdlMedia::new(width, height, title).map(|m| Rc::new(RefCell::new(Box::new(m) as Box<dyn Media>)))
 }
 
+/// Creates media that renders into memory and reads its input from `script`
+/// instead of opening a window
+pub fn create_headless_media(width: u32, height: u32, script: InputScript) -> MediaRef {
+    Rc::new(RefCell::new(Box::new(HeadlessMedia::new(width, height, script)) as Box<dyn Media>))
+}
+
 struct SdlMedia {
     context: sdl2::Sdl,
     renderer: Renderer,
+    audio: Box<dyn Audio>,
     open: bool,
     keys_pressed: HashSet<Key>,
     key_states: KeyStates<Key>,
@@ -70,9 +82,19 @@ impl SdlMedia {
         let mut context = sdl2::init()?;
         let renderer = Renderer::new(&mut context, width, height, title)?;
 
+        // The game is still playable without sound, so don't fail if there's no audio device
+        let audio: Box<dyn Audio> = match SdlAudio::new(&context) {
+            Ok(audio) => Box::new(audio),
+            Err(err) => {
+                println!("Failed to open the audio device, continuing without sound: {}", err);
+                Box::new(NullAudio::new())
+            }
+        };
+
         Ok(SdlMedia {
             context: context,
             renderer: renderer,
+            audio: audio,
             open: true,
             keys_pressed: HashSet::new(),
             key_states: KeyStates::new(HashMap::new()),
@@ -150,9 +172,13 @@ impl Media for SdlMedia {
     fn viewport_size(&self) -> Vector2<u32> {
         self.renderer.viewport_size()
     }
+
+    fn audio<'a>(&'a mut self) -> &'a mut dyn Audio {
+        &mut *self.audio
+    }
 }
 
-fn update_key_states<K: Eq + Hash + Copy>(key_states: &KeyStates<K>,
+pub fn update_key_states<K: Eq + Hash + Copy>(key_states: &KeyStates<K>,
                                           pressed_keys: &HashSet<K>)
                                           -> KeyStates<K> {
     use KeyState::*;
@@ -20,21 +20,36 @@
 // SOFTWARE.
 
 use crate::error::Result;
+use crate::framebuffer::Framebuffer;
 
 use nalgebra::Vector2;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::surface::Surface;
 
-use crate::texture::{SdlTexture, Texture, create_texture};
+use crate::texture::{Texture, TextureData, TextureInternals, create_texture};
 use crate::types::{ Rect, Color} ;
 
 // Separate so that it's not exported with the crate
-pub trait SdlRenderer {
-    fn create_texture_from_surface(&mut self, surface: sdl2::surface::Surface) -> Result<Texture>;
+pub trait RendererInternals {
+    /// Creates a texture out of RGBA8888 pixels
+    fn create_texture_from_pixels(&mut self, pixels: Vec<u32>, width: u32, height: u32) -> Result<Texture>;
+}
+
+enum Backend {
+    Sdl {
+        _video: sdl2::VideoSubsystem,
+        renderer: sdl2::render::Renderer<'static>,
+    },
+    Software {
+        draw_color: Color,
+        framebuffer: Framebuffer,
+        presented: Framebuffer,
+    },
 }
 
 pub struct Renderer {
     camera_pos: Vector2<i32>,
-    _video: sdl2::VideoSubsystem,
-    renderer: sdl2::render::Renderer<'static>,
+    backend: Backend,
 }
 
 impl Renderer {
@@ -52,24 +67,76 @@ impl Renderer {
 
         Ok(Renderer {
             camera_pos: Vector2::new(0, 0),
-            _video: video,
-            renderer: renderer,
+            backend: Backend::Sdl {
+                _video: video,
+                renderer: renderer,
+            },
         })
     }
 
+    /// Creates a renderer that draws into a framebuffer in memory instead of a window
+    pub fn software(width: u32, height: u32) -> Renderer {
+        Renderer {
+            camera_pos: Vector2::new(0, 0),
+            backend: Backend::Software {
+                draw_color: Color::rgba(0, 0, 0, 0),
+                framebuffer: Framebuffer::new(width, height),
+                presented: Framebuffer::new(width, height),
+            },
+        }
+    }
+
+    /// The most recently presented frame, if this is a software renderer
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        match self.backend {
+            Backend::Software { ref presented, .. } => Some(presented),
+            Backend::Sdl { .. } => None,
+        }
+    }
+
+    /// Reads back everything rendered since the last `present`
+    pub fn capture(&mut self) -> Result<Framebuffer> {
+        match self.backend {
+            Backend::Sdl { ref renderer, .. } => {
+                let (width, height) = renderer.output_size()?;
+                let bytes = renderer.read_pixels(None, PixelFormatEnum::RGBA8888)?;
+                let pixels = bytes.chunks(4)
+                    .map(|p| u32::from_ne_bytes([p[0], p[1], p[2], p[3]]))
+                    .collect();
+                Ok(Framebuffer::from_pixels(width, height, pixels))
+            }
+            Backend::Software { ref framebuffer, .. } => Ok(framebuffer.clone()),
+        }
+    }
+
     pub fn present(&mut self) {
         self.set_render_color(Color::rgba(0, 0, 0, 0));
-        self.renderer.present();
-        self.renderer.clear();
+        match self.backend {
+            Backend::Sdl { ref mut renderer, .. } => {
+                renderer.present();
+                renderer.clear();
+            }
+            Backend::Software { draw_color, ref mut framebuffer, ref mut presented } => {
+                presented.clone_from(framebuffer);
+                framebuffer.clear(draw_color);
+            }
+        }
     }
 
     pub fn viewport_size(&self) -> Vector2<u32> {
-        let size = self.renderer.window().unwrap().size();
-        Vector2::new(size.0, size.1)
+        match self.backend {
+            Backend::Sdl { ref renderer, .. } => {
+                let size = renderer.window().unwrap().size();
+                Vector2::new(size.0, size.1)
+            }
+            Backend::Software { ref framebuffer, .. } => framebuffer.size(),
+        }
     }
 
     pub fn set_scale(&mut self, scale_x: f32, scale_y: f32) {
-        self.renderer.set_scale(scale_x, scale_y).expect("set render scale");
+        if let Backend::Sdl { ref mut renderer, .. } = self.backend {
+            renderer.set_scale(scale_x, scale_y).expect("set render scale");
+        }
     }
 
     pub fn set_camera_position(&mut self, position: &Vector2<i32>) {
@@ -84,27 +151,50 @@ impl Renderer {
                           flip_vertical: bool) {
         dst_rect.x -= self.camera_pos.x;
         dst_rect.y -= self.camera_pos.y;
-        self.renderer
-            .copy_ex(texture.sdl_texture(),
-                     src_rect.map(|r| r.into()),
-                     Some(dst_rect.into()),
-                     0.0,
-                     None,
-                     flip_horizontal,
-                     flip_vertical)
-            .unwrap_or_else(|err| {
-                println!("Failed to render texture: {}", err);
-            });
+        match (&mut self.backend, texture.data()) {
+            (Backend::Sdl { renderer, .. }, TextureData::Sdl(sdl_texture)) => {
+                renderer.copy_ex(sdl_texture,
+                             src_rect.map(|r| r.into()),
+                             Some(dst_rect.into()),
+                             0.0,
+                             None,
+                             flip_horizontal,
+                             flip_vertical)
+                    .unwrap_or_else(|err| {
+                        println!("Failed to render texture: {}", err);
+                    });
+            }
+            (Backend::Software { framebuffer, .. }, TextureData::Pixels(pixels)) => {
+                let src_rect = src_rect.unwrap_or(Rect::of(0, 0, texture.width as i32, texture.height as i32));
+                framebuffer.blit(pixels,
+                                 texture.width,
+                                 src_rect,
+                                 dst_rect,
+                                 flip_horizontal,
+                                 flip_vertical);
+            }
+            _ => println!("Failed to render texture: it was created by a different renderer"),
+        }
     }
 
     pub fn set_render_color(&mut self, color: Color) {
-        self.renderer.set_draw_color(color.into());
+        match self.backend {
+            Backend::Sdl { ref mut renderer, .. } => renderer.set_draw_color(color.into()),
+            Backend::Software { ref mut draw_color, .. } => *draw_color = color,
+        }
     }
 
     pub fn render_rect(&mut self, mut rect: Rect) {
         rect.x -= self.camera_pos.x;
         rect.y -= self.camera_pos.y;
-        self.renderer.draw_rect(rect.into()).expect("Failed to draw rect");
+        match self.backend {
+            Backend::Sdl { ref mut renderer, .. } => {
+                renderer.draw_rect(rect.into()).expect("Failed to draw rect")
+            }
+            Backend::Software { draw_color, ref mut framebuffer, .. } => {
+                framebuffer.draw_rect(rect, draw_color)
+            }
+        }
     }
 
     pub fn render_line(&mut self, mut first: Vector2<i32>, mut second: Vector2<i32>) {
@@ -112,17 +202,64 @@ impl Renderer {
         first.y -= self.camera_pos.y;
         second.x -= self.camera_pos.x;
         second.y -= self.camera_pos.y;
-        self.renderer
-            .draw_line(sdl2::rect::Point::new(first.x, first.y),
-                       sdl2::rect::Point::new(second.x, second.y))
-            .expect("Failed to draw line");
+        match self.backend {
+            Backend::Sdl { ref mut renderer, .. } => {
+                renderer.draw_line(sdl2::rect::Point::new(first.x, first.y),
+                               sdl2::rect::Point::new(second.x, second.y))
+                    .expect("Failed to draw line")
+            }
+            Backend::Software { draw_color, ref mut framebuffer, .. } => {
+                framebuffer.draw_line(first, second, draw_color)
+            }
+        }
     }
 }
 
-impl SdlRenderer for Renderer {
-    fn create_texture_from_surface(&mut self, surface: sdl2::surface::Surface) -> Result<Texture> {
-        let (width, height) = (surface.width(), surface.height());
-        let sdl_texture = self.renderer.create_texture_from_surface(surface)?;
-        Ok(create_texture(sdl_texture, width, height))
+impl RendererInternals for Renderer {
+    fn create_texture_from_pixels(&mut self, pixels: Vec<u32>, width: u32, height: u32) -> Result<Texture> {
+        let data = match self.backend {
+            Backend::Sdl { ref mut renderer, .. } => {
+                let mut bytes = Vec::with_capacity(pixels.len() * 4);
+                for pixel in &pixels {
+                    bytes.extend_from_slice(&pixel.to_ne_bytes());
+                }
+                let surface = Surface::from_data(&mut bytes, width, height, 4 * width, PixelFormatEnum::RGBA8888)?;
+                TextureData::Sdl(renderer.create_texture_from_surface(surface)?)
+            }
+            Backend::Software { .. } => TextureData::Pixels(pixels),
+        };
+        Ok(create_texture(data, width, height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Renderer, RendererInternals};
+    use crate::types::{Color, Rect};
+
+    use nalgebra::Vector2;
+
+    #[test]
+    fn test_software_rendering() {
+        let mut renderer = Renderer::software(4, 3);
+        let texture = renderer.create_texture_from_pixels(vec![0xFF0000FF, 0x00FF00FF], 2, 1).unwrap();
+
+        renderer.set_camera_position(&Vector2::new(1, 1));
+        renderer.render_texture(&texture, None, Rect::of(2, 2, 2, 1), false, false);
+        renderer.set_render_color(Color::rgb(255, 255, 255));
+        renderer.render_line(Vector2::new(1, 1), Vector2::new(4, 1));
+        assert!(renderer.framebuffer().unwrap().pixels().iter().all(|p| *p == 0));
+
+        assert_eq!(Some(Color::rgb(255, 0, 0)), renderer.capture().unwrap().pixel(1, 1));
+
+        renderer.present();
+        assert_eq!(Some(Color::rgba(0, 0, 0, 0)), renderer.capture().unwrap().pixel(1, 1));
+        let frame = renderer.framebuffer().unwrap();
+        assert_eq!(Vector2::new(4, 3), renderer.viewport_size());
+        assert_eq!(Some(Color::rgb(255, 255, 255)), frame.pixel(0, 0));
+        assert_eq!(Some(Color::rgb(255, 255, 255)), frame.pixel(3, 0));
+        assert_eq!(Some(Color::rgb(255, 0, 0)), frame.pixel(1, 1));
+        assert_eq!(Some(Color::rgb(0, 255, 0)), frame.pixel(2, 1));
+        assert_eq!(Some(Color::rgba(0, 0, 0, 0)), frame.pixel(3, 1));
     }
 }
@@ -0,0 +1,172 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::error::{ErrorKind, Result};
+use crate::identifier::WavFileId;
+
+/// A decoded sound effect; samples are signed 16-bit and interleaved when there are two channels
+#[derive(Debug)]
+pub struct Sound {
+    pub id: WavFileId,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub samples: Vec<i16>,
+}
+
+const WAVE_FORMAT_PCM: u16 = 1;
+
+impl Sound {
+    /// Decodes an uncompressed (8 or 16-bit PCM, mono or stereo) WAV file
+    pub fn from_wav(id: WavFileId, data: &[u8]) -> Result<Sound> {
+        if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(ErrorKind::InvalidWav("missing RIFF/WAVE header").into());
+        }
+
+        let mut format = None;
+        let mut samples = None;
+        let mut offset = 12;
+        while offset + 8 <= data.len() {
+            let chunk_id = &data[offset..offset + 4];
+            let chunk_size = read_u32(data, offset + 4) as usize;
+            let chunk_start = offset + 8;
+            // Some files claim more data than they have; use whatever is there
+            let chunk = &data[chunk_start..data.len().min(chunk_start + chunk_size)];
+            match chunk_id {
+                b"fmt " => {
+                    if chunk.len() < 16 {
+                        return Err(ErrorKind::InvalidWav("format chunk too small").into());
+                    }
+                    format = Some((read_u16(chunk, 0), read_u16(chunk, 2), read_u32(chunk, 4), read_u16(chunk, 14)));
+                }
+                b"data" => samples = Some(chunk),
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            offset = chunk_start + chunk_size + (chunk_size & 1);
+        }
+
+        let (format_tag, channels, sample_rate, bits_per_sample) =
+            format.ok_or(ErrorKind::InvalidWav("missing format chunk"))?;
+        let samples = samples.ok_or(ErrorKind::InvalidWav("missing data chunk"))?;
+        if format_tag != WAVE_FORMAT_PCM {
+            return Err(ErrorKind::InvalidWav("only PCM is supported").into());
+        }
+        if channels != 1 && channels != 2 {
+            return Err(ErrorKind::InvalidWav("only mono and stereo are supported").into());
+        }
+        if sample_rate == 0 {
+            return Err(ErrorKind::InvalidWav("invalid sample rate").into());
+        }
+
+        let samples = match bits_per_sample {
+            8 => samples.iter().map(|s| ((*s as i16) - 128) << 8).collect(),
+            16 => samples.chunks(2).filter(|s| s.len() == 2).map(|s| i16::from_le_bytes([s[0], s[1]])).collect(),
+            _ => return Err(ErrorKind::InvalidWav("only 8 and 16-bit samples are supported").into()),
+        };
+
+        Ok(Sound {
+            id: id,
+            channels: channels,
+            sample_rate: sample_rate,
+            samples: samples,
+        })
+    }
+
+    /// Number of sample frames (one sample per channel)
+    pub fn frame_count(&self) -> usize {
+        self.samples.len() / self.channels as usize
+    }
+
+    /// Returns the left and right samples of a frame; mono sounds play the same sample on both
+    pub fn frame(&self, index: usize) -> (i16, i16) {
+        if self.channels == 2 {
+            (self.samples[index * 2], self.samples[index * 2 + 1])
+        } else {
+            (self.samples[index], self.samples[index])
+        }
+    }
+
+    /// Length of the sound in seconds
+    pub fn duration(&self) -> f32 {
+        self.frame_count() as f32 / self.sample_rate as f32
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+/// Builds a WAV file in memory; used for testing here and in crates that depend on this one
+pub fn encode_wav(channels: u16, sample_rate: u32, bits_per_sample: u16, data: &[u8]) -> Vec<u8> {
+    let block_align = channels * bits_per_sample / 8;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&WAVE_FORMAT_PCM.to_le_bytes());
+    wav.extend_from_slice(&channels.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+    wav.extend_from_slice(&block_align.to_le_bytes());
+    wav.extend_from_slice(&bits_per_sample.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    wav.extend_from_slice(data);
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_8_bit_mono() {
+        let wav = encode_wav(1, 11025, 8, &[128, 255, 0]);
+        let sound = Sound::from_wav(5.into(), &wav).unwrap();
+        assert_eq!(11025, sound.sample_rate);
+        assert_eq!(vec![0, 127 << 8, -128 << 8], sound.samples);
+        assert_eq!((127 << 8, 127 << 8), sound.frame(1));
+    }
+
+    #[test]
+    fn test_decode_16_bit_stereo() {
+        let wav = encode_wav(2, 22050, 16, &[0x01, 0x00, 0xff, 0xff, 0x00, 0x80, 0xff, 0x7f]);
+        let sound = Sound::from_wav(5.into(), &wav).unwrap();
+        assert_eq!(2, sound.frame_count());
+        assert_eq!((1, -1), sound.frame(0));
+        assert_eq!((i16::MIN, i16::MAX), sound.frame(1));
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(Sound::from_wav(5.into(), b"RIFF").is_err());
+        assert!(Sound::from_wav(5.into(), &encode_wav(1, 11025, 24, &[0; 3])).is_err());
+        let mut compressed = encode_wav(1, 11025, 8, &[0; 4]);
+        compressed[20] = 2;
+        assert!(Sound::from_wav(5.into(), &compressed).is_err());
+    }
+}
@@ -24,26 +24,32 @@ use sdl2;
 pub struct Texture {
     pub width: u32,
     pub height: u32,
-    texture: sdl2::render::Texture,
+    data: TextureData,
+}
+
+pub enum TextureData {
+    Sdl(sdl2::render::Texture),
+    /// RGBA8888 pixels, for the software renderer
+    Pixels(Vec<u32>),
 }
 
 // TODO: Haven't quite figured out how to make a new method on Texture that is only exposed
 // to other members of the crate (but not outside of the crate)
-pub fn create_texture(sdl_texture: sdl2::render::Texture, width: u32, height: u32) -> Texture {
+pub fn create_texture(data: TextureData, width: u32, height: u32) -> Texture {
     Texture {
         width: width,
         height: height,
-        texture: sdl_texture,
+        data: data,
     }
 }
 
 // Separate so that it's not exported with the crate
-pub trait SdlTexture {
-    fn sdl_texture<'a>(&'a self) -> &'a sdl2::render::Texture;
+pub trait TextureInternals {
+    fn data<'a>(&'a self) -> &'a TextureData;
 }
 
-impl SdlTexture for Texture {
-    fn sdl_texture<'a>(&'a self) -> &'a sdl2::render::Texture {
-        &self.texture
+impl TextureInternals for Texture {
+    fn data<'a>(&'a self) -> &'a TextureData {
+        &self.data
     }
 }
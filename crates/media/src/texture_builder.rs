@@ -19,90 +19,82 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::error::{Error, Result};
-use crate::renderer::{Renderer, SdlRenderer};
-use sdl2::pixels::PixelFormatEnum;
+use crate::error::Result;
+use crate::renderer::{Renderer, RendererInternals};
 
-use sdl2::surface::Surface;
-
-use std::io::{self, Write};
-use std::mem;
 use crate::texture::Texture;
 use crate::types::Rect;
 
 pub struct TextureBuilder<'a> {
-    surface: Surface<'static>,
+    width: u32,
+    height: u32,
+    pixels: Vec<u32>,
     palette: &'a [u32],
-    error: Option<Error>,
-}
-
-fn to_rgba(palette: &[u32], src_pixels: &[u8], width: usize, height: usize) -> Result<Vec<u8>> {
-    let mut dst_pixels = io::Cursor::new(vec![0u8; width * height * 4]);
-
-    for y in 0..height {
-        for x in 0..width {
-            let src_index = y * width + x;
-            let color_index = src_pixels[src_index] as usize;
-            if color_index > 0 {
-                let color = palette[color_index];
-                let color_bytes = unsafe { mem::transmute::<u32, [u8; 4]>(color) };
-                dst_pixels.write(&color_bytes)?;
-            } else {
-                dst_pixels.write(&[0, 0, 0, 0u8])?;
-            }
-        }
-    }
-
-    Ok(dst_pixels.into_inner())
 }
 
 impl<'a> TextureBuilder<'a> {
     pub fn new(width: u32, height: u32, palette: &'a [u32]) -> Result<TextureBuilder<'a>> {
         Ok(TextureBuilder {
-            surface: Surface::new(width, height, PixelFormatEnum::RGBA8888)?,
+            width: width,
+            height: height,
+            pixels: vec![0u32; (width * height) as usize],
             palette: palette,
-            error: None,
         })
     }
 
+    /// Copies the `src_rect` part of a paletted image that is `src_rect.w` by `src_rect.h`
+    /// pixels into the texture at the position of `dst_rect`. Color index zero is transparent.
     pub fn blit_shape(mut self, pixel_buffer: &[u8], src_rect: Rect, dst_rect: Rect) -> Self {
-        let mut pixels = match to_rgba(&self.palette,
-                                       pixel_buffer,
-                                       src_rect.w as usize,
-                                       src_rect.h as usize) {
-            Ok(pixels) => pixels,
-            Err(err) => {
-                self.error = Some(err);
-                return self;
+        let src_width = src_rect.w;
+        for y in src_rect.y..src_rect.h {
+            let dst_y = dst_rect.y + y - src_rect.y;
+            if dst_y < 0 || dst_y >= self.height as i32 {
+                continue;
             }
-        };
+            for x in src_rect.x..src_width {
+                let dst_x = dst_rect.x + x - src_rect.x;
+                if dst_x < 0 || dst_x >= self.width as i32 {
+                    continue;
+                }
 
-        let surf_result = Surface::from_data(&mut pixels,
-                                             src_rect.w as u32,
-                                             src_rect.h as u32,
-                                             4 * (src_rect.w as u32),
-                                             PixelFormatEnum::RGBA8888);
-        if surf_result.is_err() {
-            self.error = Some(surf_result.err().unwrap().into());
-            return self;
-        }
-
-        let src_surface = surf_result.ok().unwrap();
-        let blit_result = src_surface.blit(Some(src_rect.into()),
-                                           &mut self.surface,
-                                           Some(dst_rect.into()));
-        if blit_result.is_err() {
-            self.error = Some(blit_result.err().unwrap().into());
+                let color_index = pixel_buffer.get((y * src_width + x) as usize).cloned().unwrap_or(0) as usize;
+                if color_index > 0 {
+                    let color = self.palette.get(color_index).cloned().unwrap_or(0);
+                    self.pixels[(dst_y as u32 * self.width + dst_x as u32) as usize] = color;
+                }
+            }
         }
-
         self
     }
 
     pub fn build(self, renderer: &mut Renderer) -> Result<Texture> {
-        if self.error.is_some() {
-            return Err(self.error.unwrap());
-        }
+        renderer.create_texture_from_pixels(self.pixels, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextureBuilder;
+    use crate::renderer::Renderer;
+    use crate::types::{Color, Rect};
+
+    #[test]
+    fn test_build_software_texture() {
+        let palette = [0, 0xFF0000FF, 0x0000FFFF];
+        let mut renderer = Renderer::software(3, 1);
+        let texture = TextureBuilder::new(3, 1, &palette)
+            .unwrap()
+            .blit_shape(&[1, 0], Rect::of(0, 0, 2, 1), Rect::of(0, 0, 2, 1))
+            .blit_shape(&[2], Rect::of(0, 0, 1, 1), Rect::of(2, 0, 1, 1))
+            .build(&mut renderer)
+            .unwrap();
+        assert_eq!((3, 1), (texture.width, texture.height));
 
-        renderer.create_texture_from_surface(self.surface)
+        renderer.render_texture(&texture, None, Rect::of(0, 0, 3, 1), false, false);
+        renderer.present();
+        let frame = renderer.framebuffer().unwrap();
+        assert_eq!(Some(Color::rgb(255, 0, 0)), frame.pixel(0, 0));
+        assert_eq!(Some(Color::rgba(0, 0, 0, 0)), frame.pixel(1, 0));
+        assert_eq!(Some(Color::rgb(0, 0, 255)), frame.pixel(2, 0));
     }
 }
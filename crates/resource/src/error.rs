@@ -57,6 +57,14 @@ error_chain! {
             description("SLP not found")
             display("{}.slp not found in \"{}\"", slp_id, drs_key.path())
         }
+        NoWavTableInDrs(drs_key: DrsKey) {
+            description("no WAVs in DRS")
+            display("no WAVs found in \"{}\"", drs_key.path())
+        }
+        WavNotFound(drs_key: DrsKey, wav_id: u32) {
+            description("WAV not found")
+            display("{}.wav not found in \"{}\"", wav_id, drs_key.path())
+        }
         GameDirInvalid(message: String) {
             description("Game directory is invalid")
             display("{}", message)
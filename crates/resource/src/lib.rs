@@ -36,6 +36,7 @@ mod game_dir;
 mod drs_manager;
 mod shape_manager;
 mod shape_metadata;
+mod sound_manager;
 mod render_command;
 
 pub use drs_manager::{DrsKey, DrsManager, DrsManagerRef};
@@ -43,3 +44,4 @@ pub use game_dir::GameDir;
 pub use render_command::*;
 pub use shape_manager::{Shape, ShapeKey, ShapeManager, ShapeManagerRef};
 pub use shape_metadata::{ShapeMetadata, ShapeMetadataKey, ShapeMetadataStore, ShapeMetadataStoreRef};
+pub use sound_manager::{SoundManager, SoundManagerRef};
@@ -64,7 +64,11 @@ pub struct Shape {
 }
 
 impl Shape {
-    fn load_from(slp: &SlpFile, palette: &[u32], renderer: &mut Renderer) -> Result<Shape> {
+    fn load_from(slp: &SlpFile,
+                 player_color: PlayerColorId,
+                 palette: &[u32],
+                 renderer: &mut Renderer)
+                 -> Result<Shape> {
         let mut total_rect = Rect::new();
         let mut dst_rects = Vec::new();
         let mut centers = Vec::new();
@@ -84,7 +88,7 @@ impl Shape {
         let mut texture_builder =
             TextureBuilder::new(total_rect.w as u32, total_rect.h as u32, &palette)?;
         for (index, shape) in slp.shapes.iter().enumerate() {
-            texture_builder = texture_builder.blit_shape(&shape.pixels,
+            texture_builder = texture_builder.blit_shape(&shape.player_pixels(*player_color),
                                                          Rect::of(0,
                                                                   0,
                                                                   shape.header.width as i32,
@@ -142,6 +146,9 @@ enum ShapeCache {
 pub struct ShapeManager {
     drs_manager: DrsManagerRef,
     shapes: HashMap<ShapeKey, ShapeCache>,
+
+    /// Decoded SLPs, shared by the shapes of every player color
+    slps: HashMap<(DrsKey, SlpFileId), Rc<SlpFile>>,
     palette: Vec<u32>,
 }
 
@@ -165,6 +172,7 @@ impl ShapeManager {
         Ok(Rc::new(RefCell::new(ShapeManager {
             drs_manager: drs_manager,
             shapes: HashMap::new(),
+            slps: HashMap::new(),
             palette: palette,
         })))
     }
@@ -192,23 +200,34 @@ impl ShapeManager {
         }
     }
 
-    fn load_shape(&self, shape_key: &ShapeKey, renderer: &mut Renderer) -> Result<Shape> {
-        let borrowed_drs = self.drs_manager.borrow();
-        let drs_file = borrowed_drs.get(shape_key.drs_key);
+    fn load_shape(&mut self, shape_key: &ShapeKey, renderer: &mut Renderer) -> Result<Shape> {
+        let slp = self.load_slp(shape_key.drs_key, shape_key.slp_id)?;
+        Shape::load_from(&slp, shape_key.player_color, &self.palette, renderer)
+    }
+
+    fn load_slp(&mut self, drs_key: DrsKey, slp_id: SlpFileId) -> Result<Rc<SlpFile>> {
+        if let Some(slp) = self.slps.get(&(drs_key, slp_id)) {
+            return Ok(slp.clone());
+        }
+
+        let slp = {
+            let borrowed_drs = self.drs_manager.borrow();
+            let drs_file = borrowed_drs.get(drs_key);
 
-        let slp_table = drs_file.find_table(DrsFileType::Slp)
-            .ok_or(ErrorKind::NoSlpTableInDrs(shape_key.drs_key))?;
+            let slp_table = drs_file.find_table(DrsFileType::Slp)
+                .ok_or(ErrorKind::NoSlpTableInDrs(drs_key))?;
 
-        let slp = match slp_table.find_file_contents(*shape_key.slp_id) {
-            Some(slp_contents) => {
-                SlpFile::read_from(&mut io::Cursor::new(slp_contents), *shape_key.player_color)?
-            },
-            None => {
-                // Load the "missing" SLP file if we can't find the requested SLP in the DRS archive
-                SlpFile::read_from_file("data/nope-64x64.slp", *shape_key.player_color)?
+            match slp_table.find_file_contents(*slp_id) {
+                Some(slp_contents) => SlpFile::read_from(&mut io::Cursor::new(slp_contents))?,
+                None => {
+                    // Load the "missing" SLP file if we can't find the requested SLP in the DRS archive
+                    SlpFile::read_from_file("data/nope-64x64.slp")?
+                }
             }
         };
 
-        Shape::load_from(&slp, &self.palette, renderer)
+        let slp = Rc::new(slp);
+        self.slps.insert((drs_key, slp_id), slp.clone());
+        Ok(slp)
     }
 }
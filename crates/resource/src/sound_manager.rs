@@ -0,0 +1,84 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::drs::DrsFileType;
+use crate::drs_manager::{DrsKey, DrsManagerRef};
+use crate::error::{ErrorKind, Result};
+use crate::identifier::WavFileId;
+use crate::media::Sound;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::Arc;
+
+enum SoundCache {
+    Cached(Arc<Sound>),
+    Failed,
+}
+
+/// Loads and caches the sound effects in sounds.drs
+pub struct SoundManager {
+    drs_manager: DrsManagerRef,
+    sounds: HashMap<WavFileId, SoundCache>,
+}
+
+pub type SoundManagerRef = Rc<RefCell<SoundManager>>;
+
+impl SoundManager {
+    pub fn new(drs_manager: DrsManagerRef) -> SoundManagerRef {
+        Rc::new(RefCell::new(SoundManager {
+            drs_manager: drs_manager,
+            sounds: HashMap::new(),
+        }))
+    }
+
+    pub fn get(&mut self, wav_id: WavFileId) -> Option<Arc<Sound>> {
+        use self::SoundCache::*;
+
+        if !self.sounds.contains_key(&wav_id) {
+            let cached = match self.load_sound(wav_id) {
+                Ok(sound) => Cached(Arc::new(sound)),
+                Err(err) => {
+                    println!("Failed to load sound {:?}: {}", wav_id, err);
+                    Failed
+                }
+            };
+            self.sounds.insert(wav_id, cached);
+        }
+
+        match *self.sounds.get(&wav_id).unwrap() {
+            Cached(ref sound) => Some(sound.clone()),
+            Failed => None,
+        }
+    }
+
+    fn load_sound(&self, wav_id: WavFileId) -> Result<Sound> {
+        let borrowed_drs = self.drs_manager.borrow();
+        let wav_table = borrowed_drs.get(DrsKey::Sounds)
+            .find_table(DrsFileType::Wav)
+            .ok_or(ErrorKind::NoWavTableInDrs(DrsKey::Sounds))?;
+        let contents = wav_table.find_file_contents(*wav_id)
+            .ok_or(ErrorKind::WavNotFound(DrsKey::Sounds, *wav_id))?;
+        Ok(Sound::from_wav(wav_id, contents)?)
+    }
+}
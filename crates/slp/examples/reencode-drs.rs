@@ -0,0 +1,93 @@
+//
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+//
+
+extern crate clap;
+extern crate chariot_drs as drs;
+extern crate chariot_slp as slp;
+
+use clap::{Arg, App};
+use std::io;
+use std::process;
+
+fn main() {
+    let matches = App::new("reencode-drs")
+        .version("1.0")
+        .about("Decodes, re-encodes and decodes again every SLP in a DRS archive, \
+                reporting any frames that don't survive the round trip")
+        .arg(Arg::with_name("INPUT")
+            .help("Sets the input drs to use")
+            .required(true)
+            .index(1))
+        .get_matches();
+
+    let file_name = matches.value_of("INPUT").unwrap();
+    let drs_file = match drs::DrsFile::read_from_file(file_name) {
+        Ok(drs_file) => drs_file,
+        Err(err) => {
+            println!("Failed to read the drs file: {}", err);
+            process::exit(1);
+        }
+    };
+
+    let slp_table = match drs_file.find_table(drs::DrsFileType::Slp) {
+        Some(table) => table,
+        None => {
+            println!("No SLPs in {}", file_name);
+            return;
+        }
+    };
+
+    let mut failures = 0;
+    for (entry, contents) in slp_table.entries.iter().zip(slp_table.contents.iter()) {
+        let original = match slp::SlpFile::read_from(&mut io::Cursor::new(&contents[..])) {
+            Ok(original) => original,
+            Err(err) => {
+                println!("{}.slp: failed to decode: {}", entry.file_id, err);
+                failures += 1;
+                continue;
+            }
+        };
+
+        let mut encoded = Vec::new();
+        if let Err(err) = original.write_to(&mut encoded) {
+            println!("{}.slp: failed to encode: {}", entry.file_id, err);
+            failures += 1;
+            continue;
+        }
+
+        let reencoded = slp::SlpFile::read_from(&mut io::Cursor::new(&encoded[..]))
+            .expect("failed to decode an SLP we just encoded");
+        for (index, (before, after)) in original.shapes.iter().zip(reencoded.shapes.iter()).enumerate() {
+            if before.pixels != after.pixels || before.alpha_mask != after.alpha_mask ||
+               before.shadow_mask != after.shadow_mask ||
+               before.player_color_mask != after.player_color_mask {
+                println!("{}.slp: frame {} differs after re-encoding", entry.file_id, index);
+                failures += 1;
+            }
+        }
+    }
+
+    println!("Checked {} SLPs, {} failures", slp_table.entries.len(), failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
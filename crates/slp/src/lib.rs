@@ -20,7 +20,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-//! Start with [SlpFile](slp/struct.SlpFile.html) if you are decoding SLPs.
+//! Start with [SlpFile](slp/struct.SlpFile.html) if you are decoding or encoding SLPs.
 //!
 //! ```ascii
 //! +-----------------------------+
@@ -59,5 +59,7 @@ pub use error::Result;
 pub use slp::SlpFile;
 pub use slp::SlpHeader;
 pub use slp::SlpLogicalShape;
+pub use slp::SlpMask;
+pub use slp::SlpPixel;
 pub use slp::SlpPixels;
 pub use slp::SlpShapeHeader;
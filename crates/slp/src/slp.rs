@@ -24,6 +24,7 @@
 use crate::error::*;
 
 use chariot_io_tools::ReadExt;
+use std::cmp;
 use std::fs::File;
 use std::io::SeekFrom;
 use std::io::prelude::*;
@@ -50,7 +51,12 @@ impl SlpHeader {
         }
     }
 
-    // TODO: Implement writing
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        stream.write_all(&self.file_version)?;
+        stream.write_all(&self.shape_count.to_le_bytes())?;
+        stream.write_all(&self.comment)?;
+        Ok(())
+    }
 
     pub fn read_from<S: Read>(stream: &mut S) -> Result<SlpHeader> {
         let mut header = SlpHeader::new();
@@ -101,7 +107,17 @@ impl SlpShapeHeader {
         }
     }
 
-    // TODO: Implement writing
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        stream.write_all(&self.shape_data_offsets.to_le_bytes())?;
+        stream.write_all(&self.shape_outline_offset.to_le_bytes())?;
+        stream.write_all(&self.palette_offset.to_le_bytes())?;
+        stream.write_all(&self.properties.to_le_bytes())?;
+        stream.write_all(&self.width.to_le_bytes())?;
+        stream.write_all(&self.height.to_le_bytes())?;
+        stream.write_all(&self.center_x.to_le_bytes())?;
+        stream.write_all(&self.center_y.to_le_bytes())?;
+        Ok(())
+    }
 
     fn read_from_file<R: Read + Seek>(file: &mut R) -> Result<SlpShapeHeader> {
         let mut header = SlpShapeHeader::new();
@@ -118,18 +134,129 @@ impl SlpShapeHeader {
 }
 
 pub type SlpPixels = Vec<u8>;
+pub type SlpMask = Vec<bool>;
+
+/// A decoded pixel, tagged with the kind of drawing command that produced it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SlpPixel {
+    Transparent,
+    /// Index into the palette
+    Color(u8),
+    /// Index relative to the start of the owning player's colors
+    PlayerColor(u8),
+    /// Drawn through the shadow table rather than the palette
+    Shadow,
+}
 
+/// A decoded frame, split into layers so that no player color has to be chosen
+/// until the frame is drawn.
 pub struct SlpLogicalShape {
     pub header: SlpShapeHeader,
+    /// Color index plane. Player color pixels hold their index relative to the start
+    /// of the player's colors; transparent and shadow pixels hold zero.
     pub pixels: SlpPixels,
+    /// Set for every pixel the SLP draws, shadows included
+    pub alpha_mask: SlpMask,
+    pub shadow_mask: SlpMask,
+    pub player_color_mask: SlpMask,
 }
 
 impl SlpLogicalShape {
-    pub fn new() -> SlpLogicalShape {
+    /// Creates a fully transparent shape
+    pub fn new(width: u32, height: u32) -> SlpLogicalShape {
+        let mut header = SlpShapeHeader::new();
+        header.width = width;
+        header.height = height;
+
+        let pixel_count = (width * height) as usize;
         SlpLogicalShape {
-            header: SlpShapeHeader::new(),
-            pixels: SlpPixels::new(),
+            header: header,
+            pixels: vec![0u8; pixel_count],
+            alpha_mask: vec![false; pixel_count],
+            shadow_mask: vec![false; pixel_count],
+            player_color_mask: vec![false; pixel_count],
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> SlpPixel {
+        self.pixel_at((y * self.header.width + x) as usize)
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: SlpPixel) {
+        let index = (y * self.header.width + x) as usize;
+        self.set_pixel_at(index, pixel);
+    }
+
+    /// Resolves the shape to palette indices for the given player.
+    /// Transparent and shadow pixels are left as zero.
+    pub fn player_pixels(&self, player_index: u8) -> SlpPixels {
+        let mut pixels = self.pixels.clone();
+        for (index, pixel) in pixels.iter_mut().enumerate() {
+            if self.player_color_mask[index] {
+                *pixel = (player_index * 16 + *pixel) | *pixel;
+            }
         }
+        pixels
+    }
+
+    fn pixel_at(&self, index: usize) -> SlpPixel {
+        if !self.alpha_mask[index] {
+            SlpPixel::Transparent
+        } else if self.shadow_mask[index] {
+            SlpPixel::Shadow
+        } else if self.player_color_mask[index] {
+            SlpPixel::PlayerColor(self.pixels[index])
+        } else {
+            SlpPixel::Color(self.pixels[index])
+        }
+    }
+
+    fn set_pixel_at(&mut self, index: usize, pixel: SlpPixel) {
+        self.pixels[index] = match pixel {
+            SlpPixel::Color(value) | SlpPixel::PlayerColor(value) => value,
+            SlpPixel::Transparent | SlpPixel::Shadow => 0,
+        };
+        self.alpha_mask[index] = pixel != SlpPixel::Transparent;
+        self.shadow_mask[index] = pixel == SlpPixel::Shadow;
+        self.player_color_mask[index] = match pixel {
+            SlpPixel::PlayerColor(_) => true,
+            _ => false,
+        };
+    }
+
+    /// Returns the left and right padding of a row, or `None` if the row is fully transparent.
+    fn outline(&self, y: u32) -> Option<(u32, u32)> {
+        let width = self.header.width as usize;
+        let row = &self.alpha_mask[(y as usize * width)..((y as usize + 1) * width)];
+        let left = row.iter().position(|opaque| *opaque)?;
+        let right = row.iter().rev().position(|opaque| *opaque)?;
+        Some((left as u32, right as u32))
+    }
+
+    fn encode_row(&self, y: u32, left: u32, right: u32, commands: &mut Vec<u8>) {
+        let row_start = (y * self.header.width) as usize;
+        let row_end = row_start + (self.header.width - right) as usize;
+        let row: Vec<SlpPixel> = ((row_start + left as usize)..row_end).map(|i| self.pixel_at(i)).collect();
+
+        let mut x = 0;
+        while x < row.len() {
+            let run_length = row[x..].iter().take_while(|p| same_command(**p, row[x])).count();
+            let run = &row[x..(x + run_length)];
+            match row[x] {
+                SlpPixel::Transparent => encode_skip(run_length, commands),
+                SlpPixel::Shadow => encode_four_bit_runs(0b1011, run_length, commands),
+                SlpPixel::Color(_) => {
+                    let colors: Vec<u8> = run.iter().map(|p| pixel_value(*p)).collect();
+                    encode_colors(&colors, false, commands);
+                }
+                SlpPixel::PlayerColor(_) => {
+                    let colors: Vec<u8> = run.iter().map(|p| pixel_value(*p)).collect();
+                    encode_colors(&colors, true, commands);
+                }
+            }
+            x += run_length;
+        }
+        commands.push(0x0F);
     }
 }
 
@@ -171,55 +298,122 @@ impl SlpEncodedLength {
 pub struct SlpFile {
     pub header: SlpHeader,
     pub shapes: Vec<SlpLogicalShape>,
-
-    // TODO: Remove this from SlpFile.
-    // We shouldn't be comitting to a player index until we hit the fragment shader.
-    pub player_index: u8,
 }
 
 impl SlpFile {
-    pub fn new(player_index: u8) -> SlpFile {
+    pub fn new() -> SlpFile {
         SlpFile {
             header: SlpHeader::new(),
             shapes: Vec::new(),
-            player_index: player_index,
         }
     }
 
-    // TODO: Implement writing
-
-    pub fn read_from_file<P: AsRef<Path>>(file_name: P, player_index: u8) -> Result<SlpFile> {
+    pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> Result<SlpFile> {
         let file_name = file_name.as_ref();
         let mut file = File::open(file_name)?;
-        return SlpFile::read_from(&mut file, player_index);
+        return SlpFile::read_from(&mut file);
     }
 
-    pub fn read_from<R: Read + Seek>(cursor: &mut R, player_index: u8) -> Result<SlpFile> {
-        let mut slp_file = SlpFile::new(player_index);
+    pub fn read_from<R: Read + Seek>(cursor: &mut R) -> Result<SlpFile> {
+        let mut slp_file = SlpFile::new();
         slp_file.header = SlpHeader::read_from(cursor)?;
         for _shape_index in 0..slp_file.header.shape_count {
-            let mut shape = SlpLogicalShape::new();
-            shape.header = SlpShapeHeader::read_from_file(cursor)?;
+            let shape_header = SlpShapeHeader::read_from_file(cursor)?;
+            let mut shape = SlpLogicalShape::new(shape_header.width, shape_header.height);
+            shape.header = shape_header;
             slp_file.shapes.push(shape);
         }
 
         for shape in &mut slp_file.shapes {
-            SlpFile::read_pixel_data(cursor, shape, player_index)?;
+            SlpFile::read_pixel_data(cursor, shape)?;
         }
 
         Ok(slp_file)
     }
 
-    fn read_pixel_data<R: Read + Seek>(cursor: &mut R,
-                                       shape: &mut SlpLogicalShape,
-                                       player_index: u8)
-                                       -> Result<()> {
+    pub fn write_to_file<P: AsRef<Path>>(&self, file_name: P) -> Result<()> {
+        let mut file = File::create(file_name.as_ref())?;
+        self.write_to(&mut file)
+    }
+
+    /// Encodes the shapes using the header's comment. Each shape is laid out as its outline
+    /// table, followed by its row offset table, followed by the drawing commands for every row.
+    /// Only the size, center, palette offset and properties of the shape headers are kept;
+    /// the offsets are recomputed.
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> Result<()> {
+        let mut header = SlpHeader::new();
+        header.file_version.copy_from_slice("2.0N".as_bytes());
+        header.shape_count = self.shapes.len() as u32;
+        header.comment = self.header.comment;
+
+        let mut offset = (SLP_HEADER_LEN + SLP_SHAPE_HEADER_LEN * self.shapes.len()) as u32;
+        let mut shape_headers = Vec::with_capacity(self.shapes.len());
+        let mut shape_data = Vec::with_capacity(self.shapes.len());
+        for shape in &self.shapes {
+            let (width, height) = (shape.header.width, shape.header.height);
+            let pixel_count = (width * height) as usize;
+            let layer_lengths = [shape.pixels.len(),
+                                 shape.alpha_mask.len(),
+                                 shape.shadow_mask.len(),
+                                 shape.player_color_mask.len()];
+            if width >= 0x8000 || layer_lengths.iter().any(|len| *len != pixel_count) {
+                return Err(ErrorKind::InvalidSlp(format!("shape size {}x{} doesn't match its pixel data",
+                                                         width,
+                                                         height))
+                    .into());
+            }
+
+            let mut shape_header = SlpShapeHeader::new();
+            shape_header.palette_offset = shape.header.palette_offset;
+            shape_header.properties = shape.header.properties;
+            shape_header.width = width;
+            shape_header.height = height;
+            shape_header.center_x = shape.header.center_x;
+            shape_header.center_y = shape.header.center_y;
+            shape_header.shape_outline_offset = offset;
+            shape_header.shape_data_offsets = offset + height * size_of::<u32>() as u32;
+
+            let mut outlines = Vec::with_capacity(height as usize * size_of::<u32>());
+            let mut row_offsets = Vec::with_capacity(height as usize * size_of::<u32>());
+            let mut commands = Vec::new();
+            let commands_offset = shape_header.shape_data_offsets + height * size_of::<u32>() as u32;
+            for y in 0..height {
+                row_offsets.extend_from_slice(&(commands_offset + commands.len() as u32).to_le_bytes());
+                match shape.outline(y) {
+                    Some((left, right)) => {
+                        outlines.extend_from_slice(&(left as u16).to_le_bytes());
+                        outlines.extend_from_slice(&(right as u16).to_le_bytes());
+                        shape.encode_row(y, left, right, &mut commands);
+                    }
+                    None => {
+                        outlines.extend_from_slice(&0x8000u16.to_le_bytes());
+                        outlines.extend_from_slice(&0x8000u16.to_le_bytes());
+                        commands.push(0x0F);
+                    }
+                }
+            }
+
+            offset = commands_offset + commands.len() as u32;
+            shape_headers.push(shape_header);
+            shape_data.push((outlines, row_offsets, commands));
+        }
+
+        header.write_to(stream)?;
+        for shape_header in &shape_headers {
+            shape_header.write_to(stream)?;
+        }
+        for (outlines, row_offsets, commands) in &shape_data {
+            stream.write_all(outlines)?;
+            stream.write_all(row_offsets)?;
+            stream.write_all(commands)?;
+        }
+        Ok(())
+    }
+
+    fn read_pixel_data<R: Read + Seek>(cursor: &mut R, shape: &mut SlpLogicalShape) -> Result<()> {
         let width = shape.header.width;
         let height = shape.header.height;
 
-        // Reserve and zero out pixel data
-        shape.pixels.resize((width * height) as usize, 0u8);
-
         for y in 0..height {
             let line_outline_offset = shape.header.shape_outline_offset + (y * size_of::<u32>() as u32);
 
@@ -277,7 +471,7 @@ impl SlpFile {
                     0b1100 => {
                         let length = SixUpperBit.decode(cmd_byte, cursor)?;
                         for _ in 0..length {
-                            shape.pixels[(y * width + x) as usize] = cursor.read_u8()?;
+                            shape.set_pixel_at((y * width + x) as usize, SlpPixel::Color(cursor.read_u8()?));
                             x += 1;
                         }
                     }
@@ -294,7 +488,7 @@ impl SlpFile {
                     0b0010 => {
                         let length = LargeLength.decode(cmd_byte, cursor)?;
                         for _ in 0..length {
-                            shape.pixels[(y * width + x) as usize] = cursor.read_u8()?;
+                            shape.set_pixel_at((y * width + x) as usize, SlpPixel::Color(cursor.read_u8()?));
                             x += 1;
                         }
                     }
@@ -308,11 +502,9 @@ impl SlpFile {
                     // Copy and colorize block
                     0b0110 => {
                         let length = FourUpperBit.decode(cmd_byte, cursor)?;
-
                         for _ in 0..length {
                             let relative_index = cursor.read_u8()?;
-                            let player_color = player_index * 16 + relative_index;
-                            shape.pixels[(y * width + x) as usize] = player_color | relative_index;
+                            shape.set_pixel_at((y * width + x) as usize, SlpPixel::PlayerColor(relative_index));
                             x += 1;
                         }
                     }
@@ -322,7 +514,7 @@ impl SlpFile {
                         let length = FourUpperBit.decode(cmd_byte, cursor)?;
                         let color = cursor.read_u8()?;
                         for _ in 0..length {
-                            shape.pixels[(y * width + x) as usize] = color;
+                            shape.set_pixel_at((y * width + x) as usize, SlpPixel::Color(color));
                             x += 1;
                         }
                     }
@@ -330,19 +522,15 @@ impl SlpFile {
                     // Transform block
                     0b1010 => {
                         let length = FourUpperBit.decode(cmd_byte, cursor)?;
-                        let relative_index = cursor.read_u8()?;
-                        let player_color = player_index * 16 + relative_index;
-
+                        let pixel = SlpPixel::PlayerColor(cursor.read_u8()?);
                         for _ in 0..length {
-                            shape.pixels[(y * width + x) as usize] = player_color | relative_index;
+                            shape.set_pixel_at((y * width + x) as usize, pixel);
                             x += 1;
                         }
                     }
 
                     // Shadow pixels
                     0b1011 => {
-                        let length = FourUpperBit.decode(cmd_byte, cursor)?;
-                        // TODO: Render the shadow instead of skipping
                         // The length is determined as in cases 6, 7 and 0x0a. For the length
                         // of the run, the destination pixels already in the buffer are used
                         // as a lookup into a "shadow table" and this lookup pixel is then
@@ -350,8 +538,11 @@ impl SlpFile {
                         // color-tinted variation of the real color table, and is generally
                         // used to draw things like the red-tinted checkerboard sprites when
                         // you try to place a building in an area where it cannot be placed.
-                        x += length as u32;
-                        println!("TODO: skipped {} instead of drawing the shadow", length);
+                        let length = FourUpperBit.decode(cmd_byte, cursor)?;
+                        for _ in 0..length {
+                            shape.set_pixel_at((y * width + x) as usize, SlpPixel::Shadow);
+                            x += 1;
+                        }
                     }
 
                     // Extended
@@ -369,13 +560,182 @@ impl SlpFile {
     }
 }
 
+const SLP_HEADER_LEN: usize = 32;
+const SLP_SHAPE_HEADER_LEN: usize = 32;
+
+/// Fill commands are only worth it for runs of at least this many identical pixels
+const MIN_FILL_LENGTH: usize = 3;
+
+fn same_command(a: SlpPixel, b: SlpPixel) -> bool {
+    ::std::mem::discriminant(&a) == ::std::mem::discriminant(&b)
+}
+
+fn pixel_value(pixel: SlpPixel) -> u8 {
+    match pixel {
+        SlpPixel::Color(value) | SlpPixel::PlayerColor(value) => value,
+        SlpPixel::Transparent | SlpPixel::Shadow => 0,
+    }
+}
+
+fn encode_six_bit(opcode: u8, length: usize, commands: &mut Vec<u8>) {
+    commands.push(((length as u8) << 2) | opcode);
+}
+
+fn encode_large(opcode: u8, length: usize, commands: &mut Vec<u8>) {
+    commands.push((((length >> 8) as u8) << 4) | opcode);
+    commands.push(length as u8);
+}
+
+fn encode_four_bit(opcode: u8, length: usize, commands: &mut Vec<u8>) {
+    if length < 16 {
+        commands.push(((length as u8) << 4) | opcode);
+    } else {
+        commands.push(opcode);
+        commands.push(length as u8);
+    }
+}
+
+fn encode_four_bit_runs(opcode: u8, mut length: usize, commands: &mut Vec<u8>) {
+    while length > 0 {
+        let chunk = cmp::min(length, 0xFF);
+        encode_four_bit(opcode, chunk, commands);
+        length -= chunk;
+    }
+}
+
+fn encode_skip(mut length: usize, commands: &mut Vec<u8>) {
+    while length > 0 {
+        let chunk = cmp::min(length, 0xFFF);
+        if chunk < 64 {
+            encode_six_bit(0b01, chunk, commands);
+        } else {
+            encode_large(0b0011, chunk, commands);
+        }
+        length -= chunk;
+    }
+}
+
+fn encode_copy(colors: &[u8], player_color: bool, commands: &mut Vec<u8>) {
+    let max_chunk = if player_color { 0xFF } else { 0xFFF };
+    for chunk in colors.chunks(max_chunk) {
+        if player_color {
+            encode_four_bit(0b0110, chunk.len(), commands);
+        } else if chunk.len() < 64 {
+            encode_six_bit(0b00, chunk.len(), commands);
+        } else {
+            encode_large(0b0010, chunk.len(), commands);
+        }
+        commands.extend_from_slice(chunk);
+    }
+}
+
+/// Encodes a run of color (or player color) pixels, using fill commands for
+/// long enough runs of the same color and copy commands for everything else.
+fn encode_colors(colors: &[u8], player_color: bool, commands: &mut Vec<u8>) {
+    let fill_opcode = if player_color { 0b1010 } else { 0b0111 };
+
+    let (mut i, mut copy_start) = (0, 0);
+    while i < colors.len() {
+        let same = colors[i..].iter().take_while(|c| **c == colors[i]).count();
+        if same >= MIN_FILL_LENGTH {
+            encode_copy(&colors[copy_start..i], player_color, commands);
+            for chunk in colors[i..(i + same)].chunks(0xFF) {
+                encode_four_bit(fill_opcode, chunk.len(), commands);
+                commands.push(colors[i]);
+            }
+            copy_start = i + same;
+        }
+        i += same;
+    }
+    encode_copy(&colors[copy_start..], player_color, commands);
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{SlpHeader, ErrorKind};
+    use super::{SlpHeader, ErrorKind, SlpFile, SlpLogicalShape, SlpPixel};
+    use std::io;
+
+    fn test_slp() -> SlpFile {
+        let mut shape = SlpLogicalShape::new(300, 4);
+        shape.header.center_x = 12;
+        shape.header.center_y = -3;
+        shape.header.properties = 0x10;
+        for x in 2..298 {
+            shape.set_pixel(x, 0, SlpPixel::Color((x % 7) as u8));
+        }
+        for x in 0..100 {
+            shape.set_pixel(x, 1, SlpPixel::Color(9));
+            shape.set_pixel(100 + x, 1, SlpPixel::PlayerColor((x / 40) as u8));
+            shape.set_pixel(200 + x, 1, SlpPixel::Shadow);
+        }
+        shape.set_pixel(5, 3, SlpPixel::PlayerColor(3));
+        shape.set_pixel(150, 3, SlpPixel::Color(0));
+
+        let mut wide_shape = SlpLogicalShape::new(5000, 1);
+        for x in 0..5000 {
+            wide_shape.set_pixel(x, 0, SlpPixel::Color(if x < 4200 { (x % 3) as u8 } else { 1 }));
+        }
+
+        let mut slp_file = SlpFile::new();
+        slp_file.shapes = vec![shape, SlpLogicalShape::new(3, 3), wide_shape];
+        slp_file
+    }
+
+    fn encode(slp_file: &SlpFile) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        slp_file.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_slp_encode_round_trip() {
+        let slp_file = test_slp();
+        let bytes = encode(&slp_file);
+
+        let decoded = SlpFile::read_from(&mut io::Cursor::new(&bytes[..])).unwrap();
+        assert_eq!(slp_file.shapes.len() as u32, decoded.header.shape_count);
+        for (expected, actual) in slp_file.shapes.iter().zip(decoded.shapes.iter()) {
+            assert_eq!(expected.header.width, actual.header.width);
+            assert_eq!(expected.header.height, actual.header.height);
+            assert_eq!(expected.header.center_x, actual.header.center_x);
+            assert_eq!(expected.header.center_y, actual.header.center_y);
+            assert_eq!(expected.header.properties, actual.header.properties);
+            assert_eq!(expected.pixels, actual.pixels);
+            assert_eq!(expected.alpha_mask, actual.alpha_mask);
+            assert_eq!(expected.shadow_mask, actual.shadow_mask);
+            assert_eq!(expected.player_color_mask, actual.player_color_mask);
+        }
+        assert_eq!(bytes, encode(&decoded));
+    }
+
+    #[test]
+    fn test_slp_layers() {
+        let bytes = encode(&test_slp());
+        let slp_file = SlpFile::read_from(&mut io::Cursor::new(&bytes[..])).unwrap();
+        let shape = &slp_file.shapes[0];
+
+        assert_eq!(SlpPixel::Transparent, shape.pixel(0, 0));
+        assert_eq!(SlpPixel::Color(0), shape.pixel(150, 3));
+        assert_eq!(SlpPixel::PlayerColor(2), shape.pixel(199, 1));
+        assert_eq!(SlpPixel::Shadow, shape.pixel(250, 1));
+        assert!(shape.alpha_mask[300 + 250] && shape.shadow_mask[300 + 250]);
+        assert!(!shape.alpha_mask[0] && !shape.alpha_mask[3 * 300 + 150 + 1]);
+
+        let player_2 = shape.player_pixels(2);
+        assert_eq!(0, player_2[0]);
+        assert_eq!(9, player_2[300]);
+        assert_eq!(32, player_2[400]);
+        assert_eq!(34, player_2[499]);
+        assert_eq!(0, player_2[500]);
+        assert_eq!(35, player_2[3 * 300 + 5]);
+
+        let player_5 = shape.player_pixels(5);
+        assert_eq!(9, player_5[300]);
+        assert_eq!(80, player_5[400]);
+    }
 
     #[test]
     fn test_slp_header_read_from() {
-        use std::io;
         let data = "2.0N\x04\0\0\0test\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0".as_bytes();
         let result = SlpHeader::read_from(&mut io::Cursor::new(data));
         match result {
@@ -386,7 +746,6 @@ mod tests {
 
     #[test]
     fn test_slp_header_read_from_bad_header() {
-        use std::io;
         let data = "2.1N\x04\0\0\0test\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0".as_bytes();
         let result = SlpHeader::read_from(&mut io::Cursor::new(data));
         match result {
@@ -19,14 +19,40 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use super::MoveToPositionParams;
+use super::{AttackParams, BuildParams, CancelTrainingParams, GatherParams, MoveToPositionParams, ResearchParams,
+            SetRallyPointParams, TrainParams};
 
 /// Enum of possible actions a unit can undertake
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Action {
     /// Clears a unit's action queue
     ClearQueue,
 
     /// Instructs a unit to move to a given position on the map
     MoveToPosition(MoveToPositionParams),
+
+    /// Instructs a unit to attack another unit until one of them dies
+    Attack(AttackParams),
+
+    /// Instructs a unit to gather from a resource, taking what it gathers to a drop site,
+    /// until the resource runs out
+    Gather(GatherParams),
+
+    /// Instructs a unit to construct a building, placing its foundation first if nobody has yet
+    Build(BuildParams),
+
+    /// Adds a unit to the end of a building's training queue, paying for it up front
+    Train(TrainParams),
+
+    /// Takes a unit out of a building's training queue and gives back what it cost
+    CancelTraining(CancelTrainingParams),
+
+    /// Sets where the units a building trains go once they're done
+    SetRallyPoint(SetRallyPointParams),
+
+    /// Starts research at a building, paying for it up front
+    Research(ResearchParams),
+
+    /// Stops the research a building is doing and gives back what it cost
+    CancelResearch,
 }
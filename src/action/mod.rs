@@ -20,7 +20,21 @@
 // SOFTWARE.
 
 mod action;
+mod attack;
+mod build;
+mod cancel_training;
+mod gather;
 mod move_to_position;
+mod research;
+mod set_rally_point;
+mod train;
 
 pub use self::action::Action;
+pub use self::attack::AttackParams;
+pub use self::build::BuildParams;
+pub use self::cancel_training::CancelTrainingParams;
+pub use self::gather::GatherParams;
 pub use self::move_to_position::MoveToPositionParams;
+pub use self::research::ResearchParams;
+pub use self::set_rally_point::SetRallyPointParams;
+pub use self::train::TrainParams;
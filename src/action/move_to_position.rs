@@ -21,7 +21,7 @@
 
 use crate::ecs::resource::path_finder::Path;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct MoveToPositionParams {
     pub path: Path,
 }
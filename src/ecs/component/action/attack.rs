@@ -0,0 +1,55 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::types::Fixed;
+use specs::{self, Entity};
+
+/// The target is normally the entity being attacked; see `ProjectileComponent` for why it
+/// can be something else.
+#[derive(Clone, Debug)]
+pub struct AttackActionComponent<Target = Entity> {
+    pub target: Target,
+
+    /// Time left before the unit can strike again
+    pub reload_time_left: Fixed,
+}
+
+impl specs::Component for AttackActionComponent {
+    type Storage = specs::HashMapStorage<AttackActionComponent>;
+}
+
+impl<Target> AttackActionComponent<Target> {
+    pub fn new(target: Target) -> AttackActionComponent<Target> {
+        AttackActionComponent {
+            target: target,
+            reload_time_left: 0.into(),
+        }
+    }
+
+    /// The same attack, with its target referred to some other way
+    pub fn with_target<T>(&self, target: T) -> AttackActionComponent<T> {
+        AttackActionComponent {
+            target: target,
+            reload_time_left: self.reload_time_left,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+use crate::identifier::UnitId;
+use crate::types::Vector3;
+use specs::{self, Entity};
+use super::TargetPath;
+
+/// The target is normally the foundation entity; see `ProjectileComponent` for why it can be
+/// something else.
+#[derive(Clone, Debug)]
+pub struct BuildActionComponent<Target = Entity> {
+    /// ID of the building to construct
+    pub unit_id: UnitId,
+    pub position: Vector3,
+
+    /// The foundation being worked on, once it has been found or placed
+    pub target: Option<Target>,
+
+    /// The way to the foundation, while walking to it
+    pub target_path: Option<TargetPath>,
+}
+
+impl specs::Component for BuildActionComponent {
+    type Storage = specs::HashMapStorage<BuildActionComponent>;
+}
+
+impl<Target> BuildActionComponent<Target> {
+    pub fn new(unit_id: UnitId, position: Vector3) -> BuildActionComponent<Target> {
+        BuildActionComponent {
+            unit_id: unit_id,
+            position: position,
+            target: None,
+            target_path: None,
+        }
+    }
+
+    /// The same construction, with its target referred to some other way
+    pub fn with_target<T>(&self, target: Option<T>) -> BuildActionComponent<T> {
+        BuildActionComponent {
+            unit_id: self.unit_id,
+            position: self.position,
+            target: target,
+            target_path: self.target_path.clone(),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::dat::ResourceType;
+use crate::types::Fixed;
+use specs::{self, Entity};
+use super::TargetPath;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GatherStage {
+    /// Walking to the resource
+    ToResource,
+
+    /// Next to the resource, taking from it
+    Gathering,
+
+    /// Taking what was gathered to a drop site
+    ToDropSite,
+}
+
+/// The target is normally the entity being gathered from; see `ProjectileComponent` for why it
+/// can be something else.
+#[derive(Clone, Debug)]
+pub struct GatherActionComponent<Target = Entity> {
+    pub target: Target,
+    pub stage: GatherStage,
+
+    /// The kind of resource being carried, once the unit has started gathering
+    pub resource_type: Option<ResourceType>,
+    pub carried: Fixed,
+
+    /// The way to the resource or drop site, while walking to one
+    pub target_path: Option<TargetPath>,
+}
+
+impl specs::Component for GatherActionComponent {
+    type Storage = specs::HashMapStorage<GatherActionComponent>;
+}
+
+impl<Target> GatherActionComponent<Target> {
+    pub fn new(target: Target) -> GatherActionComponent<Target> {
+        GatherActionComponent {
+            target: target,
+            stage: GatherStage::ToResource,
+            resource_type: None,
+            carried: 0.into(),
+            target_path: None,
+        }
+    }
+
+    /// The same gathering, with its target referred to some other way
+    pub fn with_target<T>(&self, target: T) -> GatherActionComponent<T> {
+        GatherActionComponent {
+            target: target,
+            stage: self.stage,
+            resource_type: self.resource_type,
+            carried: self.carried,
+            target_path: self.target_path.clone(),
+        }
+    }
+}
@@ -19,6 +19,12 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod attack;
+mod build;
+mod gather;
 mod move_to_position;
 
-pub use self::move_to_position::MoveToPositionActionComponent;
+pub use self::attack::AttackActionComponent;
+pub use self::build::BuildActionComponent;
+pub use self::gather::{GatherActionComponent, GatherStage};
+pub use self::move_to_position::{MoveToPositionActionComponent, TargetPath};
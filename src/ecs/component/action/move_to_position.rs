@@ -20,6 +20,7 @@
 // SOFTWARE.
 
 use crate::ecs::resource::path_finder::Path;
+use crate::types::Vector3;
 use specs;
 
 #[derive(Clone, Debug)]
@@ -36,3 +37,22 @@ impl MoveToPositionActionComponent {
         MoveToPositionActionComponent { path: path }
     }
 }
+
+/// The way around whatever is in the way of something a unit is walking up to, like a resource
+/// or a foundation. Those are in occupied tiles themselves, so the path only gets as close as it
+/// can, and the unit heads straight for the destination from there.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TargetPath {
+    /// Where the path was found to. A new one is found when the destination changes.
+    pub destination: Vector3,
+    pub path: Path,
+}
+
+impl TargetPath {
+    pub fn new(destination: Vector3, path: Path) -> TargetPath {
+        TargetPath {
+            destination: destination,
+            path: path,
+        }
+    }
+}
@@ -49,6 +49,17 @@ impl ActionQueueComponent {
     /// This should be called from a system that actually performs and action
     /// after the action is completed. Marking it as done will tell the UnitActionSystem
     /// to move on to the next action in the queue.
+    pub fn from_parts(actions: Vec<Action>,
+                      current_action: Option<Action>,
+                      current_action_done: bool)
+                      -> ActionQueueComponent {
+        ActionQueueComponent {
+            actions: actions,
+            current_action: current_action,
+            current_action_done: current_action_done,
+        }
+    }
+
     pub fn mark_current_done(&mut self) {
         self.current_action_done = true;
     }
@@ -76,6 +87,11 @@ impl ActionQueueComponent {
         &self.current_action
     }
 
+    /// Actions waiting to be started after the current one
+    pub fn queued_actions(&self) -> &[Action] {
+        &self.actions
+    }
+
     /// This should only ever be called by UnitActionSystem
     pub fn next_action(&mut self) {
         if !self.actions.is_empty() {
@@ -0,0 +1,57 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use specs;
+use crate::types::Fixed;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DeathStage {
+    /// Playing the dying graphic
+    Dying,
+
+    /// Turned into its dead unit, which goes away once `time_left` runs out
+    Decaying,
+
+    /// Turned into a dead unit that stays around for good, like a tree stump
+    Dead,
+}
+
+/// Added to units when they run out of hit points. Units with one are no longer
+/// part of the game, and are only kept around to be shown.
+#[derive(Clone, Debug)]
+pub struct DeathComponent {
+    pub stage: DeathStage,
+    pub time_left: Fixed,
+}
+
+impl specs::Component for DeathComponent {
+    type Storage = specs::HashMapStorage<DeathComponent>;
+}
+
+impl DeathComponent {
+    pub fn new(stage: DeathStage, time_left: Fixed) -> DeathComponent {
+        DeathComponent {
+            stage: stage,
+            time_left: time_left,
+        }
+    }
+}
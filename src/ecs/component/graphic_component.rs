@@ -31,6 +31,9 @@ pub struct GraphicComponent {
     pub frame_time: Fixed,
     pub flip_horizontal: bool,
     pub flip_vertical: bool,
+
+    /// Drawn over the graphic to show that the unit is damaged, like fire on buildings
+    pub damage_graphic_id: Option<GraphicId>,
 }
 
 impl specs::Component for GraphicComponent {
@@ -46,6 +49,7 @@ impl GraphicComponent {
             frame_time: 0.into(),
             flip_horizontal: false,
             flip_vertical: false,
+            damage_graphic_id: None,
         }
     }
 
@@ -0,0 +1,62 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::identifier::PlayerId;
+use specs;
+
+#[derive(Clone, Debug)]
+pub struct HealthComponent {
+    pub hit_points: i32,
+    pub max_hit_points: i32,
+
+    /// Player that last damaged the unit, who gets the credit if it dies
+    pub last_attacker_id: Option<PlayerId>,
+}
+
+impl specs::Component for HealthComponent {
+    type Storage = specs::VecStorage<HealthComponent>;
+}
+
+impl HealthComponent {
+    pub fn new(max_hit_points: i32) -> HealthComponent {
+        HealthComponent {
+            hit_points: max_hit_points,
+            max_hit_points: max_hit_points,
+            last_attacker_id: None,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.hit_points <= 0
+    }
+
+    /// Takes away hit points, without going below zero
+    pub fn damage(&mut self, amount: i32, attacker_id: PlayerId) {
+        self.hit_points = (self.hit_points - amount).max(0);
+        self.last_attacker_id = Some(attacker_id);
+    }
+
+    /// Remaining hit points as a percentage of the maximum
+    pub fn percent(&self) -> i32 {
+        if self.max_hit_points > 0 { self.hit_points * 100 / self.max_hit_points } else { 0 }
+    }
+}
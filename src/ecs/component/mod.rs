@@ -22,10 +22,17 @@
 mod action;
 mod action_queue_component;
 mod camera_component;
+mod construction_component;
+mod death_component;
 mod decal_component;
 mod graphic_component;
+mod health_component;
 mod on_screen_component;
+mod projectile_component;
+mod research_component;
+mod resource_component;
 mod selected_unit_component;
+mod training_queue_component;
 mod transform_component;
 mod unit_component;
 mod velocity_component;
@@ -33,10 +40,17 @@ mod velocity_component;
 pub use self::action::*;
 pub use self::action_queue_component::ActionQueueComponent;
 pub use self::camera_component::CameraComponent;
+pub use self::construction_component::ConstructionComponent;
+pub use self::death_component::{DeathComponent, DeathStage};
 pub use self::decal_component::DecalComponent;
 pub use self::graphic_component::GraphicComponent;
+pub use self::health_component::HealthComponent;
 pub use self::on_screen_component::OnScreenComponent;
+pub use self::projectile_component::ProjectileComponent;
+pub use self::research_component::ResearchComponent;
+pub use self::resource_component::ResourceComponent;
 pub use self::selected_unit_component::SelectedUnitComponent;
+pub use self::training_queue_component::{MAX_TRAINING_QUEUE_LENGTH, TrainingQueueComponent};
 pub use self::transform_component::TransformComponent;
 pub use self::unit_component::UnitComponent;
 pub use self::velocity_component::VelocityComponent;
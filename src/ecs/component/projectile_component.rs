@@ -0,0 +1,119 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::identifier::{CivilizationId, PlayerId, UnitId};
+use specs::{self, Entity};
+use crate::types::{Fixed, Vector3};
+
+/// A projectile in flight, fired by a ranged unit at another unit. Whether it hits is decided
+/// when it's fired; it deals its damage when it lands.
+///
+/// The target is normally the entity it was fired at. Saved games refer to it some other way,
+/// since entities only mean something inside the world they came from.
+#[derive(Clone, Debug)]
+pub struct ProjectileComponent<Target = Entity> {
+    pub player_id: PlayerId,
+    pub civilization_id: CivilizationId,
+
+    /// The unit that fired the projectile, whose attacks it deals
+    pub attacker_unit_id: UnitId,
+
+    pub target: Target,
+    pub hits: bool,
+
+    pub start: Vector3,
+    pub end: Vector3,
+
+    /// How high above the straight line between start and end the projectile peaks
+    pub arc_height: Fixed,
+
+    pub flight_time: Fixed,
+    pub elapsed_time: Fixed,
+}
+
+impl specs::Component for ProjectileComponent {
+    type Storage = specs::HashMapStorage<ProjectileComponent>;
+}
+
+impl<Target> ProjectileComponent<Target> {
+    /// The same projectile, with its target referred to some other way
+    pub fn with_target<T>(&self, target: T) -> ProjectileComponent<T> {
+        ProjectileComponent {
+            player_id: self.player_id,
+            civilization_id: self.civilization_id,
+            attacker_unit_id: self.attacker_unit_id,
+            target: target,
+            hits: self.hits,
+            start: self.start,
+            end: self.end,
+            arc_height: self.arc_height,
+            flight_time: self.flight_time,
+            elapsed_time: self.elapsed_time,
+        }
+    }
+
+    /// Position along the flight path at the given time since it was fired
+    pub fn position_at(&self, time: Fixed) -> Vector3 {
+        if time >= self.flight_time {
+            return self.end;
+        }
+        let progress = time / self.flight_time;
+        let mut position = self.start + (self.end - self.start) * progress;
+        position.z += self.arc_height * Fixed::from(4) * progress * (Fixed::from(1) - progress);
+        position
+    }
+
+    pub fn landed(&self) -> bool {
+        self.elapsed_time >= self.flight_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ProjectileComponent;
+    use crate::types::{Fixed, Vector3};
+
+    #[test]
+    fn test_position_at() {
+        let mut projectile = ProjectileComponent {
+            player_id: 1usize.into(),
+            civilization_id: 1usize.into(),
+            attacker_unit_id: 4usize.into(),
+            target: 2,
+            hits: true,
+            start: Vector3::new(0.into(), 0.into(), 1.into()),
+            end: Vector3::new(4.into(), 2.into(), 0.into()),
+            arc_height: 0.into(),
+            flight_time: 2.into(),
+            elapsed_time: 0.into(),
+        };
+        assert_eq!(projectile.start, projectile.position_at(0.into()));
+        assert_eq!(Vector3::new(2.into(), 1.into(), Fixed::from(0.5)), projectile.position_at(1.into()));
+        assert_eq!(projectile.end, projectile.position_at(3.into()));
+
+        // Ballistic projectiles peak halfway along
+        projectile.arc_height = 2.into();
+        assert_eq!(Vector3::new(2.into(), 1.into(), Fixed::from(2.5)), projectile.position_at(1.into()));
+        assert!(!projectile.landed());
+        projectile.elapsed_time = 2.into();
+        assert!(projectile.landed());
+    }
+}
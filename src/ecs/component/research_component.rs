@@ -0,0 +1,55 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+//
+use crate::action::Action;
+use crate::identifier::ResearchId;
+use specs;
+use crate::types::Fixed;
+
+/// The research a building is doing. A building researches one thing at a time, alongside
+/// whatever it's training.
+#[derive(Clone, Debug)]
+pub struct ResearchComponent {
+    /// `Research` and `CancelResearch` actions that arrived this turn, applied by the
+    /// ResearchSystem in the order they were given
+    pub orders: Vec<Action>,
+
+    /// Research that has been paid for and is underway
+    pub research_id: Option<ResearchId>,
+
+    /// How long the research has been underway
+    pub elapsed_time: Fixed,
+}
+
+impl specs::Component for ResearchComponent {
+    type Storage = specs::HashMapStorage<ResearchComponent>;
+}
+
+impl ResearchComponent {
+    pub fn new() -> ResearchComponent {
+        ResearchComponent {
+            orders: Vec::new(),
+            research_id: None,
+            elapsed_time: 0.into(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::dat::{self, ResourceType};
+use specs;
+use crate::types::Fixed;
+
+/// Something that can be gathered from, like a tree, a berry bush or a gold mine
+#[derive(Clone, Debug)]
+pub struct ResourceComponent {
+    pub resource_type: ResourceType,
+
+    /// How much is left
+    pub amount: Fixed,
+}
+
+impl specs::Component for ResourceComponent {
+    type Storage = specs::HashMapStorage<ResourceComponent>;
+}
+
+impl ResourceComponent {
+    pub fn new(resource_type: ResourceType, amount: Fixed) -> ResourceComponent {
+        ResourceComponent {
+            resource_type: resource_type,
+            amount: amount,
+        }
+    }
+
+    /// The resource a unit holds, if it's a resource unit that has any food, wood, stone or gold
+    pub fn from_unit(unit_info: &dat::Unit) -> Option<ResourceComponent> {
+        if unit_info.interaction_mode != dat::InteractionMode::Resource {
+            return None;
+        }
+        unit_info.resource_storage
+            .iter()
+            .find(|storage| {
+                let gatherable = match storage.resource_type {
+                    ResourceType::Food | ResourceType::Wood | ResourceType::Stone | ResourceType::Gold => true,
+                    ResourceType::Unknown(_) => false,
+                };
+                gatherable && storage.amount > 0.0
+            })
+            .map(|storage| ResourceComponent::new(storage.resource_type, storage.amount.into()))
+    }
+}
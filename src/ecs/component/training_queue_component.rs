@@ -0,0 +1,60 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+use crate::action::Action;
+use crate::identifier::UnitId;
+use specs;
+use crate::types::{Fixed, Vector3};
+
+/// Most units a building can have waiting to be trained at once, like in the sequel
+pub const MAX_TRAINING_QUEUE_LENGTH: usize = 15;
+
+/// The units a building is training, one after another
+#[derive(Clone, Debug)]
+pub struct TrainingQueueComponent {
+    /// `Train`, `CancelTraining` and `SetRallyPoint` actions that arrived this turn, applied by
+    /// the TrainingSystem in the order they were given
+    pub orders: Vec<Action>,
+
+    /// Units that have been paid for, the first being the one in training
+    pub units: Vec<UnitId>,
+
+    /// How long the first unit has been in training
+    pub elapsed_time: Fixed,
+
+    /// Where finished units head to, if anywhere
+    pub rally_point: Option<Vector3>,
+}
+
+impl specs::Component for TrainingQueueComponent {
+    type Storage = specs::HashMapStorage<TrainingQueueComponent>;
+}
+
+impl TrainingQueueComponent {
+    pub fn new() -> TrainingQueueComponent {
+        TrainingQueueComponent {
+            orders: Vec::new(),
+            units: Vec::new(),
+            elapsed_time: 0.into(),
+            rally_point: None,
+        }
+    }
+}
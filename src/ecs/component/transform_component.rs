@@ -42,10 +42,23 @@ impl TransformComponent {
         }
     }
 
+    /// Recreates a transform exactly as it was, including where it was on the previous tick
+    pub fn from_parts(position: Vector3, last_position: Vector3, rotation: Fixed) -> TransformComponent {
+        TransformComponent {
+            current_position: position,
+            last_position: last_position,
+            rotation: rotation,
+        }
+    }
+
     pub fn position<'a>(&self) -> &Vector3 {
         &self.current_position
     }
 
+    pub fn last_position(&self) -> &Vector3 {
+        &self.last_position
+    }
+
     pub fn set_position(&mut self, position: Vector3) {
         self.last_position = self.current_position;
         self.current_position = position;
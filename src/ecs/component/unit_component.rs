@@ -20,6 +20,7 @@
 // SOFTWARE.
 
 use crate::dat;
+use crate::ecs::resource::Technologies;
 use crate::identifier::{CivilizationId, UnitId, PlayerId};
 use specs;
 
@@ -47,4 +48,10 @@ impl UnitComponent {
     pub fn db<'a>(&self, empires: &'a dat::EmpiresDbRef) -> &'a dat::Unit {
         empires.unit(self.civilization_id, self.unit_id)
     }
+
+    /// The unit's information as its player's research has left it, which is what
+    /// anything that affects the simulation should go by
+    pub fn info<'a>(&self, technologies: &'a Technologies) -> &'a dat::Unit {
+        technologies.unit(self.player_id, self.civilization_id, self.unit_id)
+    }
 }
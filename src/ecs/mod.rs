@@ -26,4 +26,6 @@ pub mod system;
 mod world;
 
 pub use self::component::*;
-pub use self::world::{SystemGroup, WorldPlanner, create_world_planner};
+pub use self::world::{SystemGroup, WorldPlanner, create_world_planner, load_world_planner};
+#[cfg(test)]
+pub use self::world::vector;
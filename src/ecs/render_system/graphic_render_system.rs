@@ -21,7 +21,7 @@
 
 use crate::dat;
 use crate::ecs::{GraphicComponent, OnScreenComponent, TransformComponent};
-use crate::ecs::resource::{RenderCommands, ViewProjector};
+use crate::ecs::resource::{BuildingPlacement, RenderCommands, ViewProjector};
 use crate::identifier::{GraphicId, PlayerColorId};
 use nalgebra::Vector2;
 use crate::resource::{DrsKey, RenderCommand, ShapeKey};
@@ -59,15 +59,17 @@ impl GraphicRenderSystem {
                                                           flip_vertical));
         }
         for delta in &graphic.deltas {
-            let delta_position = *position + Vector2::new(delta.offset_x as i32, delta.offset_y as i32);
-            self.render_graphic(render_commands,
-                                projector,
-                                &delta_position,
-                                player_color_id,
-                                delta.graphic_id,
-                                frame,
-                                flip_horizontal,
-                                flip_vertical);
+            if let Some(delta_graphic_id) = delta.graphic_id {
+                let delta_position = *position + Vector2::new(delta.offset_x as i32, delta.offset_y as i32);
+                self.render_graphic(render_commands,
+                                    projector,
+                                    &delta_position,
+                                    player_color_id,
+                                    delta_graphic_id,
+                                    frame,
+                                    flip_horizontal,
+                                    flip_vertical);
+            }
         }
     }
 }
@@ -79,9 +81,26 @@ impl RenderSystem for GraphicRenderSystem {
             components(graphics: GraphicComponent),
             components(on_screen: OnScreenComponent),
             resource(projector: ViewProjector),
+            resource(building_placement: BuildingPlacement),
             mut resource(render_commands: RenderCommands),
         ]);
 
+        // Ghost of the building about to be placed
+        if let Some(ref placing) = building_placement.placing {
+            let building_info = self.empires.unit(placing.civilization_id, placing.unit_id);
+            if let Some(graphic_id) = building_info.standing_graphic {
+                let position = projector.project(&placing.position);
+                self.render_graphic(&mut render_commands,
+                                    &projector,
+                                    &position,
+                                    placing.player_color_id,
+                                    graphic_id,
+                                    0,
+                                    false,
+                                    false);
+            }
+        }
+
         for (transform, graphic, _on_screen) in (&transforms, &graphics, &on_screen).iter() {
             if let Some(graphic_id) = graphic.graphic_id {
                 let position = projector.project(&transform.lerped_position(lerp));
@@ -94,6 +113,18 @@ impl RenderSystem for GraphicRenderSystem {
                                     graphic.flip_horizontal,
                                     graphic.flip_vertical);
             }
+            if let Some(damage_graphic_id) = graphic.damage_graphic_id {
+                // TODO: Animate damage graphics; they're only ever shown on their first frame
+                let position = projector.project(&transform.lerped_position(lerp));
+                self.render_graphic(&mut render_commands,
+                                    &projector,
+                                    &position,
+                                    graphic.player_color_id,
+                                    damage_graphic_id,
+                                    0,
+                                    false,
+                                    false);
+            }
         }
     }
 }
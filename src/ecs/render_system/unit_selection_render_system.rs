@@ -21,7 +21,7 @@
 
 use crate::dat;
 use crate::ecs::{OnScreenComponent, SelectedUnitComponent, TransformComponent, UnitComponent};
-use crate::ecs::resource::{RenderCommands, ViewProjector};
+use crate::ecs::resource::{BuildingPlacement, RenderCommands, ViewProjector};
 use crate::resource::RenderCommand;
 use specs::{self, Join};
 use super::RenderSystem;
@@ -46,9 +46,30 @@ impl RenderSystem for UnitSelectionRenderSystem {
             components(on_screen: OnScreenComponent),
             components(selected_units: SelectedUnitComponent),
             resource(projector: ViewProjector),
+            resource(building_placement: BuildingPlacement),
             mut resource(render_commands: RenderCommands),
         ]);
 
+        // Outline the tiles the building about to be placed would cover, in red if it doesn't fit
+        if let Some(ref placing) = building_placement.placing {
+            let building_info = self.empires.unit(placing.civilization_id, placing.unit_id);
+            let (rows, cols) = unit::footprint(building_info, &placing.position);
+            let z = placing.position.z;
+            let color = if placing.valid { Color::rgb(0, 255, 0) } else { Color::rgb(255, 0, 0) };
+            let points: [Vector3; 4] = [Vector3::new(cols.start.into(), rows.start.into(), z),
+                                        Vector3::new(cols.end.into(), rows.start.into(), z),
+                                        Vector3::new(cols.end.into(), rows.end.into(), z),
+                                        Vector3::new(cols.start.into(), rows.end.into(), z)];
+            let depth = projector.project(&placing.position).y;
+            for i in 0..4 {
+                render_commands.push(RenderCommand::new_line(1,
+                                                             depth,
+                                                             color,
+                                                             projector.project(&points[i]),
+                                                             projector.project(&points[(i + 1) % 4])));
+            }
+        }
+
         let items = (&transforms, &units, &selected_units, &on_screen);
         for (transform, unit, _selected_unit, _on_screen) in items.iter() {
             let unit_info = self.empires.unit(unit.civilization_id, unit.unit_id);
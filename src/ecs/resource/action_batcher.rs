@@ -20,12 +20,60 @@
 // SOFTWARE.
 
 use crate::action::Action;
-use specs::Index;
+use specs::{Entity, Index};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use std::mem;
 
+/// An entity that actions are given to, in a form that can be sent to peers. Every peer creates
+/// the same entities in the same order (local-only things like the move marker are made up front
+/// and reused), so an index means the same unit to all of them. The generation tells apart the
+/// entities that have had the index, so that actions for a unit that was deleted before they
+/// took effect don't go to whatever got its index next.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct EntityHandle {
+    pub index: Index,
+    pub generation: i32,
+}
+
+impl EntityHandle {
+    pub fn new(entity: Entity) -> EntityHandle {
+        // specs keeps the number inside a generation to itself, but hashes it like any other i32
+        let mut generation = GenerationReader(0);
+        entity.get_gen().hash(&mut generation);
+        EntityHandle {
+            index: entity.get_id(),
+            generation: generation.0,
+        }
+    }
+
+    /// The entity at the index once a saved game has been loaded, since every entity is created
+    /// afresh in an empty world
+    pub fn restored(index: Index) -> EntityHandle {
+        EntityHandle {
+            index: index,
+            generation: 1,
+        }
+    }
+}
+
+struct GenerationReader(i32);
+
+impl Hasher for GenerationReader {
+    fn write(&mut self, _bytes: &[u8]) {}
+
+    fn write_i32(&mut self, value: i32) {
+        self.0 = value;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0 as u64
+    }
+}
+
+/// Actions given by the local player, keyed by the entity they're for
 pub struct ActionBatcher {
-    actions: HashMap<Index, Vec<Action>>,
+    actions: HashMap<EntityHandle, Vec<Action>>,
 }
 
 impl ActionBatcher {
@@ -33,14 +81,20 @@ impl ActionBatcher {
         ActionBatcher { actions: HashMap::new() }
     }
 
-    pub fn queue_for_entity(&mut self, entity_id: Index, action: Action) {
-        if !self.actions.contains_key(&entity_id) {
-            self.actions.insert(entity_id, Vec::new());
-        }
-        self.actions.get_mut(&entity_id).unwrap().push(action);
+    pub fn queue_for_entity(&mut self, entity: Entity, action: Action) {
+        self.queue(EntityHandle::new(entity), action);
+    }
+
+    pub fn queue(&mut self, entity: EntityHandle, action: Action) {
+        self.actions.entry(entity).or_insert_with(Vec::new).push(action);
+    }
+
+    /// Actions queued since the last time they were consumed
+    pub fn actions(&self) -> &HashMap<EntityHandle, Vec<Action>> {
+        &self.actions
     }
 
-    pub fn consume_actions(&mut self) -> HashMap<Index, Vec<Action>> {
+    pub fn consume_actions(&mut self) -> HashMap<EntityHandle, Vec<Action>> {
         let mut consumed = HashMap::new();
         mem::swap(&mut consumed, &mut self.actions);
         consumed
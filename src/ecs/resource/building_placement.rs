@@ -0,0 +1,49 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+use crate::identifier::{CivilizationId, PlayerColorId, UnitId};
+use crate::types::Vector3;
+
+/// A building the local player is about to place, which follows the mouse around
+#[derive(Clone, Debug)]
+pub struct PlacingBuilding {
+    pub civilization_id: CivilizationId,
+    pub player_color_id: PlayerColorId,
+    pub unit_id: UnitId,
+
+    /// Where the building would go, snapped to the tile grid
+    pub position: Vector3,
+
+    /// Whether the building fits where it is
+    pub valid: bool,
+}
+
+/// Tracks building placement mode, which is local to this client and doesn't affect
+/// the simulation until the building is actually placed
+pub struct BuildingPlacement {
+    pub placing: Option<PlacingBuilding>,
+}
+
+impl BuildingPlacement {
+    pub fn new() -> BuildingPlacement {
+        BuildingPlacement { placing: None }
+    }
+}
@@ -0,0 +1,622 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::action::Action;
+use crate::ecs::{ActionQueueComponent, AttackActionComponent, BuildActionComponent, ConstructionComponent,
+                 DeathComponent, DeathStage, GatherActionComponent, GatherStage, HealthComponent,
+                 MoveToPositionActionComponent, ProjectileComponent, ResearchComponent, ResourceComponent,
+                 TargetPath, TrainingQueueComponent, TransformComponent, UnitComponent, VelocityComponent};
+use crate::ecs::resource::{KilledUnits, OccupiedTiles, Players, Random, Technologies};
+use specs::{self, Index, Join};
+use crate::types::Vector3;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Checksum of the simulation state after the most recent tick. Every peer in a multiplayer
+/// game (and every playback of a replay) has to arrive at the same checksum on the same tick.
+#[derive(Clone, Debug, Default)]
+pub struct SimulationChecksum {
+    /// Number of ticks run so far
+    pub tick: u64,
+    pub checksum: u64,
+}
+
+impl SimulationChecksum {
+    pub fn new() -> SimulationChecksum {
+        Default::default()
+    }
+
+    /// Hashes the world after a tick and moves on to the next one
+    pub fn update(&mut self, world: &specs::World) {
+        let mut hasher = StateHasher::new();
+        visit_simulation(world, &mut hasher);
+        self.tick += 1;
+        self.checksum = hasher.finish();
+    }
+}
+
+/// Identifies a group of simulation values: either something that belongs to the whole world
+/// (like the occupied tiles), the components of a single unit, or a projectile in flight.
+/// Projectiles come after all of the units.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum StateKey {
+    World,
+    Entity(Index),
+    Projectile(Index),
+}
+
+impl fmt::Display for StateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateKey::World => write!(f, "world"),
+            StateKey::Entity(index) => write!(f, "entity {}", index),
+            StateKey::Projectile(index) => write!(f, "projectile {}", index),
+        }
+    }
+}
+
+/// Receives the simulation state as named integer fields, grouped by `StateKey` in ascending order
+pub trait StateVisitor {
+    fn key(&mut self, key: StateKey);
+    fn field(&mut self, name: &str, value: i64);
+}
+
+/// Walks everything that has to stay identical between peers. Anything that only matters to
+/// the local player (the camera, selection, decals, animation) is left out.
+pub fn visit_simulation(world: &specs::World, visitor: &mut dyn StateVisitor) {
+    let entities = world.entities();
+    let units = world.read::<UnitComponent>();
+    let transforms = world.read::<TransformComponent>();
+    let velocities = world.read::<VelocityComponent>();
+    let action_queues = world.read::<ActionQueueComponent>();
+    let mtps = world.read::<MoveToPositionActionComponent>();
+    let attacks = world.read::<AttackActionComponent>();
+    let gathers = world.read::<GatherActionComponent>();
+    let builds = world.read::<BuildActionComponent>();
+    let constructions = world.read::<ConstructionComponent>();
+    let training_queues = world.read::<TrainingQueueComponent>();
+    let researches = world.read::<ResearchComponent>();
+    let resources = world.read::<ResourceComponent>();
+    let healths = world.read::<HealthComponent>();
+    let deaths = world.read::<DeathComponent>();
+    let projectiles = world.read::<ProjectileComponent>();
+    let occupied_tiles = world.read_resource::<OccupiedTiles>();
+    let random = world.read_resource::<Random>();
+    let killed_units = world.read_resource::<KilledUnits>();
+    let players = world.read_resource::<Players>();
+    let technologies = world.read_resource::<Technologies>();
+
+    visitor.key(StateKey::World);
+    let mut tiles: Vec<_> = occupied_tiles.tiles.iter().cloned().collect();
+    tiles.sort();
+    let mut tiles_hasher = StateHasher::new();
+    for (row, col) in tiles {
+        tiles_hasher.write_i64(row as i64);
+        tiles_hasher.write_i64(col as i64);
+    }
+    visitor.field("occupied_tiles", occupied_tiles.tiles.len() as i64);
+    visitor.field("occupied_tiles_hash", tiles_hasher.finish() as i64);
+    visitor.field("random_state", random.state() as i64);
+    for (player_id, kills, losses) in killed_units.totals() {
+        visitor.field(&format!("player_{}_kills", *player_id), kills as i64);
+        visitor.field(&format!("player_{}_losses", *player_id), losses as i64);
+    }
+    for player in players.all() {
+        for (resource_type, amount) in &player.resources {
+            visitor.field(&format!("player_{}_resource_{}", *player.player_id, resource_type.to_i16()),
+                          amount.scaled);
+        }
+    }
+    for (player_id, player) in technologies.all() {
+        let mut researched_hasher = StateHasher::new();
+        for research_id in player.researched() {
+            researched_hasher.write_i64(**research_id as i64);
+        }
+        let mut disabled_hasher = StateHasher::new();
+        for research_id in player.disabled() {
+            disabled_hasher.write_i64(**research_id as i64);
+        }
+        visitor.field(&format!("player_{}_age", *player_id), technologies.age(player_id) as i64);
+        visitor.field(&format!("player_{}_researched", *player_id), player.researched().len() as i64);
+        visitor.field(&format!("player_{}_researched_hash", *player_id), researched_hasher.finish() as i64);
+        visitor.field(&format!("player_{}_disabled_research", *player_id), player.disabled().len() as i64);
+        visitor.field(&format!("player_{}_disabled_research_hash", *player_id), disabled_hasher.finish() as i64);
+    }
+
+    for (entity, unit) in (&entities, &units).iter() {
+        visitor.key(StateKey::Entity(entity.get_id()));
+        visitor.field("player", *unit.player_id as i64);
+        visitor.field("civilization", *unit.civilization_id as i64);
+        visitor.field("unit", *unit.unit_id as i64);
+        if let Some(transform) = transforms.get(entity) {
+            let position = transform.position();
+            visitor.field("x", position.x.scaled);
+            visitor.field("y", position.y.scaled);
+            visitor.field("z", position.z.scaled);
+            // Rotation is left out: it comes from floating point trigonometry, which doesn't give
+            // exactly the same result everywhere, and only affects which way units are drawn facing
+        }
+        if let Some(velocity) = velocities.get(entity) {
+            visitor.field("velocity_x", velocity.velocity.x.scaled);
+            visitor.field("velocity_y", velocity.velocity.y.scaled);
+            visitor.field("velocity_z", velocity.velocity.z.scaled);
+        }
+        if let Some(action_queue) = action_queues.get(entity) {
+            let mut current_hasher = StateHasher::new();
+            if let Some(ref action) = *action_queue.current_action() {
+                current_hasher.write_action(action);
+            }
+            let mut queued_hasher = StateHasher::new();
+            for action in action_queue.queued_actions() {
+                queued_hasher.write_action(action);
+            }
+            visitor.field("current_action_hash", current_hasher.finish() as i64);
+            visitor.field("current_action_done", action_queue.current_action_done() as i64);
+            visitor.field("queued_actions", action_queue.queued_actions().len() as i64);
+            visitor.field("queued_actions_hash", queued_hasher.finish() as i64);
+        }
+        if let Some(mtp) = mtps.get(entity) {
+            let mut path_hasher = StateHasher::new();
+            for node in &mtp.path {
+                path_hasher.write_vector(node);
+            }
+            visitor.field("path_length", mtp.path.len() as i64);
+            visitor.field("path_hash", path_hasher.finish() as i64);
+        }
+        if let Some(attack) = attacks.get(entity) {
+            visitor.field("attack_target", attack.target.get_id() as i64);
+            visitor.field("reload_time_left", attack.reload_time_left.scaled);
+        }
+        if let Some(gather) = gathers.get(entity) {
+            let stage = match gather.stage {
+                GatherStage::ToResource => 0,
+                GatherStage::Gathering => 1,
+                GatherStage::ToDropSite => 2,
+            };
+            visitor.field("gather_target", gather.target.get_id() as i64);
+            visitor.field("gather_stage", stage);
+            visitor.field("gather_resource_type", gather.resource_type.map_or(-1, |t| t.to_i16() as i64));
+            visitor.field("gather_carried", gather.carried.scaled);
+            visitor.field("gather_path_hash", target_path_hash(&gather.target_path));
+        }
+        if let Some(build) = builds.get(entity) {
+            visitor.field("build_unit", *build.unit_id as i64);
+            visitor.field("build_x", build.position.x.scaled);
+            visitor.field("build_y", build.position.y.scaled);
+            visitor.field("build_target", build.target.map_or(-1, |target| target.get_id() as i64));
+            visitor.field("build_path_hash", target_path_hash(&build.target_path));
+        }
+        if let Some(construction) = constructions.get(entity) {
+            visitor.field("construction_progress", construction.progress.scaled);
+        }
+        if let Some(training_queue) = training_queues.get(entity) {
+            let mut orders_hasher = StateHasher::new();
+            for order in &training_queue.orders {
+                orders_hasher.write_action(order);
+            }
+            let mut units_hasher = StateHasher::new();
+            for unit_id in &training_queue.units {
+                units_hasher.write_i64(**unit_id as i64);
+            }
+            visitor.field("training_orders", training_queue.orders.len() as i64);
+            visitor.field("training_orders_hash", orders_hasher.finish() as i64);
+            visitor.field("training_units", training_queue.units.len() as i64);
+            visitor.field("training_units_hash", units_hasher.finish() as i64);
+            visitor.field("training_elapsed_time", training_queue.elapsed_time.scaled);
+            if let Some(rally_point) = training_queue.rally_point {
+                visitor.field("rally_x", rally_point.x.scaled);
+                visitor.field("rally_y", rally_point.y.scaled);
+            }
+        }
+        if let Some(research) = researches.get(entity) {
+            let mut orders_hasher = StateHasher::new();
+            for order in &research.orders {
+                orders_hasher.write_action(order);
+            }
+            visitor.field("research_orders", research.orders.len() as i64);
+            visitor.field("research_orders_hash", orders_hasher.finish() as i64);
+            visitor.field("research", research.research_id.map_or(-1, |research_id| *research_id as i64));
+            visitor.field("research_elapsed_time", research.elapsed_time.scaled);
+        }
+        if let Some(resource) = resources.get(entity) {
+            visitor.field("resource_type", resource.resource_type.to_i16() as i64);
+            visitor.field("resource_amount", resource.amount.scaled);
+        }
+        if let Some(health) = healths.get(entity) {
+            visitor.field("hit_points", health.hit_points as i64);
+        }
+        if let Some(death) = deaths.get(entity) {
+            let stage = match death.stage {
+                DeathStage::Dying => 0,
+                DeathStage::Decaying => 1,
+                DeathStage::Dead => 2,
+            };
+            visitor.field("death_stage", stage);
+            visitor.field("death_time_left", death.time_left.scaled);
+        }
+    }
+
+    // Projectiles aren't units, but where they land and whether they hit decides who takes damage
+    for (entity, projectile) in (&entities, &projectiles).iter() {
+        visitor.key(StateKey::Projectile(entity.get_id()));
+        visitor.field("player", *projectile.player_id as i64);
+        visitor.field("civilization", *projectile.civilization_id as i64);
+        visitor.field("attacker_unit", *projectile.attacker_unit_id as i64);
+        visitor.field("target", projectile.target.get_id() as i64);
+        visitor.field("hits", projectile.hits as i64);
+        visitor.field("end_x", projectile.end.x.scaled);
+        visitor.field("end_y", projectile.end.y.scaled);
+        visitor.field("end_z", projectile.end.z.scaled);
+        visitor.field("flight_time", projectile.flight_time.scaled);
+        visitor.field("elapsed_time", projectile.elapsed_time.scaled);
+        if let Some(transform) = transforms.get(entity) {
+            let position = transform.position();
+            visitor.field("x", position.x.scaled);
+            visitor.field("y", position.y.scaled);
+            visitor.field("z", position.z.scaled);
+        }
+    }
+}
+
+/// 64-bit FNV-1a, which (unlike the standard library's hasher) is guaranteed to give
+/// the same result on every platform and compiler version
+pub struct StateHasher(u64);
+
+impl StateHasher {
+    pub fn new() -> StateHasher {
+        StateHasher(FNV_OFFSET_BASIS)
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    pub fn write_i64(&mut self, value: i64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_vector(&mut self, vector: &Vector3) {
+        self.write_i64(vector.x.scaled);
+        self.write_i64(vector.y.scaled);
+        self.write_i64(vector.z.scaled);
+    }
+
+    pub fn write_action(&mut self, action: &Action) {
+        match *action {
+            Action::ClearQueue => self.write_i64(0),
+            Action::MoveToPosition(ref params) => {
+                self.write_i64(1);
+                for node in &params.path {
+                    self.write_vector(node);
+                }
+            }
+            Action::Attack(ref params) => {
+                self.write_i64(2);
+                self.write_i64(params.target as i64);
+            }
+            Action::Gather(ref params) => {
+                self.write_i64(3);
+                self.write_i64(params.target as i64);
+            }
+            Action::Build(ref params) => {
+                self.write_i64(4);
+                self.write_i64(*params.unit_id as i64);
+                self.write_vector(&params.position);
+            }
+            Action::Train(ref params) => {
+                self.write_i64(5);
+                self.write_i64(*params.unit_id as i64);
+            }
+            Action::CancelTraining(ref params) => {
+                self.write_i64(6);
+                self.write_i64(params.slot as i64);
+            }
+            Action::SetRallyPoint(ref params) => {
+                self.write_i64(7);
+                self.write_vector(&params.position);
+            }
+            Action::Research(ref params) => {
+                self.write_i64(8);
+                self.write_i64(*params.research_id as i64);
+            }
+            Action::CancelResearch => self.write_i64(9),
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl StateVisitor for StateHasher {
+    fn key(&mut self, key: StateKey) {
+        match key {
+            StateKey::World => self.write_i64(-1),
+            StateKey::Entity(index) => self.write_i64(index as i64),
+            StateKey::Projectile(index) => {
+                self.write_i64(-2);
+                self.write_i64(index as i64);
+            }
+        }
+    }
+
+    fn field(&mut self, name: &str, value: i64) {
+        self.write_bytes(name.as_bytes());
+        self.write_i64(value);
+    }
+}
+
+/// A full copy of the simulation state on one tick, for finding out what went wrong when
+/// two runs stop agreeing on their checksums
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationState {
+    pub tick: u64,
+    pub values: BTreeMap<StateKey, Vec<(String, i64)>>,
+}
+
+impl SimulationState {
+    pub fn capture(world: &specs::World, tick: u64) -> SimulationState {
+        let mut state = SimulationState {
+            tick: tick,
+            values: BTreeMap::new(),
+        };
+        visit_simulation(world, &mut state.visitor());
+        state
+    }
+
+    fn visitor(&mut self) -> StateCollector<'_> {
+        StateCollector {
+            state: self,
+            key: StateKey::World,
+        }
+    }
+
+    /// Same as the checksum that `SimulationChecksum` comes up with for this state
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+        for (key, fields) in &self.values {
+            hasher.key(*key);
+            for &(ref name, value) in fields {
+                hasher.field(name, value);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// Every group of values that isn't the same in both states
+    pub fn differences(&self, other: &SimulationState) -> Vec<StateDifference> {
+        let mut keys: Vec<StateKey> = self.values.keys().chain(other.values.keys()).cloned().collect();
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| {
+                let (ours, theirs) = (self.values.get(&key), other.values.get(&key));
+                if ours == theirs {
+                    None
+                } else {
+                    Some(StateDifference {
+                        key: key,
+                        ours: ours.cloned(),
+                        theirs: theirs.cloned(),
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// Reads the next state written by `write_to`, or `None` at the end of the stream
+    pub fn read_from<R: BufRead>(stream: &mut R) -> io::Result<Option<SimulationState>> {
+        let mut line = String::new();
+        if stream.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let header: Vec<&str> = line.split_whitespace().collect();
+        let (tick, checksum) = match header[..] {
+            ["tick", tick, "checksum", checksum] => {
+                (tick.parse::<u64>().map_err(|_| invalid_data("bad tick number"))?,
+                 u64::from_str_radix(checksum, 16).map_err(|_| invalid_data("bad checksum"))?)
+            }
+            _ => return Err(invalid_data("expected a tick header")),
+        };
+
+        let mut state = SimulationState {
+            tick: tick,
+            values: BTreeMap::new(),
+        };
+        loop {
+            line.clear();
+            if stream.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            let mut words = line.split_whitespace();
+            let key = match words.next() {
+                Some("world") => StateKey::World,
+                Some("entity") => {
+                    let index = words.next().and_then(|index| index.parse().ok());
+                    StateKey::Entity(index.ok_or_else(|| invalid_data("bad entity index"))?)
+                }
+                Some("projectile") => {
+                    let index = words.next().and_then(|index| index.parse().ok());
+                    StateKey::Projectile(index.ok_or_else(|| invalid_data("bad projectile index"))?)
+                }
+                _ => return Err(invalid_data("expected world, entity or projectile values")),
+            };
+            let mut fields = Vec::new();
+            for word in words {
+                let mut parts = word.splitn(2, '=');
+                let name = parts.next().unwrap();
+                let value = parts.next().and_then(|value| value.parse().ok());
+                fields.push((name.to_string(), value.ok_or_else(|| invalid_data("bad field value"))?));
+            }
+            state.values.insert(key, fields);
+        }
+
+        if state.checksum() != checksum {
+            return Err(invalid_data("checksum doesn't match the values"));
+        }
+        Ok(Some(state))
+    }
+
+    /// Writes the state as text, one line per group of values, followed by a blank line
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> io::Result<()> {
+        writeln!(stream, "tick {} checksum {:016x}", self.tick, self.checksum())?;
+        for (key, fields) in &self.values {
+            write!(stream, "{}", key)?;
+            for &(ref name, value) in fields {
+                write!(stream, " {}={}", name, value)?;
+            }
+            writeln!(stream)?;
+        }
+        writeln!(stream)
+    }
+}
+
+struct StateCollector<'a> {
+    state: &'a mut SimulationState,
+    key: StateKey,
+}
+
+impl<'a> StateVisitor for StateCollector<'a> {
+    fn key(&mut self, key: StateKey) {
+        self.key = key;
+        self.state.values.insert(key, Vec::new());
+    }
+
+    fn field(&mut self, name: &str, value: i64) {
+        self.state.values.get_mut(&self.key).unwrap().push((name.to_string(), value));
+    }
+}
+
+/// A group of values that differs between two states. `None` means the group doesn't exist
+/// at all in that state, such as a unit that only one side created.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StateDifference {
+    pub key: StateKey,
+    pub ours: Option<Vec<(String, i64)>>,
+    pub theirs: Option<Vec<(String, i64)>>,
+}
+
+impl fmt::Display for StateDifference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.ours, &self.theirs) {
+            (&Some(ref ours), &Some(ref theirs)) => {
+                write!(f, "{}:", self.key)?;
+                for &(ref name, value) in ours {
+                    let their_value = theirs.iter().find(|field| field.0 == *name).map(|field| field.1);
+                    if their_value != Some(value) {
+                        match their_value {
+                            Some(their_value) => write!(f, " {} {} != {}", name, value, their_value)?,
+                            None => write!(f, " {} {} != (missing)", name, value)?,
+                        }
+                    }
+                }
+                for &(ref name, value) in theirs {
+                    if !ours.iter().any(|field| field.0 == *name) {
+                        write!(f, " {} (missing) != {}", name, value)?;
+                    }
+                }
+                Ok(())
+            }
+            (&Some(_), &None) => write!(f, "{}: only exists on our side", self.key),
+            (&None, &Some(_)) => write!(f, "{}: only exists on their side", self.key),
+            (&None, &None) => write!(f, "{}: no difference", self.key),
+        }
+    }
+}
+
+/// Where a gatherer or builder is walking to and the way it's taking there, or -1 if it isn't walking
+fn target_path_hash(target_path: &Option<TargetPath>) -> i64 {
+    match *target_path {
+        Some(ref target_path) => {
+            let mut hasher = StateHasher::new();
+            hasher.write_vector(&target_path.destination);
+            for node in &target_path.path {
+                hasher.write_vector(node);
+            }
+            hasher.finish() as i64
+        }
+        None => -1,
+    }
+}
+
+fn invalid_data(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SimulationState, StateKey};
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    fn state(tick: u64, x: i64) -> SimulationState {
+        let mut values = BTreeMap::new();
+        values.insert(StateKey::World, vec![("occupied_tiles".to_string(), 3)]);
+        values.insert(StateKey::Entity(4), vec![("x".to_string(), x), ("y".to_string(), -20)]);
+        values.insert(StateKey::Entity(7), vec![("x".to_string(), 5)]);
+        values.insert(StateKey::Projectile(5), vec![("hits".to_string(), 1)]);
+        SimulationState {
+            tick: tick,
+            values: values,
+        }
+    }
+
+    #[test]
+    fn test_checksum_follows_values() {
+        assert_eq!(state(1, 10).checksum(), state(2, 10).checksum());
+        assert!(state(1, 10).checksum() != state(1, 11).checksum());
+    }
+
+    #[test]
+    fn test_differences() {
+        let ours = state(1, 10);
+        let mut theirs = state(1, 11);
+        theirs.values.remove(&StateKey::Entity(7));
+
+        let differences = ours.differences(&theirs);
+        assert_eq!(vec![StateKey::Entity(4), StateKey::Entity(7)],
+                   differences.iter().map(|d| d.key).collect::<Vec<_>>());
+        assert_eq!("entity 4: x 10 != 11", differences[0].to_string());
+        assert_eq!("entity 7: only exists on our side", differences[1].to_string());
+        assert!(ours.differences(&state(2, 10)).is_empty());
+    }
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let mut log = Vec::new();
+        state(1, 10).write_to(&mut log).unwrap();
+        state(2, 11).write_to(&mut log).unwrap();
+
+        let mut stream = Cursor::new(log);
+        assert_eq!(Some(state(1, 10)), SimulationState::read_from(&mut stream).unwrap());
+        assert_eq!(Some(state(2, 11)), SimulationState::read_from(&mut stream).unwrap());
+        assert_eq!(None, SimulationState::read_from(&mut stream).unwrap());
+    }
+}
@@ -0,0 +1,96 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::identifier::PlayerId;
+
+use std::collections::BTreeMap;
+
+/// How many units each player has killed and lost over the whole game, for scoring and
+/// victory checks
+pub struct KilledUnits {
+    kills: BTreeMap<PlayerId, u32>,
+    losses: BTreeMap<PlayerId, u32>,
+}
+
+impl KilledUnits {
+    pub fn new() -> KilledUnits {
+        KilledUnits {
+            kills: BTreeMap::new(),
+            losses: BTreeMap::new(),
+        }
+    }
+
+    /// Counts a unit of the given player that ran out of hit points, along with the player
+    /// whose attack finished it off
+    pub fn record(&mut self, player_id: PlayerId, killer_id: Option<PlayerId>) {
+        *self.losses.entry(player_id).or_insert(0) += 1;
+        if let Some(killer_id) = killer_id {
+            if killer_id != player_id {
+                *self.kills.entry(killer_id).or_insert(0) += 1;
+            }
+        }
+    }
+
+    /// Number of other players' units the player has killed
+    pub fn kills(&self, player_id: PlayerId) -> u32 {
+        self.kills.get(&player_id).cloned().unwrap_or(0)
+    }
+
+    /// Number of units the player has lost
+    pub fn losses(&self, player_id: PlayerId) -> u32 {
+        self.losses.get(&player_id).cloned().unwrap_or(0)
+    }
+
+    /// Kill and loss counts of every player that has any, in player order
+    pub fn totals(&self) -> Vec<(PlayerId, u32, u32)> {
+        let mut player_ids: Vec<PlayerId> = self.kills.keys().chain(self.losses.keys()).cloned().collect();
+        player_ids.sort();
+        player_ids.dedup();
+        player_ids.into_iter().map(|player_id| (player_id, self.kills(player_id), self.losses(player_id))).collect()
+    }
+
+    /// Sets a player's counts, for loading saved games
+    pub fn set_totals(&mut self, player_id: PlayerId, kills: u32, losses: u32) {
+        self.kills.insert(player_id, kills);
+        self.losses.insert(player_id, losses);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KilledUnits;
+
+    #[test]
+    fn test_counts() {
+        let mut killed_units = KilledUnits::new();
+        killed_units.record(1usize.into(), Some(2usize.into()));
+        killed_units.record(1usize.into(), Some(1usize.into()));
+        killed_units.record(2usize.into(), None);
+
+        // Killing your own units doesn't count as a kill
+        assert_eq!(0, killed_units.kills(1usize.into()));
+        assert_eq!(1, killed_units.kills(2usize.into()));
+        assert_eq!(2, killed_units.losses(1usize.into()));
+        assert_eq!(1, killed_units.losses(2usize.into()));
+        assert_eq!(vec![(1usize.into(), 0, 2), (2usize.into(), 1, 1)], killed_units.totals());
+    }
+}
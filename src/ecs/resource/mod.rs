@@ -20,21 +20,37 @@
 // SOFTWARE.
 
 mod action_batcher;
+mod building_placement;
+mod checksum;
 mod input;
+mod killed_units;
 pub mod path_finder;
 mod occupied_tiles;
 mod players;
+mod random;
 mod render;
+mod sound;
+mod sound_events;
+mod technologies;
 pub mod terrain;
 mod view_projector;
 mod viewport;
 
-pub use self::action_batcher::ActionBatcher;
+pub use self::action_batcher::{ActionBatcher, EntityHandle};
+pub use self::building_placement::{BuildingPlacement, PlacingBuilding};
+pub use self::checksum::{SimulationChecksum, SimulationState, StateHasher};
 pub use self::input::{KeyboardKeyStates, MouseState};
+pub use self::killed_units::KilledUnits;
 pub use self::occupied_tiles::OccupiedTiles;
 pub use self::path_finder::PathFinder;
 pub use self::players::{Player, Players};
+pub use self::random::Random;
 pub use self::render::RenderCommands;
+pub use self::sound::play_sound_group;
+pub use self::sound_events::{SoundEvent, SoundEvents};
+pub use self::technologies::Technologies;
+#[cfg(test)]
+pub use self::technologies::PlayerTechnologies;
 pub use self::terrain::{Terrain, Tile};
 pub use self::view_projector::ViewProjector;
 pub use self::viewport::Viewport;
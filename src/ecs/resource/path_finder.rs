@@ -28,7 +28,7 @@ use std::collections::BinaryHeap;
 use std::collections::HashSet;
 use crate::types::{Fixed, ToFixed, Vector3, fixed_const};
 
-const PASSABILITY_THRESHOLD: f32 = 0.999;
+pub const PASSABILITY_THRESHOLD: f32 = 0.999;
 
 pub type PathNode = Vector3;
 pub type Path = Vec<PathNode>;
@@ -19,15 +19,23 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::dat::{EmpiresDb, ResearchEffectValue, ResourceCost, ResourceType};
 use crate::identifier::{CivilizationId, PlayerColorId, PlayerId};
 use crate::scn::Scenario;
-use std::collections::HashMap;
+use crate::types::Fixed;
+use std::collections::{BTreeMap, HashMap};
 
+#[derive(Clone, Debug)]
 pub struct Player {
     pub name: String,
     pub player_id: PlayerId,
     pub player_color_id: PlayerColorId,
     pub civ_id: CivilizationId,
+
+    /// How much of each resource the player has. The civ attributes that aren't resources as
+    /// such are kept here too, indexed the same way as the civ's starting values, like the
+    /// population headroom (4), the population (11) and the corpse decay time (12).
+    pub resources: BTreeMap<ResourceType, Fixed>,
 }
 
 impl Player {
@@ -41,7 +49,63 @@ impl Player {
             player_id: player_id,
             player_color_id: player_color_id,
             civ_id: civ_id,
+            resources: BTreeMap::new(),
+        }
+    }
+
+    pub fn resource(&self, resource_type: ResourceType) -> Fixed {
+        self.resources.get(&resource_type).cloned().unwrap_or_else(|| 0.into())
+    }
+
+    pub fn credit(&mut self, resource_type: ResourceType, amount: Fixed) {
+        *self.resources.entry(resource_type).or_insert_with(|| 0.into()) += amount;
+    }
+
+    /// Changes one of the player's resources (or civ attributes), like research does
+    pub fn apply_effect(&mut self, resource_type: ResourceType, effect: ResearchEffectValue) {
+        let amount = match effect {
+            ResearchEffectValue::SetTo(value) => value.into(),
+            ResearchEffectValue::Add(value) => self.resource(resource_type) + value.into(),
+            ResearchEffectValue::MultiplyBy(factor) => self.resource(resource_type) * factor.into(),
+        };
+        self.resources.insert(resource_type, amount);
+    }
+
+    /// Whether the player has enough of everything in the cost
+    pub fn can_afford<T: Copy + Into<Fixed>, E: Copy>(&self, costs: &[ResourceCost<T, E>]) -> bool {
+        self.shortfall(costs).is_none()
+    }
+
+    /// Takes a cost (like a `UnitResourceCost` or a `ResearchCost`) out of the stockpile. Either
+    /// all of it is taken, or none of it is and the resource the player is short of is returned.
+    pub fn debit<T: Copy + Into<Fixed>, E: Copy>(&mut self, costs: &[ResourceCost<T, E>]) -> Result<(), ResourceType> {
+        if let Some(resource_type) = self.shortfall(costs) {
+            return Err(resource_type);
+        }
+        for cost in costs.iter().filter(|cost| cost.enabled) {
+            let amount: Fixed = cost.amount.into();
+            self.credit(cost.resource_type, -amount);
+        }
+        Ok(())
+    }
+
+    /// Gives back a cost that was debited, like when training is cancelled
+    pub fn refund<T: Copy + Into<Fixed>, E: Copy>(&mut self, costs: &[ResourceCost<T, E>]) {
+        for cost in costs.iter().filter(|cost| cost.enabled) {
+            self.credit(cost.resource_type, cost.amount.into());
+        }
+    }
+
+    /// The first resource (in resource type order) that the player doesn't have enough of.
+    /// Costs of the same type are added up first.
+    fn shortfall<T: Copy + Into<Fixed>, E: Copy>(&self, costs: &[ResourceCost<T, E>]) -> Option<ResourceType> {
+        let mut totals: BTreeMap<ResourceType, Fixed> = BTreeMap::new();
+        for cost in costs.iter().filter(|cost| cost.enabled) {
+            *totals.entry(cost.resource_type).or_insert_with(|| 0.into()) += cost.amount.into();
         }
+        totals.into_iter()
+            .find(|&(resource_type, amount)| self.resource(resource_type) < amount)
+            .map(|(resource_type, _)| resource_type)
     }
 }
 
@@ -58,14 +122,26 @@ impl Players {
         }
     }
 
-    pub fn from_scenario(scenario: &Scenario, local_player_id: PlayerId) -> Players {
+    /// Starts every player off with their civ's starting values, and the stockpile the scenario gives them
+    pub fn from_scenario(scenario: &Scenario, empires: &EmpiresDb, local_player_id: PlayerId) -> Players {
         let mut players = Players::new();
         for player_id in scenario.player_ids() {
             let name = scenario.player_data.player_names[*player_id as usize].clone();
             let civ_id = scenario.player_data.player_civs[*player_id as usize].civilization_id;
             let color_id = player_id.into();
             let local = player_id == local_player_id;
-            players.add_player(Player::new(name, player_id, color_id, civ_id), local);
+
+            let mut player = Player::new(name, player_id, color_id, civ_id);
+            for (index, value) in empires.civilization(civ_id).starting_values().values().iter().enumerate() {
+                player.resources.insert(ResourceType::from_i16(index as i16), (*value).into());
+            }
+            if let Some(resources) = scenario.player_resources(player_id) {
+                player.resources.insert(ResourceType::Food, resources.food.into());
+                player.resources.insert(ResourceType::Wood, resources.wood.into());
+                player.resources.insert(ResourceType::Stone, resources.stone.into());
+                player.resources.insert(ResourceType::Gold, resources.gold.into());
+            }
+            players.add_player(player, local);
         }
         players
     }
@@ -81,4 +157,67 @@ impl Players {
         let local_player_id = self.local_player_id;
         &self.players[&local_player_id]
     }
+
+    pub fn player_mut(&mut self, player_id: PlayerId) -> Option<&mut Player> {
+        self.players.get_mut(&player_id)
+    }
+
+    pub fn local_player_id(&self) -> PlayerId {
+        self.local_player_id
+    }
+
+    /// Every player, in player ID order
+    pub fn all(&self) -> Vec<&Player> {
+        let mut players: Vec<&Player> = self.players.values().collect();
+        players.sort_by_key(|player| player.player_id);
+        players
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Player;
+    use crate::dat::{ResearchCost, ResearchEffectValue, ResourceType, UnitResourceCost};
+    use crate::types::Fixed;
+
+    fn player() -> Player {
+        let mut player = Player::new("One".into(), 1usize.into(), 1usize.into(), 1usize.into());
+        player.credit(ResourceType::Food, 100.into());
+        player.credit(ResourceType::Wood, 30.into());
+        player
+    }
+
+    #[test]
+    fn test_debit_is_all_or_nothing() {
+        let mut player = player();
+        let costs = vec![UnitResourceCost::new(ResourceType::Food, 50), UnitResourceCost::new(ResourceType::Wood, 40)];
+        assert!(!player.can_afford(&costs));
+        assert_eq!(Err(ResourceType::Wood), player.debit(&costs));
+        assert_eq!(Fixed::from(100), player.resource(ResourceType::Food));
+        assert_eq!(Fixed::from(30), player.resource(ResourceType::Wood));
+
+        // Costs of the same type count together
+        let costs = vec![ResearchCost::new(ResourceType::Food, 60), ResearchCost::new(ResourceType::Food, 60)];
+        assert!(player.debit(&costs).is_err());
+
+        let costs = vec![ResearchCost::new(ResourceType::Food, 60), ResearchCost::new(ResourceType::Wood, 30)];
+        assert_eq!(Ok(()), player.debit(&costs));
+        assert_eq!(Fixed::from(40), player.resource(ResourceType::Food));
+        assert_eq!(Fixed::from(0), player.resource(ResourceType::Wood));
+
+        player.refund(&costs);
+        assert_eq!(Fixed::from(100), player.resource(ResourceType::Food));
+        assert_eq!(Fixed::from(30), player.resource(ResourceType::Wood));
+    }
+
+    #[test]
+    fn test_apply_effect() {
+        let mut player = player();
+        player.apply_effect(ResourceType::Food, ResearchEffectValue::MultiplyBy(1.5));
+        assert_eq!(Fixed::from(150), player.resource(ResourceType::Food));
+        player.apply_effect(ResourceType::Food, ResearchEffectValue::Add(-25.0));
+        assert_eq!(Fixed::from(125), player.resource(ResourceType::Food));
+        player.apply_effect(ResourceType::Unknown(4), ResearchEffectValue::SetTo(4.0));
+        assert_eq!(Fixed::from(4), player.resource(ResourceType::Unknown(4)));
+    }
 }
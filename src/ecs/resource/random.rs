@@ -0,0 +1,86 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+const SEED_MIX: u64 = 0x9e3779b97f4a7c15;
+
+/// Pseudo-random numbers for the simulation. Every peer starts from the same seed and
+/// draws numbers in the same order, so they all get the same results.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        // Mixed so that small seeds don't start out with mostly zero bits,
+        // and xorshift gets stuck on zero
+        let state = seed ^ SEED_MIX;
+        Random { state: if state == 0 { SEED_MIX } else { state } }
+    }
+
+    /// Carries on from a `state` that an earlier generator got to
+    pub fn from_state(state: u64) -> Random {
+        Random { state: state }
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    /// xorshift64*
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        (self.state.wrapping_mul(0x2545f4914f6cdd1d) >> 32) as u32
+    }
+
+    /// Returns true `percent` percent of the time
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.next_u32() % 100 < percent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Random;
+
+    #[test]
+    fn test_same_seed_same_numbers() {
+        let (mut first, mut second) = (Random::new(7), Random::new(7));
+        let numbers: Vec<u32> = (0..10).map(|_| first.next_u32()).collect();
+        assert_eq!(numbers, (0..10).map(|_| second.next_u32()).collect::<Vec<u32>>());
+        assert!(numbers.iter().any(|n| *n != numbers[0]));
+
+        let mut zero = Random::new(0x9e3779b97f4a7c15);
+        assert!((0..10).any(|_| zero.next_u32() != 0));
+    }
+
+    #[test]
+    fn test_chance() {
+        let mut random = Random::new(1);
+        assert!((0..100).all(|_| !random.chance(0)));
+        assert!((0..100).all(|_| random.chance(100)));
+        let hits = (0..1000).filter(|_| random.chance(30)).count();
+        assert!(hits > 200 && hits < 400);
+    }
+}
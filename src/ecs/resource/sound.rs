@@ -0,0 +1,56 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::dat::SoundEffectGroup;
+use crate::ecs::resource::Viewport;
+use crate::media::Audio;
+use crate::resource::SoundManager;
+
+use nalgebra::Vector2;
+
+/// Picks an effect out of a sound group (see `SoundEffectGroup::choose_effect` for `roll`)
+/// and plays it, panned according to where it is on screen.
+/// Returns false if nothing was played.
+pub fn play_sound_group(audio: &mut dyn Audio,
+                        sound_manager: &mut SoundManager,
+                        viewport: &Viewport,
+                        group: &SoundEffectGroup,
+                        roll: u32,
+                        position: &Vector2<i32>)
+                        -> bool {
+    let (volume, pan) = viewport.sound_mix(position);
+    if volume <= 0.0 {
+        return false;
+    }
+
+    let wav_id = match group.choose_effect(roll).and_then(|effect| effect.resource_id) {
+        Some(wav_id) => wav_id,
+        None => return false,
+    };
+    match sound_manager.get(wav_id) {
+        Some(sound) => {
+            audio.play(&sound, volume, pan);
+            true
+        }
+        None => false,
+    }
+}
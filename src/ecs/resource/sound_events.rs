@@ -0,0 +1,94 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::dat::EmpiresDb;
+use crate::ecs::resource::{ViewProjector, Viewport, play_sound_group};
+use crate::identifier::SoundGroupId;
+use crate::media::Audio;
+use crate::resource::SoundManager;
+use crate::types::Vector3;
+
+/// A sound group that should be played at a position in the world
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoundEvent {
+    pub sound_group_id: SoundGroupId,
+    pub position: Vector3,
+}
+
+impl SoundEvent {
+    pub fn new(sound_group_id: SoundGroupId, position: Vector3) -> SoundEvent {
+        SoundEvent {
+            sound_group_id: sound_group_id,
+            position: position,
+        }
+    }
+}
+
+/// Sound events queued up by systems during an update; played back afterwards
+/// with `play_all`, since the audio device isn't available to the systems.
+pub struct SoundEvents {
+    events: Vec<SoundEvent>,
+    roll_state: u32,
+}
+
+impl SoundEvents {
+    pub fn new() -> SoundEvents {
+        SoundEvents {
+            events: Vec::new(),
+            roll_state: 0x2545f491,
+        }
+    }
+
+    pub fn push(&mut self, event: SoundEvent) {
+        self.events.push(event);
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Plays every queued event and empties the queue
+    pub fn play_all(&mut self,
+                    audio: &mut dyn Audio,
+                    sound_manager: &mut SoundManager,
+                    empires: &EmpiresDb,
+                    viewport: &Viewport,
+                    projector: &ViewProjector) {
+        let events: Vec<SoundEvent> = self.events.drain(..).collect();
+        for event in &events {
+            let roll = self.next_roll();
+            let group = empires.sound_effect_group(event.sound_group_id);
+            let position = projector.project(&event.position);
+            play_sound_group(audio, sound_manager, viewport, group, roll, &position);
+        }
+    }
+
+    fn next_roll(&mut self) -> u32 {
+        // xorshift32; which effect out of a group gets played doesn't need to be any better
+        let mut x = self.roll_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.roll_state = x;
+        x
+    }
+}
@@ -0,0 +1,463 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+use crate::dat::{self, Age, EmpiresDb, EmpiresDbRef, ResearchEffect};
+use crate::ecs::resource::{Player, Players};
+use crate::identifier::{CivilizationId, PlayerId, ResearchId, UnitClassId, UnitId};
+use crate::scn::Scenario;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// What one player has researched, and what that research has done to their units
+#[derive(Clone, Debug)]
+pub struct PlayerTechnologies {
+    civilization_id: CivilizationId,
+
+    /// Whether research may never be disabled or units turned off for the player, like with the
+    /// scenario's "all techs" option. The research the scenario disables explicitly stays disabled.
+    all_techs: bool,
+
+    /// Research whose effects have been applied, in the order they were applied
+    researched: Vec<ResearchId>,
+    disabled: BTreeSet<ResearchId>,
+
+    /// The units research has changed; every other unit is as it is in the empires db
+    units: BTreeMap<UnitId, dat::Unit>,
+    research_costs: BTreeMap<ResearchId, Vec<dat::ResearchCost>>,
+
+    /// What research has upgraded units to, by the unit they were upgraded from
+    upgrades: BTreeMap<UnitId, UnitId>,
+}
+
+impl PlayerTechnologies {
+    pub fn new(civilization_id: CivilizationId, all_techs: bool) -> PlayerTechnologies {
+        PlayerTechnologies {
+            civilization_id: civilization_id,
+            all_techs: all_techs,
+            researched: Vec::new(),
+            disabled: BTreeSet::new(),
+            units: BTreeMap::new(),
+            research_costs: BTreeMap::new(),
+            upgrades: BTreeMap::new(),
+        }
+    }
+
+    /// Research whose effects have been applied, in the order they were applied
+    pub fn researched(&self) -> &[ResearchId] {
+        &self.researched
+    }
+
+    pub fn is_researched(&self, research_id: ResearchId) -> bool {
+        self.researched.contains(&research_id)
+    }
+
+    /// Research the player can't do, in research ID order
+    pub fn disabled(&self) -> &BTreeSet<ResearchId> {
+        &self.disabled
+    }
+
+    pub fn disable(&mut self, research_id: ResearchId) {
+        self.disabled.insert(research_id);
+    }
+
+    /// Marks the research as done, and applies its effects. The player is left out when the effects
+    /// on their stockpile and units have already been applied, like when restoring a saved game.
+    /// Returns the research the effects gave the player, which hasn't been applied yet.
+    pub fn apply_effects(&mut self,
+                         empires: &EmpiresDb,
+                         research_id: ResearchId,
+                         effects: &[ResearchEffect],
+                         player: Option<&mut Player>,
+                         upgrades: &mut Vec<(UnitId, UnitId)>)
+                         -> Vec<ResearchId> {
+        if self.is_researched(research_id) {
+            return Vec::new();
+        }
+        self.researched.push(research_id);
+        self.apply_group(empires, effects, player, upgrades)
+    }
+
+    /// Applies the civ's own bonuses, which every player of the civ starts the game with. Returns
+    /// the research the bonuses give the player, like `apply_effects`.
+    pub fn apply_civ_bonus(&mut self, empires: &EmpiresDb, player: Option<&mut Player>) -> Vec<ResearchId> {
+        let effects = match empires.civilization(self.civilization_id).starting_values().age_id() {
+            Some(age_id) => empires.age(age_id).decoded_effects(),
+            None => return Vec::new(),
+        };
+        self.apply_group(empires, &effects, player, &mut Vec::new())
+    }
+
+    fn apply_group(&mut self,
+                   empires: &EmpiresDb,
+                   effects: &[ResearchEffect],
+                   mut player: Option<&mut Player>,
+                   upgrades: &mut Vec<(UnitId, UnitId)>)
+                   -> Vec<ResearchId> {
+        let mut gained = Vec::new();
+        for effect in effects {
+            match *effect {
+                ResearchEffect::UnitAttribute { target_unit_id, target_unit_class_id, attribute_id, effect } => {
+                    for unit_id in self.targets(empires, target_unit_id, target_unit_class_id) {
+                        self.unit_mut(empires, unit_id).apply_attribute(attribute_id, effect);
+                    }
+                }
+                ResearchEffect::CivHeader { target_civ_header_id, effect } => {
+                    if let Some(player) = player.as_mut() {
+                        player.apply_effect(dat::ResourceType::from_i16(target_civ_header_id), effect);
+                    }
+                }
+                ResearchEffect::SetUnitEnabled { target_unit_id: Some(unit_id), enabled } => {
+                    if enabled || !self.all_techs {
+                        for unit_id in self.targets(empires, Some(unit_id), None) {
+                            self.unit_mut(empires, unit_id).enabled = enabled;
+                        }
+                    }
+                }
+                ResearchEffect::UpgradeUnit { source_unit_id, target_unit_id } => {
+                    self.upgrades.insert(source_unit_id, target_unit_id);
+                    if player.is_some() {
+                        upgrades.push((source_unit_id, target_unit_id));
+                    }
+                }
+                ResearchEffect::ResearchCost { research_id, resource_type, effect } => {
+                    let costs = self.research_costs
+                        .entry(research_id)
+                        .or_insert_with(|| empires.research(research_id).resource_costs.clone());
+                    for cost in costs.iter_mut().filter(|cost| cost.enabled && cost.resource_type == resource_type) {
+                        cost.amount = effect.apply(cost.amount as f32).round() as i16;
+                    }
+                }
+                ResearchEffect::DisableResearch { research_id } => {
+                    if !self.all_techs {
+                        self.disabled.insert(research_id);
+                    }
+                }
+                ResearchEffect::GainResearch { research_id } => gained.push(research_id),
+                ResearchEffect::SetUnitEnabled { target_unit_id: None, .. } |
+                ResearchEffect::Unknown { .. } => {}
+            }
+        }
+        gained
+    }
+
+    /// The civ's units an effect applies to: the one unit, or every unit of the class
+    fn targets(&self,
+               empires: &EmpiresDb,
+               unit_id: Option<UnitId>,
+               unit_class_id: Option<UnitClassId>)
+               -> Vec<UnitId> {
+        if unit_id.is_none() && unit_class_id.is_none() {
+            return Vec::new();
+        }
+        empires.civilization(self.civilization_id)
+            .units()
+            .filter(|unit_info| unit_id.is_none_or(|unit_id| unit_info.id == unit_id))
+            .filter(|unit_info| unit_class_id.is_none_or(|class_id| unit_info.class_id as u32 == *class_id))
+            .map(|unit_info| unit_info.id)
+            .collect()
+    }
+
+    /// The player's own copy of the unit, made the first time research changes it
+    fn unit_mut(&mut self, empires: &EmpiresDb, unit_id: UnitId) -> &mut dat::Unit {
+        let civilization_id = self.civilization_id;
+        self.units.entry(unit_id).or_insert_with(|| empires.unit(civilization_id, unit_id).clone())
+    }
+}
+
+/// Every player's research. Systems look units up here rather than in the empires db, so that
+/// they see each player's units the way that player's research has left them.
+#[derive(Clone)]
+pub struct Technologies {
+    empires: EmpiresDbRef,
+    players: BTreeMap<PlayerId, PlayerTechnologies>,
+}
+
+impl Technologies {
+    pub fn new(empires: EmpiresDbRef) -> Technologies {
+        Technologies {
+            empires: empires,
+            players: BTreeMap::new(),
+        }
+    }
+
+    /// Sets every player up with the research the scenario disables for them, their civ's bonuses,
+    /// and the research that advances them to the age the scenario starts them in
+    pub fn from_scenario(scenario: &Scenario, empires: EmpiresDbRef, players: &mut Players) -> Technologies {
+        let mut technologies = Technologies::new(empires);
+        for player_id in scenario.player_ids() {
+            let mut player_technologies = PlayerTechnologies::new(scenario.player_civilization_id(player_id),
+                                                                  scenario.player_data.all_techs);
+            for research_id in scenario.disabled_research_ids(player_id) {
+                player_technologies.disable(research_id);
+            }
+            technologies.add_player(player_id, player_technologies);
+            if let Some(player) = players.player_mut(player_id) {
+                technologies.start(player, scenario.starting_age(player_id));
+            }
+        }
+        technologies
+    }
+
+    /// Gives the player their civ's bonuses, then advances them through the ages up to the given one
+    fn start(&mut self, player: &mut Player, age: Age) {
+        let empires = self.empires.clone();
+        let mut pending = match self.players.get_mut(&player.player_id) {
+            Some(technologies) => technologies.apply_civ_bonus(&empires, Some(&mut *player)),
+            None => return,
+        };
+        let starting_values = empires.civilization(player.civ_id).starting_values();
+        pending.extend([Age::Tool, Age::Bronze, Age::Iron]
+            .iter()
+            .filter(|&&advanced_age| advanced_age <= age)
+            .filter_map(|&advanced_age| starting_values.age_research_id(advanced_age)));
+        self.apply_all(player, pending, &mut Vec::new());
+    }
+
+    pub fn add_player(&mut self, player_id: PlayerId, player: PlayerTechnologies) {
+        self.players.insert(player_id, player);
+    }
+
+    #[cfg(test)]
+    pub fn player(&self, player_id: PlayerId) -> Option<&PlayerTechnologies> {
+        self.players.get(&player_id)
+    }
+
+    /// Every player's research, in player ID order
+    pub fn all(&self) -> impl Iterator<Item = (PlayerId, &PlayerTechnologies)> {
+        self.players.iter().map(|(&player_id, player)| (player_id, player))
+    }
+
+    /// The unit as the given player has it
+    pub fn unit(&self, player_id: PlayerId, civilization_id: CivilizationId, unit_id: UnitId) -> &dat::Unit {
+        match self.players.get(&player_id).and_then(|player| player.units.get(&unit_id)) {
+            Some(unit_info) => unit_info,
+            None => self.empires.unit(civilization_id, unit_id),
+        }
+    }
+
+    /// The unit that research has upgraded the given unit to, or the unit itself if it hasn't been
+    pub fn upgraded_unit_id(&self, player_id: PlayerId, unit_id: UnitId) -> UnitId {
+        let upgrades = match self.players.get(&player_id) {
+            Some(player) => &player.upgrades,
+            None => return unit_id,
+        };
+        // Upgrades can follow on from each other, but are limited in case the data loops back
+        let mut upgraded_unit_id = unit_id;
+        for _ in 0..upgrades.len() {
+            match upgrades.get(&upgraded_unit_id) {
+                Some(&next_unit_id) => upgraded_unit_id = next_unit_id,
+                None => break,
+            }
+        }
+        upgraded_unit_id
+    }
+
+    /// Whether the unit is the given kind of building, or what the player's research has upgraded
+    /// it to, so that upgraded buildings go on training and researching what they used to
+    pub fn is_location(&self, player_id: PlayerId, location_id: UnitId, unit_id: UnitId) -> bool {
+        unit_id == location_id || unit_id == self.upgraded_unit_id(player_id, location_id)
+    }
+
+    /// The latest age the player has advanced to
+    pub fn age(&self, player_id: PlayerId) -> Age {
+        let player = match self.players.get(&player_id) {
+            Some(player) => player,
+            None => return Age::Stone,
+        };
+        let starting_values = self.empires.civilization(player.civilization_id).starting_values();
+        [Age::Iron, Age::Bronze, Age::Tool]
+            .iter()
+            .cloned()
+            .find(|&age| {
+                starting_values.age_research_id(age).is_some_and(|research_id| player.is_researched(research_id))
+            })
+            .unwrap_or(Age::Stone)
+    }
+
+    /// Every unit the civ has, as the given player has it, in unit ID order
+    pub fn units<'a>(&'a self,
+                     player_id: PlayerId,
+                     civilization_id: CivilizationId)
+                     -> impl Iterator<Item = &'a dat::Unit> + 'a {
+        self.empires
+            .civilization(civilization_id)
+            .units()
+            .map(move |unit_info| self.unit(player_id, civilization_id, unit_info.id))
+    }
+
+    /// What the research costs the given player
+    pub fn research_costs(&self, player_id: PlayerId, research_id: ResearchId) -> &[dat::ResearchCost] {
+        match self.players.get(&player_id).and_then(|player| player.research_costs.get(&research_id)) {
+            Some(costs) => costs,
+            None => &self.empires.research(research_id).resource_costs,
+        }
+    }
+
+    /// Whether the player may start the research at the given kind of building (cost aside)
+    pub fn can_research(&self, player_id: PlayerId, research_id: ResearchId, location_id: UnitId) -> bool {
+        let research = self.empires.all_research().get(*research_id as usize);
+        research.and_then(|research| research.location)
+            .is_some_and(|research_at| self.is_location(player_id, research_at, location_id)) &&
+        self.is_available(player_id, research_id)
+    }
+
+    /// Whether the player could get the research now, wherever it's done. Research can't be done
+    /// twice, and needs every research it requires to be done first.
+    pub fn is_available(&self, player_id: PlayerId, research_id: ResearchId) -> bool {
+        let player = match self.players.get(&player_id) {
+            Some(player) => player,
+            None => return false,
+        };
+        let research = match self.empires.all_research().get(*research_id as usize) {
+            Some(research) => research,
+            None => return false,
+        };
+        !player.is_researched(research_id) && !player.disabled.contains(&research_id) &&
+        research.required_techs
+            .iter()
+            .filter(|&&required_id| required_id >= 0)
+            .all(|&required_id| player.is_researched((required_id as usize).into()))
+    }
+
+    /// The research the player may start at the given kind of building, in the order of its buttons
+    pub fn researchable_at(&self, player_id: PlayerId, location_id: UnitId) -> Vec<&dat::Research> {
+        let mut research: Vec<&dat::Research> = self.empires
+            .all_research()
+            .iter()
+            .filter(|research| self.can_research(player_id, research.id, location_id))
+            .collect();
+        research.sort_by_key(|research| research.button_id);
+        research
+    }
+
+    /// Finishes research for the player, along with whatever research it gives them for free.
+    /// Returns the (from, to) pairs of units the player's existing units should be upgraded with.
+    pub fn complete(&mut self, player: &mut Player, research_id: ResearchId) -> Vec<(UnitId, UnitId)> {
+        let mut upgrades = Vec::new();
+        self.apply_all(player, vec![research_id], &mut upgrades);
+        upgrades
+    }
+
+    /// Redoes the research done before the game was saved, in the order it was done. What it did to
+    /// the player's stockpile and units was saved along with them, so only the research changes here.
+    pub fn restore(&mut self, player_id: PlayerId, researched: &[ResearchId]) {
+        for &research_id in researched {
+            self.apply(player_id, research_id, None, &mut Vec::new());
+        }
+    }
+
+    /// Applies the research in order, along with whatever research it gives the player as it goes
+    fn apply_all(&mut self,
+                 player: &mut Player,
+                 mut pending: Vec<ResearchId>,
+                 upgrades: &mut Vec<(UnitId, UnitId)>) {
+        while !pending.is_empty() {
+            let research_id = pending.remove(0);
+            let gained = self.apply(player.player_id, research_id, Some(&mut *player), upgrades);
+            pending.extend(gained);
+        }
+    }
+
+    fn apply(&mut self,
+             player_id: PlayerId,
+             research_id: ResearchId,
+             player: Option<&mut Player>,
+             upgrades: &mut Vec<(UnitId, UnitId)>)
+             -> Vec<ResearchId> {
+        let empires = &self.empires;
+        // Saved games can name research that isn't in the data, which is then just marked as done
+        let age_id = empires.all_research().get(*research_id as usize).and_then(|research| research.age_id);
+        let effects = match age_id {
+            Some(age_id) => empires.age(age_id).decoded_effects(),
+            None => Vec::new(),
+        };
+        match self.players.get_mut(&player_id) {
+            Some(technologies) => technologies.apply_effects(empires, research_id, &effects, player, upgrades),
+            None => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlayerTechnologies, Technologies};
+    use crate::dat::{EmpiresDb, EmpiresDbRef, ResearchEffect, ResearchEffectValue, ResourceType};
+    use crate::ecs::resource::Player;
+    use crate::identifier::ResearchId;
+    use crate::types::Fixed;
+
+    fn research_id(id: usize) -> ResearchId {
+        id.into()
+    }
+
+    #[test]
+    fn test_apply_effects() {
+        let empires = EmpiresDb::new();
+        let mut player = Player::new("One".into(), 1usize.into(), 1usize.into(), 1usize.into());
+        player.credit(ResourceType::Unknown(4), 4.into());
+        let effects = vec![
+            ResearchEffect::CivHeader { target_civ_header_id: 4, effect: ResearchEffectValue::Add(4.0) },
+            ResearchEffect::DisableResearch { research_id: research_id(7) },
+            ResearchEffect::GainResearch { research_id: research_id(8) },
+            ResearchEffect::UpgradeUnit { source_unit_id: 83usize.into(), target_unit_id: 93usize.into() },
+        ];
+
+        let mut technologies = PlayerTechnologies::new(1usize.into(), false);
+        let mut upgrades = Vec::new();
+        let gained = technologies.apply_effects(&empires, research_id(3), &effects, Some(&mut player), &mut upgrades);
+        assert_eq!(vec![research_id(8)], gained);
+        assert_eq!(vec![(83usize.into(), 93usize.into())], upgrades);
+        assert_eq!(Fixed::from(8), player.resource(ResourceType::Unknown(4)));
+        assert!(technologies.is_researched(research_id(3)));
+        assert!(technologies.disabled().contains(&research_id(7)));
+
+        // Research only ever applies once
+        let gained = technologies.apply_effects(&empires, research_id(3), &effects, Some(&mut player), &mut upgrades);
+        assert!(gained.is_empty());
+        assert_eq!(Fixed::from(8), player.resource(ResourceType::Unknown(4)));
+
+        // Restoring a saved game leaves the player and their units alone, and "all techs" keeps research enabled
+        let mut technologies = PlayerTechnologies::new(1usize.into(), true);
+        let mut upgrades = Vec::new();
+        technologies.apply_effects(&empires, research_id(3), &effects, None, &mut upgrades);
+        assert!(upgrades.is_empty());
+        assert!(technologies.disabled().is_empty());
+        assert_eq!(Fixed::from(8), player.resource(ResourceType::Unknown(4)));
+        assert_eq!(&[research_id(3)], technologies.researched());
+    }
+
+    #[test]
+    fn test_upgraded_unit_id() {
+        let empires = EmpiresDbRef::new(EmpiresDb::new());
+        let effects = vec![
+            ResearchEffect::UpgradeUnit { source_unit_id: 83usize.into(), target_unit_id: 93usize.into() },
+            ResearchEffect::UpgradeUnit { source_unit_id: 93usize.into(), target_unit_id: 101usize.into() },
+        ];
+        let mut player = PlayerTechnologies::new(1usize.into(), false);
+        player.apply_effects(&empires, research_id(3), &effects, None, &mut Vec::new());
+
+        let mut technologies = Technologies::new(empires);
+        technologies.add_player(1usize.into(), player);
+        assert_eq!(101, *technologies.upgraded_unit_id(1usize.into(), 83usize.into()));
+        assert_eq!(93, *technologies.upgraded_unit_id(2usize.into(), 93usize.into()));
+        assert_eq!(7, *technologies.upgraded_unit_id(1usize.into(), 7usize.into()));
+    }
+}
@@ -36,7 +36,6 @@ pub struct Tile {
 }
 
 impl Tile {
-    #[allow(unused)] // Used from tests
     pub fn new(terrain_id: TerrainId, elevation: u8) -> Tile {
         Tile {
             terrain_id: terrain_id,
@@ -104,6 +103,20 @@ impl Terrain {
         self.height
     }
 
+    /// Every tile, row by row
+    pub fn tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    /// Swaps in a whole new set of tiles, such as from a saved game
+    pub fn replace_tiles(&mut self, width: i32, height: i32, tiles: Vec<Tile>) {
+        assert_eq!((width * height) as usize, tiles.len());
+        self.width = width;
+        self.height = height;
+        self.tiles = tiles.into_iter().map(|tile| Tile::new(tile.terrain_id, tile.elevation)).collect();
+        self.blend_cache.clear();
+    }
+
     /// Return the (min, max) elevation (inclusive on both ends)
     #[inline(always)]
     pub fn elevation_range(&self) -> (i32, i32) {
@@ -22,6 +22,9 @@
 use nalgebra::Vector2;
 use crate::types::Fixed;
 
+/// How far past the edge of the screen (as a fraction of the screen's width) a sound can still be heard
+const SOUND_FALLOFF: f32 = 0.5;
+
 pub struct Viewport {
     current_top_left: Vector2<Fixed>,
     last_top_left: Vector2<Fixed>,
@@ -55,4 +58,38 @@ impl Viewport {
         let lerped = self.current_top_left + (self.current_top_left - self.last_top_left) * lerp;
         Vector2::new(lerped.x.into(), lerped.y.into())
     }
+
+    /// Returns the volume and stereo pan for a sound made at the given projected position.
+    /// Sounds on screen play at full volume, panned by how far they are from the center,
+    /// and fade out as they get further off screen.
+    pub fn sound_mix(&self, position: &Vector2<i32>) -> (f32, f32) {
+        let relative = *position - self.top_left_i32();
+        let (width, height) = (self.size.x.max(1) as f32, self.size.y.max(1) as f32);
+
+        let half_width = width / 2.0;
+        let pan = ((relative.x as f32 - half_width) / half_width).max(-1.0).min(1.0);
+
+        let outside_x = (-relative.x as f32).max(relative.x as f32 - width).max(0.0);
+        let outside_y = (-relative.y as f32).max(relative.y as f32 - height).max(0.0);
+        let outside = outside_x.max(outside_y) / (width * SOUND_FALLOFF);
+        (1.0 - outside.min(1.0), pan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sound_mix() {
+        let mut viewport = Viewport::new(800, 600);
+        viewport.set_top_left(Vector2::new(100.into(), 50.into()));
+
+        assert_eq!((1.0, 0.0), viewport.sound_mix(&Vector2::new(500, 350)));
+        assert_eq!((1.0, -1.0), viewport.sound_mix(&Vector2::new(100, 50)));
+        assert_eq!((1.0, 0.5), viewport.sound_mix(&Vector2::new(700, 649)));
+        assert_eq!((0.5, 1.0), viewport.sound_mix(&Vector2::new(1100, 350)));
+        assert_eq!((0.0, -1.0), viewport.sound_mix(&Vector2::new(-400, 350)));
+        assert_eq!((0.75, 0.0), viewport.sound_mix(&Vector2::new(500, -50)));
+    }
 }
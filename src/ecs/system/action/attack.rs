@@ -0,0 +1,258 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::dat;
+use crate::ecs::component::{UnitComponent, AttackActionComponent, TransformComponent, GraphicComponent, ActionQueueComponent,
+                            VelocityComponent, HealthComponent, ProjectileComponent};
+use crate::ecs::resource::{Random, Technologies};
+use crate::identifier::{PlayerColorId, PlayerId, UnitId};
+use specs::{self, Entity, Index, Join};
+use super::super::System;
+use crate::types::{Fixed, Norm, Vector3};
+use crate::util::unit;
+
+use std::collections::BTreeMap;
+
+/// Position and type of a living unit that can be attacked, captured before
+/// any attacker moves so that all attacks in a tick see the same state.
+struct Target {
+    entity: Entity,
+    transform: TransformComponent,
+    unit: UnitComponent,
+}
+
+/// A projectile to create once the attackers are done being updated
+struct Launch {
+    projectile: ProjectileComponent,
+    projectile_unit_id: UnitId,
+    player_color_id: PlayerColorId,
+}
+
+pub struct AttackActionSystem;
+
+impl AttackActionSystem {
+    pub fn new() -> AttackActionSystem {
+        AttackActionSystem
+    }
+
+    /// Fires a projectile from `start` at the target, deciding now whether it will hit
+    fn launch(&self,
+              technologies: &Technologies,
+              random: &mut Random,
+              unit: &UnitComponent,
+              params: &dat::BattleParams,
+              projectile_unit_id: UnitId,
+              start: Vector3,
+              target: &Target)
+              -> ProjectileComponent {
+        let hits = random.chance(params.accuracy_percent.max(0) as u32);
+        let mut end = *target.transform.position();
+        if !hits {
+            // Misses land somewhere within a tile of the target
+            end.x += Fixed::from(random.next_u32() % 201) / 100.into() - 1.into();
+            end.y += Fixed::from(random.next_u32() % 201) / 100.into() - 1.into();
+        }
+
+        let projectile_info = technologies.unit(unit.player_id, unit.civilization_id, projectile_unit_id);
+        let distance = if (end - start).length_squared() > 0.into() { (end - start).length() } else { 0.into() };
+        let speed: Fixed = projectile_info.motion_params.as_ref().map(|params| params.speed).unwrap_or(0.0).into();
+        let flight_time = if speed > 0.into() { distance / speed } else { 0.into() };
+        let arc: Fixed = projectile_info.projectile_params.as_ref().map(|params| params.projectile_arc).unwrap_or(0.0).into();
+
+        ProjectileComponent {
+            player_id: unit.player_id,
+            civilization_id: unit.civilization_id,
+            attacker_unit_id: unit.unit_id,
+            target: target.entity,
+            hits: hits,
+            start: start,
+            end: end,
+            arc_height: distance * arc,
+            flight_time: flight_time,
+            elapsed_time: 0.into(),
+        }
+    }
+}
+
+impl System for AttackActionSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(units: UnitComponent),
+            mut components(transforms: TransformComponent),
+            mut components(attacks: AttackActionComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(action_queues: ActionQueueComponent),
+            mut components(healths: HealthComponent),
+            mut components(projectiles: ProjectileComponent),
+            resource(technologies: Technologies),
+            mut resource(random: Random),
+        ]);
+
+        // Sorted since it comes straight out of the join, which goes in index order
+        let targets: Vec<Target> = (&entities, &transforms, &units, &healths)
+            .iter()
+            .filter(|&(_, _, _, health)| !health.is_dead())
+            .map(|(entity, transform, unit, _)| {
+                Target {
+                    entity: entity,
+                    transform: transform.clone(),
+                    unit: unit.clone(),
+                }
+            })
+            .collect();
+
+        let mut damages: BTreeMap<Index, (i32, PlayerId)> = BTreeMap::new();
+        let mut launches: Vec<Launch> = Vec::new();
+
+        let items = (&entities, &units, &mut transforms, &mut velocities, &mut graphics, &mut attacks, &mut action_queues);
+        for (entity, unit, transform, velocity, graphic, attack, action_queue) in items.iter() {
+            let unit_info = unit.info(&technologies);
+            let attacker_alive = match healths.get(entity) {
+                Some(health) => !health.is_dead(),
+                None => true,
+            };
+            // Comparing whole entities rather than indices means a target that has been deleted
+            // isn't mistaken for whatever got its index afterwards
+            let target = targets.binary_search_by_key(&attack.target, |t| t.entity)
+                .ok()
+                .map(|i| &targets[i])
+                .filter(|t| t.entity != entity);
+
+            let done = match (target, unit_info.battle_params.as_ref()) {
+                (Some(target), Some(params)) if attacker_alive => {
+                    let target_info = target.unit.info(&technologies);
+                    let distance = unit::edge_distance(unit_info, transform, target_info, &target.transform);
+                    let mut direction = *target.transform.position() - *transform.position();
+                    direction.z = 0.into();
+                    if direction.length_squared() > 0.into() {
+                        direction.normalize();
+                    }
+
+                    attack.reload_time_left = (attack.reload_time_left - time_step).max(0.into());
+
+                    if distance < params.min_range.into() {
+                        // Too close to use this weapon; give up rather than back away
+                        true
+                    } else if distance > params.max_range.into() {
+                        match unit_info.motion_params {
+                            Some(ref motion_params) => {
+                                let walking_graphic = motion_params.walking_graphics[0];
+                                if walking_graphic.is_some() && graphic.graphic_id != walking_graphic {
+                                    graphic.set_graphic(walking_graphic);
+                                }
+                                let speed: Fixed = motion_params.speed.into();
+                                velocity.velocity = direction * speed;
+                                false
+                            }
+                            None => true,
+                        }
+                    } else {
+                        velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+                        transform.rotation = {
+                            // Only used for rendering, same as in the velocity system
+                            let x: f32 = direction.x.into();
+                            let y: f32 = direction.y.into();
+                            y.atan2(x).into()
+                        };
+                        if params.attack_graphic_id.is_some() && graphic.graphic_id != params.attack_graphic_id {
+                            graphic.set_graphic(params.attack_graphic_id);
+                        }
+
+                        if attack.reload_time_left == 0.into() {
+                            attack.reload_time_left = params.reload_time.into();
+                            if let Some(projectile_unit_id) = params.projectile_unit_id {
+                                // Ranged attacks deal their damage when the projectile lands
+                                launches.push(Launch {
+                                    projectile: self.launch(&technologies,
+                                                            &mut random,
+                                                            unit,
+                                                            params,
+                                                            projectile_unit_id,
+                                                            launch_position(params, transform.position(), &direction),
+                                                            target),
+                                    projectile_unit_id: projectile_unit_id,
+                                    player_color_id: graphic.player_color_id,
+                                });
+                            } else {
+                                damages.entry(target.entity.get_id()).or_insert((0, unit.player_id)).0 +=
+                                    unit::attack_damage(unit_info, target_info);
+
+                                if params.blast_width > 0.0 {
+                                    let blast_width: Fixed = params.blast_width.into();
+                                    for other in &targets {
+                                        if other.entity != target.entity && other.unit.player_id != unit.player_id &&
+                                           unit::in_blast(target.transform.position(),
+                                                          other.transform.position(),
+                                                          blast_width) {
+                                            let other_info = other.unit.info(&technologies);
+                                            damages.entry(other.entity.get_id()).or_insert((0, unit.player_id)).0 +=
+                                                unit::attack_damage(unit_info, other_info);
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        false
+                    }
+                }
+                _ => true,
+            };
+
+            if done {
+                graphic.set_graphic(unit_info.standing_graphic);
+                velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+                action_queue.mark_current_done();
+            }
+        }
+
+        for (entity, health) in (&entities, &mut healths).iter() {
+            if let Some(&(damage, attacker_id)) = damages.get(&entity.get_id()) {
+                health.damage(damage, attacker_id);
+            }
+        }
+
+        for launch in launches {
+            let projectile_info = technologies.unit(launch.projectile.player_id,
+                                                    launch.projectile.civilization_id,
+                                                    launch.projectile_unit_id);
+            let mut graphic = GraphicComponent::new();
+            graphic.player_color_id = launch.player_color_id;
+            graphic.graphic_id = projectile_info.standing_graphic;
+
+            let entity = arg.create();
+            transforms.insert(entity, TransformComponent::new(launch.projectile.start, 0.into()));
+            velocities.insert(entity, VelocityComponent::new());
+            graphics.insert(entity, graphic);
+            projectiles.insert(entity, launch.projectile);
+        }
+    }
+}
+
+/// Where projectiles start from. The displacements are forward, sideways and up from the
+/// attacker, relative to the direction it's facing.
+fn launch_position(params: &dat::BattleParams, position: &Vector3, direction: &Vector3) -> Vector3 {
+    let (forward, sideways, up): (Fixed, Fixed, Fixed) = (params.graphic_displacements[0].into(),
+                                                          params.graphic_displacements[1].into(),
+                                                          params.graphic_displacements[2].into());
+    let side_direction = Vector3::new(-direction.y, direction.x, 0.into());
+    *position + *direction * forward + side_direction * sideways + Vector3::new(0.into(), 0.into(), up)
+}
@@ -0,0 +1,332 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+use crate::dat::{self, EmpiresDbRef};
+use crate::ecs::component::{ActionQueueComponent, BuildActionComponent, ConstructionComponent, DeathComponent,
+                            GraphicComponent, HealthComponent, TransformComponent, UnitComponent, VelocityComponent};
+use crate::ecs::resource::{OccupiedTiles, PathFinder, Players, SoundEvent, SoundEvents, Technologies,
+                           Terrain};
+use specs::{self, Entity, Join};
+use super::super::System;
+use super::gather::{stop_facing, walk_towards};
+use crate::types::{Fixed, Vector3};
+use crate::util::unit;
+
+use std::collections::BTreeMap;
+
+/// A building under construction, captured before any builder works on it
+struct Foundation {
+    transform: TransformComponent,
+    unit: UnitComponent,
+}
+
+pub struct BuildActionSystem {
+    empires: EmpiresDbRef,
+}
+
+impl BuildActionSystem {
+    pub fn new(empires: EmpiresDbRef) -> BuildActionSystem {
+        BuildActionSystem { empires: empires }
+    }
+
+}
+
+/// The building the builder has been told to construct, if it's one it knows how to build
+fn building_info<'a>(technologies: &'a Technologies,
+                     unit: &UnitComponent,
+                     build: &BuildActionComponent)
+                     -> Option<&'a dat::Unit> {
+    let builder_info = unit.info(technologies);
+    unit::buildable_buildings(technologies, unit.player_id, unit.civilization_id, builder_info)
+        .into_iter()
+        .find(|building_info| building_info.id == build.unit_id)
+}
+
+impl System for BuildActionSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(deaths: DeathComponent),
+            mut components(units: UnitComponent),
+            mut components(transforms: TransformComponent),
+            mut components(builds: BuildActionComponent),
+            mut components(constructions: ConstructionComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(healths: HealthComponent),
+            mut components(action_queues: ActionQueueComponent),
+            resource(terrain: Terrain),
+            resource(occupied_tiles: OccupiedTiles),
+            resource(path_finder: PathFinder),
+            resource(technologies: Technologies),
+            mut resource(players: Players),
+            mut resource(sound_events: SoundEvents),
+        ]);
+
+        let mut foundations: BTreeMap<Entity, Foundation> = BTreeMap::new();
+        for (entity, transform, unit, _) in (&entities, &transforms, &units, &constructions).iter() {
+            if deaths.get(entity).is_none() {
+                foundations.insert(entity,
+                                   Foundation {
+                                       transform: transform.clone(),
+                                       unit: unit.clone(),
+                                   });
+            }
+        }
+
+        // Foundations are placed as builders get to their orders, so the ones placed earlier
+        // in the tick have to be checked for by hand; they aren't in the occupied tiles yet
+        let mut placed: Vec<(Entity, UnitComponent, TransformComponent)> = Vec::new();
+        let mut builder_counts: BTreeMap<Entity, i32> = BTreeMap::new();
+
+        let items = (&entities, &units, &mut transforms, &mut velocities, &mut graphics, &mut builds, &mut action_queues);
+        for (_entity, unit, transform, velocity, graphic, build, action_queue) in items.iter() {
+            let unit_info = unit.info(&technologies);
+            let command = match unit::build_command(unit_info) {
+                Some(command) => command,
+                None => {
+                    action_queue.mark_current_done();
+                    continue;
+                }
+            };
+
+            if build.target.is_none() {
+                build.target = foundations.iter()
+                    .find(|&(_, foundation)| {
+                        foundation.unit.player_id == unit.player_id && foundation.unit.unit_id == build.unit_id &&
+                        *foundation.transform.position() == build.position
+                    })
+                    .map(|(&entity, _)| entity);
+            }
+
+            if build.target.is_none() {
+                if let Some(building_info) = building_info(&technologies, unit, build) {
+                    let position = unit::snap_to_tiles(building_info, &build.position);
+                    let (rows, cols) = unit::footprint(building_info, &position);
+                    let overlaps_placed = placed.iter().any(|(_, other_unit, other_transform)| {
+                        let other_info = other_unit.info(&technologies);
+                        let (other_rows, other_cols) = unit::footprint(other_info, other_transform.position());
+                        rows.start < other_rows.end && other_rows.start < rows.end && cols.start < other_cols.end &&
+                        other_cols.start < cols.end
+                    });
+                    let costs = unit::train_costs(building_info);
+                    if position == build.position && !overlaps_placed &&
+                       unit::can_place_building(&self.empires, &terrain, &occupied_tiles, building_info, &position) &&
+                       players.player_mut(unit.player_id).is_some_and(|player| player.debit(costs).is_ok()) {
+                        let entity = arg.create();
+                        let foundation = Foundation {
+                            transform: TransformComponent::new(position, 0.into()),
+                            unit: UnitComponent::new(unit.player_id, unit.civilization_id, build.unit_id),
+                        };
+                        placed.push((entity, foundation.unit.clone(), foundation.transform.clone()));
+                        foundations.insert(entity, foundation);
+                        build.target = Some(entity);
+
+                        if let Some(sound_group_id) = building_info.building_params
+                            .as_ref()
+                            .and_then(|params| params.construction_sound_id) {
+                            sound_events.push(SoundEvent::new(sound_group_id, position));
+                        }
+                    }
+                }
+            }
+
+            let done = match build.target.and_then(|target| foundations.get(&target).map(|f| (target, f))) {
+                Some((target, foundation)) => {
+                    let foundation_info = foundation.unit.info(&technologies);
+                    let distance = unit::edge_distance(unit_info, transform, foundation_info, &foundation.transform);
+                    if distance > command.execution_radius.into() {
+                        !walk_towards(&path_finder,
+                                      &terrain,
+                                      &occupied_tiles,
+                                      unit_info,
+                                      transform,
+                                      velocity,
+                                      graphic,
+                                      &mut build.target_path,
+                                      foundation.transform.position(),
+                                      command.proceeding_graphic_id)
+                    } else {
+                        build.target_path = None;
+                        stop_facing(transform, velocity, foundation.transform.position());
+                        if command.action_graphic_id.is_some() && graphic.graphic_id != command.action_graphic_id {
+                            graphic.set_graphic(command.action_graphic_id);
+                        }
+                        *builder_counts.entry(target).or_insert(0) += 1;
+                        false
+                    }
+                }
+                // Couldn't be placed, or it's finished or destroyed
+                None => true,
+            };
+
+            if done {
+                graphic.set_graphic(unit_info.standing_graphic);
+                velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+                action_queue.mark_current_done();
+            }
+        }
+
+        for (entity, unit, transform) in placed {
+            let building_info = unit.info(&technologies);
+            let mut graphic = GraphicComponent::new();
+            graphic.player_color_id = unit.player_id.into();
+            graphic.graphic_id = match building_info.building_params {
+                Some(ref params) => Some(params.construction_graphic_id),
+                None => building_info.standing_graphic,
+            };
+
+            // Foundations start out with a single hit point and gain the rest as they're built
+            let mut health = HealthComponent::new(building_info.hit_points as i32);
+            health.hit_points = 1;
+
+            transforms.insert(entity, transform);
+            velocities.insert(entity, VelocityComponent::new());
+            graphics.insert(entity, graphic);
+            action_queues.insert(entity, ActionQueueComponent::new());
+            healths.insert(entity, health);
+            constructions.insert(entity, ConstructionComponent::new());
+            units.insert(entity, unit);
+        }
+
+        let mut finished = Vec::new();
+        for (entity, unit, construction, health, graphic) in
+            (&entities, &units, &mut constructions, &mut healths, &mut graphics).iter() {
+            let builder_count = match builder_counts.get(&entity) {
+                Some(&count) => count,
+                None => continue,
+            };
+            let building_info = unit.info(&technologies);
+            let train_time: Fixed = building_info.trainable_params.as_ref().map_or(0, |params| params.train_time).into();
+
+            // Every builder after the first adds a third of a builder's work, like in the original
+            let progress = if train_time > 0.into() {
+                let rate = Fixed::from(builder_count + 2) / (Fixed::from(3) * train_time);
+                (construction.progress + rate * time_step).min(1.into())
+            } else {
+                1.into()
+            };
+
+            // Damage taken while under construction isn't undone by building
+            let max_hit_points: Fixed = health.max_hit_points.into();
+            let gained = i32::from(max_hit_points * progress) - i32::from(max_hit_points * construction.progress);
+            health.hit_points = (health.hit_points + gained).min(health.max_hit_points);
+            construction.progress = progress;
+
+            if progress >= 1.into() {
+                graphic.set_graphic(building_info.standing_graphic);
+                finished.push(entity);
+            }
+        }
+        for entity in finished {
+            constructions.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BuildActionSystem;
+    use crate::action::{Action, BuildParams};
+    use crate::dat;
+    use crate::ecs::{ActionQueueComponent, BuildActionComponent, ConstructionComponent, GraphicComponent,
+                     HealthComponent, TransformComponent, UnitComponent, VelocityComponent};
+    use crate::ecs::world::{run_ticks, test_empires, test_planner, test_world, vector};
+    use crate::types::{Fixed, Vector3};
+    use crate::util::unit;
+
+    /// A villager (83) and a house (70) that takes one villager 8 seconds to build
+    fn empires() -> dat::EmpiresDbRef {
+        let mut build = dat::UnitCommand::default();
+        build.type_id = unit::BUILD_COMMAND_TYPE;
+        build.execution_radius = 1.0;
+        let mut params = dat::CommandableParams::default();
+        params.commands = vec![build];
+        let mut villager = dat::Unit::default();
+        villager.id = 83usize.into();
+        villager.commandable_params = Some(params);
+
+        let mut trainable = dat::TrainableParams::default();
+        trainable.train_time = 8;
+        let mut house = dat::Unit::default();
+        house.id = 70usize.into();
+        house.hit_points = 100;
+        house.interaction_mode = dat::InteractionMode::Building;
+        house.trainable_params = Some(trainable);
+
+        test_empires(vec![villager, house])
+    }
+
+    /// A house foundation at `position`, with the given number of villagers next to it building it
+    fn foundation(world: &mut specs::World, position: Vector3, builder_count: usize) -> specs::Entity {
+        let mut health = HealthComponent::new(100);
+        health.hit_points = 1;
+        let house = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 70usize.into()))
+            .with(TransformComponent::new(position, 0.into()))
+            .with(GraphicComponent::new())
+            .with(health)
+            .with(ConstructionComponent::new())
+            .build();
+        for _ in 0..builder_count {
+            let mut action_queue = ActionQueueComponent::new();
+            action_queue.add(Action::Build(BuildParams::new(70usize.into(), position)));
+            action_queue.next_action();
+            let mut build = BuildActionComponent::new(70usize.into(), position);
+            build.target = Some(house);
+            world.create_now()
+                .with(UnitComponent::new(1usize.into(), 1usize.into(), 83usize.into()))
+                .with(TransformComponent::new(position + vector(1, 0), 0.into()))
+                .with(VelocityComponent::new())
+                .with(GraphicComponent::new())
+                .with(action_queue)
+                .with(build)
+                .build();
+        }
+        house
+    }
+
+    #[test]
+    fn test_more_builders_build_faster() {
+        let empires = empires();
+        let mut world = test_world(empires.clone());
+        let alone = foundation(&mut world, vector(2, 2), 1);
+        let together = foundation(&mut world, vector(8, 2), 4);
+        let mut planner = test_planner(world, Box::new(BuildActionSystem::new(empires)));
+
+        // Every villager after the first adds a third of a villager's work, so four of them build
+        // twice as fast as one
+        run_ticks(&mut planner, 4, 1.into());
+        {
+            let world = planner.mut_world();
+            let constructions = world.read::<ConstructionComponent>();
+            let healths = world.read::<HealthComponent>();
+            assert_eq!(Fixed::from(0.5), constructions.get(alone).unwrap().progress);
+            assert_eq!(51, healths.get(alone).unwrap().hit_points);
+            assert!(constructions.get(together).is_none());
+            assert_eq!(100, healths.get(together).unwrap().hit_points);
+        }
+
+        run_ticks(&mut planner, 4, 1.into());
+        let world = planner.mut_world();
+        assert!(world.read::<ConstructionComponent>().get(alone).is_none());
+        assert_eq!(100, world.read::<HealthComponent>().get(alone).unwrap().hit_points);
+    }
+}
@@ -0,0 +1,465 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+use crate::dat::{self, ResourceType};
+use crate::ecs::component::{ActionQueueComponent, ConstructionComponent, DeathComponent, DeathStage,
+                            GatherActionComponent, GatherStage, GraphicComponent, HealthComponent,
+                            ResourceComponent, TargetPath, TransformComponent, UnitComponent,
+                            VelocityComponent};
+use crate::ecs::resource::{OccupiedTiles, PathFinder, Players, SoundEvent, SoundEvents, Technologies,
+                           Terrain};
+use crate::identifier::GraphicId;
+use crate::partition::GridPartition;
+use specs::{self, Entity, Join};
+use super::super::System;
+use super::move_to_position::THRESHOLD;
+use crate::types::{Fixed, Norm, Vector3};
+use crate::util::unit;
+
+use std::collections::BTreeMap;
+
+/// How close gatherers have to get to a drop site to drop off what they're carrying
+const DROP_OFF_DISTANCE: f32 = 0.5;
+
+/// A resource that can be gathered from, captured before any gatherer takes from it.
+/// Gatherers take from the copy in the order they're updated, and whatever is left is
+/// written back afterwards.
+struct Resource {
+    transform: TransformComponent,
+    unit: UnitComponent,
+    resource: ResourceComponent,
+}
+
+/// A finished building that gatherers might be able to drop resources off at
+struct DropSite {
+    transform: TransformComponent,
+    unit: UnitComponent,
+}
+
+pub struct GatherActionSystem;
+
+impl GatherActionSystem {
+    pub fn new() -> GatherActionSystem {
+        GatherActionSystem
+    }
+
+    /// The closest of the player's buildings that the unit can drop resources off at
+    fn nearest_drop_site<'a>(&self,
+                             drop_sites: &'a [DropSite],
+                             unit: &UnitComponent,
+                             params: &dat::CommandableParams,
+                             transform: &TransformComponent)
+                             -> Option<&'a DropSite> {
+        let mut nearest: Option<(&DropSite, Fixed)> = None;
+        for drop_site in drop_sites {
+            let unit_id = *drop_site.unit.unit_id as i16;
+            if drop_site.unit.player_id != unit.player_id || !params.drop_sites.contains(&unit_id) {
+                continue;
+            }
+            let offset = *drop_site.transform.position() - *transform.position();
+            let distance = Vector3::new(offset.x, offset.y, 0.into()).length_squared();
+            let closer = match nearest {
+                Some((_, nearest_distance)) => distance < nearest_distance,
+                None => true,
+            };
+            if closer {
+                nearest = Some((drop_site, distance));
+            }
+        }
+        nearest.map(|(drop_site, _)| drop_site)
+    }
+}
+
+impl System for GatherActionSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(units: UnitComponent),
+            components(healths: HealthComponent),
+            components(deaths: DeathComponent),
+            components(constructions: ConstructionComponent),
+            mut components(transforms: TransformComponent),
+            mut components(resources: ResourceComponent),
+            mut components(gathers: GatherActionComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(action_queues: ActionQueueComponent),
+            resource(technologies: Technologies),
+            resource(path_finder: PathFinder),
+            resource(terrain: Terrain),
+            resource(occupied_tiles: OccupiedTiles),
+            mut resource(players: Players),
+            mut resource(grid: GridPartition),
+            mut resource(sound_events: SoundEvents),
+        ]);
+
+        // Animals have to be killed before they can be gathered from, and their
+        // carcasses are only left behind once they're done dying
+        let mut targets: BTreeMap<Entity, Resource> = BTreeMap::new();
+        for (entity, transform, unit, resource) in (&entities, &transforms, &units, &resources).iter() {
+            let alive = match healths.get(entity) {
+                Some(health) => !health.is_dead(),
+                None => false,
+            };
+            let dying = match deaths.get(entity) {
+                Some(death) => death.stage == DeathStage::Dying,
+                None => false,
+            };
+            if !alive && !dying {
+                targets.insert(entity,
+                               Resource {
+                                   transform: transform.clone(),
+                                   unit: unit.clone(),
+                                   resource: resource.clone(),
+                               });
+            }
+        }
+
+        let drop_sites: Vec<DropSite> = (&entities, &transforms, &units)
+            .iter()
+            .filter(|&(entity, _, unit)| {
+                deaths.get(entity).is_none() && constructions.get(entity).is_none() &&
+                unit.info(&technologies).interaction_mode == dat::InteractionMode::Building
+            })
+            .map(|(_, transform, unit)| {
+                DropSite {
+                    transform: transform.clone(),
+                    unit: unit.clone(),
+                }
+            })
+            .collect();
+
+        let items = (&entities, &units, &mut transforms, &mut velocities, &mut graphics, &mut gathers, &mut action_queues);
+        for (_entity, unit, transform, velocity, graphic, gather, action_queue) in items.iter() {
+            let unit_info = unit.info(&technologies);
+            let params = match unit_info.commandable_params {
+                Some(ref params) => params,
+                None => {
+                    action_queue.mark_current_done();
+                    continue;
+                }
+            };
+
+            let done = match gather.stage {
+                GatherStage::ToResource | GatherStage::Gathering => {
+                    match targets.get_mut(&gather.target) {
+                        Some(target) => {
+                            let target_info = target.unit.info(&technologies);
+                            let resource_type = target.resource.resource_type;
+                            match unit::gather_command(unit_info, target_info, resource_type) {
+                                Some(command) => {
+                                    if gather.resource_type != Some(resource_type) {
+                                        // Anything else the unit was carrying is dropped, like in the original
+                                        gather.resource_type = Some(resource_type);
+                                        gather.carried = 0.into();
+                                    }
+
+                                    let distance =
+                                        unit::edge_distance(unit_info, transform, target_info, &target.transform);
+                                    if distance > command.execution_radius.into() {
+                                        gather.stage = GatherStage::ToResource;
+                                        !walk_towards(&path_finder,
+                                                      &terrain,
+                                                      &occupied_tiles,
+                                                      unit_info,
+                                                      transform,
+                                                      velocity,
+                                                      graphic,
+                                                      &mut gather.target_path,
+                                                      target.transform.position(),
+                                                      command.proceeding_graphic_id)
+                                    } else {
+                                        gather.stage = GatherStage::Gathering;
+                                        gather.target_path = None;
+                                        stop_facing(transform, velocity, target.transform.position());
+                                        if command.action_graphic_id.is_some() &&
+                                           graphic.graphic_id != command.action_graphic_id {
+                                            graphic.set_graphic(command.action_graphic_id);
+                                        }
+
+                                        let capacity: Fixed = unit_info.resource_capacity.into();
+                                        let work_rate: Fixed = params.work_rate.into();
+                                        let amount = (work_rate * time_step)
+                                            .min(capacity - gather.carried)
+                                            .min(target.resource.amount)
+                                            .max(0.into());
+                                        target.resource.amount -= amount;
+                                        gather.carried += amount;
+                                        if gather.carried >= capacity || target.resource.amount <= 0.into() {
+                                            gather.stage = GatherStage::ToDropSite;
+                                        }
+                                        false
+                                    }
+                                }
+                                None => true,
+                            }
+                        }
+                        None => {
+                            // The resource is gone, but what was gathered from it still gets dropped off
+                            if gather.carried > 0.into() {
+                                gather.stage = GatherStage::ToDropSite;
+                                false
+                            } else {
+                                true
+                            }
+                        }
+                    }
+                }
+                GatherStage::ToDropSite => {
+                    let command = gather.resource_type.and_then(|resource_type| carrying_command(unit_info, resource_type));
+                    match self.nearest_drop_site(&drop_sites, unit, params, transform) {
+                        Some(drop_site) => {
+                            let drop_site_info = drop_site.unit.info(&technologies);
+                            let distance = unit::edge_distance(unit_info, transform, drop_site_info, &drop_site.transform);
+                            if distance > DROP_OFF_DISTANCE.into() {
+                                let carrying_graphic_id = command.and_then(|command| command.carrying_graphic_id);
+                                !walk_towards(&path_finder,
+                                              &terrain,
+                                              &occupied_tiles,
+                                              unit_info,
+                                              transform,
+                                              velocity,
+                                              graphic,
+                                              &mut gather.target_path,
+                                              drop_site.transform.position(),
+                                              carrying_graphic_id)
+                            } else {
+                                if let (Some(resource_type), Some(player)) = (gather.resource_type,
+                                                                              players.player_mut(unit.player_id)) {
+                                    player.credit(resource_type, gather.carried);
+                                }
+                                if let Some(sound_group_id) = command.and_then(|command| command.resource_deposit_sound_id) {
+                                    sound_events.push(SoundEvent::new(sound_group_id, *transform.position()));
+                                }
+                                gather.carried = 0.into();
+                                gather.stage = GatherStage::ToResource;
+                                gather.target_path = None;
+
+                                // Head back for more, unless there's nothing left
+                                match targets.get(&gather.target) {
+                                    Some(target) => target.resource.amount <= 0.into(),
+                                    None => true,
+                                }
+                            }
+                        }
+                        None => true,
+                    }
+                }
+            };
+
+            if done {
+                graphic.set_graphic(unit_info.standing_graphic);
+                velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+                action_queue.mark_current_done();
+            }
+        }
+
+        for (entity, resource) in (&entities, &mut resources).iter() {
+            if let Some(target) = targets.get(&entity) {
+                if resource.amount > 0.into() && target.resource.amount <= 0.into() {
+                    // Used up. Taken out of the grid and velocity system right away, since the
+                    // deletion doesn't happen until all the systems have run
+                    velocities.remove(entity);
+                    grid.remove_entity(entity.get_id());
+                    arg.delete(entity);
+                }
+                resource.amount = target.resource.amount;
+            }
+        }
+    }
+}
+
+/// The gather command for the kind of resource the unit is carrying, for its carrying graphic
+/// and drop off sound
+fn carrying_command(unit_info: &dat::Unit, resource_type: ResourceType) -> Option<&dat::UnitCommand> {
+    unit_info.commandable_params.as_ref().and_then(|params| {
+        params.commands.iter().find(|command| {
+            command.type_id == unit::GATHER_COMMAND_TYPE && ResourceType::from_i16(command.resource_in) == resource_type
+        })
+    })
+}
+
+/// Heads the unit for `destination` along a path around whatever is in the way, showing the given
+/// graphic (or its walking graphic) on the way. The path is found the first time the unit heads for
+/// the destination, and kept in `target_path`. Returns false if the unit can't move.
+pub fn walk_towards(path_finder: &PathFinder,
+                    terrain: &Terrain,
+                    occupied_tiles: &OccupiedTiles,
+                    unit_info: &dat::Unit,
+                    transform: &TransformComponent,
+                    velocity: &mut VelocityComponent,
+                    graphic: &mut GraphicComponent,
+                    target_path: &mut Option<TargetPath>,
+                    destination: &Vector3,
+                    graphic_id: Option<GraphicId>)
+                    -> bool {
+    let motion_params = match unit_info.motion_params {
+        Some(ref motion_params) => motion_params,
+        None => return false,
+    };
+    let graphic_id = graphic_id.or(motion_params.walking_graphics[0]);
+    if graphic_id.is_some() && graphic.graphic_id != graphic_id {
+        graphic.set_graphic(graphic_id);
+    }
+
+    if target_path.as_ref().map_or(true, |target_path| target_path.destination != *destination) {
+        let path = path_finder.find_path(terrain,
+                                         occupied_tiles,
+                                         transform.position(),
+                                         destination,
+                                         unit_info.terrain_restriction);
+        *target_path = Some(TargetPath::new(*destination, path));
+    }
+    let path = &mut target_path.as_mut().unwrap().path;
+    let flat_offset = |position: &Vector3| {
+        let mut offset = *position - *transform.position();
+        offset.z = 0.into();
+        offset
+    };
+    while path.first().map_or(false, |node| flat_offset(node).length() <= THRESHOLD) {
+        path.remove(0);
+    }
+
+    let mut direction = flat_offset(path.first().unwrap_or(destination));
+    if direction.length_squared() > 0.into() {
+        direction.normalize();
+    }
+    let speed: Fixed = motion_params.speed.into();
+    velocity.velocity = direction * speed;
+    true
+}
+
+/// Stops the unit and turns it to face `position`
+pub fn stop_facing(transform: &mut TransformComponent, velocity: &mut VelocityComponent, position: &Vector3) {
+    velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+    let direction = *position - *transform.position();
+    if direction.x != 0.into() || direction.y != 0.into() {
+        // Only used for rendering, same as in the velocity system
+        let x: f32 = direction.x.into();
+        let y: f32 = direction.y.into();
+        transform.rotation = y.atan2(x).into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GatherActionSystem;
+    use crate::action::{Action, GatherParams};
+    use crate::dat::{self, ResourceType};
+    use crate::ecs::{ActionQueueComponent, GatherActionComponent, GatherStage, GraphicComponent, ResourceComponent,
+                     TransformComponent, UnitComponent, VelocityComponent};
+    use crate::ecs::resource::{Player, Players};
+    use crate::ecs::world::{run_ticks, test_empires, test_planner, test_world, vector};
+    use crate::types::Fixed;
+
+    /// A villager (83) that chops wood three at a time, a tree (123) and a town center (109)
+    fn empires() -> dat::EmpiresDbRef {
+        let mut chop = dat::UnitCommand::default();
+        chop.type_id = 5;
+        chop.class_id = 15;
+        chop.resource_in = 1;
+        chop.execution_radius = 1.0;
+        let mut params = dat::CommandableParams::default();
+        params.work_rate = 1.0;
+        params.drop_sites = [109, -1];
+        params.commands = vec![chop];
+        let mut villager = dat::Unit::default();
+        villager.id = 83usize.into();
+        villager.resource_capacity = 3;
+        villager.commandable_params = Some(params);
+
+        let mut tree = dat::Unit::default();
+        tree.id = 123usize.into();
+        tree.class_id = 15;
+        tree.interaction_mode = dat::InteractionMode::Resource;
+
+        let mut town_center = dat::Unit::default();
+        town_center.id = 109usize.into();
+        town_center.collision_size_x = 0.5;
+        town_center.interaction_mode = dat::InteractionMode::Building;
+
+        test_empires(vec![villager, tree, town_center])
+    }
+
+    fn at(x: i32, y: i32) -> TransformComponent {
+        TransformComponent::new(vector(x, y), 0.into())
+    }
+
+    fn wood(world: &specs::World) -> Fixed {
+        world.read_resource::<Players>().local_player().resource(ResourceType::Wood)
+    }
+
+    #[test]
+    fn test_gather_and_drop_off() {
+        let empires = empires();
+        let mut world = test_world(empires.clone());
+        let player = Player::new("One".into(), 1usize.into(), 1usize.into(), 1usize.into());
+        world.write_resource::<Players>().add_player(player, true);
+        world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 109usize.into()))
+            .with(at(3, 2))
+            .build();
+        let tree = world.create_now()
+            .with(UnitComponent::new(0usize.into(), 1usize.into(), 123usize.into()))
+            .with(at(2, 3))
+            .with(ResourceComponent::new(ResourceType::Wood, 4.into()))
+            .build();
+        let mut action_queue = ActionQueueComponent::new();
+        action_queue.add(Action::Gather(GatherParams::new(tree.get_id())));
+        action_queue.next_action();
+        let villager = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 83usize.into()))
+            .with(at(2, 2))
+            .with(VelocityComponent::new())
+            .with(GraphicComponent::new())
+            .with(action_queue)
+            .with(GatherActionComponent::new(tree))
+            .build();
+        let mut planner = test_planner(world, Box::new(GatherActionSystem::new()));
+
+        // Already next to the tree and the town center, so it fills up and then drops it all off
+        run_ticks(&mut planner, 3, 1.into());
+        {
+            let world = planner.mut_world();
+            let gathers = world.read::<GatherActionComponent>();
+            let gather = gathers.get(villager).unwrap();
+            assert_eq!(GatherStage::ToDropSite, gather.stage);
+            assert_eq!(Fixed::from(3), gather.carried);
+            assert_eq!(Fixed::from(1), world.read::<ResourceComponent>().get(tree).unwrap().amount);
+            assert_eq!(Fixed::from(0), wood(world));
+        }
+        run_ticks(&mut planner, 1, 1.into());
+        {
+            let world = planner.mut_world();
+            let gathers = world.read::<GatherActionComponent>();
+            assert_eq!(GatherStage::ToResource, gathers.get(villager).unwrap().stage);
+            assert_eq!(Fixed::from(3), wood(world));
+        }
+
+        // The last of the tree is taken, which uses it up, and the villager is done once that's dropped off
+        run_ticks(&mut planner, 1, 1.into());
+        assert!(!planner.mut_world().is_alive(tree));
+        run_ticks(&mut planner, 1, 1.into());
+        let world = planner.mut_world();
+        assert_eq!(Fixed::from(4), wood(world));
+        assert!(world.read::<ActionQueueComponent>().get(villager).unwrap().current_action_done());
+    }
+}
@@ -19,6 +19,12 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+mod attack;
+mod build;
+mod gather;
 mod move_to_position;
 
+pub use self::attack::AttackActionSystem;
+pub use self::build::BuildActionSystem;
+pub use self::gather::GatherActionSystem;
 pub use self::move_to_position::MoveToPositionActionSystem;
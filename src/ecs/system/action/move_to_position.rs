@@ -19,21 +19,20 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::dat::EmpiresDbRef;
 use crate::ecs::component::{UnitComponent, MoveToPositionActionComponent, TransformComponent, GraphicComponent, ActionQueueComponent, VelocityComponent};
+use crate::ecs::resource::Technologies;
 use specs::{self, Join};
 use super::super::System;
 use crate::types::{Fixed, Norm, Vector3, fixed_const};
 
-const THRESHOLD: Fixed = fixed_const!(0.1);
+/// How close units have to get to a node of their path to have reached it
+pub const THRESHOLD: Fixed = fixed_const!(0.1);
 
-pub struct MoveToPositionActionSystem {
-    empires: EmpiresDbRef,
-}
+pub struct MoveToPositionActionSystem;
 
 impl MoveToPositionActionSystem {
-    pub fn new(empires: EmpiresDbRef) -> MoveToPositionActionSystem {
-        MoveToPositionActionSystem { empires: empires }
+    pub fn new() -> MoveToPositionActionSystem {
+        MoveToPositionActionSystem
     }
 }
 
@@ -46,6 +45,7 @@ impl System for MoveToPositionActionSystem {
             mut components(velocities: VelocityComponent),
             mut components(graphics: GraphicComponent),
             mut components(action_queues: ActionQueueComponent),
+            resource(technologies: Technologies),
         ]);
 
         let items = (&mut velocities, &transforms, &units, &mut graphics, &mut mtps, &mut action_queues);
@@ -61,7 +61,7 @@ impl System for MoveToPositionActionSystem {
                     mtps.path.remove(0);
                     mtps.path.is_empty()
                 } else {
-                    match unit.db(&self.empires).motion_params {
+                    match unit.info(&technologies).motion_params {
                         Some(ref params) => {
                             if params.walking_graphics[0].is_some() &&
                                graphic.graphic_id != params.walking_graphics[0] {
@@ -77,7 +77,7 @@ impl System for MoveToPositionActionSystem {
             };
 
             if done {
-                let unit_info = unit.db(&self.empires);
+                let unit_info = unit.info(&technologies);
                 graphic.set_graphic(unit_info.standing_graphic);
                 velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
                 action_queue.mark_current_done();
@@ -21,12 +21,17 @@
 
 use crate::dat;
 use crate::ecs::{GraphicComponent, TransformComponent};
+use crate::ecs::resource::{SoundEvent, SoundEvents};
+use crate::identifier::SoundGroupId;
 use crate::resource::{DrsKey, ShapeMetadataKey, ShapeMetadataStoreRef};
 use specs::{self, Join};
 use std::ops::Rem;
 use super::System;
 use crate::types::Fixed;
 
+/// Graphics with attack sounds have this many of them per angle
+const ATTACK_SOUNDS_PER_ANGLE: usize = 3;
+
 pub struct AnimationSystem {
     empires: dat::EmpiresDbRef,
     shape_metadata: ShapeMetadataStoreRef,
@@ -40,13 +45,17 @@ impl AnimationSystem {
         }
     }
 
+    /// Advances the graphic's animation, and returns the sound groups that should be played
+    /// as a result of the frames that were passed through
     fn update_graphic(&self,
                       graphic: &mut GraphicComponent,
                       rotation: Fixed,
                       graphic_info: &dat::Graphic,
-                      time_step: Fixed) {
+                      time_step: Fixed)
+                      -> Vec<SoundGroupId> {
         graphic.frame_time += time_step;
 
+        let mut sounds = Vec::new();
         if let Some(slp_id) = graphic_info.slp_id {
             let shape_key = ShapeMetadataKey::new(DrsKey::Graphics, slp_id);
             if let Some(shape_metadata) = self.shape_metadata.get(&shape_key) {
@@ -54,18 +63,23 @@ impl AnimationSystem {
                                                                                shape_metadata.shape_count,
                                                                                graphic_info.frame_count,
                                                                                graphic_info.angle_count);
-                let current_frame = start_frame +
-                                    frame_at_time(graphic.frame_time,
-                                                  graphic_info.frame_rate.into(),
-                                                  graphic_info.frame_count,
-                                                  graphic_info.replay_delay.into());
+                let previous_frame = graphic.frame % graphic_info.frame_count;
+                let animation_frame = frame_at_time(graphic.frame_time,
+                                                    graphic_info.frame_rate.into(),
+                                                    graphic_info.frame_count,
+                                                    graphic_info.replay_delay.into());
+                let current_frame = start_frame + animation_frame;
                 if current_frame < graphic.frame {
                     graphic.frame_time = 0.into();
                 }
                 graphic.frame = current_frame;
                 graphic.flip_horizontal = flip_horizontal;
+
+                let angle = angle_index(rotation, graphic_info.angle_count);
+                sounds = animation_sounds(graphic_info, angle, previous_frame, animation_frame);
             }
         }
+        sounds
     }
 }
 
@@ -74,13 +88,18 @@ impl System for AnimationSystem {
         fetch_components!(arg, _entities, [
             components(transforms: TransformComponent),
             mut components(graphics: GraphicComponent),
+            mut resource(sound_events: SoundEvents),
         ]);
 
         for (transform, graphic) in (&transforms, &mut graphics).iter() {
             if let Some(graphic_id) = graphic.graphic_id {
                 let graphic_info = self.empires.graphic(graphic_id);
-                if graphic_info.frame_count > 1 {
-                    self.update_graphic(graphic, transform.rotation, graphic_info, time_step);
+                // Graphics with a single frame can still have one for each angle, like projectiles
+                if graphic_info.frame_count > 1 || graphic_info.angle_count > 1 {
+                    let sounds = self.update_graphic(graphic, transform.rotation, graphic_info, time_step);
+                    for sound_group_id in sounds {
+                        sound_events.push(SoundEvent::new(sound_group_id, *transform.position()));
+                    }
                 }
             }
         }
@@ -93,17 +112,22 @@ fn wrap_angle(angle: Fixed) -> Fixed {
     if modded < 0.into() { Fixed::two_pi() - modded.abs() } else { modded }
 }
 
+/// Returns the index of the angle closest to the given rotation
+fn angle_index(rotation: Fixed, angle_count: u16) -> u16 {
+    let rotation = wrap_angle(rotation);
+    u16::from((rotation * angle_count.into() / Fixed::two_pi()).round()) % angle_count
+}
+
 /// Returns the start frame for the given rotation, and whether mirroring should occur
 fn start_frame_and_mirroring(rotation: Fixed,
                              shape_count: u32,
                              frame_count: u16,
                              angle_count: u16)
                              -> (u16, bool) {
-    let rotation = wrap_angle(rotation);
     let angles_in_slp = shape_count as u16 / frame_count;
     let mirror_count = angle_count - angles_in_slp;
 
-    let mut angle_index = u16::from((rotation * angle_count.into() / Fixed::two_pi()).round()) % angle_count;
+    let mut angle_index = angle_index(rotation, angle_count);
     let mut mirror = false;
     if angle_index < mirror_count {
         angle_index = angle_count - angle_index - (angle_count / 4);
@@ -131,9 +155,54 @@ fn frame_at_time(time: Fixed, frame_rate: Fixed, frame_count: u16, replay_delay:
     }
 }
 
+/// Returns true if `frame` was reached when the animation moved from `previous` to `current`,
+/// taking into account that the animation may have looped back around
+fn passed_frame(previous: u16, current: u16, frame: u16) -> bool {
+    if current > previous {
+        frame > previous && frame <= current
+    } else if current < previous {
+        frame > previous || frame <= current
+    } else {
+        false
+    }
+}
+
+/// Returns the sound groups to play when the animation moves from frame `previous` to `current`.
+/// The graphic's own sound plays each time the animation loops, and the attack sounds for
+/// the current angle play when their delay frame is reached.
+fn animation_sounds(graphic_info: &dat::Graphic,
+                    angle: u16,
+                    previous: u16,
+                    current: u16)
+                    -> Vec<SoundGroupId> {
+    let mut sounds = Vec::new();
+    if current < previous {
+        if let Some(sound_group_id) = graphic_info.sound_group_id {
+            sounds.push(sound_group_id);
+        }
+    }
+
+    let attack_sounds = &graphic_info.attack_sounds;
+    let first = angle as usize * ATTACK_SOUNDS_PER_ANGLE;
+    let angle_sounds = attack_sounds.get(first..first + ATTACK_SOUNDS_PER_ANGLE)
+        .or_else(|| attack_sounds.get(0..ATTACK_SOUNDS_PER_ANGLE))
+        .unwrap_or(&[]);
+    for attack_sound in angle_sounds {
+        if let Some(sound_group_id) = attack_sound.sound_group_id {
+            if attack_sound.sound_delay >= 0 &&
+               passed_frame(previous, current, attack_sound.sound_delay as u16) {
+                sounds.push(sound_group_id);
+            }
+        }
+    }
+    sounds
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{frame_at_time, start_frame_and_mirroring, wrap_angle};
+    use super::{animation_sounds, frame_at_time, passed_frame, start_frame_and_mirroring, wrap_angle};
+    use crate::dat;
+    use crate::identifier::SoundGroupId;
     use crate::types::Fixed;
 
     #[test]
@@ -180,4 +249,44 @@ mod tests {
         assert_eq!(3u16, frame_at_time(2.0.into(), 1.into(), 4, 1.into()));
         assert_eq!(0u16, frame_at_time(3.0.into(), 1.into(), 4, 1.into()));
     }
+
+    #[test]
+    fn test_passed_frame() {
+        assert!(passed_frame(0, 2, 1));
+        assert!(passed_frame(0, 2, 2));
+        assert!(!passed_frame(0, 2, 0));
+        assert!(!passed_frame(0, 2, 3));
+        assert!(!passed_frame(2, 2, 2));
+
+        // Looped from frame 3 back around to frame 1
+        assert!(passed_frame(3, 1, 0));
+        assert!(passed_frame(3, 1, 1));
+        assert!(!passed_frame(3, 1, 2));
+        assert!(!passed_frame(3, 1, 3));
+    }
+
+    #[test]
+    fn test_animation_sounds() {
+        let sound = |id: usize| -> SoundGroupId { id.into() };
+        let attack_sound = |delay: i16, id: Option<usize>| {
+            dat::GraphicAttackSound {
+                sound_delay: delay,
+                sound_group_id: id.map(|id| id.into()),
+            }
+        };
+
+        let mut graphic: dat::Graphic = Default::default();
+        graphic.sound_group_id = Some(sound(1));
+        graphic.attack_sounds = vec![attack_sound(2, Some(10)), attack_sound(3, None), attack_sound(0, Some(11)),
+                                     attack_sound(1, Some(20)), attack_sound(2, None), attack_sound(0, None)];
+
+        assert_eq!(Vec::<SoundGroupId>::new(), animation_sounds(&graphic, 0, 0, 1));
+        assert_eq!(vec![sound(10)], animation_sounds(&graphic, 0, 1, 2));
+        assert_eq!(vec![sound(1), sound(11)],
+                   animation_sounds(&graphic, 0, 3, 0));
+        assert_eq!(vec![sound(20)], animation_sounds(&graphic, 1, 0, 1));
+
+        // Angles past the end of the attack sounds fall back to the first angle's sounds
+        assert_eq!(vec![sound(10)], animation_sounds(&graphic, 5, 1, 2));
+    }
 }
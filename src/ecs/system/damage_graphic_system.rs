@@ -0,0 +1,112 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+use crate::dat::{self, EmpiresDbRef};
+use crate::ecs::{DeathComponent, GraphicComponent, HealthComponent, UnitComponent};
+use specs::{self, Join};
+use super::System;
+use crate::types::Fixed;
+
+/// Damage graphics with this apply mode are shown instead of the unit's own graphic,
+/// rather than on top of it
+const REPLACE_APPLY_MODE: u8 = 2;
+
+/// Shows how badly damaged units (mostly buildings) are, using their damage graphics
+pub struct DamageGraphicSystem {
+    empires: EmpiresDbRef,
+}
+
+impl DamageGraphicSystem {
+    pub fn new(empires: EmpiresDbRef) -> DamageGraphicSystem {
+        DamageGraphicSystem { empires: empires }
+    }
+}
+
+impl System for DamageGraphicSystem {
+    fn update(&mut self, arg: specs::RunArg, _time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(units: UnitComponent),
+            components(healths: HealthComponent),
+            components(deaths: DeathComponent),
+            mut components(graphics: GraphicComponent),
+        ]);
+
+        for (entity, unit, health, graphic) in (&entities, &units, &healths, &mut graphics).iter() {
+            let unit_info = unit.db(&self.empires);
+            if unit_info.damage_graphics.is_empty() || deaths.get(entity).is_some() {
+                continue;
+            }
+
+            let (replacement, overlay) = match damage_graphic(unit_info, health.percent()) {
+                Some(damage_graphic) if damage_graphic.apply_mode == REPLACE_APPLY_MODE => {
+                    (Some(damage_graphic.graphic_id), None)
+                }
+                Some(damage_graphic) => (None, Some(damage_graphic.graphic_id)),
+                None => (None, None),
+            };
+
+            // Other graphics, like a tower's attack graphic, are left alone until the unit goes back to standing
+            let standing = graphic.graphic_id == unit_info.standing_graphic ||
+                           unit_info.damage_graphics.iter().any(|d| graphic.graphic_id == Some(d.graphic_id));
+            if standing {
+                let graphic_id = replacement.or(unit_info.standing_graphic);
+                if graphic.graphic_id != graphic_id {
+                    graphic.set_graphic(graphic_id);
+                }
+            }
+            graphic.damage_graphic_id = overlay;
+        }
+    }
+}
+
+/// The damage graphic for the unit at the given percentage of its hit points: the one with the
+/// lowest threshold that has been reached, if any
+fn damage_graphic(unit_info: &dat::Unit, hit_point_percent: i32) -> Option<&dat::DamageGraphic> {
+    unit_info.damage_graphics
+        .iter()
+        .filter(|d| hit_point_percent <= d.damage_percent as i32)
+        .min_by_key(|d| d.damage_percent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::damage_graphic;
+    use crate::dat;
+
+    #[test]
+    fn test_damage_graphic() {
+        let mut unit_info: dat::Unit = Default::default();
+        for &(graphic_id, damage_percent) in &[(10usize, 75u8), (11, 25), (12, 50)] {
+            let mut graphic: dat::DamageGraphic = Default::default();
+            graphic.graphic_id = graphic_id.into();
+            graphic.damage_percent = damage_percent;
+            unit_info.damage_graphics.push(graphic);
+        }
+
+        let graphic_at = |percent| damage_graphic(&unit_info, percent).map(|d| *d.graphic_id);
+        assert_eq!(None, graphic_at(100));
+        assert_eq!(None, graphic_at(76));
+        assert_eq!(Some(10), graphic_at(75));
+        assert_eq!(Some(12), graphic_at(40));
+        assert_eq!(Some(11), graphic_at(0));
+    }
+}
@@ -0,0 +1,250 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+use crate::dat::{self, EmpiresDbRef};
+use crate::ecs::{ActionQueueComponent, AttackActionComponent, BuildActionComponent, ConstructionComponent,
+                 DeathComponent, DeathStage, GatherActionComponent, GraphicComponent, HealthComponent,
+                 MoveToPositionActionComponent, ResearchComponent, SelectedUnitComponent, TrainingQueueComponent,
+                 TransformComponent, UnitComponent, VelocityComponent};
+use crate::ecs::resource::{KilledUnits, Players, SoundEvent, SoundEvents, Technologies};
+use crate::identifier::GraphicId;
+use crate::partition::GridPartition;
+use specs::{self, Join};
+use super::System;
+use crate::types::{Fixed, Vector3};
+use crate::util::unit;
+
+/// Corpses keep how long they take to decay in a resource storage slot of this type,
+/// which gets used up at the unit's `resource_decay` rate
+const CORPSE_DECAY_RESOURCE: dat::ResourceType = dat::ResourceType::Unknown(12);
+
+/// Takes units that have run out of hit points through their dying animation, turns them into
+/// their dead unit, and gets rid of that once it has decayed
+pub struct DeathSystem {
+    empires: EmpiresDbRef,
+}
+
+impl DeathSystem {
+    pub fn new(empires: EmpiresDbRef) -> DeathSystem {
+        DeathSystem { empires: empires }
+    }
+
+    /// How long it takes to play the graphic's animation through once
+    fn animation_time(&self, graphic_id: Option<GraphicId>) -> Fixed {
+        match graphic_id {
+            Some(graphic_id) => {
+                let graphic_info = self.empires.graphic(graphic_id);
+                // Same timing as the animation system uses
+                let seconds_per_frame = Fixed::from(graphic_info.frame_rate) / 2.into();
+                seconds_per_frame * graphic_info.frame_count.into()
+            }
+            None => 0.into(),
+        }
+    }
+}
+
+impl System for DeathSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(transforms: TransformComponent),
+            mut components(units: UnitComponent),
+            mut components(healths: HealthComponent),
+            mut components(deaths: DeathComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(action_queues: ActionQueueComponent),
+            mut components(mtps: MoveToPositionActionComponent),
+            mut components(attacks: AttackActionComponent),
+            mut components(gathers: GatherActionComponent),
+            mut components(builds: BuildActionComponent),
+            mut components(constructions: ConstructionComponent),
+            mut components(selected_units: SelectedUnitComponent),
+            mut components(training_queues: TrainingQueueComponent),
+            mut components(researches: ResearchComponent),
+            resource(technologies: Technologies),
+            mut resource(grid: GridPartition),
+            mut resource(killed_units: KilledUnits),
+            mut resource(players: Players),
+            mut resource(sound_events: SoundEvents),
+        ]);
+
+        for (entity, unit, health, transform) in (&entities, &units, &healths, &transforms).iter() {
+            if !health.is_dead() || deaths.get(entity).is_some() {
+                continue;
+            }
+
+            // Whatever the unit was doing stops, and it can't be given anything else to do
+            action_queues.remove(entity);
+            mtps.remove(entity);
+            attacks.remove(entity);
+            gathers.remove(entity);
+            builds.remove(entity);
+            constructions.remove(entity);
+            selected_units.remove(entity);
+            if let Some(velocity) = velocities.get_mut(entity) {
+                velocity.velocity = Vector3::new(0.into(), 0.into(), 0.into());
+            }
+
+            // Units still waiting to be trained in a destroyed building are paid back, as is its research
+            if let (Some(training_queue), Some(player)) = (training_queues.remove(entity),
+                                                           players.player_mut(unit.player_id)) {
+                for unit_id in training_queue.units {
+                    let unit_info = technologies.unit(unit.player_id, unit.civilization_id, unit_id);
+                    player.refund(unit::train_costs(unit_info));
+                }
+            }
+            let research_id = researches.remove(entity).and_then(|research| research.research_id);
+            if let (Some(research_id), Some(player)) = (research_id, players.player_mut(unit.player_id)) {
+                player.refund(technologies.research_costs(unit.player_id, research_id));
+            }
+
+            let unit_info = unit.info(&technologies);
+            if let Some(graphic) = graphics.get_mut(entity) {
+                graphic.set_graphic(unit_info.dying_graphic);
+                graphic.damage_graphic_id = None;
+            }
+            if unit_info.dying_sound >= 0 {
+                sound_events.push(SoundEvent::new((unit_info.dying_sound as usize).into(), *transform.position()));
+            }
+
+            killed_units.record(unit.player_id, health.last_attacker_id);
+            // The body stays in the grid partition until it's deleted, carcasses or not. The grid is
+            // what decides which entities are on screen, so taking the body out would stop it from
+            // being drawn. Selection and targeting check for themselves whether a unit is dead.
+            deaths.insert(entity,
+                          DeathComponent::new(DeathStage::Dying, self.animation_time(unit_info.dying_graphic)));
+        }
+
+        for (entity, unit, death) in (&entities, &mut units, &mut deaths).iter() {
+            if death.stage == DeathStage::Dead {
+                continue;
+            }
+            death.time_left = (death.time_left - time_step).max(0.into());
+            if death.time_left > 0.into() {
+                continue;
+            }
+
+            match unit.info(&technologies).dead_unit_id {
+                Some(dead_unit_id) if dead_unit_id != unit.unit_id => {
+                    unit.unit_id = dead_unit_id;
+                    let dead_unit_info = unit.info(&technologies);
+                    if let Some(graphic) = graphics.get_mut(entity) {
+                        graphic.set_graphic(dead_unit_info.standing_graphic);
+                    }
+                    healths.remove(entity);
+                    *death = match decay_time(dead_unit_info) {
+                        Some(decay_time) => DeathComponent::new(DeathStage::Decaying, decay_time),
+                        None => DeathComponent::new(DeathStage::Dead, 0.into()),
+                    };
+                }
+                _ => {
+                    // Taken out of the grid and velocity system right away, since the deletion
+                    // doesn't happen until all the systems have run
+                    velocities.remove(entity);
+                    grid.remove_entity(entity.get_id());
+                    arg.delete(entity);
+                }
+            }
+        }
+    }
+}
+
+/// How long a dead unit lasts before it's gone, or `None` if it never decays
+fn decay_time(unit_info: &dat::Unit) -> Option<Fixed> {
+    let storage = unit_info.resource_storage
+        .iter()
+        .find(|storage| storage.resource_type == CORPSE_DECAY_RESOURCE);
+    match storage {
+        Some(storage) if unit_info.resource_decay > 0.0 && storage.amount > 0.0 => {
+            Some(Fixed::from(storage.amount / unit_info.resource_decay))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CORPSE_DECAY_RESOURCE, DeathSystem};
+    use crate::dat::{self, ResourceCost};
+    use crate::ecs::{ActionQueueComponent, DeathComponent, DeathStage, GraphicComponent, HealthComponent,
+                     TransformComponent, UnitComponent};
+    use crate::ecs::resource::KilledUnits;
+    use crate::ecs::world::{run_ticks, test_empires, test_planner, test_world, vector};
+    use crate::partition::GridPartition;
+    use crate::types::Fixed;
+    use nalgebra::Vector2;
+
+    /// A villager (83) that leaves a body (84) behind, which takes 5 seconds to decay
+    fn empires() -> dat::EmpiresDbRef {
+        let mut villager: dat::Unit = Default::default();
+        villager.id = 83usize.into();
+        villager.hit_points = 25;
+        villager.dead_unit_id = Some(84usize.into());
+        let mut body: dat::Unit = Default::default();
+        body.id = 84usize.into();
+        body.resource_decay = 2.0;
+        body.resource_storage.push(ResourceCost::new(CORPSE_DECAY_RESOURCE, 10.0));
+        test_empires(vec![villager, body])
+    }
+
+    #[test]
+    fn test_death_and_decay() {
+        let empires = empires();
+        let mut world = test_world(empires.clone());
+        let mut health = HealthComponent::new(25);
+        health.damage(25, 2usize.into());
+        let villager = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 83usize.into()))
+            .with(TransformComponent::new(vector(1, 1), 0.into()))
+            .with(ActionQueueComponent::new())
+            .with(GraphicComponent::new())
+            .with(health)
+            .build();
+        world.write_resource::<GridPartition>().update_entity(villager.get_id(), &Vector2::new(1, 1));
+        let mut planner = test_planner(world, Box::new(DeathSystem::new(empires)));
+
+        // No dying animation, so the villager is a body straight away and stops taking orders
+        run_ticks(&mut planner, 1, 1.into());
+        {
+            let world = planner.mut_world();
+            assert_eq!(84, *world.read::<UnitComponent>().get(villager).unwrap().unit_id);
+            assert!(world.read::<HealthComponent>().get(villager).is_none());
+            assert!(world.read::<ActionQueueComponent>().get(villager).is_none());
+            let deaths = world.read::<DeathComponent>();
+            let death = deaths.get(villager).unwrap();
+            assert_eq!(DeathStage::Decaying, death.stage);
+            assert_eq!(Fixed::from(5), death.time_left);
+
+            let killed_units = world.read_resource::<KilledUnits>();
+            assert_eq!(1, killed_units.kills(2usize.into()));
+            assert_eq!(1, killed_units.losses(1usize.into()));
+        }
+
+        // The body lasts until it has decayed, and is then gone from the world and the grid
+        run_ticks(&mut planner, 4, 1.into());
+        assert!(planner.mut_world().is_alive(villager));
+        run_ticks(&mut planner, 1, 1.into());
+        let world = planner.mut_world();
+        assert!(!world.is_alive(villager));
+        assert!(!world.read_resource::<GridPartition>().contains(villager.get_id()));
+    }
+}
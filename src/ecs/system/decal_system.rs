@@ -19,7 +19,7 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-use crate::ecs::DecalComponent;
+use crate::ecs::{DecalComponent, TransformComponent};
 use crate::resource::{ShapeMetadataKey, ShapeMetadataStoreRef};
 use specs::{self, Join};
 use super::System;
@@ -40,9 +40,13 @@ impl DecalSystem {
 
 impl System for DecalSystem {
     fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
-        fetch_components!(arg, entities, [ mut components(decals: DecalComponent), ]);
+        fetch_components!(arg, entities, [
+            mut components(decals: DecalComponent),
+            mut components(transforms: TransformComponent),
+        ]);
 
-        for (entity, decal) in (&entities, &mut decals).iter() {
+        let mut finished = Vec::new();
+        for (entity, decal, _transform) in (&entities, &mut decals, &transforms).iter() {
             let shape_key = ShapeMetadataKey::new(decal.drs_key, decal.slp_file_id);
             if let Some(shape_metadata) = self.shape_metadata.get(&shape_key) {
                 decal.frame_time += time_step;
@@ -50,10 +54,15 @@ impl System for DecalSystem {
                     decal.frame_time -= SECONDS_PER_FRAME;
                     decal.frame += 1;
                     if decal.frame >= shape_metadata.shape_count as u16 {
-                        arg.delete(entity);
+                        finished.push(entity);
                     }
                 }
             }
         }
+
+        // Decals are hidden by taking away their transform, and shown again by whatever gives them a new one
+        for entity in finished {
+            transforms.remove(entity);
+        }
     }
 }
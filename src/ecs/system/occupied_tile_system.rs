@@ -20,54 +20,49 @@
 // SOFTWARE.
 
 use crate::dat;
-use crate::ecs::{TransformComponent, UnitComponent};
-use crate::ecs::resource::OccupiedTiles;
+use crate::ecs::{DeathComponent, HealthComponent, TransformComponent, UnitComponent};
+use crate::ecs::resource::{OccupiedTiles, Technologies};
 use specs::{self, Join};
 use super::System;
-use crate::types::{Fixed, ToPrimitive};
+use crate::types::Fixed;
 use crate::util::unit;
 
-pub struct OccupiedTileSystem {
-    empires: dat::EmpiresDbRef,
-}
+pub struct OccupiedTileSystem;
 
 impl OccupiedTileSystem {
-    pub fn new(empires: dat::EmpiresDbRef) -> OccupiedTileSystem {
-        OccupiedTileSystem { empires: empires }
+    pub fn new() -> OccupiedTileSystem {
+        OccupiedTileSystem
     }
 }
 
 impl System for OccupiedTileSystem {
     fn update(&mut self, arg: specs::RunArg, _time_step: Fixed) {
-        fetch_components!(arg, _entities, [
+        fetch_components!(arg, entities, [
+            components(deaths: DeathComponent),
+            components(healths: HealthComponent),
             components(transforms: TransformComponent),
             components(units: UnitComponent),
+            resource(technologies: Technologies),
             mut resource(occupied_tiles: OccupiedTiles),
         ]);
 
         occupied_tiles.tiles.clear();
-        for (transform, unit) in (&transforms, &units).iter() {
-            let unit_info = self.empires.unit(unit.civilization_id, unit.unit_id);
-            let unit_blocks_tiles = match unit_info.interaction_mode {
-                dat::InteractionMode::Building => true,
-                dat::InteractionMode::Resource => {
-                    match unit_info.motion_params {
-                        Some(ref params) => params.speed < 0.001f32,
-                        None => true,
-                    }
-                }
-                _ => false,
-            };
+        for (entity, transform, unit) in (&entities, &transforms, &units).iter() {
+            // Dying and dead units are out of the way. Units killed this tick only get a death
+            // component once the death system gets to them, so hit points are checked as well.
+            let out_of_hit_points = healths.get(entity).map_or(false, |health| health.is_dead());
+            if out_of_hit_points || deaths.get(entity).is_some() {
+                continue;
+            }
 
-            if unit_blocks_tiles {
-                let collision_box = unit::collision_box(unit_info, transform);
+            let unit_info = unit.info(&technologies);
+            let unit_blocks_tiles = unit_info.interaction_mode == dat::InteractionMode::Building ||
+                                    unit::is_immobile_resource(unit_info);
 
-                let (start_row, end_row) = (collision_box.min.y.to_i32().unwrap(),
-                                            collision_box.max.y.to_i32().unwrap());
-                let (start_col, end_col) = (collision_box.min.x.to_i32().unwrap(),
-                                            collision_box.max.x.to_i32().unwrap());
-                for row in start_row..(end_row + 1) {
-                    for col in start_col..(end_col + 1) {
+            if unit_blocks_tiles {
+                let (rows, cols) = unit::footprint(unit_info, transform.position());
+                for row in rows {
+                    for col in cols.clone() {
                         occupied_tiles.tiles.insert((row, col));
                     }
                 }
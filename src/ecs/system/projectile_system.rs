@@ -0,0 +1,100 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::ecs::{HealthComponent, ProjectileComponent, TransformComponent, UnitComponent, VelocityComponent};
+use crate::ecs::resource::Technologies;
+use crate::identifier::PlayerId;
+use crate::partition::GridPartition;
+use specs::{self, Index, Join};
+use super::System;
+use crate::types::Fixed;
+use crate::util::unit;
+
+use std::collections::BTreeMap;
+
+/// Flies projectiles along their paths, and deals their damage when they land
+pub struct ProjectileSystem;
+
+impl ProjectileSystem {
+    pub fn new() -> ProjectileSystem {
+        ProjectileSystem
+    }
+}
+
+impl System for ProjectileSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(transforms: TransformComponent),
+            components(units: UnitComponent),
+            mut components(projectiles: ProjectileComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(healths: HealthComponent),
+            resource(technologies: Technologies),
+            mut resource(grid: GridPartition),
+        ]);
+
+        let mut landed = Vec::new();
+        for (entity, projectile, transform) in (&entities, &mut projectiles, &transforms).iter() {
+            projectile.elapsed_time += time_step;
+            if projectile.landed() {
+                // Taken out of the grid and velocity system right away, since the deletion
+                // doesn't happen until all the systems have run
+                velocities.remove(entity);
+                grid.remove_entity(entity.get_id());
+                arg.delete(entity);
+                landed.push(projectile.clone());
+            } else if let Some(velocity) = velocities.get_mut(entity) {
+                let next_position = projectile.position_at(projectile.elapsed_time);
+                velocity.velocity = (next_position - *transform.position()) / time_step;
+            }
+        }
+
+        let mut damages: BTreeMap<Index, (i32, PlayerId)> = BTreeMap::new();
+        for projectile in &landed {
+            let attacker_info = technologies.unit(projectile.player_id,
+                                                  projectile.civilization_id,
+                                                  projectile.attacker_unit_id);
+            let blast_width: Fixed = match attacker_info.battle_params {
+                Some(ref params) => params.blast_width.into(),
+                None => 0.into(),
+            };
+
+            for (entity, unit, transform, health) in (&entities, &units, &transforms, &healths).iter() {
+                if health.is_dead() {
+                    continue;
+                }
+                let is_target = entity == projectile.target;
+                let caught_in_blast = !is_target && blast_width > 0.into() && unit.player_id != projectile.player_id &&
+                                      unit::in_blast(&projectile.end, transform.position(), blast_width);
+                if (is_target && projectile.hits) || caught_in_blast {
+                    damages.entry(entity.get_id()).or_insert((0, projectile.player_id)).0 +=
+                        unit::attack_damage(attacker_info, unit.info(&technologies));
+                }
+            }
+        }
+
+        for (entity, health) in (&entities, &mut healths).iter() {
+            if let Some(&(damage, attacker_id)) = damages.get(&entity.get_id()) {
+                health.damage(damage, attacker_id);
+            }
+        }
+    }
+}
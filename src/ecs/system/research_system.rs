@@ -0,0 +1,293 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+use crate::action::Action;
+use crate::dat::EmpiresDbRef;
+use crate::ecs::{ConstructionComponent, DeathComponent, GraphicComponent, HealthComponent, ResearchComponent,
+                 UnitComponent};
+use crate::ecs::resource::{Players, Technologies};
+use crate::identifier::{GraphicId, PlayerId, ResearchId};
+use specs::{self, Join};
+use super::System;
+use crate::types::Fixed;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Applies the orders given to buildings' research, and once research is done (including advancing
+/// to a new age, or the research that comes with a finished building), applies its effects to the
+/// player and to the units they already have
+pub struct ResearchSystem {
+    empires: EmpiresDbRef,
+}
+
+impl ResearchSystem {
+    pub fn new(empires: EmpiresDbRef) -> ResearchSystem {
+        ResearchSystem { empires: empires }
+    }
+}
+
+impl System for ResearchSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(deaths: DeathComponent),
+            components(constructions: ConstructionComponent),
+            mut components(units: UnitComponent),
+            mut components(researches: ResearchComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(healths: HealthComponent),
+            mut resource(players: Players),
+            mut resource(technologies: Technologies),
+        ]);
+
+        // A player can only have each research underway at one building at a time
+        let mut underway: BTreeSet<(PlayerId, ResearchId)> = (&units, &researches)
+            .iter()
+            .filter_map(|(unit, research)| research.research_id.map(|research_id| (unit.player_id, research_id)))
+            .collect();
+
+        let mut completed = Vec::new();
+        for (entity, unit, research) in (&entities, &units, &mut researches).iter() {
+            // The death system pays back the research of buildings that are destroyed
+            if deaths.get(entity).is_some() {
+                continue;
+            }
+
+            let orders: Vec<Action> = research.orders.drain(..).collect();
+            for order in orders {
+                match order {
+                    Action::Research(params) => {
+                        let research_id = params.research_id;
+                        let researchable = research.research_id.is_none() && constructions.get(entity).is_none() &&
+                                           !underway.contains(&(unit.player_id, research_id)) &&
+                                           technologies.can_research(unit.player_id, research_id, unit.unit_id);
+                        if !researchable {
+                            continue;
+                        }
+                        let costs = technologies.research_costs(unit.player_id, research_id);
+                        if players.player_mut(unit.player_id).is_some_and(|player| player.debit(costs).is_ok()) {
+                            research.research_id = Some(research_id);
+                            research.elapsed_time = 0.into();
+                            underway.insert((unit.player_id, research_id));
+                        }
+                    }
+                    Action::CancelResearch => {
+                        if let Some(research_id) = research.research_id.take() {
+                            if let Some(player) = players.player_mut(unit.player_id) {
+                                player.refund(technologies.research_costs(unit.player_id, research_id));
+                            }
+                            underway.remove(&(unit.player_id, research_id));
+                            research.elapsed_time = 0.into();
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let research_id = match research.research_id {
+                Some(research_id) => research_id,
+                None => continue,
+            };
+            let research_time: Fixed = self.empires.research(research_id).time_seconds.into();
+            research.elapsed_time = (research.elapsed_time + time_step).min(research_time);
+            if research.elapsed_time >= research_time {
+                research.research_id = None;
+                research.elapsed_time = 0.into();
+                completed.push((unit.player_id, research_id));
+            }
+        }
+
+        // Finished buildings give the player their research, as soon as what it requires has been done
+        for (entity, unit) in (&entities, &units).iter() {
+            if deaths.get(entity).is_some() || constructions.get(entity).is_some() {
+                continue;
+            }
+            let building_params = unit.info(&technologies).building_params.as_ref();
+            if let Some(research_id) = building_params.and_then(|params| params.research_id) {
+                if technologies.is_available(unit.player_id, research_id) &&
+                   !completed.contains(&(unit.player_id, research_id)) {
+                    completed.push((unit.player_id, research_id));
+                }
+            }
+        }
+
+        for (player_id, research_id) in completed {
+            // Upgraded units only change graphic while standing, which has to be told apart using the
+            // graphic their unit had before the research changed it
+            let standing_graphics: BTreeMap<specs::Entity, Option<GraphicId>> = (&entities, &units)
+                .iter()
+                .filter(|&(_, unit)| unit.player_id == player_id)
+                .map(|(entity, unit)| (entity, unit.info(&technologies).standing_graphic))
+                .collect();
+            let upgrades = match players.player_mut(player_id) {
+                Some(player) => technologies.complete(player, research_id),
+                None => continue,
+            };
+
+            for (entity, unit) in (&entities, &mut units).iter() {
+                if unit.player_id != player_id || deaths.get(entity).is_some() {
+                    continue;
+                }
+
+                if let Some(&(_, upgraded_unit_id)) = upgrades.iter().find(|&&(unit_id, _)| unit_id == unit.unit_id) {
+                    unit.unit_id = upgraded_unit_id;
+                    if let Some(graphic) = graphics.get_mut(entity) {
+                        if graphic.graphic_id == standing_graphics[&entity] {
+                            graphic.set_graphic(unit.info(&technologies).standing_graphic);
+                        }
+                    }
+                }
+
+                // Units gain (or lose) as many hit points as research adds to (or takes from) their maximum,
+                // except for those killed this tick, which stay dead
+                if let Some(health) = healths.get_mut(entity).filter(|health| !health.is_dead()) {
+                    let max_hit_points = unit.info(&technologies).hit_points as i32;
+                    if max_hit_points != health.max_hit_points {
+                        health.hit_points = (health.hit_points + max_hit_points - health.max_hit_points).max(1);
+                        health.max_hit_points = max_hit_points;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ResearchSystem;
+    use crate::dat::{self, EmpiresDb, ResearchEffectGroup, ResearchEffectRecord};
+    use crate::ecs::{GraphicComponent, HealthComponent, ResearchComponent, UnitComponent};
+    use crate::ecs::resource::{Player, PlayerTechnologies, Players, Technologies};
+    use crate::ecs::world::{run_ticks, test_planner, test_world};
+    use std::sync::Arc;
+
+    /// Sets up player 1 as Egyptian, with a building (109) that has the given research underway
+    fn start_research(world: &mut specs::World, research_id: usize) {
+        let player = Player::new("Player".into(), 1usize.into(), 1usize.into(), 1usize.into());
+        world.write_resource::<Players>().add_player(player, true);
+        world.write_resource::<Technologies>()
+            .add_player(1usize.into(), PlayerTechnologies::new(1usize.into(), false));
+
+        let mut research = ResearchComponent::new();
+        research.research_id = Some(research_id.into());
+        world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 109usize.into()))
+            .with(research)
+            .build();
+    }
+
+    fn villager(world: &mut specs::World, hit_points: i32) -> specs::Entity {
+        let mut health = HealthComponent::new(25);
+        health.hit_points = hit_points;
+        world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 83usize.into()))
+            .with(health)
+            .build()
+    }
+
+    #[test]
+    fn test_research_adds_hit_points() {
+        // Research 0 takes a second and gives villagers (83) 10 more hit points
+        let mut research = dat::Research::default();
+        research.time_seconds = 1;
+        research.age_id = Some(0usize.into());
+        let mut effect = ResearchEffectRecord::default();
+        effect.type_id = 4;
+        effect.param_a = 83;
+        effect.param_b = -1;
+        effect.param_c = 0;
+        effect.param_d = 10.0;
+        let mut group = ResearchEffectGroup::default();
+        group.effects.push(effect);
+        let mut villager_info = dat::Unit::default();
+        villager_info.id = 83usize.into();
+        villager_info.hit_points = 25;
+        let mut building = dat::Unit::default();
+        building.id = 109usize.into();
+        let units = vec![villager_info, building];
+        let civ = dat::Civilization::sample(1usize.into(), "Egyptian", units, [-1, -1, -1]);
+        let empires = Arc::new(EmpiresDb::sample(vec![civ], vec![research], vec![group]));
+
+        let mut world = test_world(empires.clone());
+        start_research(&mut world, 0);
+        let wounded = villager(&mut world, 20);
+        let killed = villager(&mut world, 0);
+        let mut planner = test_planner(world, Box::new(ResearchSystem::new(empires)));
+
+        // Living villagers gain the hit points, but one killed this tick isn't brought back
+        run_ticks(&mut planner, 1, 1.into());
+        let world = planner.mut_world();
+        let healths = world.read::<HealthComponent>();
+        let wounded = healths.get(wounded).unwrap();
+        assert_eq!((30, 35), (wounded.hit_points, wounded.max_hit_points));
+        assert_eq!(0, healths.get(killed).unwrap().hit_points);
+    }
+
+    #[test]
+    fn test_age_advancement() {
+        // Research 0 advances to the tool age, which upgrades villagers (83) to a unit (93)
+        // that stands around differently
+        let mut research = dat::Research::default();
+        research.time_seconds = 1;
+        research.age_id = Some(0usize.into());
+        let mut effect = ResearchEffectRecord::default();
+        effect.type_id = 3;
+        effect.param_a = 83;
+        effect.param_b = 93;
+        let mut group = ResearchEffectGroup::default();
+        group.effects.push(effect);
+        let mut villager_info = dat::Unit::default();
+        villager_info.id = 83usize.into();
+        villager_info.standing_graphic = Some(10usize.into());
+        let mut upgraded_info = dat::Unit::default();
+        upgraded_info.id = 93usize.into();
+        upgraded_info.standing_graphic = Some(20usize.into());
+        let mut building = dat::Unit::default();
+        building.id = 109usize.into();
+        let units = vec![villager_info, upgraded_info, building];
+        let civ = dat::Civilization::sample(1usize.into(), "Egyptian", units, [0, -1, -1]);
+        let empires = Arc::new(EmpiresDb::sample(vec![civ], vec![research], vec![group]));
+
+        let mut world = test_world(empires.clone());
+        start_research(&mut world, 0);
+        let mut villagers = Vec::new();
+        for &graphic_id in &[10usize, 11] {
+            let mut graphic = GraphicComponent::new();
+            graphic.graphic_id = Some(graphic_id.into());
+            villagers.push(world.create_now()
+                .with(UnitComponent::new(1usize.into(), 1usize.into(), 83usize.into()))
+                .with(graphic)
+                .build());
+        }
+        let mut planner = test_planner(world, Box::new(ResearchSystem::new(empires)));
+
+        // Both villagers are upgraded, but only the one standing around looks any different
+        run_ticks(&mut planner, 1, 1.into());
+        let world = planner.mut_world();
+        assert_eq!(dat::Age::Tool, world.read_resource::<Technologies>().age(1usize.into()));
+        let units = world.read::<UnitComponent>();
+        let graphics = world.read::<GraphicComponent>();
+        for (&villager, &graphic_id) in villagers.iter().zip(&[20usize, 11]) {
+            assert_eq!(93, *units.get(villager).unwrap().unit_id);
+            assert_eq!(Some(graphic_id.into()), graphics.get(villager).unwrap().graphic_id);
+        }
+    }
+}
@@ -0,0 +1,322 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+use crate::action::{Action, MoveToPositionParams};
+use crate::dat::{self, EmpiresDbRef};
+use crate::ecs::{ActionQueueComponent, ConstructionComponent, DeathComponent, GraphicComponent, HealthComponent,
+                 ResourceComponent, TrainingQueueComponent, TransformComponent, UnitComponent, VelocityComponent,
+                 MAX_TRAINING_QUEUE_LENGTH};
+use crate::ecs::resource::{OccupiedTiles, PathFinder, Players, SoundEvent, SoundEvents, Technologies, Terrain};
+use crate::identifier::PlayerId;
+use specs::{self, Join};
+use super::System;
+use crate::types::{Fixed, Vector3};
+use crate::util::unit;
+
+use std::collections::BTreeMap;
+
+/// Units use up population headroom, and houses and town centers provide it, through
+/// resource storage slots of this type. Civs can start out with some as well.
+const POPULATION_HEADROOM: dat::ResourceType = dat::ResourceType::Unknown(4);
+
+/// A unit that has finished training and is about to leave the building
+struct TrainedUnit {
+    unit: UnitComponent,
+    position: Vector3,
+    rally_point: Option<Vector3>,
+}
+
+/// Applies the orders given to buildings' training queues, trains the first unit in each queue,
+/// and brings it out next to the building once it's done and there's room for it
+pub struct TrainingSystem {
+    empires: EmpiresDbRef,
+}
+
+impl TrainingSystem {
+    pub fn new(empires: EmpiresDbRef) -> TrainingSystem {
+        TrainingSystem { empires: empires }
+    }
+}
+
+impl System for TrainingSystem {
+    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+        fetch_components!(arg, entities, [
+            components(deaths: DeathComponent),
+            components(constructions: ConstructionComponent),
+            mut components(units: UnitComponent),
+            mut components(transforms: TransformComponent),
+            mut components(training_queues: TrainingQueueComponent),
+            mut components(velocities: VelocityComponent),
+            mut components(graphics: GraphicComponent),
+            mut components(healths: HealthComponent),
+            mut components(resources: ResourceComponent),
+            mut components(action_queues: ActionQueueComponent),
+            resource(terrain: Terrain),
+            resource(occupied_tiles: OccupiedTiles),
+            resource(path_finder: PathFinder),
+            resource(technologies: Technologies),
+            mut resource(players: Players),
+            mut resource(sound_events: SoundEvents),
+        ]);
+
+        // Unfinished buildings don't provide any headroom yet, and dead units don't take any up
+        let mut headroom: BTreeMap<PlayerId, Fixed> = players.all()
+            .iter()
+            .map(|player| (player.player_id, player.resource(POPULATION_HEADROOM)))
+            .collect();
+        for (entity, unit) in (&entities, &units).iter() {
+            if deaths.get(entity).is_none() && constructions.get(entity).is_none() {
+                if let Some(player_headroom) = headroom.get_mut(&unit.player_id) {
+                    *player_headroom += unit::resource_storage(unit.info(&technologies), POPULATION_HEADROOM);
+                }
+            }
+        }
+
+        let mut trained = Vec::new();
+        for (entity, unit, transform, training_queue) in
+            (&entities, &units, &transforms, &mut training_queues).iter() {
+            // The death system pays back whatever was queued in buildings that are destroyed
+            if deaths.get(entity).is_some() {
+                continue;
+            }
+
+            let building_info = unit.info(&technologies);
+            let orders: Vec<Action> = training_queue.orders.drain(..).collect();
+            for order in orders {
+                match order {
+                    Action::Train(params) => {
+                        let trainable = constructions.get(entity).is_none() &&
+                                        training_queue.units.len() < MAX_TRAINING_QUEUE_LENGTH &&
+                                        unit::trainable_units(&technologies,
+                                                              unit.player_id,
+                                                              unit.civilization_id,
+                                                              building_info)
+                                            .iter()
+                                            .any(|unit_info| unit_info.id == params.unit_id);
+                        if !trainable {
+                            continue;
+                        }
+                        let unit_info = technologies.unit(unit.player_id, unit.civilization_id, params.unit_id);
+                        let costs = unit::train_costs(unit_info);
+                        if players.player_mut(unit.player_id).is_some_and(|player| player.debit(costs).is_ok()) {
+                            training_queue.units.push(params.unit_id);
+                        }
+                    }
+                    Action::CancelTraining(params) => {
+                        let slot = params.slot as usize;
+                        if slot >= training_queue.units.len() {
+                            continue;
+                        }
+                        let unit_id = training_queue.units.remove(slot);
+                        if let Some(player) = players.player_mut(unit.player_id) {
+                            let unit_info = technologies.unit(unit.player_id, unit.civilization_id, unit_id);
+                            player.refund(unit::train_costs(unit_info));
+                        }
+                        if slot == 0 {
+                            training_queue.elapsed_time = 0.into();
+                        }
+                    }
+                    Action::SetRallyPoint(params) => training_queue.rally_point = Some(params.position),
+                    _ => {}
+                }
+            }
+
+            let unit_id = match training_queue.units.first() {
+                Some(&unit_id) => unit_id,
+                None => continue,
+            };
+            let unit_info = technologies.unit(unit.player_id, unit.civilization_id, unit_id);
+            let train_time: Fixed = unit_info.trainable_params.as_ref().map_or(0, |params| params.train_time).into();
+            training_queue.elapsed_time = (training_queue.elapsed_time + time_step).min(train_time);
+            if training_queue.elapsed_time < train_time {
+                continue;
+            }
+
+            // A finished unit waits in the building until the player has room for it,
+            // and until there's somewhere free next to the building for it to stand
+            let population = unit::resource_storage(unit_info, POPULATION_HEADROOM);
+            let player_headroom = headroom.entry(unit.player_id).or_insert_with(|| 0.into());
+            if population < 0.into() && *player_headroom + population < 0.into() {
+                continue;
+            }
+            let position = match unit::spawn_position(&self.empires,
+                                                      &terrain,
+                                                      &occupied_tiles,
+                                                      building_info,
+                                                      transform.position(),
+                                                      unit_info) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            *player_headroom += population;
+            training_queue.units.remove(0);
+            training_queue.elapsed_time = 0.into();
+            trained.push(TrainedUnit {
+                unit: UnitComponent::new(unit.player_id, unit.civilization_id, unit_id),
+                position: position,
+                rally_point: training_queue.rally_point,
+            });
+        }
+
+        let local_player_id = players.local_player_id();
+        for trained_unit in trained {
+            let unit_info = trained_unit.unit.info(&technologies);
+            let entity = arg.create();
+
+            let mut graphic = GraphicComponent::new();
+            graphic.player_color_id = trained_unit.unit.player_id.into();
+            graphic.graphic_id = unit_info.standing_graphic;
+
+            // Heading to the rally point is part of coming out of the building, so it's the same
+            // on every peer without having to go through the action batcher
+            let mut actions = Vec::new();
+            if let Some(rally_point) = trained_unit.rally_point {
+                let path = path_finder.find_path(&terrain,
+                                                 &occupied_tiles,
+                                                 &trained_unit.position,
+                                                 &rally_point,
+                                                 unit_info.terrain_restriction);
+                actions.push(Action::MoveToPosition(MoveToPositionParams::new(path)));
+            }
+
+            if trained_unit.unit.player_id == local_player_id {
+                if let Some(sound_group_id) = unit_info.train_sound_id {
+                    sound_events.push(SoundEvent::new(sound_group_id, trained_unit.position));
+                }
+            }
+            if !unit::is_immobile_resource(unit_info) {
+                healths.insert(entity, HealthComponent::new(unit_info.hit_points as i32));
+            }
+            if let Some(resource) = ResourceComponent::from_unit(unit_info) {
+                resources.insert(entity, resource);
+            }
+            transforms.insert(entity, TransformComponent::new(trained_unit.position, 0.into()));
+            velocities.insert(entity, VelocityComponent::new());
+            graphics.insert(entity, graphic);
+            action_queues.insert(entity, ActionQueueComponent::from_parts(actions, None, true));
+            units.insert(entity, trained_unit.unit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{POPULATION_HEADROOM, TrainingSystem};
+    use crate::action::{Action, CancelTrainingParams, TrainParams};
+    use crate::dat::{self, ResourceCost, ResourceType};
+    use crate::ecs::{TrainingQueueComponent, TransformComponent, UnitComponent};
+    use crate::ecs::resource::{Player, Players, Terrain, Tile};
+    use crate::ecs::world::{run_ticks, test_empires, test_planner, test_world};
+    use crate::types::{Fixed, Vector3};
+    use specs::Join;
+
+    /// A town center (109) that trains villagers (83) for 50 food in 2 seconds
+    fn empires() -> dat::EmpiresDbRef {
+        let mut trainable = dat::TrainableParams::default();
+        trainable.train_time = 2;
+        trainable.train_location_id = Some(109usize.into());
+        trainable.resource_costs.push(ResourceCost::new(ResourceType::Food, 50));
+        let mut villager = dat::Unit::default();
+        villager.id = 83usize.into();
+        villager.enabled = true;
+        villager.hit_points = 25;
+        villager.trainable_params = Some(trainable);
+        villager.resource_storage.push(ResourceCost::new(POPULATION_HEADROOM, -1.0));
+
+        let mut town_center = dat::Unit::default();
+        town_center.id = 109usize.into();
+        town_center.interaction_mode = dat::InteractionMode::Building;
+        town_center.collision_size_x = 0.5;
+        town_center.collision_size_y = 0.5;
+
+        test_empires(vec![villager, town_center])
+    }
+
+    fn villager_count(world: &specs::World) -> usize {
+        world.read::<UnitComponent>().iter().filter(|unit| *unit.unit_id == 83).count()
+    }
+
+    fn food(world: &specs::World) -> Fixed {
+        world.read_resource::<Players>().all()[0].resource(ResourceType::Food)
+    }
+
+    #[test]
+    fn test_cancel_and_population_cap() {
+        let empires = empires();
+        let mut world = test_world(empires.clone());
+        let tiles = (0..36).map(|_| Tile::new(0usize.into(), 0)).collect();
+        world.add_resource(Terrain::new(6, 6, tiles, empires.clone()));
+        let mut player = Player::new("Player".into(), 1usize.into(), 1usize.into(), 1usize.into());
+        player.credit(ResourceType::Food, 100.into());
+        world.write_resource::<Players>().add_player(player, true);
+
+        let mut training_queue = TrainingQueueComponent::new();
+        training_queue.orders.push(Action::Train(TrainParams::new(83usize.into())));
+        training_queue.orders.push(Action::Train(TrainParams::new(83usize.into())));
+        let position = Vector3::new(Fixed::from(2.5), Fixed::from(2.5), 0.into());
+        let town_center = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 1usize.into(), 109usize.into()))
+            .with(TransformComponent::new(position, 0.into()))
+            .with(training_queue)
+            .build();
+        let mut planner = test_planner(world, Box::new(TrainingSystem::new(empires)));
+
+        // Both villagers are paid for up front, and cancelling the second one gives its food back
+        run_ticks(&mut planner, 1, 1.into());
+        {
+            let world = planner.mut_world();
+            assert_eq!(Fixed::from(0), food(world));
+            let mut training_queues = world.write::<TrainingQueueComponent>();
+            let training_queue = training_queues.get_mut(town_center).unwrap();
+            assert_eq!(2, training_queue.units.len());
+            training_queue.orders.push(Action::CancelTraining(CancelTrainingParams::new(1)));
+        }
+        run_ticks(&mut planner, 1, 1.into());
+        {
+            let world = planner.mut_world();
+            assert_eq!(Fixed::from(50), food(world));
+            assert_eq!(1, world.read::<TrainingQueueComponent>().get(town_center).unwrap().units.len());
+        }
+
+        // The first villager is done, but waits in the town center while the player has no room for it
+        run_ticks(&mut planner, 3, 1.into());
+        {
+            let world = planner.mut_world();
+            assert_eq!(0, villager_count(world));
+            let training_queues = world.read::<TrainingQueueComponent>();
+            let training_queue = training_queues.get(town_center).unwrap();
+            assert_eq!(1, training_queue.units.len());
+            assert_eq!(Fixed::from(2), training_queue.elapsed_time);
+        }
+
+        // It comes out as soon as there's room
+        planner.mut_world()
+            .write_resource::<Players>()
+            .player_mut(1usize.into())
+            .unwrap()
+            .credit(POPULATION_HEADROOM, 1.into());
+        run_ticks(&mut planner, 1, 1.into());
+        let world = planner.mut_world();
+        assert_eq!(1, villager_count(world));
+        assert!(world.read::<TrainingQueueComponent>().get(town_center).unwrap().units.is_empty());
+    }
+}
@@ -20,30 +20,57 @@
 // SOFTWARE.
 
 use crate::action::Action;
-use crate::ecs::component::{MoveToPositionActionComponent, ActionQueueComponent};
-use crate::ecs::resource::ActionBatcher;
+use crate::ecs::component::{AttackActionComponent, BuildActionComponent, GatherActionComponent, MoveToPositionActionComponent, ActionQueueComponent, ResearchComponent, TrainingQueueComponent, UnitComponent};
+use crate::ecs::resource::{ActionBatcher, EntityHandle};
+use crate::net::Lockstep;
 use specs::{self, Join};
 use super::System;
-use crate::types::{Fixed, fixed_const};
-
-// This is just a temporary batch length value
-// It'll be subject to the latencies of networking later
-const TURN_LENGTH_SECONDS: Fixed = fixed_const!(0.1);
+use crate::types::Fixed;
 
 macro_rules! detach_action_component {
-    ($action:expr, $entity:expr, $mtps:expr) => {
+    ($action:expr, $entity:expr, $mtps:expr, $attacks:expr, $gathers:expr, $builds:expr) => {
         match $action {
             Action::MoveToPosition(_) => { $mtps.remove($entity); }
+            Action::Attack(_) => { $attacks.remove($entity); }
+            Action::Gather(_) => { $gathers.remove($entity); }
+            Action::Build(_) => { $builds.remove($entity); }
             _ => panic!("Failed to detach unknown action: {:?}", $action)
         }
     }
 }
 
+/// Evaluates to false if the action can't be started, because what it targets is already gone
 macro_rules! attach_action_component {
-    ($action:expr, $entity:expr, $mtps:expr) => {
+    ($action:expr, $entity:expr, $entities:expr, $mtps:expr, $attacks:expr, $gathers:expr, $builds:expr) => {
         match $action {
             Action::MoveToPosition(ref params) => {
                 $mtps.insert($entity, MoveToPositionActionComponent::new(params.path.clone()));
+                true
+            }
+            Action::Attack(ref params) => {
+                // Actions name their target by index (which is all that goes over the network), so
+                // this is where it becomes an entity, before the index can be reused by anything else
+                match ($entities).iter().find(|target| target.get_id() == params.target) {
+                    Some(target) => {
+                        $attacks.insert($entity, AttackActionComponent::new(target));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Action::Gather(ref params) => {
+                // Same as for attacks
+                match ($entities).iter().find(|target| target.get_id() == params.target) {
+                    Some(target) => {
+                        $gathers.insert($entity, GatherActionComponent::new(target));
+                        true
+                    }
+                    None => false,
+                }
+            }
+            Action::Build(ref params) => {
+                $builds.insert($entity, BuildActionComponent::new(params.unit_id, params.position));
+                true
             }
             _ => panic!("Failed to attach unknown action: {:?}", $action)
         }
@@ -51,36 +78,59 @@ macro_rules! attach_action_component {
 }
 
 /// This system exists to take the actions batched up in the ActionBatcher
-/// synchronize them across the network (in multiplayer), and then add
-/// the actions to each individual unit.
-pub struct UnitActionSystem {
-    turn_accumulator: Fixed,
-}
+/// synchronize them across the network with the Lockstep resource (in multiplayer),
+/// and then add the actions to each individual unit at the start of each turn.
+pub struct UnitActionSystem;
 
 impl UnitActionSystem {
     pub fn new() -> UnitActionSystem {
-        UnitActionSystem { turn_accumulator: 0.into() }
+        UnitActionSystem
     }
 }
 
 impl System for UnitActionSystem {
-    fn update(&mut self, arg: specs::RunArg, time_step: Fixed) {
+    fn update(&mut self, arg: specs::RunArg, _time_step: Fixed) {
         fetch_components!(arg, entities, [
+            components(units: UnitComponent),
             mut components(action_queues: ActionQueueComponent),
             mut components(mtps: MoveToPositionActionComponent),
+            mut components(attacks: AttackActionComponent),
+            mut components(gathers: GatherActionComponent),
+            mut components(builds: BuildActionComponent),
+            mut components(training_queues: TrainingQueueComponent),
+            mut components(researches: ResearchComponent),
             mut resource(action_batcher: ActionBatcher),
+            mut resource(lockstep: Lockstep),
         ]);
 
-        self.turn_accumulator += time_step;
-        if self.turn_accumulator >= TURN_LENGTH_SECONDS {
-            self.turn_accumulator -= TURN_LENGTH_SECONDS;
-
-            let action_batch = action_batcher.consume_actions();
-            for (entity, action_queue) in (&entities, &mut action_queues).iter() {
-                if let Some(actions) = action_batch.get(&entity.get_id()) {
+        if let Some(action_batch) = lockstep.tick(|| action_batcher.consume_actions()) {
+            // Orders only go to units owned by the player who gave them, and orders for
+            // entities that have since died (even if their index has been reused) go nowhere
+            for (entity, action_queue, unit) in (&entities, &mut action_queues, &units).iter() {
+                let actions = action_batch.get(&unit.player_id)
+                    .and_then(|player_actions| player_actions.get(&EntityHandle::new(entity)));
+                if let Some(actions) = actions {
                     for action in actions {
                         match *action {
                             Action::ClearQueue => action_queue.clear(),
+                            // Buildings take training and research orders alongside whatever they're doing,
+                            // instead of one after another
+                            Action::Train(_) | Action::CancelTraining(_) | Action::SetRallyPoint(_) => {
+                                if training_queues.get(entity).is_none() {
+                                    training_queues.insert(entity, TrainingQueueComponent::new());
+                                }
+                                if let Some(training_queue) = training_queues.get_mut(entity) {
+                                    training_queue.orders.push(action.clone());
+                                }
+                            }
+                            Action::Research(_) | Action::CancelResearch => {
+                                if researches.get(entity).is_none() {
+                                    researches.insert(entity, ResearchComponent::new());
+                                }
+                                if let Some(research) = researches.get_mut(entity) {
+                                    research.orders.push(action.clone());
+                                }
+                            }
                             _ => action_queue.add(action.clone()),
                         }
                     }
@@ -95,12 +145,29 @@ impl System for UnitActionSystem {
             // Handle the actual action via separate systems.
             if action_queue.current_action_done() {
                 if let &Some(ref action) = action_queue.current_action() {
-                    detach_action_component!(*action, entity, &mut mtps);
+                    detach_action_component!(*action,
+                                             entity,
+                                             &mut mtps,
+                                             &mut attacks,
+                                             &mut gathers,
+                                             &mut builds);
                 }
                 action_queue.next_action();
 
-                if let &Some(ref action) = action_queue.current_action() {
-                    attach_action_component!(*action, entity, &mut mtps);
+                let attached = match *action_queue.current_action() {
+                    Some(ref action) => {
+                        attach_action_component!(*action,
+                                                 entity,
+                                                 &entities,
+                                                 &mut mtps,
+                                                 &mut attacks,
+                                                 &mut gathers,
+                                                 &mut builds)
+                    }
+                    None => true,
+                };
+                if !attached {
+                    action_queue.mark_current_done();
                 }
             }
         }
@@ -20,11 +20,15 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
-//! This system is responsible for unit selection and queuing up a MoveToPosition action.
+//! This system is responsible for unit selection, building placement, queuing up MoveToPosition, Attack,
+//! Gather and Build actions, and giving buildings their training and research orders.
 
-use crate::action::{Action, MoveToPositionParams};
+use crate::action::{Action, AttackParams, BuildParams, CancelTrainingParams, GatherParams, MoveToPositionParams,
+                    ResearchParams, SetRallyPointParams, TrainParams};
 use crate::dat;
-use crate::ecs::{DecalComponent, OnScreenComponent, SelectedUnitComponent, TransformComponent, UnitComponent};
+use crate::ecs::{ConstructionComponent, DeathComponent, DeathStage, DecalComponent, HealthComponent,
+                 OnScreenComponent, ResearchComponent, ResourceComponent, SelectedUnitComponent,
+                 TrainingQueueComponent, TransformComponent, UnitComponent};
 
 use crate::ecs::resource::{
     MouseState,
@@ -34,12 +38,14 @@ use crate::ecs::resource::{
     ViewProjector,
     Viewport,
     OccupiedTiles,
+    Technologies,
     Terrain,
     ActionBatcher,
+    BuildingPlacement,
+    PlacingBuilding,
 };
 
 use crate::media::{KeyState, MouseButton, Key};
-use crate::resource::DrsKey;
 use specs::{self, Join};
 use super::System;
 use crate::types::{Fixed, Vector3};
@@ -60,6 +66,12 @@ impl System for UnitSelectionSystem {
         fetch_components!(arg, entities, [
             components(on_screen: OnScreenComponent),
             components(units: UnitComponent),
+            components(healths: HealthComponent),
+            components(deaths: DeathComponent),
+            components(resources: ResourceComponent),
+            components(constructions: ConstructionComponent),
+            components(training_queues: TrainingQueueComponent),
+            components(researches: ResearchComponent),
             mut components(decals: DecalComponent),
             mut components(selected_units: SelectedUnitComponent),
             mut components(transforms: TransformComponent),
@@ -70,10 +82,135 @@ impl System for UnitSelectionSystem {
             resource(view_projector: ViewProjector),
             resource(viewport: Viewport),
             resource(occupied_tiles: OccupiedTiles),
+            resource(technologies: Technologies),
             resource(terrain: Terrain),
             mut resource(action_batcher: ActionBatcher),
+            mut resource(building_placement: BuildingPlacement),
         ]);
 
+        let local_player_id = players.local_player().player_id;
+        let is_builder = |unit: &UnitComponent| {
+            unit.player_id == local_player_id && unit::build_command(unit.info(&technologies)).is_some()
+        };
+        let is_building = |unit: &UnitComponent| {
+            unit.player_id == local_player_id &&
+            unit.info(&technologies).interaction_mode == dat::InteractionMode::Building
+        };
+
+        // Number keys pick a building for the selected villagers to place, or a unit for the selected
+        // buildings to train, in the order of its button. With the left shift key held, they pick research
+        // for the first selected building that can do it instead.
+        if let Some(button_index) = pressed_button_hotkey(&keyboard_state) {
+            let builder = (&units, &selected_units)
+                .iter()
+                .map(|(unit, _)| unit)
+                .find(|&unit| is_builder(unit));
+            if let (Some(builder), false) = (builder, keyboard_state.is_down(Key::ShiftLeft)) {
+                let builder_info = builder.info(&technologies);
+                let buildings = unit::buildable_buildings(&technologies,
+                                                          builder.player_id,
+                                                          builder.civilization_id,
+                                                          builder_info);
+                if let Some(building_info) = buildings.get(button_index) {
+                    building_placement.placing = Some(PlacingBuilding {
+                        civilization_id: builder.civilization_id,
+                        player_color_id: local_player_id.into(),
+                        unit_id: building_info.id,
+                        position: Vector3::new(0.into(), 0.into(), 0.into()),
+                        valid: false,
+                    });
+                }
+            } else {
+                for (entity, unit, _selected_unit) in (&entities, &units, &selected_units).iter() {
+                    if !is_building(unit) || constructions.get(entity).is_some() {
+                        continue;
+                    }
+                    if keyboard_state.is_down(Key::ShiftLeft) {
+                        let idle = researches.get(entity).is_none_or(|research| research.research_id.is_none());
+                        let researchable = technologies.researchable_at(unit.player_id, unit.unit_id);
+                        if let (Some(research), true) = (researchable.get(button_index), idle) {
+                            let params = ResearchParams::new(research.id);
+                            action_batcher.queue_for_entity(entity, Action::Research(params));
+                            break;
+                        }
+                        continue;
+                    }
+                    let building_info = unit.info(&technologies);
+                    let trainable =
+                        unit::trainable_units(&technologies, unit.player_id, unit.civilization_id, building_info);
+                    if let Some(unit_info) = trainable.get(button_index) {
+                        let params = TrainParams::new(unit_info.id);
+                        action_batcher.queue_for_entity(entity, Action::Train(params));
+                    }
+                }
+            }
+        }
+
+        // Backspace takes the last unit back out of a selected building's training queue, or with the
+        // left shift key held, stops its research
+        let backspace_pressed = keyboard_state.key_state(Key::Backspace) == KeyState::TransitionDown;
+        if backspace_pressed && keyboard_state.is_down(Key::ShiftLeft) {
+            let items = (&entities, &units, &researches, &selected_units);
+            for (entity, unit, research, _selected_unit) in items.iter() {
+                if unit.player_id == local_player_id && research.research_id.is_some() {
+                    action_batcher.queue_for_entity(entity, Action::CancelResearch);
+                    break;
+                }
+            }
+        } else if backspace_pressed {
+            let items = (&entities, &units, &training_queues, &selected_units);
+            for (entity, unit, training_queue, _selected_unit) in items.iter() {
+                if unit.player_id == local_player_id && !training_queue.units.is_empty() {
+                    let slot = training_queue.units.len() as u32 - 1;
+                    action_batcher.queue_for_entity(entity,
+                                                    Action::CancelTraining(CancelTrainingParams::new(slot)));
+                    break;
+                }
+            }
+        }
+
+        if keyboard_state.key_state(Key::Escape) == KeyState::TransitionDown {
+            building_placement.placing = None;
+        }
+
+        if let Some(mut placing) = building_placement.placing.take() {
+            let building_info = technologies.unit(local_player_id, placing.civilization_id, placing.unit_id);
+            let mouse_ray = calculate_mouse_ray(&viewport, &mouse_state, &view_projector, &terrain);
+            let costs = unit::train_costs(building_info);
+            placing.position = unit::snap_to_tiles(building_info, &mouse_ray.world_coord);
+            placing.valid = players.local_player().can_afford(costs) &&
+                            unit::can_place_building(&self.empires,
+                                                     &terrain,
+                                                     &occupied_tiles,
+                                                     building_info,
+                                                     &placing.position);
+
+            let left_clicked = mouse_state.key_states.key_state(MouseButton::Left) == KeyState::TransitionUp;
+            let right_clicked = mouse_state.key_states.key_state(MouseButton::Right) == KeyState::TransitionUp;
+            let mut done_placing = right_clicked;
+            if !right_clicked && left_clicked && placing.valid {
+                for (entity, unit, _selected_unit) in (&entities, &units, &selected_units).iter() {
+                    if is_builder(unit) {
+                        if keyboard_state.is_up(Key::CtrlLeft) {
+                            action_batcher.queue_for_entity(entity, Action::ClearQueue);
+                        }
+                        action_batcher.queue_for_entity(entity,
+                                                        Action::Build(BuildParams::new(placing.unit_id,
+                                                                                       placing.position)));
+                    }
+                }
+
+                // Holding the left shift key places more of the same building
+                done_placing = keyboard_state.is_up(Key::ShiftLeft);
+            }
+            if !done_placing {
+                building_placement.placing = Some(placing);
+            }
+
+            // Clicks while placing a building don't select or command units
+            return;
+        }
+
         if mouse_state.key_states.key_state(MouseButton::Left) == KeyState::TransitionUp {
             // Holding the left shift key while left clicking a unit will add them to the current selection.
             if keyboard_state.is_up(Key::ShiftLeft) {
@@ -82,8 +219,8 @@ impl System for UnitSelectionSystem {
 
             let mouse_ray = calculate_mouse_ray(&viewport, &mouse_state, &view_projector, &terrain);
             for (entity, _, unit, transform) in (&entities, &on_screen, &units, &transforms).iter() {
-                let unit_info = self.empires.unit(unit.civilization_id, unit.unit_id);
-                if unit_info.interaction_mode != dat::InteractionMode::NonInteracting {
+                let unit_info = unit.info(&technologies);
+                if unit_info.interaction_mode != dat::InteractionMode::NonInteracting && deaths.get(entity).is_none() {
                     let unit_box = unit::selection_box(unit_info, transform);
 
                     // Cast a ray from the mouse position through to the terrain and select any unit
@@ -98,13 +235,89 @@ impl System for UnitSelectionSystem {
 
         if mouse_state.key_states.key_state(MouseButton::Right) == KeyState::TransitionUp {
             let mouse_ray = calculate_mouse_ray(&viewport, &mouse_state, &view_projector, &terrain);
+
+            // Right clicking another player's unit attacks it
+            let mut target = None;
+            for (entity, _, unit, transform, health) in (&entities, &on_screen, &units, &transforms, &healths).iter() {
+                let unit_info = unit.info(&technologies);
+                if unit.player_id != local_player_id && !health.is_dead() &&
+                   unit::selection_box(unit_info, transform).intersects_ray(&mouse_ray.origin, &mouse_ray.direction) {
+                    target = Some(entity.get_id());
+                    break;
+                }
+            }
+
+            // Right clicking a tree, a mine or a carcass gathers from it
+            let mut resource_target = None;
+            for (entity, _, unit, transform, resource) in (&entities, &on_screen, &units, &transforms, &resources).iter() {
+                let alive = match healths.get(entity) {
+                    Some(health) => !health.is_dead(),
+                    None => false,
+                };
+                let dying = match deaths.get(entity) {
+                    Some(death) => death.stage == DeathStage::Dying,
+                    None => false,
+                };
+                let unit_info = unit.info(&technologies);
+                if !alive && !dying &&
+                   unit::selection_box(unit_info, transform).intersects_ray(&mouse_ray.origin, &mouse_ray.direction) {
+                    resource_target = Some((entity.get_id(), unit_info, resource.resource_type));
+                    break;
+                }
+            }
+
+            // Right clicking one of our own unfinished buildings helps build it
+            let mut foundation_target = None;
+            for (_, unit, transform, _) in (&on_screen, &units, &transforms, &constructions).iter() {
+                let unit_info = unit.info(&technologies);
+                let unit_box = unit::selection_box(unit_info, transform);
+                if unit.player_id == local_player_id &&
+                   unit_box.intersects_ray(&mouse_ray.origin, &mouse_ray.direction) {
+                    foundation_target = Some(BuildParams::new(unit.unit_id, *transform.position()));
+                    break;
+                }
+            }
+
             let mut moving_unit = false;
             for (entity, transform, unit, _selected_unit) in (&entities, &transforms, &units, &selected_units).iter() {
-                if unit.player_id != players.local_player().player_id {
+                if unit.player_id != local_player_id {
+                    continue;
+                }
+
+                // Right clicking with a building selected sets where the units it trains go
+                if is_building(unit) {
+                    let params = SetRallyPointParams::new(mouse_ray.world_coord);
+                    action_batcher.queue_for_entity(entity, Action::SetRallyPoint(params));
+                    continue;
+                }
+
+                let unit_info = unit.info(&technologies);
+                if let (Some(params), true) = (foundation_target.as_ref(), is_builder(unit)) {
+                    if keyboard_state.is_up(Key::CtrlLeft) {
+                        action_batcher.queue_for_entity(entity, Action::ClearQueue);
+                    }
+                    action_batcher.queue_for_entity(entity, Action::Build(params.clone()));
+                    continue;
+                }
+
+                if let Some((target, target_info, resource_type)) = resource_target {
+                    if unit::gather_command(unit_info, target_info, resource_type).is_some() {
+                        if keyboard_state.is_up(Key::CtrlLeft) {
+                            action_batcher.queue_for_entity(entity, Action::ClearQueue);
+                        }
+                        action_batcher.queue_for_entity(entity, Action::Gather(GatherParams::new(target)));
+                        continue;
+                    }
+                }
+
+                if let (Some(target), true) = (target, unit_info.battle_params.is_some()) {
+                    if keyboard_state.is_up(Key::CtrlLeft) {
+                        action_batcher.queue_for_entity(entity, Action::ClearQueue);
+                    }
+                    action_batcher.queue_for_entity(entity, Action::Attack(AttackParams::new(target)));
                     continue;
                 }
 
-                let unit_info = self.empires.unit(unit.civilization_id, unit.unit_id);
                 let path = path_finder.find_path(&*terrain,
                                                     &*occupied_tiles,
                                                     transform.position(),
@@ -112,25 +325,33 @@ impl System for UnitSelectionSystem {
                                                     unit_info.terrain_restriction);
                 // Enqueue sequential actions by holding left-control.
                 if keyboard_state.is_up(Key::CtrlLeft) {
-                    action_batcher.queue_for_entity(entity.get_id(), Action::ClearQueue);
+                    action_batcher.queue_for_entity(entity, Action::ClearQueue);
                 }
 
-                action_batcher.queue_for_entity(entity.get_id(),
+                action_batcher.queue_for_entity(entity,
                                                 Action::MoveToPosition(MoveToPositionParams::new(path)));
                 moving_unit = true;
             }
 
             if moving_unit {
-                let decal = arg.create();
-                transforms.insert(decal,
-                                  TransformComponent::new(mouse_ray.world_coord, 0.into()));
-                decals.insert(decal,
-                              DecalComponent::new(0.into(), DrsKey::Interfac, 50405.into()));
+                // Show the move marker where the units were sent, starting its animation over
+                for (decal_entity, decal) in (&entities, &mut decals).iter() {
+                    decal.frame = 0;
+                    decal.frame_time = 0.into();
+                    transforms.insert(decal_entity, TransformComponent::new(mouse_ray.world_coord, 0.into()));
+                }
             }
         }
     }
 }
 
+/// Index of the command button picked with the number keys, if one was just pressed
+fn pressed_button_hotkey(keyboard_state: &KeyboardKeyStates) -> Option<usize> {
+    let keys = [Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8,
+                Key::Num9];
+    keys.iter().position(|&key| keyboard_state.key_state(key) == KeyState::TransitionDown)
+}
+
 struct MouseRay {
     world_coord: Vector3,
     origin: Vector3,
@@ -21,18 +21,25 @@
 
 use crate::dat::EmpiresDbRef;
 use crate::ecs::render_system::{TerrainRenderSystem, GraphicRenderSystem, UnitSelectionRenderSystem, RenderSystemWrapper, DecalRenderSystem, TileDebugRenderSystem};
-use crate::ecs::resource::{ViewProjector, RenderCommands, PathFinder, KeyboardKeyStates, Players, OccupiedTiles, ActionBatcher, Viewport, Terrain, MouseState};
-use crate::ecs::system::{VelocitySystem, SystemWrapper, DecalSystem, AnimationSystem, UnitSelectionSystem, OccupiedTileSystem, CameraPositionSystem, MoveToPositionActionSystem, UnitActionSystem, GridSystem, CameraInputSystem};
+use crate::ecs::resource::{ViewProjector, RenderCommands, PathFinder, KeyboardKeyStates, Players, OccupiedTiles, ActionBatcher, Viewport, Terrain, MouseState, SoundEvents, SimulationChecksum, Random, KilledUnits, BuildingPlacement, Technologies};
+use crate::ecs::system::{VelocitySystem, SystemWrapper, DecalSystem, AnimationSystem, UnitSelectionSystem, OccupiedTileSystem, CameraPositionSystem, MoveToPositionActionSystem, AttackActionSystem, GatherActionSystem, BuildActionSystem, TrainingSystem, ResearchSystem, ProjectileSystem, DeathSystem, DamageGraphicSystem, UnitActionSystem, GridSystem, CameraInputSystem};
 use crate::media::MediaRef;
+use crate::net::Lockstep;
 use crate::partition::GridPartition;
-use crate::resource::ShapeMetadataStoreRef;
+use crate::resource::{DrsKey, ShapeMetadataStoreRef};
+use crate::save::SaveGame;
 use crate::scn;
 use specs;
 use std::collections::HashMap;
-use super::component::{DecalComponent, UnitComponent, OnScreenComponent, CameraComponent, MoveToPositionActionComponent, TransformComponent, GraphicComponent, ActionQueueComponent, VelocityComponent, SelectedUnitComponent};
+use std::io;
+use super::component::{DecalComponent, UnitComponent, OnScreenComponent, CameraComponent, MoveToPositionActionComponent, AttackActionComponent, TransformComponent, GraphicComponent, ActionQueueComponent, VelocityComponent, SelectedUnitComponent, HealthComponent, ProjectileComponent, DeathComponent, GatherActionComponent, ResourceComponent, BuildActionComponent, ConstructionComponent, ResearchComponent, TrainingQueueComponent};
 use crate::types::{Fixed, Vector3};
+use crate::util::unit;
 
-const NUM_THREADS: usize = 4;
+/// Systems run one at a time, in priority order. With more threads, systems that don't share any
+/// components run side by side, and the order they create entities in (and so the indices the new
+/// entities get, which actions refer to them by) would differ between peers.
+const NUM_THREADS: usize = 1;
 const GRID_CELL_SIZE: i32 = 10; // in tiles
 
 pub type WorldPlanner = specs::Planner<(SystemGroup, Fixed)>;
@@ -46,13 +53,29 @@ pub enum SystemGroup {
 pub fn create_world_planner(media: MediaRef,
                             empires: EmpiresDbRef,
                             shape_metadata: ShapeMetadataStoreRef,
-                            scenario: &scn::Scenario)
+                            scenario: &scn::Scenario,
+                            lockstep: Lockstep)
                             -> WorldPlanner {
     let mut world = specs::World::new();
     register_components(&mut world);
-    add_resources(&mut world, &media, &empires, scenario);
+    add_resources(&mut world, &media, &empires, scenario, lockstep);
+
+    // Camera entity
+    world.create_now()
+        .with(TransformComponent::new(Vector3::new(0.into(), 0.into(), 0.into()), 0.into()))
+        .with(VelocityComponent::new())
+        .with(CameraComponent)
+        .build();
+
+    // Marker shown where units were last ordered to move to. It's hidden between orders rather than
+    // deleted, so that the orders (which only the peer giving them shows a marker for) don't use up
+    // entity indices that the other peers would give to units.
+    world.create_now()
+        .with(DecalComponent::new(0.into(), DrsKey::Interfac, 50405.into()))
+        .build();
 
     // Create entities for each unit in the SCN
+    let technologies = world.read_resource::<Technologies>().clone();
     for player_id in scenario.player_ids() {
         let units = scenario.player_units(player_id);
         let civ_id = scenario.player_civilization_id(player_id);
@@ -62,20 +85,30 @@ pub fn create_world_planner(media: MediaRef,
                                                                            unit.position_z.into()),
                                                               unit.rotation.into());
 
-            let unit_info = empires.unit(civ_id, unit.unit_id);
+            // Units start out as what the civ's bonuses and the starting age have upgraded them to
+            let unit_id = technologies.upgraded_unit_id(player_id, unit.unit_id);
+            let unit_info = technologies.unit(player_id, civ_id, unit_id);
 
             let mut graphic_component = GraphicComponent::new();
             graphic_component.player_color_id = player_id.into();
             graphic_component.graphic_id = unit_info.standing_graphic;
 
             // TODO: Use the bulk creation iterator for better performance
-            world.create_now()
+            let entity = world.create_now()
                 .with(ActionQueueComponent::new())
                 .with(transform_component)
                 .with(graphic_component)
-                .with(UnitComponent::new(player_id, civ_id, unit.unit_id))
+                .with(UnitComponent::new(player_id, civ_id, unit_id))
                 .with(VelocityComponent::new())
                 .build();
+
+            // Trees, mines and the like can only be gathered from, not attacked
+            if !unit::is_immobile_resource(unit_info) {
+                world.write::<HealthComponent>().insert(entity, HealthComponent::new(unit_info.hit_points as i32));
+            }
+            if let Some(resource) = ResourceComponent::from_unit(unit_info) {
+                world.write::<ResourceComponent>().insert(entity, resource);
+            }
         }
     }
 
@@ -85,14 +118,46 @@ pub fn create_world_planner(media: MediaRef,
     planner
 }
 
+/// Creates a world for a game saved from the given scenario, picking up where it left off
+pub fn load_world_planner(media: MediaRef,
+                          empires: EmpiresDbRef,
+                          shape_metadata: ShapeMetadataStoreRef,
+                          scenario: &scn::Scenario,
+                          save_game: &SaveGame)
+                          -> io::Result<WorldPlanner> {
+    let mut world = specs::World::new();
+    register_components(&mut world);
+    add_resources(&mut world,
+                  &media,
+                  &empires,
+                  scenario,
+                  Lockstep::resume(save_game.turn_state.clone()));
+    save_game.restore(&mut world)?;
+
+    let mut planner = WorldPlanner::new(world, NUM_THREADS);
+    attach_systems(&mut planner, &empires, &shape_metadata);
+    attach_render_systems(&mut planner, &empires);
+    Ok(planner)
+}
+
 fn register_components(world: &mut specs::World) {
     world.register::<ActionQueueComponent>();
+    world.register::<AttackActionComponent>();
+    world.register::<BuildActionComponent>();
     world.register::<CameraComponent>();
+    world.register::<ConstructionComponent>();
+    world.register::<DeathComponent>();
     world.register::<DecalComponent>();
+    world.register::<GatherActionComponent>();
     world.register::<GraphicComponent>();
+    world.register::<HealthComponent>();
     world.register::<MoveToPositionActionComponent>();
     world.register::<OnScreenComponent>();
+    world.register::<ProjectileComponent>();
+    world.register::<ResearchComponent>();
+    world.register::<ResourceComponent>();
     world.register::<SelectedUnitComponent>();
+    world.register::<TrainingQueueComponent>();
     world.register::<TransformComponent>();
     world.register::<UnitComponent>();
     world.register::<VelocityComponent>();
@@ -101,7 +166,8 @@ fn register_components(world: &mut specs::World) {
 fn add_resources(world: &mut specs::World,
                  media: &MediaRef,
                  empires: &EmpiresDbRef,
-                 scenario: &scn::Scenario) {
+                 scenario: &scn::Scenario,
+                 lockstep: Lockstep) {
     let viewport_size = media.borrow().viewport_size();
     let (tile_half_width, tile_half_height) = empires.tile_half_sizes();
 
@@ -114,20 +180,27 @@ fn add_resources(world: &mut specs::World,
     world.add_resource(ViewProjector::new(tile_half_width, tile_half_height));
     world.add_resource(GridPartition::new(GRID_CELL_SIZE, GRID_CELL_SIZE));
 
-    // Camera resources and entity
+    // Sound resources
+    world.add_resource(SoundEvents::new());
+
+    // Camera resources
     world.add_resource(Viewport::new(viewport_size.x as i32, viewport_size.y as i32));
-    world.create_now()
-        .with(TransformComponent::new(Vector3::new(0.into(), 0.into(), 0.into()), 0.into()))
-        .with(VelocityComponent::new())
-        .with(CameraComponent)
-        .build();
 
-    // Players
-    // TODO: Local player currently hardcoded to 1; should be determined in menu
-    world.add_resource(Players::from_scenario(scenario, 1.into()));
+    // Players, with their civ's bonuses and the age they start in
+    let mut players = Players::from_scenario(scenario, empires, lockstep.local_player());
+    let technologies = Technologies::from_scenario(scenario, empires.clone(), &mut players);
+    world.add_resource(players);
+    world.add_resource(technologies);
 
     // Unit resources
     world.add_resource(ActionBatcher::new());
+    world.add_resource(BuildingPlacement::new());
+    world.add_resource(KilledUnits::new());
+
+    // Synchronization resources
+    world.add_resource(Random::new(lockstep.seed()));
+    world.add_resource(lockstep);
+    world.add_resource(SimulationChecksum::new());
 
     // Terrain resources
     world.add_resource(OccupiedTiles::new());
@@ -135,6 +208,54 @@ fn add_resources(world: &mut specs::World,
     world.add_resource(Terrain::from(&scenario.map, empires.clone()));
 }
 
+/// An empires db with a single civilization (1) that has the given units, and no research or ages
+#[cfg(test)]
+pub fn test_empires(units: Vec<crate::dat::Unit>) -> EmpiresDbRef {
+    let civ = crate::dat::Civilization::sample(1usize.into(), "Egyptian", units, [-1, -1, -1]);
+    std::sync::Arc::new(crate::dat::EmpiresDb::sample(vec![civ], Vec::new(), Vec::new()))
+}
+
+/// A position on the ground
+#[cfg(test)]
+pub fn vector(x: i32, y: i32) -> Vector3 {
+    Vector3::new(x.into(), y.into(), 0.into())
+}
+
+/// A world with every component registered and the resources that the simulation systems use,
+/// on an empty map and without anything that needs media or a scenario, for testing systems on
+#[cfg(test)]
+pub fn test_world(empires: EmpiresDbRef) -> specs::World {
+    let mut world = specs::World::new();
+    register_components(&mut world);
+    world.add_resource(GridPartition::new(GRID_CELL_SIZE, GRID_CELL_SIZE));
+    world.add_resource(SoundEvents::new());
+    world.add_resource(Players::new());
+    world.add_resource(Technologies::new(empires.clone()));
+    world.add_resource(KilledUnits::new());
+    world.add_resource(Random::new(0));
+    world.add_resource(OccupiedTiles::new());
+    world.add_resource(PathFinder::new(empires.clone()));
+    world.add_resource(Terrain::new(0, 0, Vec::new(), empires));
+    world
+}
+
+/// Runs the given system on its own, a tick at a time
+#[cfg(test)]
+pub fn test_planner(world: specs::World, system: Box<dyn crate::ecs::system::System>) -> WorldPlanner {
+    let mut planner = WorldPlanner::new(world, NUM_THREADS);
+    planner.add_system(SystemWrapper::new(system), "TestSystem", 0);
+    planner
+}
+
+/// Runs the planner's systems for the given number of ticks
+#[cfg(test)]
+pub fn run_ticks(planner: &mut WorldPlanner, ticks: u32, time_step: Fixed) {
+    for _ in 0..ticks {
+        planner.dispatch((SystemGroup::Normal, time_step));
+        planner.wait();
+    }
+}
+
 macro_rules! system {
     ($planner:expr, $typ:ident, $priority:expr) => {
         $planner.add_system(SystemWrapper::new(Box::new($typ::new())), stringify!($typ), $priority);
@@ -147,32 +268,61 @@ macro_rules! system {
 fn attach_systems(planner: &mut WorldPlanner,
                   empires: &EmpiresDbRef,
                   shape_metadata: &ShapeMetadataStoreRef) {
-    system!(planner, VelocitySystem, 1000);
-    system!(planner, CameraInputSystem, 1000);
-    system!(planner, CameraPositionSystem, 1000);
-    system!(planner, CameraPositionSystem, 1000);
-    system!(planner, GridSystem, 1000);
-    system!(planner,
-            DecalSystem,
-            DecalSystem::new(shape_metadata.clone()),
-            1000);
-    system!(planner,
-            AnimationSystem,
-            AnimationSystem::new(empires.clone(), shape_metadata.clone()),
-            1000);
-    system!(planner, UnitActionSystem, UnitActionSystem::new(), 1000);
+    // Every system has a priority of its own, so that they always run in the same order
+    system!(planner, CameraInputSystem, 1800);
     system!(planner,
             UnitSelectionSystem,
             UnitSelectionSystem::new(empires.clone()),
-            1000);
+            1700);
+    system!(planner, UnitActionSystem, UnitActionSystem::new(), 1600);
     system!(planner,
             MoveToPositionActionSystem,
-            MoveToPositionActionSystem::new(empires.clone()),
-            1000);
+            MoveToPositionActionSystem::new(),
+            1500);
+    system!(planner,
+            AttackActionSystem,
+            AttackActionSystem::new(),
+            1400);
     system!(planner,
-            OccupiedTileSystem,
-            OccupiedTileSystem::new(empires.clone()),
+            GatherActionSystem,
+            GatherActionSystem::new(),
+            1300);
+    system!(planner,
+            BuildActionSystem,
+            BuildActionSystem::new(empires.clone()),
+            1200);
+    system!(planner,
+            TrainingSystem,
+            TrainingSystem::new(empires.clone()),
+            1100);
+    system!(planner,
+            ResearchSystem,
+            ResearchSystem::new(empires.clone()),
             1000);
+    system!(planner,
+            ProjectileSystem,
+            ProjectileSystem::new(),
+            900);
+    system!(planner, VelocitySystem, 800);
+    system!(planner,
+            DeathSystem,
+            DeathSystem::new(empires.clone()),
+            700);
+    system!(planner,
+            DamageGraphicSystem,
+            DamageGraphicSystem::new(empires.clone()),
+            600);
+    system!(planner, OccupiedTileSystem, 500);
+    system!(planner, CameraPositionSystem, 400);
+    system!(planner, GridSystem, 300);
+    system!(planner,
+            DecalSystem,
+            DecalSystem::new(shape_metadata.clone()),
+            200);
+    system!(planner,
+            AnimationSystem,
+            AnimationSystem::new(empires.clone(), shape_metadata.clone()),
+            100);
 }
 
 macro_rules! render_system {
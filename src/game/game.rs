@@ -20,9 +20,9 @@
 // SOFTWARE.
 
 use crate::dat::{EmpiresDb, EmpiresDbRef};
-use crate::media::{self, MediaRef};
+use crate::media::{self, Framebuffer, InputScript, MediaRef};
 use crate::resource::{DrsManager, DrsManagerRef, GameDir, ShapeManager, ShapeManagerRef, ShapeMetadataStore,
-               ShapeMetadataStoreRef};
+               ShapeMetadataStoreRef, SoundManager, SoundManagerRef};
 use super::state::GameState;
 use std::time::{Instant};
 use crate::types::Fixed;
@@ -31,18 +31,27 @@ const WINDOW_TITLE: &'static str = "Chariot";
 const WINDOW_WIDTH: u32 = 1024;
 const WINDOW_HEIGHT: u32 = 768;
 
+/// How the game should present itself
+pub enum MediaMode {
+    /// Open a window, and play sound and take input through SDL
+    Window,
+    /// Render into memory and take input from a script, for running without a display
+    Headless(InputScript),
+}
+
 pub struct Game {
     game_dir: GameDir,
     drs_manager: DrsManagerRef,
     shape_manager: ShapeManagerRef,
     shape_metadata: ShapeMetadataStoreRef,
+    sound_manager: SoundManagerRef,
     empires: EmpiresDbRef,
     media: MediaRef,
     states: Vec<Box<dyn GameState>>,
 }
 
 impl Game {
-    pub fn new(game_data_dir: &str) -> Game {
+    pub fn new(game_data_dir: &str, media_mode: MediaMode) -> Game {
         let game_dir = GameDir::new(game_data_dir).unwrap_or_else(|err| {
             unrecoverable!("{}", err);
         });
@@ -57,6 +66,7 @@ impl Game {
         });
 
         let shape_metadata = ShapeMetadataStoreRef::new(ShapeMetadataStore::load(&*drs_manager.borrow()));
+        let sound_manager = SoundManager::new(drs_manager.clone());
 
         let empires_dat_location = game_dir.find_file("data/empires.dat").unwrap();
         let empires = EmpiresDbRef::new(EmpiresDb::read_from_file(empires_dat_location)
@@ -64,15 +74,21 @@ impl Game {
                 unrecoverable!("Failed to load empires.dat: {}", err);
             }));
 
-        let media = media::create_media(WINDOW_WIDTH, WINDOW_HEIGHT, WINDOW_TITLE).unwrap_or_else(|err| {
-            unrecoverable!("Failed to create media window: {}", err);
-        });
+        let media = match media_mode {
+            MediaMode::Window => {
+                media::create_media(WINDOW_WIDTH, WINDOW_HEIGHT, WINDOW_TITLE).unwrap_or_else(|err| {
+                    unrecoverable!("Failed to create media window: {}", err);
+                })
+            }
+            MediaMode::Headless(script) => media::create_headless_media(WINDOW_WIDTH, WINDOW_HEIGHT, script),
+        };
 
         Game {
             game_dir: game_dir,
             drs_manager: drs_manager,
             shape_manager: shape_manager,
             shape_metadata: shape_metadata,
+            sound_manager: sound_manager,
             empires: empires,
             media: media,
             states: Vec::new(),
@@ -113,6 +129,34 @@ impl Game {
         }
     }
 
+    /// Runs a fixed number of frames as fast as possible, with each frame doing a single
+    /// update and render. Stops early if the media is closed. Used when running headless,
+    /// since nobody is watching in real time.
+    pub fn run_frames(&mut self, frame_count: u32) {
+        let time_step_seconds = Fixed::from(1) / Fixed::from(60);
+
+        for _ in 0..frame_count {
+            if !self.media.borrow().is_open() {
+                break;
+            }
+
+            self.media.borrow_mut().update();
+            self.update(time_step_seconds);
+            if let Some(state) = self.current_state() {
+                state.render(0.into());
+            }
+            self.media.borrow_mut().renderer().present();
+        }
+    }
+
+    /// Renders the current state without presenting it, and reads back the result
+    pub fn capture_frame(&mut self) -> media::Result<Framebuffer> {
+        if let Some(state) = self.current_state() {
+            state.render(0.into());
+        }
+        self.media.borrow_mut().renderer().capture()
+    }
+
     fn pop_state(&mut self) {
         if let Some(state) = self.current_state() {
             state.stop();
@@ -165,6 +209,10 @@ impl Game {
         self.shape_metadata.clone()
     }
 
+    pub fn sound_manager(&self) -> SoundManagerRef {
+        self.sound_manager.clone()
+    }
+
     pub fn empires_db(&self) -> EmpiresDbRef {
         self.empires.clone()
     }
@@ -22,5 +22,5 @@
 mod game;
 mod state;
 
-pub use self::game::Game;
-pub use self::state::{GameState, ScenarioGameState};
+pub use self::game::{Game, MediaMode};
+pub use self::state::{GameState, ReplayGameState, ScenarioGameState};
@@ -20,7 +20,9 @@
 // SOFTWARE.
 
 mod game_state;
+mod replay_game_state;
 mod scenario_game_state;
 
 pub use self::game_state::GameState;
+pub use self::replay_game_state::ReplayGameState;
 pub use self::scenario_game_state::ScenarioGameState;
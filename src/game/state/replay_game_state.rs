@@ -0,0 +1,139 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::game::{Game, GameState};
+use crate::media::{Key, KeyState, MediaRef};
+use crate::net::{Lockstep, TURN_LENGTH_TICKS};
+use crate::replay::Replay;
+use crate::scn;
+use crate::types::Fixed;
+use super::ScenarioGameState;
+
+/// How far PageUp/PageDown seek (ten seconds)
+const SEEK_TICKS: u64 = 10 * 60;
+
+/// Playback speeds that F cycles through, in ticks per update
+const SPEEDS: [u32; 4] = [1, 2, 4, 8];
+
+/// Plays back a recorded game. The recorded actions take the place of the player's orders, but
+/// the camera and selection still work. P pauses, F changes the speed, and PageUp/PageDown seek
+/// backwards and forwards. Seeking backwards re-simulates from the start of the scenario.
+pub struct ReplayGameState {
+    media: MediaRef,
+    scenario_state: ScenarioGameState,
+    replay: Replay,
+    paused: bool,
+    speed_index: usize,
+    reached_end: bool,
+}
+
+impl ReplayGameState {
+    pub fn new(g: &Game, scenario: scn::Scenario, replay: Replay) -> ReplayGameState {
+        let lockstep = Lockstep::playback(&replay);
+        ReplayGameState {
+            media: g.media(),
+            scenario_state: ScenarioGameState::new(g, scenario, lockstep),
+            replay: replay,
+            paused: false,
+            speed_index: 0,
+            reached_end: false,
+        }
+    }
+
+    /// First tick after the last recorded turn
+    fn end_tick(&self) -> u64 {
+        (self.replay.last_turn() as u64 + 1) * TURN_LENGTH_TICKS
+    }
+
+    fn key_pressed(&self, key: Key) -> bool {
+        self.media.borrow().key_states().key_state(key) == KeyState::TransitionDown
+    }
+
+    fn seek(&mut self, target_tick: u64, time_step: Fixed) -> bool {
+        let target_tick = target_tick.min(self.end_tick());
+        if target_tick < self.scenario_state.tick() {
+            self.scenario_state.restart(Lockstep::playback(&self.replay));
+            self.reached_end = false;
+        }
+        let ticks = target_tick - self.scenario_state.tick();
+        if !self.scenario_state.fast_forward(ticks, time_step) {
+            return false;
+        }
+        println!("Replay at {}s of {}s",
+                 self.scenario_state.tick() / 60,
+                 self.end_tick() / 60);
+        true
+    }
+}
+
+impl GameState for ReplayGameState {
+    fn start(&mut self) {
+        self.scenario_state.start();
+    }
+
+    fn stop(&mut self) {
+        self.scenario_state.stop();
+    }
+
+    fn update(&mut self, time_step: Fixed) -> bool {
+        if self.key_pressed(Key::P) {
+            self.paused = !self.paused;
+        }
+        if self.key_pressed(Key::F) {
+            self.speed_index = (self.speed_index + 1) % SPEEDS.len();
+            println!("Replay speed: {}x", SPEEDS[self.speed_index]);
+        }
+        if self.key_pressed(Key::PageUp) {
+            let tick = self.scenario_state.tick();
+            if !self.seek(tick.saturating_sub(SEEK_TICKS), time_step) {
+                return false;
+            }
+        }
+        if self.key_pressed(Key::PageDown) {
+            let tick = self.scenario_state.tick();
+            if !self.seek(tick + SEEK_TICKS, time_step) {
+                return false;
+            }
+        }
+
+        if self.paused {
+            self.scenario_state.update_input();
+            return true;
+        }
+        for _ in 0..SPEEDS[self.speed_index] {
+            if !self.scenario_state.update(time_step) {
+                return false;
+            }
+        }
+
+        if !self.reached_end && self.scenario_state.tick() >= self.end_tick() {
+            println!("Reached the end of the replay");
+            self.reached_end = true;
+            self.paused = true;
+        }
+        true
+    }
+
+    fn render(&mut self, lerp: Fixed) {
+        self.scenario_state.render(lerp);
+    }
+}
@@ -19,27 +19,192 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use crate::dat;
 use crate::ecs;
-use crate::ecs::resource::{KeyboardKeyStates, MouseState, RenderCommands, Viewport};
+use crate::ecs::resource::{KeyboardKeyStates, MouseState, RenderCommands, SimulationChecksum, SimulationState, SoundEvents,
+                           ViewProjector, Viewport};
 use crate::game::{Game, GameState};
-use crate::media::MediaRef;
+use crate::media::{Key, KeyState, MediaRef};
+use crate::net::Lockstep;
 use nalgebra::{Vector2, convert};
-use crate::resource::ShapeManagerRef;
+use crate::resource::{ShapeManagerRef, ShapeMetadataStoreRef, SoundManagerRef};
+use crate::save::{SaveGame, SaveHeader};
 use crate::scn;
 use crate::types::Fixed;
 
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Where F5 saves the game to and F9 loads it back from
+struct QuickSave {
+    file_name: PathBuf,
+    header: SaveHeader,
+}
+
 pub struct ScenarioGameState {
     media: MediaRef,
     shape_manager: ShapeManagerRef,
+    sound_manager: SoundManagerRef,
+    empires: dat::EmpiresDbRef,
+    shape_metadata: ShapeMetadataStoreRef,
+    scenario: scn::Scenario,
     planner: ecs::WorldPlanner,
+    screenshot_requested: bool,
+    checksum_log: Option<BufWriter<File>>,
+    checksum_reference: Option<BufReader<File>>,
+    quick_save: Option<QuickSave>,
 }
 
 impl ScenarioGameState {
-    pub fn new(g: &Game, scenario: scn::Scenario) -> ScenarioGameState {
+    pub fn new(g: &Game, scenario: scn::Scenario, lockstep: Lockstep) -> ScenarioGameState {
+        let planner = ecs::create_world_planner(g.media(), g.empires_db(), g.shape_metadata(), &scenario, lockstep);
+        ScenarioGameState::with_planner(g, scenario, planner)
+    }
+
+    /// Carries on with a game that was saved from the scenario
+    pub fn load(g: &Game, scenario: scn::Scenario, save_game: &SaveGame) -> io::Result<ScenarioGameState> {
+        let planner = ecs::load_world_planner(g.media(), g.empires_db(), g.shape_metadata(), &scenario, save_game)?;
+        Ok(ScenarioGameState::with_planner(g, scenario, planner))
+    }
+
+    fn with_planner(g: &Game, scenario: scn::Scenario, planner: ecs::WorldPlanner) -> ScenarioGameState {
         ScenarioGameState {
             media: g.media(),
             shape_manager: g.shape_manager(),
-            planner: ecs::create_world_planner(g.media(), g.empires_db(), g.shape_metadata(), &scenario),
+            sound_manager: g.sound_manager(),
+            empires: g.empires_db(),
+            shape_metadata: g.shape_metadata(),
+            planner: planner,
+            scenario: scenario,
+            screenshot_requested: false,
+            checksum_log: None,
+            checksum_reference: None,
+            quick_save: None,
+        }
+    }
+
+    /// Throws away the world and starts the scenario over from the beginning
+    pub fn restart(&mut self, lockstep: Lockstep) {
+        self.planner = ecs::create_world_planner(self.media.clone(),
+                                                 self.empires.clone(),
+                                                 self.shape_metadata.clone(),
+                                                 &self.scenario,
+                                                 lockstep);
+    }
+
+    /// Number of ticks simulated since the scenario started
+    pub fn tick(&mut self) -> u64 {
+        self.planner.mut_world().read_resource::<SimulationChecksum>().tick
+    }
+
+    /// Simulates a number of ticks as fast as possible, without taking input or playing sounds.
+    /// Returns false if the game can't go on.
+    pub fn fast_forward(&mut self, ticks: u64, time_step: Fixed) -> bool {
+        for _ in 0..ticks {
+            if !self.simulate(time_step) {
+                return false;
+            }
+            self.planner.mut_world().write_resource::<SoundEvents>().clear();
+        }
+        true
+    }
+
+    /// Takes keyboard and mouse input for the next tick
+    pub fn update_input(&mut self) {
+        let (save_pressed, load_pressed, screenshot_pressed) = {
+            let media = self.media.borrow();
+            let key_states = media.key_states();
+            (key_states.key_state(Key::F5) == KeyState::TransitionDown,
+             key_states.key_state(Key::F9) == KeyState::TransitionDown,
+             key_states.key_state(Key::F12) == KeyState::TransitionDown)
+        };
+        if save_pressed {
+            self.quick_save();
+        }
+        if load_pressed {
+            self.quick_load();
+        }
+        if screenshot_pressed {
+            self.screenshot_requested = true;
+        }
+        self.update_input_resources();
+    }
+
+    /// Writes the full simulation state after every tick to a file, so that it can be
+    /// compared against by a later run with `compare_checksums_with`
+    pub fn log_checksums_to(&mut self, file: File) {
+        self.checksum_log = Some(BufWriter::new(file));
+    }
+
+    /// Compares the simulation after every tick against a file written by `log_checksums_to`,
+    /// and prints what's different the first time they don't match
+    pub fn compare_checksums_with(&mut self, file: File) {
+        self.checksum_reference = Some(BufReader::new(file));
+    }
+
+    /// Lets the player save the game with F5 and load it back with F9
+    pub fn enable_quick_save(&mut self, file_name: PathBuf, header: SaveHeader) {
+        self.quick_save = Some(QuickSave {
+            file_name: file_name,
+            header: header,
+        });
+    }
+
+    /// Throws away the world and replaces it with the saved game's, unless it fails to load
+    fn load_save_game(&mut self, save_game: &SaveGame) -> io::Result<()> {
+        self.planner = ecs::load_world_planner(self.media.clone(),
+                                               self.empires.clone(),
+                                               self.shape_metadata.clone(),
+                                               &self.scenario,
+                                               save_game)?;
+        Ok(())
+    }
+
+    fn quick_save(&mut self) {
+        let quick_save = match self.quick_save {
+            Some(ref quick_save) => quick_save,
+            None => return,
+        };
+        let save_game = SaveGame::capture(quick_save.header.clone(), self.planner.mut_world());
+        match save_game.write_to_file(&quick_save.file_name) {
+            Ok(_) => println!("Saved the game to {}", quick_save.file_name.display()),
+            Err(err) => println!("Failed to save the game: {}", err),
+        }
+    }
+
+    fn quick_load(&mut self) {
+        let (file_name, scenario_checksum) = match self.quick_save {
+            Some(ref quick_save) => (quick_save.file_name.clone(), quick_save.header.scenario_checksum),
+            None => return,
+        };
+        {
+            let lockstep = self.planner.mut_world().read_resource::<Lockstep>();
+            if !lockstep.peer_players().is_empty() {
+                println!("Can't load a game in the middle of a multiplayer game");
+                return;
+            }
+            if lockstep.is_recording() {
+                println!("Can't load a game while recording a replay");
+                return;
+            }
+        }
+
+        let save_game = match SaveGame::read_from_file(&file_name) {
+            Ok(save_game) => save_game,
+            Err(err) => {
+                println!("Failed to load the game: {}", err);
+                return;
+            }
+        };
+        if save_game.header.scenario_checksum != scenario_checksum {
+            println!("Failed to load the game: it was saved from a different scenario ({})",
+                     save_game.header.scenario_name);
+            return;
+        }
+        match self.load_save_game(&save_game) {
+            Ok(_) => println!("Loaded the game from {} at tick {}", file_name.display(), save_game.tick),
+            Err(err) => println!("Failed to load the game: {}", err),
         }
     }
 
@@ -60,25 +225,117 @@ impl ScenarioGameState {
         (*mouse_state).position = media.mouse_position().clone();
         (*mouse_state).key_states = media.mouse_button_states().clone();
     }
-}
-
-impl GameState for ScenarioGameState {
-    fn start(&mut self) {}
-
-    fn stop(&mut self) {}
 
-    fn update(&mut self, time_step: Fixed) -> bool {
-        self.update_input_resources();
+    /// Saves what has been rendered so far this frame to the first unused screenshot file name
+    fn save_screenshot(&mut self) {
+        let file_name = match (1..10000).map(|n| format!("screenshot{:04}.png", n)).find(|f| !Path::new(f).exists()) {
+            Some(file_name) => file_name,
+            None => {
+                println!("Failed to save screenshot: out of file names");
+                return;
+            }
+        };
+        let result = self.media.borrow_mut().renderer().capture().and_then(|frame| frame.write_png_to_file(&file_name));
+        match result {
+            Ok(_) => println!("Saved screenshot to {}", file_name),
+            Err(err) => println!("Failed to save screenshot: {}", err),
+        }
+    }
 
+    /// Runs a tick, unless still waiting on other players. Returns false if the game can't go on.
+    fn simulate(&mut self, time_step: Fixed) -> bool {
         {
             let world = self.planner.mut_world();
             let mut render_commands = world.write_resource::<RenderCommands>();
             render_commands.clear_debug();
         }
 
+        // Hold the simulation until every peer's actions for the next turn have arrived
+        {
+            let world = self.planner.mut_world();
+            let mut lockstep = world.write_resource::<Lockstep>();
+            if let Err(err) = lockstep.poll() {
+                println!("Game ended: {}", err);
+                return false;
+            }
+            if lockstep.is_waiting() {
+                return true;
+            }
+        }
+
         self.planner.dispatch((ecs::SystemGroup::Normal, time_step));
         self.planner.wait();
 
+        self.update_checksum();
+        true
+    }
+
+    fn update_checksum(&mut self) {
+        let world = self.planner.mut_world();
+        let (tick, checksum) = {
+            let mut simulation_checksum = world.write_resource::<SimulationChecksum>();
+            simulation_checksum.update(world);
+            (simulation_checksum.tick, simulation_checksum.checksum)
+        };
+        world.write_resource::<Lockstep>().record_checksum(checksum);
+
+        if self.checksum_log.is_none() && self.checksum_reference.is_none() {
+            return;
+        }
+        let state = SimulationState::capture(world, tick);
+
+        let log_result = self.checksum_log.as_mut().map(|log| state.write_to(log));
+        if let Some(Err(err)) = log_result {
+            println!("Failed to write checksum log: {}", err);
+            self.checksum_log = None;
+        }
+
+        // A loaded game starts part of the way in, so skip ahead to the tick it's on
+        let reference = loop {
+            match self.checksum_reference.as_mut().map(|reference| SimulationState::read_from(reference)) {
+                Some(Ok(Some(ref reference))) if reference.tick < tick => continue,
+                Some(result) => break result,
+                None => return,
+            }
+        };
+        match reference {
+            Ok(Some(ref reference)) if reference.tick == tick && reference.checksum() == checksum => return,
+            Ok(Some(reference)) => {
+                println!("Simulation diverged from the reference run on tick {} (this run != reference):", tick);
+                for difference in state.differences(&reference) {
+                    println!("  {}", difference);
+                }
+            }
+            Ok(None) => println!("Reference run ended before tick {}", tick),
+            Err(err) => println!("Failed to read reference run: {}", err),
+        }
+        self.checksum_reference = None;
+    }
+
+    fn play_sound_events(&mut self) {
+        let world = self.planner.mut_world();
+        let mut sound_events = world.write_resource::<SoundEvents>();
+        let viewport = world.read_resource::<Viewport>();
+        let projector = world.read_resource::<ViewProjector>();
+        sound_events.play_all(self.media.borrow_mut().audio(),
+                              &mut *self.sound_manager.borrow_mut(),
+                              &*self.empires,
+                              &*viewport,
+                              &*projector);
+    }
+}
+
+impl GameState for ScenarioGameState {
+    fn start(&mut self) {}
+
+    fn stop(&mut self) {}
+
+    fn update(&mut self, time_step: Fixed) -> bool {
+        self.update_input();
+        if !self.simulate(time_step) {
+            return false;
+        }
+        self.play_sound_events();
         true
     }
 
@@ -88,10 +345,17 @@ impl GameState for ScenarioGameState {
         self.planner.dispatch((ecs::SystemGroup::Render, lerp));
         self.planner.wait();
 
-        let world = self.planner.mut_world();
-        let mut render_commands = world.write_resource::<RenderCommands>();
-        render_commands.execute(self.media.borrow_mut().renderer(),
-                                &mut *self.shape_manager.borrow_mut());
-        render_commands.clear_rendered();
+        {
+            let world = self.planner.mut_world();
+            let mut render_commands = world.write_resource::<RenderCommands>();
+            render_commands.execute(self.media.borrow_mut().renderer(),
+                                    &mut *self.shape_manager.borrow_mut());
+            render_commands.clear_rendered();
+        }
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            self.save_screenshot();
+        }
     }
 }
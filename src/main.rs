@@ -36,10 +36,20 @@ mod macros;
 mod action;
 mod ecs;
 mod game;
+mod net;
 mod partition;
+mod replay;
+mod save;
 mod util;
 
-use game::{Game, GameState, ScenarioGameState};
+use game::{Game, GameState, MediaMode, ReplayGameState, ScenarioGameState};
+use net::Lockstep;
+use replay::{Replay, ReplayHeader};
+use save::{SaveGame, SaveHeader};
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::TcpListener;
+use std::path::Path;
 
 fn main() {
     let arg_matches = clap::App::new("Chariot")
@@ -50,6 +60,103 @@ fn main() {
             .value_name("GAME_DATA_DIR")
             .help("Sets the directory to look in for game data. Defaults to \"game\".")
             .takes_value(true))
+        .arg(clap::Arg::with_name("headless")
+            .long("headless")
+            .help("Runs without a window or sound, rendering into memory instead."))
+        .arg(clap::Arg::with_name("frames")
+            .long("frames")
+            .value_name("FRAMES")
+            .requires("headless")
+            .help("Number of frames to run when headless. Defaults to 600.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("input_script")
+            .long("input-script")
+            .value_name("INPUT_SCRIPT")
+            .requires("headless")
+            .help("File with scripted keyboard and mouse input to use when headless.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("capture_tick")
+            .long("capture-tick")
+            .value_name("TICK")
+            .help("Runs the scenario for this many ticks, then saves what's on screen as a PNG and exits.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("capture_file")
+            .long("capture-file")
+            .value_name("CAPTURE_FILE")
+            .requires("capture_tick")
+            .help("Where to save the image for --capture-tick. Defaults to \"capture.png\".")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("player")
+            .long("player")
+            .value_name("PLAYER")
+            .help("Which player in the scenario to play as. Defaults to 1.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("listen")
+            .long("listen")
+            .value_name("ADDRESS")
+            .help("Address to wait for other players to connect on, such as \"0.0.0.0:27015\".")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("connect")
+            .long("connect")
+            .value_name("ADDRESS")
+            .help("Address of another player to connect to. May be given more than once.")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1))
+        .arg(clap::Arg::with_name("peers")
+            .long("peers")
+            .value_name("PEERS")
+            .help("Total number of other players in the game. Defaults to the number of --connect \
+                   addresses, plus one if --listen is given.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("turn_latency")
+            .long("turn-latency")
+            .value_name("TURNS")
+            .help("Number of turns (of 6 ticks each) before an action takes effect. Higher values \
+                   hide more network lag. Defaults to 2 in multiplayer games.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("checksum_log")
+            .long("checksum-log")
+            .value_name("FILE")
+            .help("Writes the simulation state after every tick to a file, for --compare-checksums.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("compare_checksums")
+            .long("compare-checksums")
+            .value_name("FILE")
+            .help("Compares the simulation after every tick with a file from --checksum-log, and \
+                   prints the units that differ when they first diverge.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Seed for the game's random numbers, which must be the same for every player. \
+                   Defaults to 0.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("record_replay")
+            .long("record-replay")
+            .value_name("FILE")
+            .help("Records the game to a replay file.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("replay")
+            .long("replay")
+            .value_name("FILE")
+            .conflicts_with_all(&["listen", "connect", "peers", "player", "turn_latency", "seed", "record_replay",
+                                  "checksum_log", "compare_checksums"])
+            .help("Plays back a replay file recorded on the scenario. P pauses, F changes the speed, and \
+                   PageUp/PageDown seek backwards and forwards.")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("save_file")
+            .long("save-file")
+            .value_name("FILE")
+            .help("Where F5 saves the game, and F9 loads it back from. Defaults to \"quicksave.sav\".")
+            .takes_value(true))
+        .arg(clap::Arg::with_name("load")
+            .long("load")
+            .value_name("FILE")
+            .conflicts_with_all(&["listen", "connect", "peers", "player", "turn_latency", "seed", "record_replay",
+                                  "replay"])
+            .help("Carries on with a game that was saved from the scenario.")
+            .takes_value(true))
         .arg(clap::Arg::with_name("SCENARIO")
             .required(true)
             .help("Scenario file to load (temporary while there's no menu)"))
@@ -64,9 +171,176 @@ fn main() {
                        err);
     });
 
-    let mut game = Game::new(game_data_dir);
-    let initial_state = Box::new(ScenarioGameState::new(&game, scenario));
-    game.push_state(initial_state as Box<dyn GameState>);
+    let headless = arg_matches.is_present("headless");
+    let media_mode = if headless {
+        let script = match arg_matches.value_of("input_script") {
+            Some(file_name) => {
+                media::InputScript::read_from_file(file_name).unwrap_or_else(|err| {
+                    unrecoverable!("Failed to load input script \"{}\": {}", file_name, err);
+                })
+            }
+            None => media::InputScript::new(),
+        };
+        MediaMode::Headless(script)
+    } else {
+        MediaMode::Window
+    };
+
+    let scenario_checksum = replay::scenario_checksum(scenario_file_name).unwrap_or_else(|err| {
+        unrecoverable!("Failed to read scenario \"{}\": {}", scenario_file_name, err);
+    });
+    let scenario_name = Path::new(scenario_file_name)
+        .file_name()
+        .map_or(scenario_file_name.to_string(), |name| name.to_string_lossy().into_owned());
+
+    let mut game;
+    if let Some(file_name) = arg_matches.value_of("replay") {
+        let replay = Replay::read_from_file(file_name).unwrap_or_else(|err| {
+            unrecoverable!("Failed to load replay \"{}\": {}", file_name, err);
+        });
+        if replay.header.scenario_checksum != scenario_checksum {
+            unrecoverable!("Replay \"{}\" was recorded on a different scenario ({})",
+                           file_name,
+                           replay.header.scenario_name);
+        }
+
+        game = Game::new(game_data_dir, media_mode);
+        let initial_state = Box::new(ReplayGameState::new(&game, scenario, replay));
+        game.push_state(initial_state as Box<dyn GameState>);
+    } else {
+        let mut initial_state = if let Some(file_name) = arg_matches.value_of("load") {
+            let save_game = SaveGame::read_from_file(file_name).unwrap_or_else(|err| {
+                unrecoverable!("Failed to load saved game \"{}\": {}", file_name, err);
+            });
+            if save_game.header.scenario_checksum != scenario_checksum {
+                unrecoverable!("Saved game \"{}\" is from a different scenario ({})",
+                               file_name,
+                               save_game.header.scenario_name);
+            }
+
+            game = Game::new(game_data_dir, media_mode);
+            let state = ScenarioGameState::load(&game, scenario, &save_game).unwrap_or_else(|err| {
+                unrecoverable!("Failed to load saved game \"{}\": {}", file_name, err);
+            });
+            Box::new(state)
+        } else {
+            let mut lockstep = connect_peers(&arg_matches);
+            if let Some(file_name) = arg_matches.value_of("record_replay") {
+                let header = ReplayHeader {
+                    scenario_name: scenario_name.clone(),
+                    scenario_checksum: scenario_checksum,
+                    seed: lockstep.seed(),
+                    player_id: lockstep.local_player(),
+                };
+                let result = File::create(file_name).and_then(|file| lockstep.record_to(BufWriter::new(file), &header));
+                if let Err(err) = result {
+                    unrecoverable!("Failed to record replay to \"{}\": {}", file_name, err);
+                }
+            }
+
+            game = Game::new(game_data_dir, media_mode);
+            Box::new(ScenarioGameState::new(&game, scenario, lockstep))
+        };
+        let save_header = SaveHeader {
+            scenario_name: scenario_name,
+            scenario_checksum: scenario_checksum,
+        };
+        initial_state.enable_quick_save(arg_matches.value_of("save_file").unwrap_or("quicksave.sav").into(),
+                                        save_header);
+        if let Some(file_name) = arg_matches.value_of("checksum_log") {
+            let file = File::create(file_name).unwrap_or_else(|err| {
+                unrecoverable!("Failed to create checksum log \"{}\": {}", file_name, err);
+            });
+            initial_state.log_checksums_to(file);
+        }
+        if let Some(file_name) = arg_matches.value_of("compare_checksums") {
+            let file = File::open(file_name).unwrap_or_else(|err| {
+                unrecoverable!("Failed to open checksum log \"{}\": {}", file_name, err);
+            });
+            initial_state.compare_checksums_with(file);
+        }
+        game.push_state(initial_state as Box<dyn GameState>);
+    }
+
+    if let Some(tick) = arg_matches.value_of("capture_tick") {
+        let tick = parse_count(tick, "tick");
+        let file_name = arg_matches.value_of("capture_file").unwrap_or("capture.png");
+        game.run_frames(tick);
+        if let Err(err) = game.capture_frame().and_then(|frame| frame.write_png_to_file(file_name)) {
+            unrecoverable!("Failed to capture tick {} to \"{}\": {}", tick, file_name, err);
+        }
+    } else if headless {
+        game.run_frames(parse_count(arg_matches.value_of("frames").unwrap_or("600"), "frame count"));
+    } else {
+        game.game_loop();
+    }
+}
+
+/// Connects to every other player in the game before it starts. Addresses given with
+/// --connect are connected to first, and then the remaining peers are accepted on --listen.
+fn connect_peers(arg_matches: &clap::ArgMatches) -> Lockstep {
+    let player = parse_count(arg_matches.value_of("player").unwrap_or("1"), "player");
+    if player == 0 || player > 8 {
+        unrecoverable!("Invalid player: {}", player);
+    }
+    let connect_addresses: Vec<&str> = arg_matches.values_of("connect").map(|v| v.collect()).unwrap_or_default();
+    let listen_address = arg_matches.value_of("listen");
+    let peers = match arg_matches.value_of("peers") {
+        Some(peers) => parse_count(peers, "peer count") as usize,
+        None => connect_addresses.len() + if listen_address.is_some() { 1 } else { 0 },
+    };
+    if peers < connect_addresses.len() {
+        unrecoverable!("Can't have fewer peers ({}) than --connect addresses ({})", peers, connect_addresses.len());
+    }
+
+    let turn_latency = match arg_matches.value_of("turn_latency") {
+        Some(turns) => parse_count(turns, "turn latency"),
+        None if peers > 0 => net::DEFAULT_TURN_LATENCY,
+        None => 0,
+    };
+
+    let seed = arg_matches.value_of("seed").map_or(0, |seed| {
+        seed.parse::<u64>().unwrap_or_else(|_| {
+            unrecoverable!("Invalid seed: {}", seed);
+        })
+    });
+
+    let mut lockstep = Lockstep::new((player as usize).into(), turn_latency, seed);
+    for address in &connect_addresses {
+        match lockstep.connect_to(*address) {
+            Ok(peer) => println!("Connected to player {} at {}", *peer, address),
+            Err(err) => {
+                unrecoverable!("Failed to connect to \"{}\": {}", address, err);
+            }
+        }
+    }
+
+    let accept_count = peers - connect_addresses.len();
+    if accept_count > 0 {
+        let listen_address = listen_address.unwrap_or_else(|| {
+            unrecoverable!("--listen is needed to wait for the other {} player(s)", accept_count);
+        });
+        let listener = TcpListener::bind(listen_address).unwrap_or_else(|err| {
+            unrecoverable!("Failed to listen on \"{}\": {}", listen_address, err);
+        });
+        for _ in 0..accept_count {
+            println!("Waiting for players on {}...", listen_address);
+            match lockstep.accept_from(&listener) {
+                Ok(peer) => println!("Player {} connected", *peer),
+                Err(err) => {
+                    unrecoverable!("Failed to accept a player: {}", err);
+                }
+            }
+        }
+    }
+    if peers > 0 {
+        println!("Playing as player {} with a turn latency of {}", player, lockstep.turn_latency());
+    }
+    lockstep
+}
 
-    game.game_loop();
+fn parse_count(value: &str, what: &str) -> u32 {
+    value.parse::<u32>().unwrap_or_else(|_| {
+        unrecoverable!("Invalid {}: {}", what, value);
+    })
 }
@@ -0,0 +1,169 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(test)]
+use std::sync::{Arc, Mutex};
+
+/// A reliable, ordered channel of messages to another peer
+pub trait Connection: Send + Sync {
+    fn send(&mut self, message: &[u8]) -> io::Result<()>;
+
+    /// Returns the next message if one has fully arrived, without blocking
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// Connection over TCP. Messages are prefixed with their length as a little endian u32.
+pub struct TcpConnection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+    closed: bool,
+}
+
+impl TcpConnection {
+    pub fn new(stream: TcpStream) -> io::Result<TcpConnection> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+        Ok(TcpConnection {
+            stream: stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+            closed: false,
+        })
+    }
+
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<TcpConnection> {
+        TcpConnection::new(TcpStream::connect(address)?)
+    }
+
+    /// Blocks until a peer connects to the listener
+    pub fn accept(listener: &TcpListener) -> io::Result<TcpConnection> {
+        let (stream, _) = listener.accept()?;
+        TcpConnection::new(stream)
+    }
+
+    /// Writes as much of the outgoing buffer as the socket will currently take
+    fn flush_outgoing(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "peer stopped accepting data")),
+                Ok(written) => {
+                    self.outgoing.drain(0..written);
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads everything that has arrived so far
+    fn fill_incoming(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 4096];
+        while !self.closed {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => self.closed = true,
+                Ok(read) => self.incoming.extend_from_slice(&buffer[0..read]),
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Connection for TcpConnection {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        self.outgoing.extend_from_slice(&(message.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(message);
+        self.flush_outgoing()
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        self.flush_outgoing()?;
+        if let Some(message) = take_message(&mut self.incoming) {
+            return Ok(Some(message));
+        }
+
+        self.fill_incoming()?;
+        match take_message(&mut self.incoming) {
+            Some(message) => Ok(Some(message)),
+            None if self.closed => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "peer disconnected")),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Removes and returns the first length-prefixed message in the buffer, if it's complete
+fn take_message(buffer: &mut Vec<u8>) -> Option<Vec<u8>> {
+    if buffer.len() < 4 {
+        return None;
+    }
+    let length = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
+    if buffer.len() < 4 + length {
+        return None;
+    }
+    let message = buffer[4..(4 + length)].to_vec();
+    buffer.drain(0..(4 + length));
+    Some(message)
+}
+
+/// Connection to another peer in the same process, for testing
+#[cfg(test)]
+pub struct MemoryConnection {
+    incoming: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    outgoing: Arc<Mutex<VecDeque<Vec<u8>>>>,
+}
+
+#[cfg(test)]
+impl MemoryConnection {
+    /// Creates both ends of a connection
+    pub fn pair() -> (MemoryConnection, MemoryConnection) {
+        let (first, second) = (Arc::new(Mutex::new(VecDeque::new())), Arc::new(Mutex::new(VecDeque::new())));
+        (MemoryConnection {
+            incoming: first.clone(),
+            outgoing: second.clone(),
+        },
+         MemoryConnection {
+            incoming: second,
+            outgoing: first,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Connection for MemoryConnection {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        self.outgoing.lock().unwrap().push_back(message.to_vec());
+        Ok(())
+    }
+
+    fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.incoming.lock().unwrap().pop_front())
+    }
+}
@@ -0,0 +1,664 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::action::Action;
+use crate::ecs::resource::EntityHandle;
+use crate::identifier::PlayerId;
+use crate::replay::{Replay, ReplayHeader, ReplayWriter};
+use crate::util::binary::invalid_data;
+use super::connection::{Connection, TcpConnection};
+use super::wire::{Message, TurnActions};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::net::{TcpListener, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Number of simulation ticks in a turn (a tenth of a second at 60 ticks per second).
+/// Actions only ever take effect at the start of a turn.
+pub const TURN_LENGTH_TICKS: u64 = 6;
+
+/// Default number of turns between an action being issued and it taking effect in
+/// multiplayer games, which gives the action time to reach the other peers
+pub const DEFAULT_TURN_LATENCY: u32 = 2;
+
+/// How long to wait for a newly connected peer to introduce itself
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Every player's actions for a turn, by player and then by the entity they were issued to
+pub type ActionBatch = BTreeMap<PlayerId, BTreeMap<EntityHandle, Vec<Action>>>;
+
+struct Peer {
+    player_id: PlayerId,
+    connection: Box<dyn Connection>,
+}
+
+/// Turns being played back from a replay instead of coming from the players
+struct Playback {
+    turns: BTreeMap<u32, ActionBatch>,
+    checksums: BTreeMap<u32, u64>,
+}
+
+/// Checksums for a turn that haven't been compared with every peer's yet
+#[derive(Default)]
+struct TurnChecksums {
+    local: Option<u64>,
+    peers: Vec<(PlayerId, u64)>,
+}
+
+/// Where a game is in its turns, so that it can be saved and picked back up later
+#[derive(Clone, Debug, PartialEq)]
+pub struct TurnState {
+    pub local_player: PlayerId,
+    pub turn_latency: u32,
+    pub seed: u64,
+    pub tick: u64,
+    pub next_turn: u32,
+    /// Actions that have been issued but not executed yet, by turn and then by player
+    pub pending: BTreeMap<u32, ActionBatch>,
+}
+
+/// Keeps every peer's simulation in step. Actions issued locally during a turn are sent to
+/// all peers to be executed `turn_latency` turns later, and no peer starts a turn until it has
+/// every other player's actions for it. Since each peer then executes the same actions on the
+/// same tick, the simulations stay identical without ever sending any game state.
+///
+/// Without any peers, this just hands local actions back at the start of each turn.
+/// Since it sees every action that gets executed, it's also what records and plays back replays.
+pub struct Lockstep {
+    local_player: PlayerId,
+    turn_latency: u32,
+    seed: u64,
+    tick: u64,
+    next_turn: u32,
+    peers: Vec<Peer>,
+    pending: BTreeMap<u32, ActionBatch>,
+    checksums: BTreeMap<u32, TurnChecksums>,
+    deferred_error: Option<io::Error>,
+    playback: Option<Playback>,
+    replay_writer: Option<ReplayWriter<Box<dyn Write + Send + Sync>>>,
+}
+
+impl Lockstep {
+    pub fn new(local_player: PlayerId, turn_latency: u32, seed: u64) -> Lockstep {
+        Lockstep {
+            local_player: local_player,
+            turn_latency: turn_latency,
+            seed: seed,
+            tick: 0,
+            next_turn: 0,
+            peers: Vec::new(),
+            pending: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            deferred_error: None,
+            playback: None,
+            replay_writer: None,
+        }
+    }
+
+    /// Plays back the turns of a replay, from the point of view of the player who recorded it.
+    /// Local actions are ignored.
+    pub fn playback(replay: &Replay) -> Lockstep {
+        let mut lockstep = Lockstep::new(replay.header.player_id, 0, replay.header.seed);
+        lockstep.playback = Some(Playback {
+            turns: replay.turns.clone(),
+            checksums: replay.checksums.clone(),
+        });
+        lockstep
+    }
+
+    /// Picks a saved game back up where it left off, without any peers. Actions the other
+    /// players had already issued when the game was saved are still executed.
+    pub fn resume(state: TurnState) -> Lockstep {
+        let mut lockstep = Lockstep::new(state.local_player, state.turn_latency, state.seed);
+        lockstep.tick = state.tick;
+        lockstep.next_turn = state.next_turn;
+        lockstep.pending = state.pending;
+        lockstep
+    }
+
+    pub fn turn_state(&self) -> TurnState {
+        TurnState {
+            local_player: self.local_player,
+            turn_latency: self.turn_latency,
+            seed: self.seed,
+            tick: self.tick,
+            next_turn: self.next_turn,
+            pending: self.pending.clone(),
+        }
+    }
+
+    /// Records every turn from now on into a replay
+    pub fn record_to<W: Write + Send + Sync + 'static>(&mut self,
+                                                       stream: W,
+                                                       header: &ReplayHeader)
+                                                       -> io::Result<()> {
+        let stream: Box<dyn Write + Send + Sync> = Box::new(stream);
+        self.replay_writer = Some(ReplayWriter::new(stream, header)?);
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.replay_writer.is_some()
+    }
+
+    pub fn local_player(&self) -> PlayerId {
+        self.local_player
+    }
+
+    pub fn turn_latency(&self) -> u32 {
+        self.turn_latency
+    }
+
+    /// Seed for the simulation's random numbers, which all peers have to agree on
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn peer_players(&self) -> Vec<PlayerId> {
+        self.peers.iter().map(|peer| peer.player_id).collect()
+    }
+
+    /// Adds a peer whose player is already known, without a handshake
+    pub fn add_peer(&mut self, player_id: PlayerId, connection: Box<dyn Connection>) {
+        self.peers.push(Peer {
+            player_id: player_id,
+            connection: connection,
+        });
+    }
+
+    /// Introduces ourselves over a new connection, waits for the peer to do the same, and adds it
+    pub fn handshake(&mut self, mut connection: Box<dyn Connection>) -> io::Result<PlayerId> {
+        connection.send(&Message::Hello {
+                player_id: self.local_player,
+                turn_latency: self.turn_latency,
+                seed: self.seed,
+            }
+            .encode())?;
+
+        let started = Instant::now();
+        let bytes = loop {
+            if let Some(bytes) = connection.receive()? {
+                break bytes;
+            }
+            if started.elapsed() > HANDSHAKE_TIMEOUT {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "peer never introduced itself"));
+            }
+            thread::sleep(Duration::from_millis(1));
+        };
+
+        match Message::decode(&bytes)? {
+            Message::Hello { player_id, turn_latency, seed } => {
+                if turn_latency != self.turn_latency {
+                    return Err(invalid_data("peer is using a different turn latency"));
+                }
+                if seed != self.seed {
+                    return Err(invalid_data("peer is using a different random seed"));
+                }
+                if player_id == self.local_player || self.peer_players().contains(&player_id) {
+                    return Err(invalid_data("peer is playing as a player that's already taken"));
+                }
+                self.add_peer(player_id, connection);
+                Ok(player_id)
+            }
+            _ => Err(invalid_data("expected a hello from the peer")),
+        }
+    }
+
+    /// Connects to a peer that is listening for us over TCP
+    pub fn connect_to<A: ToSocketAddrs>(&mut self, address: A) -> io::Result<PlayerId> {
+        let connection = TcpConnection::connect(address)?;
+        self.handshake(Box::new(connection))
+    }
+
+    /// Waits for the next peer to connect to the listener
+    pub fn accept_from(&mut self, listener: &TcpListener) -> io::Result<PlayerId> {
+        let connection = TcpConnection::accept(listener)?;
+        self.handshake(Box::new(connection))
+    }
+
+    /// Receives whatever the peers have sent so far. Errors mean that a peer has
+    /// disconnected or misbehaved, and the game can't go on.
+    pub fn poll(&mut self) -> io::Result<()> {
+        if let Some(err) = self.deferred_error.take() {
+            return Err(err);
+        }
+
+        let mut checked_turns = Vec::new();
+        for peer in &mut self.peers {
+            while let Some(bytes) = peer.connection.receive()? {
+                match Message::decode(&bytes)? {
+                    Message::Turn(turn_actions) => {
+                        if turn_actions.player_id != peer.player_id {
+                            return Err(invalid_data("peer sent actions for a different player"));
+                        }
+                        if turn_actions.turn < self.next_turn {
+                            return Err(invalid_data("peer sent actions for a turn that already happened"));
+                        }
+                        self.pending
+                            .entry(turn_actions.turn)
+                            .or_insert_with(BTreeMap::new)
+                            .insert(turn_actions.player_id, turn_actions.actions);
+                    }
+                    Message::Checksum { turn, player_id, checksum } => {
+                        if player_id != peer.player_id {
+                            return Err(invalid_data("peer sent a checksum for a different player"));
+                        }
+                        self.checksums.entry(turn).or_insert_with(Default::default).peers.push((player_id, checksum));
+                        checked_turns.push(turn);
+                    }
+                    Message::Hello { .. } => return Err(invalid_data("peer introduced itself twice")),
+                }
+            }
+        }
+        for turn in checked_turns {
+            self.check_checksums(turn)?;
+        }
+        Ok(())
+    }
+
+    /// Records the simulation checksum after a tick has been run. At the end of each turn, it's
+    /// sent to the peers, and a mismatch with any of theirs (or with the replay being played back)
+    /// is reported as an error by `poll`.
+    pub fn record_checksum(&mut self, checksum: u64) {
+        if self.tick == 0 || self.tick % TURN_LENGTH_TICKS != 0 {
+            return;
+        }
+        let turn = self.next_turn - 1;
+        if let Some(ref mut writer) = self.replay_writer {
+            if let Err(err) = writer.write_checksum(turn, checksum) {
+                println!("Stopped recording the replay: {}", err);
+                self.replay_writer = None;
+            }
+        }
+        let expected = self.playback.as_ref().and_then(|playback| playback.checksums.get(&turn).cloned());
+        if expected.map_or(false, |expected| expected != checksum) {
+            let reason = format!("simulation went out of sync with the replay during turn {}", turn);
+            self.deferred_error.get_or_insert(io::Error::new(io::ErrorKind::InvalidData, reason));
+        }
+        if self.peers.is_empty() {
+            return;
+        }
+
+        let message = Message::Checksum {
+                turn: turn,
+                player_id: self.local_player,
+                checksum: checksum,
+            }
+            .encode();
+        for peer in &mut self.peers {
+            if let Err(err) = peer.connection.send(&message) {
+                self.deferred_error.get_or_insert(err);
+            }
+        }
+        self.checksums.entry(turn).or_insert_with(Default::default).local = Some(checksum);
+        if let Err(err) = self.check_checksums(turn) {
+            self.deferred_error.get_or_insert(err);
+        }
+    }
+
+    fn check_checksums(&mut self, turn: u32) -> io::Result<()> {
+        let finished = match self.checksums.get(&turn) {
+            Some(&TurnChecksums { local: Some(local), ref peers }) => {
+                if let Some(&(player_id, _)) = peers.iter().find(|&&(_, checksum)| checksum != local) {
+                    let reason = format!("simulation went out of sync with player {} during turn {}", *player_id, turn);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+                }
+                peers.len() == self.peers.len()
+            }
+            _ => false,
+        };
+        if finished {
+            self.checksums.remove(&turn);
+        }
+        Ok(())
+    }
+
+    /// Players whose actions haven't arrived yet for the turn that's about to start. The
+    /// simulation has to wait for them before running another tick.
+    pub fn waiting_for(&self) -> Vec<PlayerId> {
+        if self.tick % TURN_LENGTH_TICKS != 0 || self.next_turn < self.turn_latency {
+            return Vec::new();
+        }
+        let received = self.pending.get(&self.next_turn);
+        self.peer_players()
+            .into_iter()
+            .filter(|player_id| received.map_or(true, |r| !r.contains_key(player_id)))
+            .collect()
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        !self.waiting_for().is_empty()
+    }
+
+    /// Advances by one tick. When a turn starts, `local_actions` is asked for the actions issued
+    /// locally since the previous turn (which are scheduled `turn_latency` turns ahead), and every
+    /// player's actions for the new turn are returned, keyed by the player who issued them.
+    /// Must not be called while waiting for peers.
+    pub fn tick<F>(&mut self, local_actions: F) -> Option<ActionBatch>
+        where F: FnOnce() -> HashMap<EntityHandle, Vec<Action>>
+    {
+        let actions = if self.tick % TURN_LENGTH_TICKS == 0 {
+            Some(self.start_turn(local_actions()))
+        } else {
+            None
+        };
+        self.tick += 1;
+        actions
+    }
+
+    fn start_turn(&mut self, local_actions: HashMap<EntityHandle, Vec<Action>>) -> ActionBatch {
+        assert!(!self.is_waiting(), "tried to start a turn without every peer's actions");
+
+        let turn = self.next_turn;
+        if let Some(ref mut playback) = self.playback {
+            self.next_turn += 1;
+            return playback.turns.remove(&turn).unwrap_or_default();
+        }
+
+        let scheduled = TurnActions {
+            turn: turn + self.turn_latency,
+            player_id: self.local_player,
+            actions: local_actions.into_iter().collect(),
+        };
+        let message = Message::Turn(scheduled.clone()).encode();
+        for peer in &mut self.peers {
+            if let Err(err) = peer.connection.send(&message) {
+                // Reported by the next poll
+                self.deferred_error.get_or_insert(err);
+            }
+        }
+        self.pending
+            .entry(scheduled.turn)
+            .or_insert_with(BTreeMap::new)
+            .insert(scheduled.player_id, scheduled.actions);
+
+        self.next_turn += 1;
+        let mut batch = self.pending.remove(&turn).unwrap_or_default();
+        batch.retain(|_, actions| !actions.is_empty());
+        if let Some(ref mut writer) = self.replay_writer {
+            if let Err(err) = writer.write_turn(turn, &batch) {
+                println!("Stopped recording the replay: {}", err);
+                self.replay_writer = None;
+            }
+        }
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActionBatch, Lockstep, TURN_LENGTH_TICKS};
+    use crate::action::Action;
+    use crate::ecs::resource::EntityHandle;
+    use crate::identifier::PlayerId;
+    use crate::net::connection::MemoryConnection;
+    use crate::replay::{Replay, ReplayHeader};
+
+    use std::collections::{BTreeMap, HashMap};
+    use std::io::{self, Cursor, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Barrier, Mutex};
+    use std::thread;
+
+    type Executed = Vec<(u64, ActionBatch)>;
+
+    fn player(id: usize) -> PlayerId {
+        id.into()
+    }
+
+    fn entity(index: u32) -> EntityHandle {
+        EntityHandle {
+            index: index,
+            generation: 1,
+        }
+    }
+
+    /// Each player clears the queue of the entity with the same index as their player ID on turn 0
+    fn turn_zero_actions(lockstep: &Lockstep, tick: u64) -> HashMap<EntityHandle, Vec<Action>> {
+        let mut actions = HashMap::new();
+        if tick == 0 {
+            actions.insert(entity(*lockstep.local_player() as u32), vec![Action::ClearQueue]);
+        }
+        actions
+    }
+
+    /// Runs the peers in turns (one tick each, when they aren't waiting) until they've all run `ticks` ticks
+    fn run_in_step(peers: &mut Vec<Lockstep>, ticks: u64) -> Vec<Executed> {
+        let mut executed: Vec<Executed> = peers.iter().map(|_| Vec::new()).collect();
+        let mut ticks_run = vec![0u64; peers.len()];
+        while ticks_run.iter().any(|t| *t < ticks) {
+            for (index, lockstep) in peers.iter_mut().enumerate() {
+                lockstep.poll().unwrap();
+                if ticks_run[index] < ticks && !lockstep.is_waiting() {
+                    let tick = ticks_run[index];
+                    let actions = turn_zero_actions(lockstep, tick);
+                    if let Some(actions) = lockstep.tick(|| actions) {
+                        executed[index].push((tick, actions));
+                    }
+                    ticks_run[index] += 1;
+                }
+            }
+        }
+        executed
+    }
+
+    #[test]
+    fn test_single_player_has_no_delay() {
+        let mut lockstep = Lockstep::new(player(1), 0, 0);
+        let mut actions = HashMap::new();
+        actions.insert(entity(5), vec![Action::ClearQueue]);
+        let mut expected = BTreeMap::new();
+        expected.insert(player(1), actions.clone().into_iter().collect());
+        assert_eq!(Some(expected), lockstep.tick(|| actions));
+        for _ in 1..TURN_LENGTH_TICKS {
+            assert_eq!(None, lockstep.tick(|| panic!("only asked for actions at the start of a turn")));
+        }
+        assert_eq!(Some(BTreeMap::new()), lockstep.tick(HashMap::new));
+    }
+
+    #[test]
+    fn test_peers_execute_same_actions() {
+        let (first, second) = MemoryConnection::pair();
+        let mut peers = vec![Lockstep::new(player(1), 2, 0), Lockstep::new(player(2), 2, 0)];
+        peers[0].add_peer(player(2), Box::new(first));
+        peers[1].add_peer(player(1), Box::new(second));
+
+        let executed = run_in_step(&mut peers, 4 * TURN_LENGTH_TICKS);
+        assert_eq!(executed[0], executed[1]);
+
+        // Both players' actions from turn 0 take effect on turn 2
+        let turn_two = &executed[0][2];
+        assert_eq!(2 * TURN_LENGTH_TICKS, turn_two.0);
+        assert_eq!(vec![Action::ClearQueue], turn_two.1[&player(1)][&entity(1)]);
+        assert_eq!(vec![Action::ClearQueue], turn_two.1[&player(2)][&entity(2)]);
+        assert!(executed[0].iter().filter(|e| e.0 != turn_two.0).all(|e| e.1.is_empty()));
+    }
+
+    #[test]
+    fn test_resume_continues_the_same() {
+        let mut lockstep = Lockstep::new(player(1), 2, 9);
+        let mut executed = Vec::new();
+        let mut resumed = None;
+        for tick in 0..(5 * TURN_LENGTH_TICKS) {
+            if tick == TURN_LENGTH_TICKS + 3 {
+                resumed = Some(Lockstep::resume(lockstep.turn_state()));
+            }
+            let actions = turn_zero_actions(&lockstep, tick);
+            if let Some(actions) = lockstep.tick(|| actions) {
+                executed.push((tick, actions));
+            }
+        }
+
+        // The action from turn 0 is still pending when resumed, and comes out on turn 2 all the same
+        let mut resumed = resumed.unwrap();
+        assert_eq!(TURN_LENGTH_TICKS + 3, resumed.turn_state().tick);
+        let resumed_executed: Vec<_> = ((TURN_LENGTH_TICKS + 3)..(5 * TURN_LENGTH_TICKS))
+            .filter_map(|tick| resumed.tick(HashMap::new).map(|actions| (tick, actions)))
+            .collect();
+        assert_eq!(&executed[2..], &resumed_executed[..]);
+        assert_eq!(vec![Action::ClearQueue], resumed_executed[0].1[&player(1)][&entity(1)]);
+    }
+
+    #[test]
+    fn test_waits_for_late_peer() {
+        let (first, _second) = MemoryConnection::pair();
+        let mut lockstep = Lockstep::new(player(1), 1, 0);
+        lockstep.add_peer(player(2), Box::new(first));
+
+        // Turn 0 doesn't need anything from the peer, but turn 1 does
+        for _ in 0..TURN_LENGTH_TICKS {
+            assert!(!lockstep.is_waiting());
+            lockstep.tick(HashMap::new);
+        }
+        lockstep.poll().unwrap();
+        assert_eq!(vec![player(2)], lockstep.waiting_for());
+    }
+
+    #[test]
+    fn test_detects_desync() {
+        let (first, second) = MemoryConnection::pair();
+        let mut peers = vec![Lockstep::new(player(1), 1, 0), Lockstep::new(player(2), 1, 0)];
+        peers[0].add_peer(player(2), Box::new(first));
+        peers[1].add_peer(player(1), Box::new(second));
+
+        // Both agree on turn 0, but not on turn 1
+        for tick in 0..(2 * TURN_LENGTH_TICKS) {
+            for (index, lockstep) in peers.iter_mut().enumerate() {
+                lockstep.poll().unwrap();
+                lockstep.tick(HashMap::new);
+                let checksum = if tick < TURN_LENGTH_TICKS { 100 } else { 200 + index as u64 };
+                lockstep.record_checksum(checksum);
+            }
+        }
+        assert!(!peers[0].checksums.contains_key(&0));
+        let err = peers[0].poll().unwrap_err();
+        assert_eq!("simulation went out of sync with player 2 during turn 1", err.to_string());
+        assert!(peers[1].poll().is_err());
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_and_play_back() {
+        let header = ReplayHeader {
+            scenario_name: "test.scn".to_string(),
+            scenario_checksum: 1,
+            seed: 5,
+            player_id: player(3),
+        };
+        let buffer = SharedBuffer::default();
+        let mut recording = Lockstep::new(player(3), 0, 5);
+        recording.record_to(buffer.clone(), &header).unwrap();
+
+        let mut recorded = Vec::new();
+        for tick in 0..(3 * TURN_LENGTH_TICKS) {
+            let mut actions = HashMap::new();
+            if tick == TURN_LENGTH_TICKS {
+                actions.insert(entity(4), vec![Action::ClearQueue]);
+            }
+            recorded.push(recording.tick(|| actions));
+            recording.record_checksum(tick);
+        }
+
+        let bytes = buffer.0.lock().unwrap().clone();
+        let replay = Replay::read_from(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(header, replay.header);
+        assert_eq!(2, replay.last_turn());
+
+        // Local actions are ignored, and the recorded ones come back on the same ticks
+        let mut playback = Lockstep::playback(&replay);
+        assert_eq!((player(3), 5), (playback.local_player(), playback.seed()));
+        for tick in 0..(3 * TURN_LENGTH_TICKS) {
+            let mut local = HashMap::new();
+            local.insert(entity(9), vec![Action::ClearQueue]);
+            assert_eq!(recorded[tick as usize], playback.tick(|| local));
+            playback.record_checksum(if tick == 2 * TURN_LENGTH_TICKS - 1 { 0 } else { tick });
+        }
+        let err = playback.poll().unwrap_err();
+        assert_eq!("simulation went out of sync with the replay during turn 1", err.to_string());
+    }
+
+    #[test]
+    fn test_peers_over_loopback() {
+        const PEERS: usize = 3;
+        let listeners: Vec<TcpListener> = (0..PEERS).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect();
+        let addresses: Vec<_> = listeners.iter().map(|l| l.local_addr().unwrap()).collect();
+        let finished = Arc::new(Barrier::new(PEERS));
+
+        // Every peer connects to the ones before it, and accepts connections from the ones after it
+        let threads: Vec<_> = listeners.into_iter()
+            .enumerate()
+            .map(|(index, listener)| {
+                let earlier = addresses[0..index].to_vec();
+                let finished = finished.clone();
+                thread::spawn(move || {
+                    let mut lockstep = Lockstep::new(player(index + 1), 2, 0);
+                    for address in earlier {
+                        lockstep.connect_to(address).unwrap();
+                    }
+                    for _ in (index + 1)..PEERS {
+                        lockstep.accept_from(&listener).unwrap();
+                    }
+
+                    let mut executed: Executed = Vec::new();
+                    let mut tick = 0;
+                    while tick < 5 * TURN_LENGTH_TICKS {
+                        lockstep.poll().unwrap();
+                        if lockstep.is_waiting() {
+                            thread::yield_now();
+                            continue;
+                        }
+                        let actions = turn_zero_actions(&lockstep, tick);
+                        if let Some(actions) = lockstep.tick(|| actions) {
+                            executed.push((tick, actions));
+                        }
+                        tick += 1;
+                    }
+
+                    // Disconnecting counts as an error, so don't hang up on peers that are still going
+                    finished.wait();
+                    executed
+                })
+            })
+            .collect();
+
+        let executed: Vec<Executed> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+        for peer_executed in &executed[1..] {
+            assert_eq!(&executed[0], peer_executed);
+        }
+        let turn_two = &executed[0][2].1;
+        assert_eq!(PEERS, turn_two.len());
+    }
+}
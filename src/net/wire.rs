@@ -0,0 +1,204 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//! Binary encoding of the messages peers send to each other. Everything is little endian.
+
+use crate::action::Action;
+use crate::ecs::resource::EntityHandle;
+use crate::identifier::PlayerId;
+use crate::util::binary::{BinaryReader, BinaryWriter, invalid_data};
+
+use std::collections::BTreeMap;
+use std::io;
+
+const HELLO_MESSAGE: u8 = 0;
+const TURN_MESSAGE: u8 = 1;
+const CHECKSUM_MESSAGE: u8 = 2;
+
+/// The actions one player issued that are to be executed on a given turn
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TurnActions {
+    pub turn: u32,
+    pub player_id: PlayerId,
+    pub actions: BTreeMap<EntityHandle, Vec<Action>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Message {
+    /// First message sent over a new connection, to say which player is on the other end
+    /// (and to make sure both ends agree on the turn latency and random seed)
+    Hello {
+        player_id: PlayerId,
+        turn_latency: u32,
+        seed: u64,
+    },
+    Turn(TurnActions),
+    /// Checksum of the sender's simulation at the end of a turn, to catch peers going out of sync
+    Checksum {
+        turn: u32,
+        player_id: PlayerId,
+        checksum: u64,
+    },
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = BinaryWriter::new();
+        match *self {
+            Message::Hello { player_id, turn_latency, seed } => {
+                writer.u8(HELLO_MESSAGE);
+                writer.u8(*player_id);
+                writer.u32(turn_latency);
+                writer.u64(seed);
+            }
+            Message::Turn(ref turn_actions) => {
+                writer.u8(TURN_MESSAGE);
+                writer.u32(turn_actions.turn);
+                writer.u8(*turn_actions.player_id);
+                writer.u32(turn_actions.actions.len() as u32);
+                for (entity, actions) in &turn_actions.actions {
+                    writer.u32(entity.index);
+                    writer.i32(entity.generation);
+                    writer.u32(actions.len() as u32);
+                    for action in actions {
+                        writer.action(action);
+                    }
+                }
+            }
+            Message::Checksum { turn, player_id, checksum } => {
+                writer.u8(CHECKSUM_MESSAGE);
+                writer.u32(turn);
+                writer.u8(*player_id);
+                writer.u64(checksum);
+            }
+        }
+        writer.into_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Message> {
+        let mut reader = BinaryReader::new(bytes);
+        let message = match reader.u8()? {
+            HELLO_MESSAGE => {
+                Message::Hello {
+                    player_id: (reader.u8()? as usize).into(),
+                    turn_latency: reader.u32()?,
+                    seed: reader.u64()?,
+                }
+            }
+            TURN_MESSAGE => {
+                let turn = reader.u32()?;
+                let player_id = (reader.u8()? as usize).into();
+                let mut actions = BTreeMap::new();
+                for _ in 0..reader.u32()? {
+                    let entity = EntityHandle {
+                        index: reader.u32()?,
+                        generation: reader.i32()?,
+                    };
+                    let action_count = reader.u32()?;
+                    let entity_actions = (0..action_count).map(|_| reader.action()).collect::<io::Result<_>>()?;
+                    actions.insert(entity, entity_actions);
+                }
+                Message::Turn(TurnActions {
+                    turn: turn,
+                    player_id: player_id,
+                    actions: actions,
+                })
+            }
+            CHECKSUM_MESSAGE => {
+                Message::Checksum {
+                    turn: reader.u32()?,
+                    player_id: (reader.u8()? as usize).into(),
+                    checksum: reader.u64()?,
+                }
+            }
+            _ => return Err(invalid_data("unknown message type")),
+        };
+        if !reader.is_empty() {
+            return Err(invalid_data("unexpected bytes after the end of the message"));
+        }
+        Ok(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Message, TurnActions};
+    use crate::action::{Action, AttackParams, BuildParams, CancelTrainingParams, GatherParams, MoveToPositionParams,
+                        ResearchParams, SetRallyPointParams, TrainParams};
+    use crate::ecs::resource::EntityHandle;
+    use crate::types::{Fixed, Vector3};
+
+    fn entity(index: u32) -> EntityHandle {
+        EntityHandle {
+            index: index,
+            generation: 2,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut turn_actions = TurnActions {
+            turn: 42,
+            player_id: 3usize.into(),
+            ..Default::default()
+        };
+        let path = vec![Vector3::new(1.into(), Fixed::from(2.5), 0.into()),
+                        Vector3::new((-4).into(), 5.into(), Fixed::from(0.125))];
+        turn_actions.actions.insert(entity(7),
+                                    vec![Action::ClearQueue,
+                                         Action::MoveToPosition(MoveToPositionParams::new(path))]);
+        turn_actions.actions.insert(entity(2),
+                                    vec![Action::ClearQueue, Action::Attack(AttackParams::new(7))]);
+        turn_actions.actions.insert(entity(9), vec![Action::Gather(GatherParams::new(2))]);
+        let site = Vector3::new(Fixed::from(4.5), Fixed::from(7.5), 1.into());
+        turn_actions.actions.insert(entity(4), vec![Action::Build(BuildParams::new(109usize.into(), site))]);
+        turn_actions.actions.insert(entity(5),
+                                    vec![Action::Train(TrainParams::new(83usize.into())),
+                                         Action::CancelTraining(CancelTrainingParams::new(1)),
+                                         Action::SetRallyPoint(SetRallyPointParams::new(site))]);
+        turn_actions.actions.insert(entity(6),
+                                    vec![Action::Research(ResearchParams::new(16usize.into())),
+                                         Action::CancelResearch]);
+
+        for message in vec![Message::Hello {
+                                 player_id: 5usize.into(),
+                                 turn_latency: 3,
+                                 seed: 77,
+                             },
+                             Message::Turn(turn_actions),
+                             Message::Checksum {
+                                 turn: 12,
+                                 player_id: 2usize.into(),
+                                 checksum: 0xfedcba9876543210,
+                             }] {
+            let bytes = message.encode();
+            assert_eq!(message, Message::decode(&bytes).unwrap());
+
+            // Truncated and padded messages are rejected
+            assert!(Message::decode(&bytes[0..(bytes.len() - 1)]).is_err());
+            let mut padded = bytes.clone();
+            padded.push(0);
+            assert!(Message::decode(&padded).is_err());
+        }
+        assert!(Message::decode(&[9]).is_err());
+    }
+}
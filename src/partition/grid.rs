@@ -99,15 +99,19 @@ impl GridPartition {
     /// Tells the grid where an entity is so that it can be queried later
     pub fn update_entity(&mut self, entity_id: u32, position: &Vector2<i32>) {
         let cell_key = self.cell_key(&position);
-        if !self.entities.contains_key(&entity_id) {
-            self.entities.insert(entity_id, cell_key);
-        } else {
-            let old_cell_key = *self.entities.get(&entity_id).unwrap();
+        if let Some(old_cell_key) = self.entities.insert(entity_id, cell_key) {
             self.remove_from_cell(old_cell_key, entity_id);
         }
         self.add_to_cell(cell_key, GridEntity::new(entity_id, *position));
     }
 
+    /// Forgets about an entity, such as one that's about to be deleted
+    pub fn remove_entity(&mut self, entity_id: u32) {
+        if let Some(cell_key) = self.entities.remove(&entity_id) {
+            self.remove_from_cell(cell_key, entity_id);
+        }
+    }
+
     /// Returns the entity IDs that lie in the cells overlapped by the given bounds
     /// Note: the returned entity IDs can lie outside of the bounds
     pub fn query(&self, start_position: &Vector2<i32>, end_position: &Vector2<i32>) -> HashSet<u32> {
@@ -232,6 +236,15 @@ mod tests {
         assert!(grid.cell_mut(CellKey::new(0, 0)).entities().is_empty());
         assert_eq!(&vec![entity2], grid.cell_mut(CellKey::new(0, 1)).entities());
         assert_eq!(&vec![entity1], grid.cell_mut(CellKey::new(1, 2)).entities());
+
+        grid.update_entity(1, &v(35, 15));
+        assert!(grid.cell_mut(CellKey::new(1, 2)).entities().is_empty());
+
+        grid.remove_entity(1);
+        grid.remove_entity(1); // shouldn't panic
+        assert!(!grid.contains(1));
+        assert!(grid.cell_mut(CellKey::new(1, 3)).entities().is_empty());
+        assert_eq!(&vec![entity2], grid.cell_mut(CellKey::new(0, 1)).entities());
     }
 
     #[test]
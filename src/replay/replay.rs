@@ -0,0 +1,260 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::ecs::resource::StateHasher;
+use crate::identifier::PlayerId;
+use crate::net::{ActionBatch, Message, TurnActions};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Write};
+use std::path::Path;
+
+const REPLAY_MAGIC: &'static [u8; 4] = b"CHRP";
+const REPLAY_VERSION: u32 = 2;
+
+/// Identifies the game that was recorded, so that it can be set up the same way for playback
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReplayHeader {
+    /// File name of the scenario, for showing to the user
+    pub scenario_name: String,
+    /// Checksum of the scenario file's contents, from `scenario_checksum`
+    pub scenario_checksum: u64,
+    /// Seed for the simulation's random numbers
+    pub seed: u64,
+    /// Player that the replay was recorded by, and whose view it is played back from
+    pub player_id: PlayerId,
+}
+
+/// A recorded game: every action executed by every player, by the turn it was executed on,
+/// and the simulation checksum at the end of each turn to make sure playback matches
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub turns: BTreeMap<u32, ActionBatch>,
+    pub checksums: BTreeMap<u32, u64>,
+}
+
+impl Replay {
+    pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> io::Result<Replay> {
+        let file = File::open(file_name.as_ref())?;
+        Replay::read_from(&mut BufReader::new(file))
+    }
+
+    pub fn read_from<R: Read>(stream: &mut R) -> io::Result<Replay> {
+        let mut magic = [0u8; 4];
+        stream.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(invalid_data("not a replay file"));
+        }
+        if read_u32(stream)? != REPLAY_VERSION {
+            return Err(invalid_data("unsupported replay version"));
+        }
+
+        let mut scenario_name = vec![0u8; read_u32(stream)? as usize];
+        stream.read_exact(&mut scenario_name)?;
+        let header = ReplayHeader {
+            scenario_name: String::from_utf8(scenario_name).map_err(|_| invalid_data("bad scenario name"))?,
+            scenario_checksum: read_u64(stream)?,
+            seed: read_u64(stream)?,
+            player_id: (read_u8(stream)? as usize).into(),
+        };
+
+        let mut replay = Replay {
+            header: header,
+            turns: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+        };
+        loop {
+            // The recording may have been cut short by a crash, so stop at the first partial record
+            let length = match read_u32(stream) {
+                Ok(length) => length as usize,
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            };
+            let mut message = vec![0u8; length];
+            match stream.read_exact(&mut message) {
+                Ok(_) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(err) => return Err(err),
+            }
+            match Message::decode(&message)? {
+                Message::Turn(turn_actions) => {
+                    replay.turns
+                        .entry(turn_actions.turn)
+                        .or_insert_with(BTreeMap::new)
+                        .insert(turn_actions.player_id, turn_actions.actions);
+                }
+                Message::Checksum { turn, checksum, .. } => {
+                    replay.checksums.insert(turn, checksum);
+                }
+                Message::Hello { .. } => return Err(invalid_data("unexpected message in replay")),
+            }
+        }
+        Ok(replay)
+    }
+
+    /// The last turn that anything was recorded for
+    pub fn last_turn(&self) -> u32 {
+        let last_actions = self.turns.keys().next_back().cloned().unwrap_or(0);
+        let last_checksum = self.checksums.keys().next_back().cloned().unwrap_or(0);
+        last_actions.max(last_checksum)
+    }
+}
+
+/// Writes a replay as the game goes, so that it's usable even if the game crashes
+pub struct ReplayWriter<W: Write> {
+    stream: W,
+    player_id: PlayerId,
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(mut stream: W, header: &ReplayHeader) -> io::Result<ReplayWriter<W>> {
+        stream.write_all(REPLAY_MAGIC)?;
+        stream.write_all(&REPLAY_VERSION.to_le_bytes())?;
+        stream.write_all(&(header.scenario_name.len() as u32).to_le_bytes())?;
+        stream.write_all(header.scenario_name.as_bytes())?;
+        stream.write_all(&header.scenario_checksum.to_le_bytes())?;
+        stream.write_all(&header.seed.to_le_bytes())?;
+        stream.write_all(&[*header.player_id])?;
+        Ok(ReplayWriter {
+            stream: stream,
+            player_id: header.player_id,
+        })
+    }
+
+    /// Records the actions executed at the start of a turn, one record for each player who
+    /// issued any. Turns without any are skipped.
+    pub fn write_turn(&mut self, turn: u32, batch: &ActionBatch) -> io::Result<()> {
+        for (player_id, actions) in batch {
+            if actions.is_empty() {
+                continue;
+            }
+            let message = Message::Turn(TurnActions {
+                turn: turn,
+                player_id: *player_id,
+                actions: actions.clone(),
+            });
+            self.write_message(&message)?;
+        }
+        Ok(())
+    }
+
+    /// Records the simulation checksum at the end of a turn, and flushes the turn to the stream
+    pub fn write_checksum(&mut self, turn: u32, checksum: u64) -> io::Result<()> {
+        let message = Message::Checksum {
+            turn: turn,
+            player_id: self.player_id,
+            checksum: checksum,
+        };
+        self.write_message(&message)?;
+        self.stream.flush()
+    }
+
+    fn write_message(&mut self, message: &Message) -> io::Result<()> {
+        let bytes = message.encode();
+        self.stream.write_all(&(bytes.len() as u32).to_le_bytes())?;
+        self.stream.write_all(&bytes)
+    }
+}
+
+/// Checksum of a scenario file's contents, to make sure that a replay is played back
+/// on the same scenario it was recorded on
+pub fn scenario_checksum<P: AsRef<Path>>(file_name: P) -> io::Result<u64> {
+    let mut contents = Vec::new();
+    File::open(file_name.as_ref())?.read_to_end(&mut contents)?;
+    let mut hasher = StateHasher::new();
+    hasher.write_bytes(&contents);
+    Ok(hasher.finish())
+}
+
+fn read_u8<R: Read>(stream: &mut R) -> io::Result<u8> {
+    let mut bytes = [0u8; 1];
+    stream.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u32<R: Read>(stream: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    stream.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(stream: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    stream.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn invalid_data(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Replay, ReplayHeader, ReplayWriter};
+    use crate::action::Action;
+    use crate::ecs::resource::EntityHandle;
+
+    use std::collections::BTreeMap;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_write_read_round_trip() {
+        let header = ReplayHeader {
+            scenario_name: "MUF7E5_1.SCN".to_string(),
+            scenario_checksum: 0x1234,
+            seed: 99,
+            player_id: 2usize.into(),
+        };
+        let entity = EntityHandle {
+            index: 8,
+            generation: 1,
+        };
+        let mut actions = BTreeMap::new();
+        actions.insert(entity, vec![Action::ClearQueue]);
+        let mut turn_three = BTreeMap::new();
+        turn_three.insert(1usize.into(), actions.clone());
+        turn_three.insert(4usize.into(), actions);
+
+        let mut writer = ReplayWriter::new(Vec::new(), &header).unwrap();
+        writer.write_turn(0, &BTreeMap::new()).unwrap();
+        writer.write_checksum(0, 10).unwrap();
+        writer.write_turn(3, &turn_three).unwrap();
+        writer.write_checksum(3, 13).unwrap();
+        let mut bytes = writer.stream;
+
+        let replay = Replay::read_from(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(header, replay.header);
+        assert_eq!(vec![3], replay.turns.keys().cloned().collect::<Vec<_>>());
+        assert_eq!(turn_three, replay.turns[&3]);
+        assert_eq!(vec![(0, 10), (3, 13)], replay.checksums.into_iter().collect::<Vec<_>>());
+        assert_eq!(3, Replay::read_from(&mut Cursor::new(&bytes)).unwrap().last_turn());
+
+        // A replay cut off in the middle of a record still has everything before it
+        bytes.pop();
+        let replay = Replay::read_from(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(vec![(0, 10)], replay.checksums.into_iter().collect::<Vec<_>>());
+
+        assert!(Replay::read_from(&mut Cursor::new(b"CHRQ")).is_err());
+    }
+}
@@ -0,0 +1,1286 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+use crate::action::Action;
+use crate::dat::ResourceType;
+use crate::ecs::{ActionQueueComponent, AttackActionComponent, BuildActionComponent, CameraComponent,
+                 ConstructionComponent, DeathComponent, DeathStage, DecalComponent, GatherActionComponent, GatherStage,
+                 GraphicComponent, HealthComponent, MoveToPositionActionComponent, ProjectileComponent,
+                 ResearchComponent, ResourceComponent, SelectedUnitComponent, TargetPath,
+                 TrainingQueueComponent, TransformComponent, UnitComponent, VelocityComponent};
+use crate::ecs::resource::{ActionBatcher, EntityHandle, KilledUnits, OccupiedTiles, Player, Players, Random,
+                           SimulationChecksum, Technologies, Terrain, Tile};
+use crate::identifier::{PlayerId, ResearchId};
+use crate::net::{Lockstep, TurnState};
+use crate::partition::GridPartition;
+use crate::resource::DrsKey;
+use crate::util::binary::{BinaryReader, BinaryWriter, invalid_data};
+use nalgebra::Vector2;
+use specs::{self, Entity, Index, Join};
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const SAVE_MAGIC: &'static [u8; 4] = b"CHSV";
+
+/// Bumped whenever the format changes. Saves from other versions are refused rather than
+/// loaded into a game that would play out differently.
+const SAVE_VERSION: u32 = 1;
+
+/// Identifies the scenario that a game was saved from, since the saved game only makes sense on it
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveHeader {
+    /// File name of the scenario, for showing to the user
+    pub scenario_name: String,
+    /// Checksum of the scenario file's contents, from `replay::scenario_checksum`
+    pub scenario_checksum: u64,
+}
+
+/// What a saved attack, gathering, construction or projectile is aimed at: the index of the target,
+/// or `None` if the target had already been deleted
+pub type SavedTarget = Option<Index>;
+
+/// The components of one entity, by the index it had in the world
+#[derive(Clone, Debug, Default)]
+pub struct SavedEntity {
+    pub index: Index,
+    pub unit: Option<UnitComponent>,
+    pub transform: Option<TransformComponent>,
+    pub velocity: Option<VelocityComponent>,
+    pub action_queue: Option<ActionQueueComponent>,
+    pub move_to_position: Option<MoveToPositionActionComponent>,
+    pub attack: Option<AttackActionComponent<SavedTarget>>,
+    pub gather: Option<GatherActionComponent<SavedTarget>>,
+    pub build: Option<BuildActionComponent<SavedTarget>>,
+    pub health: Option<HealthComponent>,
+    pub resource: Option<ResourceComponent>,
+    pub construction: Option<ConstructionComponent>,
+    pub training_queue: Option<TrainingQueueComponent>,
+    pub research: Option<ResearchComponent>,
+    pub projectile: Option<ProjectileComponent<SavedTarget>>,
+    pub death: Option<DeathComponent>,
+    pub graphic: Option<GraphicComponent>,
+    pub decal: Option<DecalComponent>,
+    pub selected: bool,
+    pub camera: bool,
+}
+
+/// A snapshot of a running game, taken between ticks. Loading it into a world built from the same
+/// scenario picks the simulation up exactly where it was, so that it plays out the same as if it
+/// had never stopped.
+#[derive(Clone, Debug)]
+pub struct SaveGame {
+    pub header: SaveHeader,
+    pub turn_state: TurnState,
+    /// Number of ticks run, and the checksum of the last one
+    pub tick: u64,
+    pub checksum: u64,
+    pub random_state: u64,
+    pub players: Vec<Player>,
+    /// Research each player has finished, in the order it finished in
+    pub researched: Vec<(PlayerId, Vec<ResearchId>)>,
+    /// Actions issued locally since the current turn started, which go out with the next one
+    pub batched_actions: BTreeMap<EntityHandle, Vec<Action>>,
+    /// Units killed and lost by each player
+    pub kill_totals: Vec<(PlayerId, u32, u32)>,
+    pub occupied_tiles: Vec<(i32, i32)>,
+    pub terrain_width: i32,
+    pub terrain_height: i32,
+    pub terrain_tiles: Vec<Tile>,
+    /// Every living entity, in index order
+    pub entities: Vec<SavedEntity>,
+}
+
+impl SaveGame {
+    /// Takes a snapshot of the world. Must be called between ticks.
+    pub fn capture(header: SaveHeader, world: &specs::World) -> SaveGame {
+        let simulation_checksum = world.read_resource::<SimulationChecksum>();
+        let players = world.read_resource::<Players>();
+        let action_batcher = world.read_resource::<ActionBatcher>();
+        let occupied_tiles = world.read_resource::<OccupiedTiles>();
+        let terrain = world.read_resource::<Terrain>();
+
+        let mut occupied: Vec<_> = occupied_tiles.tiles.iter().cloned().collect();
+        occupied.sort();
+
+        let living: HashSet<EntityHandle> = (&world.entities()).iter().map(EntityHandle::new).collect();
+        let mut turn_state = world.read_resource::<Lockstep>().turn_state();
+        for players in turn_state.pending.values_mut() {
+            for actions in players.values_mut() {
+                *actions = saved_actions(&living, actions.iter());
+            }
+        }
+
+        SaveGame {
+            header: header,
+            turn_state: turn_state,
+            tick: simulation_checksum.tick,
+            checksum: simulation_checksum.checksum,
+            random_state: world.read_resource::<Random>().state(),
+            players: players.all().into_iter().cloned().collect(),
+            researched: world.read_resource::<Technologies>()
+                .all()
+                .map(|(player_id, player)| (player_id, player.researched().to_vec()))
+                .collect(),
+            batched_actions: saved_actions(&living, action_batcher.actions()),
+            kill_totals: world.read_resource::<KilledUnits>().totals(),
+            occupied_tiles: occupied,
+            terrain_width: terrain.width(),
+            terrain_height: terrain.height(),
+            terrain_tiles: terrain.tiles().to_vec(),
+            entities: capture_entities(world),
+        }
+    }
+
+    /// Loads the snapshot into a world that has had its components registered and its resources
+    /// added, but no entities created yet. The `Lockstep` resource isn't touched, and should be
+    /// created from `turn_state` beforehand.
+    pub fn restore(&self, world: &mut specs::World) -> io::Result<()> {
+        if (&world.entities()).iter().next().is_some() {
+            return Err(invalid_data("saved games must be loaded into a world without entities"));
+        }
+
+        {
+            let mut simulation_checksum = world.write_resource::<SimulationChecksum>();
+            simulation_checksum.tick = self.tick;
+            simulation_checksum.checksum = self.checksum;
+        }
+        *world.write_resource::<Random>() = Random::from_state(self.random_state);
+
+        let mut players = Players::new();
+        for player in &self.players {
+            players.add_player(player.clone(), player.player_id == self.turn_state.local_player);
+        }
+        *world.write_resource::<Players>() = players;
+
+        {
+            // The players' resources were saved with the research already applied, so only the
+            // unit stats and the research that's been disabled need to be worked out again
+            let mut technologies = world.write_resource::<Technologies>();
+            for (player_id, researched) in &self.researched {
+                technologies.restore(*player_id, researched);
+            }
+        }
+
+        {
+            let mut action_batcher = world.write_resource::<ActionBatcher>();
+            for (entity, actions) in &self.batched_actions {
+                for action in actions {
+                    action_batcher.queue(*entity, action.clone());
+                }
+            }
+        }
+
+        {
+            let mut killed_units = world.write_resource::<KilledUnits>();
+            for &(player_id, kills, losses) in &self.kill_totals {
+                killed_units.set_totals(player_id, kills, losses);
+            }
+        }
+
+        world.write_resource::<OccupiedTiles>().tiles = self.occupied_tiles.iter().cloned().collect();
+        world.write_resource::<Terrain>()
+            .replace_tiles(self.terrain_width, self.terrain_height, self.terrain_tiles.clone());
+
+        restore_entities(world, &self.entities)
+    }
+
+    pub fn read_from_file<P: AsRef<Path>>(file_name: P) -> io::Result<SaveGame> {
+        let file = File::open(file_name.as_ref())?;
+        SaveGame::read_from(&mut BufReader::new(file))
+    }
+
+    pub fn read_from<R: Read>(stream: &mut R) -> io::Result<SaveGame> {
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes)?;
+        let mut reader = BinaryReader::new(&bytes);
+        if reader.bytes(SAVE_MAGIC.len()).ok() != Some(&SAVE_MAGIC[..]) {
+            return Err(invalid_data("not a saved game"));
+        }
+        let version = reader.u32()?;
+        if version < SAVE_VERSION {
+            let reason = format!("saved by an older version of the game (save format {}, but this version \
+                                  can only load format {})",
+                                 version,
+                                 SAVE_VERSION);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
+        } else if version > SAVE_VERSION {
+            return Err(invalid_data("saved by a newer version of the game"));
+        }
+
+        let header = SaveHeader {
+            scenario_name: reader.string()?,
+            scenario_checksum: reader.u64()?,
+        };
+        let turn_state = read_turn_state(&mut reader)?;
+        let (tick, checksum, random_state) = (reader.u64()?, reader.u64()?, reader.u64()?);
+
+        let mut players = Vec::new();
+        for _ in 0..reader.u32()? {
+            let mut player = Player::new(reader.string()?,
+                                         player_id(reader.u8()?),
+                                         (reader.u8()? as usize).into(),
+                                         (reader.u8()? as usize).into());
+            for _ in 0..reader.u32()? {
+                let resource_type = read_resource_type(&mut reader)?;
+                player.resources.insert(resource_type, reader.fixed()?);
+            }
+            players.push(player);
+        }
+        let mut researched = Vec::new();
+        for _ in 0..reader.u32()? {
+            let player_id = player_id(reader.u8()?);
+            let research_ids = (0..reader.u32()?)
+                .map(|_| reader.u32().map(|research_id| (research_id as usize).into()))
+                .collect::<io::Result<_>>()?;
+            researched.push((player_id, research_ids));
+        }
+        let batched_actions = read_entity_actions(&mut reader)?;
+
+        let mut kill_totals = Vec::new();
+        for _ in 0..reader.u32()? {
+            kill_totals.push((player_id(reader.u8()?), reader.u32()?, reader.u32()?));
+        }
+
+        let mut occupied_tiles = Vec::new();
+        for _ in 0..reader.u32()? {
+            occupied_tiles.push((reader.i32()?, reader.i32()?));
+        }
+
+        let (terrain_width, terrain_height) = (reader.i32()?, reader.i32()?);
+        if terrain_width < 0 || terrain_height < 0 {
+            return Err(invalid_data("bad terrain size"));
+        }
+        let mut terrain_tiles = Vec::new();
+        for _ in 0..(terrain_width as u64 * terrain_height as u64) {
+            terrain_tiles.push(Tile::new((reader.u8()? as usize).into(), reader.u8()?));
+        }
+
+        let mut entities = Vec::new();
+        for _ in 0..reader.u32()? {
+            entities.push(read_entity(&mut reader)?);
+        }
+        if entities.windows(2).any(|pair| pair[0].index >= pair[1].index) {
+            return Err(invalid_data("entities out of order"));
+        }
+        if !reader.is_empty() {
+            return Err(invalid_data("unexpected bytes after the end of the saved game"));
+        }
+
+        Ok(SaveGame {
+            header: header,
+            turn_state: turn_state,
+            tick: tick,
+            checksum: checksum,
+            random_state: random_state,
+            players: players,
+            researched: researched,
+            batched_actions: batched_actions,
+            kill_totals: kill_totals,
+            occupied_tiles: occupied_tiles,
+            terrain_width: terrain_width,
+            terrain_height: terrain_height,
+            terrain_tiles: terrain_tiles,
+            entities: entities,
+        })
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(&self, file_name: P) -> io::Result<()> {
+        let mut file = BufWriter::new(File::create(file_name.as_ref())?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write_to<W: Write>(&self, stream: &mut W) -> io::Result<()> {
+        let mut writer = BinaryWriter::new();
+        writer.bytes(SAVE_MAGIC);
+        writer.u32(SAVE_VERSION);
+        writer.string(&self.header.scenario_name);
+        writer.u64(self.header.scenario_checksum);
+        write_turn_state(&mut writer, &self.turn_state);
+        writer.u64(self.tick);
+        writer.u64(self.checksum);
+        writer.u64(self.random_state);
+
+        writer.u32(self.players.len() as u32);
+        for player in &self.players {
+            writer.string(&player.name);
+            writer.u8(*player.player_id);
+            writer.u8(*player.player_color_id);
+            writer.u8(*player.civ_id);
+            writer.u32(player.resources.len() as u32);
+            for (resource_type, amount) in &player.resources {
+                write_resource_type(&mut writer, *resource_type);
+                writer.fixed(*amount);
+            }
+        }
+        writer.u32(self.researched.len() as u32);
+        for (player_id, research_ids) in &self.researched {
+            writer.u8(**player_id);
+            writer.u32(research_ids.len() as u32);
+            for research_id in research_ids {
+                writer.u32(**research_id);
+            }
+        }
+        write_entity_actions(&mut writer, &self.batched_actions);
+
+        writer.u32(self.kill_totals.len() as u32);
+        for &(player_id, kills, losses) in &self.kill_totals {
+            writer.u8(*player_id);
+            writer.u32(kills);
+            writer.u32(losses);
+        }
+
+        writer.u32(self.occupied_tiles.len() as u32);
+        for &(row, col) in &self.occupied_tiles {
+            writer.i32(row);
+            writer.i32(col);
+        }
+
+        writer.i32(self.terrain_width);
+        writer.i32(self.terrain_height);
+        for tile in &self.terrain_tiles {
+            writer.u8(*tile.terrain_id);
+            writer.u8(tile.elevation);
+        }
+
+        writer.u32(self.entities.len() as u32);
+        for entity in &self.entities {
+            write_entity(&mut writer, entity);
+        }
+        stream.write_all(&writer.into_bytes())
+    }
+}
+
+/// Orders waiting to be executed, as they'll be for the restored entities. Those get their generations
+/// back from scratch when the game is loaded, and orders for entities that are already gone are dropped.
+fn saved_actions<'a, I>(living: &HashSet<EntityHandle>, actions: I) -> BTreeMap<EntityHandle, Vec<Action>>
+    where I: IntoIterator<Item = (&'a EntityHandle, &'a Vec<Action>)>
+{
+    actions.into_iter()
+        .filter(|&(entity, _)| living.contains(entity))
+        .map(|(entity, actions)| (EntityHandle::restored(entity.index), actions.clone()))
+        .collect()
+}
+
+fn capture_entities(world: &specs::World) -> Vec<SavedEntity> {
+    let entities = world.entities();
+    let units = world.read::<UnitComponent>();
+    let transforms = world.read::<TransformComponent>();
+    let velocities = world.read::<VelocityComponent>();
+    let action_queues = world.read::<ActionQueueComponent>();
+    let mtps = world.read::<MoveToPositionActionComponent>();
+    let attacks = world.read::<AttackActionComponent>();
+    let gathers = world.read::<GatherActionComponent>();
+    let builds = world.read::<BuildActionComponent>();
+    let healths = world.read::<HealthComponent>();
+    let resources = world.read::<ResourceComponent>();
+    let constructions = world.read::<ConstructionComponent>();
+    let training_queues = world.read::<TrainingQueueComponent>();
+    let researches = world.read::<ResearchComponent>();
+    let projectiles = world.read::<ProjectileComponent>();
+    let deaths = world.read::<DeathComponent>();
+    let graphics = world.read::<GraphicComponent>();
+    let decals = world.read::<DecalComponent>();
+    let selected_units = world.read::<SelectedUnitComponent>();
+    let cameras = world.read::<CameraComponent>();
+
+    let saved_target = |target: Entity| if world.is_alive(target) { Some(target.get_id()) } else { None };
+    let mut saved: Vec<SavedEntity> = (&entities)
+        .iter()
+        .map(|entity| {
+            SavedEntity {
+                index: entity.get_id(),
+                unit: units.get(entity).cloned(),
+                transform: transforms.get(entity).cloned(),
+                velocity: velocities.get(entity).cloned(),
+                action_queue: action_queues.get(entity).cloned(),
+                move_to_position: mtps.get(entity).cloned(),
+                attack: attacks.get(entity).map(|attack| attack.with_target(saved_target(attack.target))),
+                gather: gathers.get(entity).map(|gather| gather.with_target(saved_target(gather.target))),
+                build: builds.get(entity).map(|build| build.with_target(build.target.map(saved_target))),
+                health: healths.get(entity).cloned(),
+                resource: resources.get(entity).cloned(),
+                construction: constructions.get(entity).cloned(),
+                training_queue: training_queues.get(entity).cloned(),
+                research: researches.get(entity).cloned(),
+                projectile: projectiles.get(entity)
+                    .map(|projectile| projectile.with_target(saved_target(projectile.target))),
+                death: deaths.get(entity).cloned(),
+                graphic: graphics.get(entity).cloned(),
+                decal: decals.get(entity).cloned(),
+                selected: selected_units.get(entity).is_some(),
+                camera: cameras.get(entity).is_some(),
+            }
+        })
+        .collect();
+    saved.sort_by_key(|entity| entity.index);
+    saved
+}
+
+/// Recreates the entities with the same indices they were saved with, since actions refer to
+/// entities by index. Indices that were free when the game was saved are created and then
+/// deleted again, so that the world hands out the same indices to new entities from then on.
+fn restore_entities(world: &mut specs::World, saved: &[SavedEntity]) -> io::Result<()> {
+    // Every entity is created before any components go in, since attacks, gathering, construction
+    // and projectiles can be aimed at entities further along
+    let entity_count = saved.last().map_or(0, |saved_entity| saved_entity.index + 1);
+    let mut created: Vec<Entity> = Vec::new();
+    for index in 0..entity_count {
+        let entity = world.create_now().build();
+        if entity.get_id() != index {
+            return Err(invalid_data("saved games must be loaded into a world without entities"));
+        }
+        created.push(entity);
+    }
+    let mut unused: Vec<Entity> = created.iter()
+        .filter(|entity| !saved.iter().any(|saved_entity| saved_entity.index == entity.get_id()))
+        .cloned()
+        .collect();
+
+    // Targets that had been deleted get an entity that's deleted again, so they stay missed
+    let restored = |target: SavedTarget| target.and_then(|index| created.get(index as usize).cloned());
+    let aimed_at_nothing = saved.iter().any(|saved_entity| {
+        saved_entity.attack.as_ref().map_or(false, |attack| restored(attack.target).is_none()) ||
+        saved_entity.gather.as_ref().map_or(false, |gather| restored(gather.target).is_none()) ||
+        saved_entity.build
+            .as_ref()
+            .and_then(|build| build.target)
+            .map_or(false, |target| restored(target).is_none()) ||
+        saved_entity.projectile.as_ref().map_or(false, |projectile| restored(projectile.target).is_none())
+    });
+    let deleted_target = if aimed_at_nothing {
+        let entity = world.create_now().build();
+        unused.push(entity);
+        Some(entity)
+    } else {
+        None
+    };
+    let target = |target: SavedTarget| restored(target).or(deleted_target).unwrap();
+
+    for saved_entity in saved {
+        let entity = created[saved_entity.index as usize];
+        if let Some(ref unit) = saved_entity.unit {
+            world.write::<UnitComponent>().insert(entity, unit.clone());
+        }
+        if let Some(ref transform) = saved_entity.transform {
+            world.write::<TransformComponent>().insert(entity, transform.clone());
+        }
+        if let Some(ref velocity) = saved_entity.velocity {
+            world.write::<VelocityComponent>().insert(entity, velocity.clone());
+        }
+        if let Some(ref action_queue) = saved_entity.action_queue {
+            world.write::<ActionQueueComponent>().insert(entity, action_queue.clone());
+        }
+        if let Some(ref mtp) = saved_entity.move_to_position {
+            world.write::<MoveToPositionActionComponent>().insert(entity, mtp.clone());
+        }
+        if let Some(ref attack) = saved_entity.attack {
+            world.write::<AttackActionComponent>().insert(entity, attack.with_target(target(attack.target)));
+        }
+        if let Some(ref gather) = saved_entity.gather {
+            world.write::<GatherActionComponent>().insert(entity, gather.with_target(target(gather.target)));
+        }
+        if let Some(ref build) = saved_entity.build {
+            world.write::<BuildActionComponent>().insert(entity, build.with_target(build.target.map(target)));
+        }
+        if let Some(ref health) = saved_entity.health {
+            world.write::<HealthComponent>().insert(entity, health.clone());
+        }
+        if let Some(ref resource) = saved_entity.resource {
+            world.write::<ResourceComponent>().insert(entity, resource.clone());
+        }
+        if let Some(ref construction) = saved_entity.construction {
+            world.write::<ConstructionComponent>().insert(entity, construction.clone());
+        }
+        if let Some(ref training_queue) = saved_entity.training_queue {
+            world.write::<TrainingQueueComponent>().insert(entity, training_queue.clone());
+        }
+        if let Some(ref research) = saved_entity.research {
+            world.write::<ResearchComponent>().insert(entity, research.clone());
+        }
+        if let Some(ref projectile) = saved_entity.projectile {
+            world.write::<ProjectileComponent>()
+                .insert(entity, projectile.with_target(target(projectile.target)));
+        }
+        if let Some(ref death) = saved_entity.death {
+            world.write::<DeathComponent>().insert(entity, death.clone());
+        }
+        if let Some(ref graphic) = saved_entity.graphic {
+            world.write::<GraphicComponent>().insert(entity, graphic.clone());
+        }
+        if let Some(ref decal) = saved_entity.decal {
+            world.write::<DecalComponent>().insert(entity, decal.clone());
+        }
+        if saved_entity.selected {
+            world.write::<SelectedUnitComponent>().insert(entity, SelectedUnitComponent);
+        }
+        if saved_entity.camera {
+            world.write::<CameraComponent>().insert(entity, CameraComponent);
+        }
+
+        // The velocity system treats anything missing from the grid as having just been created,
+        // so moving entities that were already placed would have their rotation reset
+        if let (Some(transform), Some(_)) = (saved_entity.transform.as_ref(), saved_entity.velocity.as_ref()) {
+            let position = transform.position();
+            world.write_resource::<GridPartition>()
+                .update_entity(entity.get_id(), &Vector2::new(position.x.into(), position.y.into()));
+        }
+    }
+    for entity in unused {
+        world.delete_now(entity);
+    }
+    Ok(())
+}
+
+fn player_id(value: u8) -> PlayerId {
+    (value as usize).into()
+}
+
+fn write_turn_state(writer: &mut BinaryWriter, turn_state: &TurnState) {
+    writer.u8(*turn_state.local_player);
+    writer.u32(turn_state.turn_latency);
+    writer.u64(turn_state.seed);
+    writer.u64(turn_state.tick);
+    writer.u32(turn_state.next_turn);
+    writer.u32(turn_state.pending.len() as u32);
+    for (turn, players) in &turn_state.pending {
+        writer.u32(*turn);
+        writer.u32(players.len() as u32);
+        for (player_id, actions) in players {
+            writer.u8(**player_id);
+            write_entity_actions(writer, actions);
+        }
+    }
+}
+
+fn read_turn_state(reader: &mut BinaryReader) -> io::Result<TurnState> {
+    let mut turn_state = TurnState {
+        local_player: player_id(reader.u8()?),
+        turn_latency: reader.u32()?,
+        seed: reader.u64()?,
+        tick: reader.u64()?,
+        next_turn: reader.u32()?,
+        pending: BTreeMap::new(),
+    };
+    for _ in 0..reader.u32()? {
+        let turn = reader.u32()?;
+        let mut players = BTreeMap::new();
+        for _ in 0..reader.u32()? {
+            let player_id = player_id(reader.u8()?);
+            players.insert(player_id, read_entity_actions(reader)?);
+        }
+        turn_state.pending.insert(turn, players);
+    }
+    Ok(turn_state)
+}
+
+fn write_actions(writer: &mut BinaryWriter, actions: &[Action]) {
+    writer.u32(actions.len() as u32);
+    for action in actions {
+        writer.action(action);
+    }
+}
+
+fn read_actions(reader: &mut BinaryReader) -> io::Result<Vec<Action>> {
+    let count = reader.u32()?;
+    (0..count).map(|_| reader.action()).collect()
+}
+
+fn write_entity_actions(writer: &mut BinaryWriter, actions: &BTreeMap<EntityHandle, Vec<Action>>) {
+    writer.u32(actions.len() as u32);
+    for (entity, entity_actions) in actions {
+        writer.u32(entity.index);
+        write_actions(writer, entity_actions);
+    }
+}
+
+fn read_entity_actions(reader: &mut BinaryReader) -> io::Result<BTreeMap<EntityHandle, Vec<Action>>> {
+    let mut actions = BTreeMap::new();
+    for _ in 0..reader.u32()? {
+        let entity = EntityHandle::restored(reader.u32()?);
+        actions.insert(entity, read_actions(reader)?);
+    }
+    Ok(actions)
+}
+
+fn write_drs_key(writer: &mut BinaryWriter, drs_key: DrsKey) {
+    writer.u8(match drs_key {
+        DrsKey::Border => 0,
+        DrsKey::Graphics => 1,
+        DrsKey::Interfac => 2,
+        DrsKey::Sounds => 3,
+        DrsKey::Terrain => 4,
+    });
+}
+
+fn read_drs_key(reader: &mut BinaryReader) -> io::Result<DrsKey> {
+    match reader.u8()? {
+        0 => Ok(DrsKey::Border),
+        1 => Ok(DrsKey::Graphics),
+        2 => Ok(DrsKey::Interfac),
+        3 => Ok(DrsKey::Sounds),
+        4 => Ok(DrsKey::Terrain),
+        _ => Err(invalid_data("unknown DRS file")),
+    }
+}
+
+fn write_resource_type(writer: &mut BinaryWriter, resource_type: ResourceType) {
+    writer.u16(resource_type.to_i16() as u16);
+}
+
+fn read_resource_type(reader: &mut BinaryReader) -> io::Result<ResourceType> {
+    Ok(ResourceType::from_i16(reader.u16()? as i16))
+}
+
+fn write_target(writer: &mut BinaryWriter, target: SavedTarget) {
+    writer.bool(target.is_some());
+    if let Some(index) = target {
+        writer.u32(index);
+    }
+}
+
+fn read_target(reader: &mut BinaryReader) -> io::Result<SavedTarget> {
+    Ok(if reader.bool()? { Some(reader.u32()?) } else { None })
+}
+
+fn write_target_path(writer: &mut BinaryWriter, target_path: &Option<TargetPath>) {
+    writer.bool(target_path.is_some());
+    if let Some(ref target_path) = *target_path {
+        writer.vector(&target_path.destination);
+        writer.u32(target_path.path.len() as u32);
+        for node in &target_path.path {
+            writer.vector(node);
+        }
+    }
+}
+
+fn read_target_path(reader: &mut BinaryReader) -> io::Result<Option<TargetPath>> {
+    if !reader.bool()? {
+        return Ok(None);
+    }
+    let destination = reader.vector()?;
+    let node_count = reader.u32()?;
+    let path = (0..node_count).map(|_| reader.vector()).collect::<io::Result<_>>()?;
+    Ok(Some(TargetPath::new(destination, path)))
+}
+
+fn write_entity(writer: &mut BinaryWriter, entity: &SavedEntity) {
+    writer.u32(entity.index);
+
+    writer.bool(entity.unit.is_some());
+    if let Some(ref unit) = entity.unit {
+        writer.u8(*unit.player_id);
+        writer.u8(*unit.civilization_id);
+        writer.u32(*unit.unit_id);
+    }
+
+    writer.bool(entity.transform.is_some());
+    if let Some(ref transform) = entity.transform {
+        writer.vector(transform.position());
+        writer.vector(transform.last_position());
+        writer.fixed(transform.rotation);
+    }
+
+    writer.bool(entity.velocity.is_some());
+    if let Some(ref velocity) = entity.velocity {
+        writer.vector(&velocity.velocity);
+    }
+
+    writer.bool(entity.action_queue.is_some());
+    if let Some(ref action_queue) = entity.action_queue {
+        write_actions(writer, action_queue.queued_actions());
+        writer.bool(action_queue.current_action().is_some());
+        if let Some(ref action) = *action_queue.current_action() {
+            writer.action(action);
+        }
+        writer.bool(action_queue.current_action_done());
+    }
+
+    writer.bool(entity.move_to_position.is_some());
+    if let Some(ref mtp) = entity.move_to_position {
+        writer.u32(mtp.path.len() as u32);
+        for node in &mtp.path {
+            writer.vector(node);
+        }
+    }
+
+    writer.bool(entity.attack.is_some());
+    if let Some(ref attack) = entity.attack {
+        write_target(writer, attack.target);
+        writer.fixed(attack.reload_time_left);
+    }
+
+    writer.bool(entity.gather.is_some());
+    if let Some(ref gather) = entity.gather {
+        write_target(writer, gather.target);
+        writer.u8(match gather.stage {
+            GatherStage::ToResource => 0,
+            GatherStage::Gathering => 1,
+            GatherStage::ToDropSite => 2,
+        });
+        writer.bool(gather.resource_type.is_some());
+        if let Some(resource_type) = gather.resource_type {
+            write_resource_type(writer, resource_type);
+        }
+        writer.fixed(gather.carried);
+        write_target_path(writer, &gather.target_path);
+    }
+
+    writer.bool(entity.build.is_some());
+    if let Some(ref build) = entity.build {
+        writer.u32(*build.unit_id);
+        writer.vector(&build.position);
+        writer.bool(build.target.is_some());
+        if let Some(target) = build.target {
+            write_target(writer, target);
+        }
+        write_target_path(writer, &build.target_path);
+    }
+
+    writer.bool(entity.health.is_some());
+    if let Some(ref health) = entity.health {
+        writer.i32(health.hit_points);
+        writer.i32(health.max_hit_points);
+        writer.bool(health.last_attacker_id.is_some());
+        if let Some(last_attacker_id) = health.last_attacker_id {
+            writer.u8(*last_attacker_id);
+        }
+    }
+
+    writer.bool(entity.resource.is_some());
+    if let Some(ref resource) = entity.resource {
+        write_resource_type(writer, resource.resource_type);
+        writer.fixed(resource.amount);
+    }
+
+    writer.bool(entity.construction.is_some());
+    if let Some(ref construction) = entity.construction {
+        writer.fixed(construction.progress);
+    }
+
+    writer.bool(entity.training_queue.is_some());
+    if let Some(ref training_queue) = entity.training_queue {
+        write_actions(writer, &training_queue.orders);
+        writer.u32(training_queue.units.len() as u32);
+        for unit_id in &training_queue.units {
+            writer.u32(**unit_id);
+        }
+        writer.fixed(training_queue.elapsed_time);
+        writer.bool(training_queue.rally_point.is_some());
+        if let Some(ref rally_point) = training_queue.rally_point {
+            writer.vector(rally_point);
+        }
+    }
+
+    writer.bool(entity.research.is_some());
+    if let Some(ref research) = entity.research {
+        write_actions(writer, &research.orders);
+        writer.bool(research.research_id.is_some());
+        if let Some(research_id) = research.research_id {
+            writer.u32(*research_id);
+        }
+        writer.fixed(research.elapsed_time);
+    }
+
+    writer.bool(entity.projectile.is_some());
+    if let Some(ref projectile) = entity.projectile {
+        writer.u8(*projectile.player_id);
+        writer.u8(*projectile.civilization_id);
+        writer.u32(*projectile.attacker_unit_id);
+        write_target(writer, projectile.target);
+        writer.bool(projectile.hits);
+        writer.vector(&projectile.start);
+        writer.vector(&projectile.end);
+        writer.fixed(projectile.arc_height);
+        writer.fixed(projectile.flight_time);
+        writer.fixed(projectile.elapsed_time);
+    }
+
+    writer.bool(entity.death.is_some());
+    if let Some(ref death) = entity.death {
+        writer.u8(match death.stage {
+            DeathStage::Dying => 0,
+            DeathStage::Decaying => 1,
+            DeathStage::Dead => 2,
+        });
+        writer.fixed(death.time_left);
+    }
+
+    writer.bool(entity.graphic.is_some());
+    if let Some(ref graphic) = entity.graphic {
+        writer.u8(*graphic.player_color_id);
+        writer.bool(graphic.graphic_id.is_some());
+        if let Some(graphic_id) = graphic.graphic_id {
+            writer.u32(*graphic_id);
+        }
+        writer.u16(graphic.frame);
+        writer.fixed(graphic.frame_time);
+        writer.bool(graphic.flip_horizontal);
+        writer.bool(graphic.flip_vertical);
+        writer.bool(graphic.damage_graphic_id.is_some());
+        if let Some(damage_graphic_id) = graphic.damage_graphic_id {
+            writer.u32(*damage_graphic_id);
+        }
+    }
+
+    writer.bool(entity.decal.is_some());
+    if let Some(ref decal) = entity.decal {
+        writer.u8(*decal.player_color_id);
+        write_drs_key(writer, decal.drs_key);
+        writer.u32(*decal.slp_file_id);
+        writer.u16(decal.frame);
+        writer.fixed(decal.frame_time);
+    }
+
+    writer.bool(entity.selected);
+    writer.bool(entity.camera);
+}
+
+fn read_entity(reader: &mut BinaryReader) -> io::Result<SavedEntity> {
+    let mut entity = SavedEntity { index: reader.u32()?, ..Default::default() };
+
+    if reader.bool()? {
+        entity.unit = Some(UnitComponent::new(player_id(reader.u8()?),
+                                              (reader.u8()? as usize).into(),
+                                              (reader.u32()? as usize).into()));
+    }
+
+    if reader.bool()? {
+        let (position, last_position) = (reader.vector()?, reader.vector()?);
+        entity.transform = Some(TransformComponent::from_parts(position, last_position, reader.fixed()?));
+    }
+
+    if reader.bool()? {
+        entity.velocity = Some(VelocityComponent { velocity: reader.vector()? });
+    }
+
+    if reader.bool()? {
+        let actions = read_actions(reader)?;
+        let current_action = if reader.bool()? { Some(reader.action()?) } else { None };
+        entity.action_queue = Some(ActionQueueComponent::from_parts(actions, current_action, reader.bool()?));
+    }
+
+    if reader.bool()? {
+        let node_count = reader.u32()?;
+        let path = (0..node_count).map(|_| reader.vector()).collect::<io::Result<_>>()?;
+        entity.move_to_position = Some(MoveToPositionActionComponent::new(path));
+    }
+
+    if reader.bool()? {
+        let mut attack = AttackActionComponent::new(read_target(reader)?);
+        attack.reload_time_left = reader.fixed()?;
+        entity.attack = Some(attack);
+    }
+
+    if reader.bool()? {
+        let mut gather = GatherActionComponent::new(read_target(reader)?);
+        gather.stage = match reader.u8()? {
+            0 => GatherStage::ToResource,
+            1 => GatherStage::Gathering,
+            2 => GatherStage::ToDropSite,
+            _ => return Err(invalid_data("unknown gather stage")),
+        };
+        gather.resource_type = if reader.bool()? { Some(read_resource_type(reader)?) } else { None };
+        gather.carried = reader.fixed()?;
+        gather.target_path = read_target_path(reader)?;
+        entity.gather = Some(gather);
+    }
+
+    if reader.bool()? {
+        let unit_id = (reader.u32()? as usize).into();
+        let mut build = BuildActionComponent::new(unit_id, reader.vector()?);
+        build.target = if reader.bool()? { Some(read_target(reader)?) } else { None };
+        build.target_path = read_target_path(reader)?;
+        entity.build = Some(build);
+    }
+
+    if reader.bool()? {
+        let mut health = HealthComponent::new(0);
+        health.hit_points = reader.i32()?;
+        health.max_hit_points = reader.i32()?;
+        health.last_attacker_id = if reader.bool()? { Some(player_id(reader.u8()?)) } else { None };
+        entity.health = Some(health);
+    }
+
+    if reader.bool()? {
+        let resource_type = read_resource_type(reader)?;
+        entity.resource = Some(ResourceComponent::new(resource_type, reader.fixed()?));
+    }
+
+    if reader.bool()? {
+        let mut construction = ConstructionComponent::new();
+        construction.progress = reader.fixed()?;
+        entity.construction = Some(construction);
+    }
+
+    if reader.bool()? {
+        let mut training_queue = TrainingQueueComponent::new();
+        training_queue.orders = read_actions(reader)?;
+        for _ in 0..reader.u32()? {
+            training_queue.units.push((reader.u32()? as usize).into());
+        }
+        training_queue.elapsed_time = reader.fixed()?;
+        training_queue.rally_point = if reader.bool()? { Some(reader.vector()?) } else { None };
+        entity.training_queue = Some(training_queue);
+    }
+
+    if reader.bool()? {
+        let mut research = ResearchComponent::new();
+        research.orders = read_actions(reader)?;
+        research.research_id = if reader.bool()? { Some((reader.u32()? as usize).into()) } else { None };
+        research.elapsed_time = reader.fixed()?;
+        entity.research = Some(research);
+    }
+
+    if reader.bool()? {
+        entity.projectile = Some(ProjectileComponent {
+            player_id: player_id(reader.u8()?),
+            civilization_id: (reader.u8()? as usize).into(),
+            attacker_unit_id: (reader.u32()? as usize).into(),
+            target: read_target(reader)?,
+            hits: reader.bool()?,
+            start: reader.vector()?,
+            end: reader.vector()?,
+            arc_height: reader.fixed()?,
+            flight_time: reader.fixed()?,
+            elapsed_time: reader.fixed()?,
+        });
+    }
+
+    if reader.bool()? {
+        let stage = match reader.u8()? {
+            0 => DeathStage::Dying,
+            1 => DeathStage::Decaying,
+            2 => DeathStage::Dead,
+            _ => return Err(invalid_data("unknown death stage")),
+        };
+        entity.death = Some(DeathComponent::new(stage, reader.fixed()?));
+    }
+
+    if reader.bool()? {
+        let mut graphic = GraphicComponent::new();
+        graphic.player_color_id = (reader.u8()? as usize).into();
+        graphic.graphic_id = if reader.bool()? { Some((reader.u32()? as usize).into()) } else { None };
+        graphic.frame = reader.u16()?;
+        graphic.frame_time = reader.fixed()?;
+        graphic.flip_horizontal = reader.bool()?;
+        graphic.flip_vertical = reader.bool()?;
+        graphic.damage_graphic_id = if reader.bool()? { Some((reader.u32()? as usize).into()) } else { None };
+        entity.graphic = Some(graphic);
+    }
+
+    if reader.bool()? {
+        let player_color_id = (reader.u8()? as usize).into();
+        let drs_key = read_drs_key(reader)?;
+        let mut decal = DecalComponent::new(player_color_id, drs_key, (reader.u32()? as usize).into());
+        decal.frame = reader.u16()?;
+        decal.frame_time = reader.fixed()?;
+        entity.decal = Some(decal);
+    }
+
+    entity.selected = reader.bool()?;
+    entity.camera = reader.bool()?;
+    Ok(entity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SaveGame, SaveHeader};
+    use crate::action::{Action, AttackParams, MoveToPositionParams, ResearchParams, TrainParams};
+    use crate::dat::{EmpiresDb, EmpiresDbRef, ResourceType};
+    use crate::ecs::{ActionQueueComponent, AttackActionComponent, BuildActionComponent, CameraComponent,
+                     ConstructionComponent, DeathComponent, DeathStage, DecalComponent, GatherActionComponent,
+                     GatherStage, GraphicComponent, HealthComponent, MoveToPositionActionComponent,
+                     ProjectileComponent, ResearchComponent, ResourceComponent, SelectedUnitComponent,
+                     TargetPath, TrainingQueueComponent, TransformComponent, UnitComponent, VelocityComponent,
+                     vector};
+    use crate::ecs::resource::{ActionBatcher, EntityHandle, KilledUnits, OccupiedTiles, Player, PlayerTechnologies,
+                               Players, Random, SimulationChecksum, Technologies, Terrain, Tile};
+    use crate::identifier::ResearchId;
+    use crate::net::Lockstep;
+    use crate::partition::GridPartition;
+    use crate::resource::DrsKey;
+    use crate::types::{Fixed, Vector3};
+    use specs::Join;
+
+    /// A world with the resources a save touches, and no entities
+    fn empty_world(lockstep: Lockstep) -> specs::World {
+        let mut world = specs::World::new();
+        world.register::<ActionQueueComponent>();
+        world.register::<AttackActionComponent>();
+        world.register::<BuildActionComponent>();
+        world.register::<CameraComponent>();
+        world.register::<ConstructionComponent>();
+        world.register::<DeathComponent>();
+        world.register::<DecalComponent>();
+        world.register::<GatherActionComponent>();
+        world.register::<GraphicComponent>();
+        world.register::<HealthComponent>();
+        world.register::<MoveToPositionActionComponent>();
+        world.register::<ProjectileComponent>();
+        world.register::<ResearchComponent>();
+        world.register::<ResourceComponent>();
+        world.register::<SelectedUnitComponent>();
+        world.register::<TrainingQueueComponent>();
+        world.register::<TransformComponent>();
+        world.register::<UnitComponent>();
+        world.register::<VelocityComponent>();
+
+        let tiles = (0..6).map(|_| Tile::new(0usize.into(), 0)).collect();
+        let empires = EmpiresDbRef::new(EmpiresDb::new());
+        world.add_resource(Terrain::new(3, 2, tiles, empires.clone()));
+        world.add_resource(Players::new());
+        let mut technologies = Technologies::new(empires);
+        technologies.add_player(1usize.into(), PlayerTechnologies::new(3usize.into(), false));
+        technologies.add_player(2usize.into(), PlayerTechnologies::new(4usize.into(), false));
+        world.add_resource(technologies);
+        world.add_resource(ActionBatcher::new());
+        world.add_resource(KilledUnits::new());
+        world.add_resource(Random::new(lockstep.seed()));
+        world.add_resource(lockstep);
+        world.add_resource(SimulationChecksum::new());
+        world.add_resource(OccupiedTiles::new());
+        world.add_resource(GridPartition::new(10, 10));
+        world
+    }
+
+    fn header() -> SaveHeader {
+        SaveHeader {
+            scenario_name: "test.scn".into(),
+            scenario_checksum: 0x1234,
+        }
+    }
+
+    fn encode(save: &SaveGame) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        save.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Plays a little of a game: a camera, two units (one of them moving, selected, carrying
+    /// food and about to help build, the other wounded, attacking, half built and with units
+    /// queued up to train and research underway), research already done, a projectile in flight at a
+    /// unit that's since been deleted, a decaying corpse with food left on it, the move marker and
+    /// the gap where the deleted unit used to be, with actions still waiting to be executed
+    fn played_world() -> specs::World {
+        let mut lockstep = Lockstep::new(2usize.into(), 1, 5);
+        let mut batched = ActionBatcher::new();
+        batched.queue(EntityHandle::restored(1), Action::ClearQueue);
+        lockstep.tick(|| batched.consume_actions());
+        lockstep.tick(Default::default);
+
+        let mut world = empty_world(lockstep);
+        world.write_resource::<Players>().add_player(Player::new("One".into(), 1usize.into(), 1usize.into(), 3usize.into()),
+                                                     false);
+        let mut two = Player::new("Two".into(), 2usize.into(), 2usize.into(), 4usize.into());
+        two.credit(ResourceType::Food, Fixed::from(17.5));
+        two.credit(ResourceType::Gold, 3.into());
+        world.write_resource::<Players>().add_player(two, true);
+        world.write_resource::<Technologies>().restore(2usize.into(), &[5usize.into(), 2usize.into()]);
+        world.write_resource::<OccupiedTiles>().tiles.insert((1, 2));
+        world.write_resource::<Random>().next_u32();
+        world.write_resource::<SimulationChecksum>().tick = 2;
+        let mut tiles = world.read_resource::<Terrain>().tiles().to_vec();
+        tiles[4] = Tile::new(7usize.into(), 2);
+        world.write_resource::<Terrain>().replace_tiles(3, 2, tiles);
+
+        world.create_now()
+            .with(TransformComponent::new(vector(0, 0), 0.into()))
+            .with(VelocityComponent::new())
+            .with(CameraComponent)
+            .build();
+
+        let mut queue = ActionQueueComponent::new();
+        queue.add(Action::MoveToPosition(MoveToPositionParams::new(vec![vector(2, 2)])));
+        queue.next_action();
+        queue.add(Action::ClearQueue);
+        let site = Vector3::new(Fixed::from(2.5), 1.into(), 0.into());
+        let build = BuildActionComponent::new(109usize.into(), site);
+        let mut moving = TransformComponent::new(vector(1, 1), 0.into());
+        moving.set_position(Vector3::new(Fixed::from(1.5), 1.into(), 0.into()));
+        let moving_unit = world.create_now()
+            .with(UnitComponent::new(2usize.into(), 4usize.into(), 83usize.into()))
+            .with(moving)
+            .with(VelocityComponent { velocity: Vector3::new(Fixed::from(0.5), 0.into(), 0.into()) })
+            .with(queue)
+            .with(MoveToPositionActionComponent::new(vec![vector(2, 1), vector(2, 2)]))
+            .with(build)
+            .with(GraphicComponent::new())
+            .with(HealthComponent::new(25))
+            .with(SelectedUnitComponent)
+            .build();
+
+        let removed = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 3usize.into(), 83usize.into()))
+            .build();
+        world.write_resource::<ActionBatcher>().queue_for_entity(removed, Action::ClearQueue);
+        world.write_resource::<ActionBatcher>().queue_for_entity(moving_unit, Action::ClearQueue);
+
+        let mut graphic = GraphicComponent::new();
+        graphic.graphic_id = Some(12usize.into());
+        graphic.frame = 3;
+        let mut attacking = ActionQueueComponent::new();
+        attacking.add(Action::Attack(AttackParams::new(1)));
+        attacking.next_action();
+        let mut attack = AttackActionComponent::new(moving_unit);
+        attack.reload_time_left = Fixed::from(0.75);
+        let mut health = HealthComponent::new(40);
+        health.damage(12, 2usize.into());
+        let mut construction = ConstructionComponent::new();
+        construction.progress = Fixed::from(0.5);
+        let mut training_queue = TrainingQueueComponent::new();
+        training_queue.orders.push(Action::Train(TrainParams::new(83usize.into())));
+        training_queue.units = vec![83usize.into(), 75usize.into()];
+        training_queue.elapsed_time = Fixed::from(3.25);
+        training_queue.rally_point = Some(vector(1, 2));
+        let mut research = ResearchComponent::new();
+        research.orders.push(Action::Research(ResearchParams::new(6usize.into())));
+        research.research_id = Some(4usize.into());
+        research.elapsed_time = Fixed::from(1.5);
+        let building = world.create_now()
+            .with(UnitComponent::new(1usize.into(), 3usize.into(), 109usize.into()))
+            .with(TransformComponent::new(vector(2, 0), Fixed::from(0.25)))
+            .with(attacking)
+            .with(attack)
+            .with(health)
+            .with(construction)
+            .with(training_queue)
+            .with(research)
+            .with(graphic)
+            .build();
+        world.write::<BuildActionComponent>().get_mut(moving_unit).unwrap().target = Some(building);
+
+        world.create_now()
+            .with(TransformComponent::new(Vector3::new(1.into(), Fixed::from(0.5), 1.into()), 0.into()))
+            .with(VelocityComponent { velocity: Vector3::new((-2).into(), 1.into(), 0.into()) })
+            .with(ProjectileComponent {
+                player_id: 1usize.into(),
+                civilization_id: 3usize.into(),
+                attacker_unit_id: 109usize.into(),
+                target: removed,
+                hits: false,
+                start: vector(2, 0),
+                end: Vector3::new(Fixed::from(0.75), Fixed::from(1.25), 0.into()),
+                arc_height: Fixed::from(0.5),
+                flight_time: 1.into(),
+                elapsed_time: Fixed::from(0.25),
+            })
+            .build();
+
+        let mut graphic = GraphicComponent::new();
+        graphic.damage_graphic_id = Some(40usize.into());
+        let corpse = world.create_now()
+            .with(UnitComponent::new(2usize.into(), 4usize.into(), 98usize.into()))
+            .with(TransformComponent::new(vector(3, 1), 0.into()))
+            .with(DeathComponent::new(DeathStage::Decaying, Fixed::from(4.5)))
+            .with(ResourceComponent::new(ResourceType::Food, Fixed::from(140.25)))
+            .with(graphic)
+            .build();
+        let mut gather = GatherActionComponent::new(corpse);
+        gather.stage = GatherStage::ToDropSite;
+        gather.resource_type = Some(ResourceType::Food);
+        gather.carried = Fixed::from(6.25);
+        gather.target_path = Some(TargetPath::new(vector(3, 1), vec![vector(2, 1)]));
+        world.write::<GatherActionComponent>().insert(moving_unit, gather);
+        world.write_resource::<KilledUnits>().record(2usize.into(), Some(1usize.into()));
+
+        let mut decal = DecalComponent::new(0usize.into(), DrsKey::Interfac, 50405usize.into());
+        decal.frame = 2;
+        world.create_now()
+            .with(TransformComponent::new(vector(2, 2), 0.into()))
+            .with(decal)
+            .build();
+
+        world.delete_now(removed);
+        world
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let save = SaveGame::capture(header(), &played_world());
+        assert_eq!(vec![0, 1, 3, 4, 5, 6], save.entities.iter().map(|e| e.index).collect::<Vec<_>>());
+        assert_eq!(vec![EntityHandle::restored(1)], save.batched_actions.keys().cloned().collect::<Vec<_>>());
+
+        let bytes = encode(&save);
+        let loaded = SaveGame::read_from(&mut &bytes[..]).unwrap();
+        assert_eq!(save.header, loaded.header);
+        assert_eq!(save.turn_state, loaded.turn_state);
+        assert_eq!(bytes, encode(&loaded));
+
+        // Truncated, padded, and unknown versions of files are all refused
+        assert!(SaveGame::read_from(&mut &bytes[..(bytes.len() - 1)]).is_err());
+        let mut padded = bytes.clone();
+        padded.push(0);
+        assert!(SaveGame::read_from(&mut &padded[..]).is_err());
+        let mut newer = bytes.clone();
+        newer[4] += 1;
+        assert!(SaveGame::read_from(&mut &newer[..]).is_err());
+        let mut older = bytes.clone();
+        older[4] -= 1;
+        let error = SaveGame::read_from(&mut &older[..]).unwrap_err();
+        assert!(error.to_string().starts_with("saved by an older version of the game"));
+    }
+
+    #[test]
+    fn test_restore_recreates_world() {
+        let save = SaveGame::capture(header(), &played_world());
+
+        let mut world = empty_world(Lockstep::resume(save.turn_state.clone()));
+        save.restore(&mut world).unwrap();
+        assert_eq!(encode(&save), encode(&SaveGame::capture(header(), &world)));
+        assert_eq!(2, *world.read_resource::<Players>().local_player_id());
+
+        // Aimed at the same unit as before, and at nothing if that has since been deleted
+        let attack_target = (&world.read::<AttackActionComponent>()).iter().next().unwrap().target;
+        assert_eq!(1, attack_target.get_id());
+        assert!(world.is_alive(attack_target));
+        let projectile_target = (&world.read::<ProjectileComponent>()).iter().next().unwrap().target;
+        assert!(!world.is_alive(projectile_target));
+        let gather_target = (&world.read::<GatherActionComponent>()).iter().next().unwrap().target;
+        assert_eq!(5, gather_target.get_id());
+        assert!(world.is_alive(gather_target));
+        let build_target = (&world.read::<BuildActionComponent>()).iter().next().unwrap().target.unwrap();
+        assert_eq!(3, build_target.get_id());
+        assert!(world.is_alive(build_target));
+
+        assert!(world.read_resource::<GridPartition>().contains(1));
+        assert_eq!(1, world.read_resource::<KilledUnits>().kills(1usize.into()));
+        assert_eq!(Fixed::from(17.5), world.read_resource::<Players>().local_player().resource(ResourceType::Food));
+        let researched: Vec<ResearchId> = vec![5usize.into(), 2usize.into()];
+        assert_eq!(&researched[..],
+                   world.read_resource::<Technologies>().player(2usize.into()).unwrap().researched());
+
+        // The index freed up by the deleted unit is the next one handed out, like it would have been
+        assert_eq!(2, world.create_now().build().get_id());
+    }
+
+    #[test]
+    fn test_restore_needs_empty_world() {
+        let save = SaveGame::capture(header(), &played_world());
+        let mut world = empty_world(Lockstep::resume(save.turn_state.clone()));
+        world.create_now().build();
+        assert!(save.restore(&mut world).is_err());
+    }
+}
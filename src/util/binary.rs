@@ -0,0 +1,232 @@
+// Chariot: An open source reimplementation of Age of Empires (1997)
+// Copyright (c) 2016 Kevin Fuller
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+//
+
+//! Little endian binary encoding, shared by the network protocol and saved games
+
+use crate::action::{Action, AttackParams, BuildParams, CancelTrainingParams, GatherParams, MoveToPositionParams,
+                    ResearchParams, SetRallyPointParams, TrainParams};
+use crate::types::{Fixed, Vector3};
+
+use std::io;
+
+const CLEAR_QUEUE_ACTION: u8 = 0;
+const MOVE_TO_POSITION_ACTION: u8 = 1;
+const ATTACK_ACTION: u8 = 2;
+const GATHER_ACTION: u8 = 3;
+const BUILD_ACTION: u8 = 4;
+const TRAIN_ACTION: u8 = 5;
+const CANCEL_TRAINING_ACTION: u8 = 6;
+const SET_RALLY_POINT_ACTION: u8 = 7;
+const RESEARCH_ACTION: u8 = 8;
+const CANCEL_RESEARCH_ACTION: u8 = 9;
+
+pub fn invalid_data(reason: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
+
+pub struct BinaryWriter(Vec<u8>);
+
+impl BinaryWriter {
+    pub fn new() -> BinaryWriter {
+        BinaryWriter(Vec::new())
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn fixed(&mut self, value: Fixed) {
+        self.0.extend_from_slice(&value.scaled.to_le_bytes());
+    }
+
+    pub fn vector(&mut self, value: &Vector3) {
+        self.fixed(value.x);
+        self.fixed(value.y);
+        self.fixed(value.z);
+    }
+
+    /// Length prefixed UTF-8
+    pub fn string(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn action(&mut self, action: &Action) {
+        match *action {
+            Action::ClearQueue => self.u8(CLEAR_QUEUE_ACTION),
+            Action::MoveToPosition(ref params) => {
+                self.u8(MOVE_TO_POSITION_ACTION);
+                self.u32(params.path.len() as u32);
+                for node in &params.path {
+                    self.vector(node);
+                }
+            }
+            Action::Attack(ref params) => {
+                self.u8(ATTACK_ACTION);
+                self.u32(params.target);
+            }
+            Action::Gather(ref params) => {
+                self.u8(GATHER_ACTION);
+                self.u32(params.target);
+            }
+            Action::Build(ref params) => {
+                self.u8(BUILD_ACTION);
+                self.u32(*params.unit_id);
+                self.vector(&params.position);
+            }
+            Action::Train(ref params) => {
+                self.u8(TRAIN_ACTION);
+                self.u32(*params.unit_id);
+            }
+            Action::CancelTraining(ref params) => {
+                self.u8(CANCEL_TRAINING_ACTION);
+                self.u32(params.slot);
+            }
+            Action::SetRallyPoint(ref params) => {
+                self.u8(SET_RALLY_POINT_ACTION);
+                self.vector(&params.position);
+            }
+            Action::Research(ref params) => {
+                self.u8(RESEARCH_ACTION);
+                self.u32(*params.research_id);
+            }
+            Action::CancelResearch => self.u8(CANCEL_RESEARCH_ACTION),
+        }
+    }
+}
+
+pub struct BinaryReader<'a>(&'a [u8]);
+
+impl<'a> BinaryReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> BinaryReader<'a> {
+        BinaryReader(bytes)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn bytes(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < count {
+            return Err(invalid_data("data ended early"));
+        }
+        let (bytes, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> io::Result<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("bad boolean")),
+        }
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> io::Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn fixed(&mut self) -> io::Result<Fixed> {
+        Ok(Fixed { scaled: self.u64()? as i64 })
+    }
+
+    pub fn vector(&mut self) -> io::Result<Vector3> {
+        Ok(Vector3::new(self.fixed()?, self.fixed()?, self.fixed()?))
+    }
+
+    pub fn string(&mut self) -> io::Result<String> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.bytes(length)?.to_vec()).map_err(|_| invalid_data("bad string"))
+    }
+
+    pub fn action(&mut self) -> io::Result<Action> {
+        match self.u8()? {
+            CLEAR_QUEUE_ACTION => Ok(Action::ClearQueue),
+            MOVE_TO_POSITION_ACTION => {
+                let node_count = self.u32()?;
+                let path = (0..node_count).map(|_| self.vector()).collect::<io::Result<_>>()?;
+                Ok(Action::MoveToPosition(MoveToPositionParams::new(path)))
+            }
+            ATTACK_ACTION => Ok(Action::Attack(AttackParams::new(self.u32()?))),
+            GATHER_ACTION => Ok(Action::Gather(GatherParams::new(self.u32()?))),
+            BUILD_ACTION => {
+                let unit_id = (self.u32()? as usize).into();
+                Ok(Action::Build(BuildParams::new(unit_id, self.vector()?)))
+            }
+            TRAIN_ACTION => Ok(Action::Train(TrainParams::new((self.u32()? as usize).into()))),
+            CANCEL_TRAINING_ACTION => Ok(Action::CancelTraining(CancelTrainingParams::new(self.u32()?))),
+            SET_RALLY_POINT_ACTION => Ok(Action::SetRallyPoint(SetRallyPointParams::new(self.vector()?))),
+            RESEARCH_ACTION => Ok(Action::Research(ResearchParams::new((self.u32()? as usize).into()))),
+            CANCEL_RESEARCH_ACTION => Ok(Action::CancelResearch),
+            _ => Err(invalid_data("unknown action type")),
+        }
+    }
+}
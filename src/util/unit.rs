@@ -22,9 +22,19 @@
 
 use crate::dat;
 use crate::ecs::TransformComponent;
+use crate::ecs::resource::{OccupiedTiles, Technologies, Terrain};
+use crate::ecs::resource::path_finder::PASSABILITY_THRESHOLD;
+use crate::identifier::{CivilizationId, PlayerId};
 
 use nalgebra::Vector3;
-use crate::types::AABox;
+use std::ops::Range;
+use crate::types::{AABox, Fixed, Norm};
+
+/// `UnitCommand::type_id` of commands for gathering resources
+pub const GATHER_COMMAND_TYPE: i16 = 5;
+
+/// `UnitCommand::type_id` of commands for constructing buildings
+pub const BUILD_COMMAND_TYPE: i16 = 101;
 
 pub fn selection_box(unit_info: &dat::Unit, transform: &TransformComponent) -> AABox {
     let position = transform.position();
@@ -36,12 +46,347 @@ pub fn selection_box(unit_info: &dat::Unit, transform: &TransformComponent) -> A
                             position.z))
 }
 
-pub fn collision_box(unit_info: &dat::Unit, transform: &TransformComponent) -> AABox {
-    let position = transform.position();
-    AABox::new(Vector3::new(position.x - unit_info.collision_size_x.into(),
-                            position.y - unit_info.collision_size_y.into(),
-                            position.z + unit_info.collision_size_z.into()),
-               Vector3::new(position.x + unit_info.collision_size_x.into(),
-                            position.y + unit_info.collision_size_y.into(),
-                            position.z))
+/// Damage dealt by a single strike of the attacker against the target. Each attack class
+/// is reduced by the target's armor of the same class (or its default armor, if it has
+/// none for that class), and every strike does at least one point of damage.
+pub fn attack_damage(attacker_info: &dat::Unit, target_info: &dat::Unit) -> i32 {
+    let attacks = match attacker_info.battle_params {
+        Some(ref params) => &params.attacks,
+        None => return 0,
+    };
+
+    let damage: i32 = attacks.iter()
+        .map(|&(class, amount)| {
+            let armor = match target_info.battle_params {
+                Some(ref params) => {
+                    params.armors
+                        .iter()
+                        .find(|&&(armor_class, _)| armor_class == class)
+                        .map(|&(_, armor)| armor as i32)
+                        .unwrap_or(params.default_armor as i32)
+                }
+                None => 0,
+            };
+            (amount as i32 - armor).max(0)
+        })
+        .sum();
+    damage.max(1)
+}
+
+/// Distance between the edges of two units' collision boxes on the ground plane
+pub fn edge_distance(unit_info: &dat::Unit,
+                     transform: &TransformComponent,
+                     other_info: &dat::Unit,
+                     other_transform: &TransformComponent)
+                     -> Fixed {
+    let offset = *other_transform.position() - *transform.position();
+    let offset = Vector3::new(offset.x, offset.y, 0.into());
+    let center_distance = if offset.length_squared() > 0.into() { offset.length() } else { 0.into() };
+    let radii: Fixed = Fixed::from(unit_info.collision_size_x) + other_info.collision_size_x.into();
+    (center_distance - radii).max(0.into())
+}
+
+/// Whether the unit is a resource that stays put, like a tree or a gold mine, rather than an animal
+pub fn is_immobile_resource(unit_info: &dat::Unit) -> bool {
+    if unit_info.interaction_mode != dat::InteractionMode::Resource {
+        return false;
+    }
+    match unit_info.motion_params {
+        Some(ref params) => params.speed < 0.001f32,
+        None => true,
+    }
+}
+
+/// The command the unit would use to gather the given resource from the target, if it can.
+/// Commands either name the exact unit they work on or a whole class of units.
+pub fn gather_command<'a>(unit_info: &'a dat::Unit,
+                          target_info: &dat::Unit,
+                          resource_type: dat::ResourceType)
+                          -> Option<&'a dat::UnitCommand> {
+    let params = match unit_info.commandable_params {
+        Some(ref params) => params,
+        None => return None,
+    };
+    params.commands.iter().find(|command| {
+        let applies = match command.unit_id {
+            Some(unit_id) => unit_id == target_info.id,
+            None => command.class_id == target_info.class_id,
+        };
+        command.type_id == GATHER_COMMAND_TYPE && applies &&
+        dat::ResourceType::from_i16(command.resource_in) == resource_type
+    })
+}
+
+/// The command the unit uses to construct buildings, if it can construct them at all
+pub fn build_command(unit_info: &dat::Unit) -> Option<&dat::UnitCommand> {
+    unit_info.commandable_params
+        .as_ref()
+        .and_then(|params| params.commands.iter().find(|command| command.type_id == BUILD_COMMAND_TYPE))
+}
+
+/// The buildings the builder can construct, in the order of their command panel buttons
+pub fn buildable_buildings<'a>(technologies: &'a Technologies,
+                               player_id: PlayerId,
+                               civilization_id: CivilizationId,
+                               builder_info: &dat::Unit)
+                               -> Vec<&'a dat::Unit> {
+    let mut buildings = trained_at(technologies, player_id, civilization_id, builder_info);
+    buildings.retain(|unit_info| unit_info.interaction_mode == dat::InteractionMode::Building);
+    buildings
+}
+
+/// The units the building can train, in the order of their command panel buttons
+pub fn trainable_units<'a>(technologies: &'a Technologies,
+                           player_id: PlayerId,
+                           civilization_id: CivilizationId,
+                           building_info: &dat::Unit)
+                           -> Vec<&'a dat::Unit> {
+    let mut units = trained_at(technologies, player_id, civilization_id, building_info);
+    units.retain(|unit_info| unit_info.interaction_mode != dat::InteractionMode::Building);
+    units
+}
+
+/// Everything the player makes at (or, for buildings, with) the given unit, sorted by button.
+/// Units that haven't been enabled yet (or have been disabled) by research are left out.
+fn trained_at<'a>(technologies: &'a Technologies,
+                  player_id: PlayerId,
+                  civilization_id: CivilizationId,
+                  trainer_info: &dat::Unit)
+                  -> Vec<&'a dat::Unit> {
+    let mut units: Vec<&dat::Unit> = technologies.units(player_id, civilization_id)
+        .filter(|unit_info| {
+            unit_info.enabled &&
+            unit_info.trainable_params
+                .as_ref()
+                .and_then(|params| params.train_location_id)
+                .is_some_and(|location_id| technologies.is_location(player_id, location_id, trainer_info.id))
+        })
+        .collect();
+    units.sort_by_key(|unit_info| {
+        unit_info.trainable_params.as_ref().map_or(0, |params| params.button_id)
+    });
+    units
+}
+
+/// What it costs to train (or build) the unit
+pub fn train_costs(unit_info: &dat::Unit) -> &[dat::UnitResourceCost] {
+    match unit_info.trainable_params {
+        Some(ref params) => &params.resource_costs,
+        None => &[],
+    }
+}
+
+/// Total the unit keeps in its enabled resource storage slots of the given type
+pub fn resource_storage(unit_info: &dat::Unit, resource_type: dat::ResourceType) -> Fixed {
+    unit_info.resource_storage
+        .iter()
+        .filter(|storage| storage.enabled && storage.resource_type == resource_type)
+        .fold(0.into(), |total, storage| total + storage.amount.into())
+}
+
+/// The (rows, cols) of tiles covered by the unit's collision box when it stands at `position`
+pub fn footprint(unit_info: &dat::Unit, position: &Vector3<Fixed>) -> (Range<i32>, Range<i32>) {
+    let (size_x, size_y): (Fixed, Fixed) = (unit_info.collision_size_x.into(),
+                                            unit_info.collision_size_y.into());
+    let floor = |value: Fixed| i32::from(value);
+    let ceil = |value: Fixed| -i32::from(-value);
+    (floor(position.y - size_y)..ceil(position.y + size_y),
+     floor(position.x - size_x)..ceil(position.x + size_x))
+}
+
+/// Moves `position` so that the unit's collision box lines up with the tile grid
+pub fn snap_to_tiles(unit_info: &dat::Unit, position: &Vector3<Fixed>) -> Vector3<Fixed> {
+    let (size_x, size_y): (Fixed, Fixed) = (unit_info.collision_size_x.into(),
+                                            unit_info.collision_size_y.into());
+    Vector3::new((position.x - size_x).round() + size_x,
+                 (position.y - size_y).round() + size_y,
+                 position.z)
+}
+
+/// Whether the building can be placed at `position`: every tile under it must be on the map,
+/// passable for its terrain restriction and not already taken by another building or resource
+pub fn can_place_building(empires: &dat::EmpiresDb,
+                          terrain: &Terrain,
+                          occupied_tiles: &OccupiedTiles,
+                          unit_info: &dat::Unit,
+                          position: &Vector3<Fixed>)
+                          -> bool {
+    // TODO: Buildings that straddle two kinds of terrain, like docks, need per-tile restrictions
+    let restrictions = empires.terrain_restrictions(unit_info.terrain_restriction);
+    let (rows, cols) = footprint(unit_info, position);
+    rows.clone().all(|row| {
+        cols.clone().all(|col| {
+            if row < 0 || col < 0 || row >= terrain.height() || col >= terrain.width() ||
+               occupied_tiles.tiles.contains(&(row, col)) {
+                return false;
+            }
+            let terrain_id = terrain.tile_at_row_col(row, col).terrain_id;
+            restrictions.passability(terrain_id) >= PASSABILITY_THRESHOLD
+        })
+    })
+}
+
+/// The tiles in a ring `distance` tiles out from the given rows and columns, row by row
+pub fn tiles_around(rows: &Range<i32>, cols: &Range<i32>, distance: i32) -> Vec<(i32, i32)> {
+    let mut tiles = Vec::new();
+    for row in (rows.start - distance)..(rows.end + distance) {
+        for col in (cols.start - distance)..(cols.end + distance) {
+            let inside = row > rows.start - distance && row < rows.end + distance - 1 &&
+                         col > cols.start - distance && col < cols.end + distance - 1;
+            if !inside {
+                tiles.push((row, col));
+            }
+        }
+    }
+    tiles
+}
+
+/// Where a unit coming out of the building should stand: the middle of the nearest free tile
+/// around the building's footprint that the unit can walk on, if there is one close enough
+pub fn spawn_position(empires: &dat::EmpiresDb,
+                      terrain: &Terrain,
+                      occupied_tiles: &OccupiedTiles,
+                      building_info: &dat::Unit,
+                      building_position: &Vector3<Fixed>,
+                      unit_info: &dat::Unit)
+                      -> Option<Vector3<Fixed>> {
+    const MAX_SPAWN_DISTANCE: i32 = 3;
+
+    let restrictions = empires.terrain_restrictions(unit_info.terrain_restriction);
+    let (rows, cols) = footprint(building_info, building_position);
+    (1..(MAX_SPAWN_DISTANCE + 1))
+        .flat_map(|distance| tiles_around(&rows, &cols, distance))
+        .find(|&(row, col)| {
+            row >= 0 && col >= 0 && row < terrain.height() && col < terrain.width() &&
+            !occupied_tiles.tiles.contains(&(row, col)) &&
+            restrictions.passability(terrain.tile_at_row_col(row, col).terrain_id) >= PASSABILITY_THRESHOLD
+        })
+        .map(|(row, col)| {
+            let half = Fixed::from(0.5);
+            Vector3::new(Fixed::from(col) + half, Fixed::from(row) + half, building_position.z)
+        })
+}
+
+/// Whether something at `position` is caught in a blast of the given width centered on `center`
+pub fn in_blast(center: &Vector3<Fixed>, position: &Vector3<Fixed>, blast_width: Fixed) -> bool {
+    let offset = *position - *center;
+    Vector3::new(offset.x, offset.y, 0.into()).length_squared() <= blast_width * blast_width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attack_damage, footprint, gather_command, in_blast, resource_storage, snap_to_tiles, tiles_around};
+    use crate::dat;
+    use crate::types::{Fixed, Vector3};
+
+    fn unit(attacks: Vec<(i16, i16)>, armors: Vec<(i16, i16)>, default_armor: u8) -> dat::Unit {
+        let mut battle_params = dat::BattleParams::default();
+        battle_params.attacks = attacks;
+        battle_params.armors = armors;
+        battle_params.default_armor = default_armor;
+
+        let mut unit = dat::Unit::default();
+        unit.battle_params = Some(battle_params);
+        unit
+    }
+
+    #[test]
+    fn test_attack_damage() {
+        let axeman = unit(vec![(4, 5)], vec![(4, 0)], 0);
+        let soldier = unit(vec![(4, 3)], vec![(4, 2), (3, 1)], 0);
+        let tower = unit(vec![(3, 3)], vec![], 2);
+
+        assert_eq!(3, attack_damage(&axeman, &soldier));
+        assert_eq!(3, attack_damage(&soldier, &axeman));
+        assert_eq!(3, attack_damage(&axeman, &tower));
+
+        // Armor can absorb the attack, but every strike does at least one damage
+        let archer = unit(vec![(3, 1)], vec![], 0);
+        assert_eq!(1, attack_damage(&archer, &soldier));
+
+        // Units without battle params can't attack and have no armor
+        let villager = dat::Unit::default();
+        assert_eq!(0, attack_damage(&villager, &axeman));
+        assert_eq!(5, attack_damage(&axeman, &villager));
+    }
+
+    #[test]
+    fn test_in_blast() {
+        let center = Vector3::new(2.into(), 2.into(), 0.into());
+        assert!(in_blast(&center, &Vector3::new(3.into(), 2.into(), 5.into()), 1.into()));
+        assert!(!in_blast(&center, &Vector3::new(3.into(), 3.into(), 0.into()), 1.into()));
+        assert!(in_blast(&center, &Vector3::new(3.into(), 3.into(), 0.into()), Fixed::from(1.5)));
+    }
+
+    #[test]
+    fn test_gather_command() {
+        let mut lumberjack_command = dat::UnitCommand::default();
+        lumberjack_command.type_id = 5;
+        lumberjack_command.class_id = 15;
+        lumberjack_command.resource_in = 1;
+        let mut forager_command = dat::UnitCommand::default();
+        forager_command.type_id = 5;
+        forager_command.unit_id = Some(59usize.into());
+        forager_command.resource_in = 0;
+        let mut params = dat::CommandableParams::default();
+        params.commands = vec![lumberjack_command, forager_command];
+        let mut villager = dat::Unit::default();
+        villager.commandable_params = Some(params);
+
+        let mut tree = dat::Unit::default();
+        tree.class_id = 15;
+        let mut bush = dat::Unit::default();
+        bush.id = 59usize.into();
+        bush.class_id = 7;
+
+        assert!(gather_command(&villager, &tree, dat::ResourceType::Wood).is_some());
+        assert!(gather_command(&villager, &tree, dat::ResourceType::Food).is_none());
+        assert!(gather_command(&villager, &bush, dat::ResourceType::Food).is_some());
+        assert!(gather_command(&dat::Unit::default(), &tree, dat::ResourceType::Wood).is_none());
+    }
+
+    #[test]
+    fn test_footprint_and_snapping() {
+        let mut house = dat::Unit::default();
+        house.collision_size_x = 1.0;
+        house.collision_size_y = 1.0;
+        let mut barracks = dat::Unit::default();
+        barracks.collision_size_x = 1.5;
+        barracks.collision_size_y = 1.5;
+
+        let cursor = Vector3::new(Fixed::from(4.3), Fixed::from(7.8), 0.into());
+        let house_position = snap_to_tiles(&house, &cursor);
+        assert_eq!(Vector3::new(4.into(), 8.into(), 0.into()), house_position);
+        assert_eq!((7..9, 3..5), footprint(&house, &house_position));
+
+        let barracks_position = snap_to_tiles(&barracks, &cursor);
+        assert_eq!(Vector3::new(Fixed::from(4.5), Fixed::from(7.5), 0.into()), barracks_position);
+        assert_eq!((6..9, 3..6), footprint(&barracks, &barracks_position));
+    }
+
+    #[test]
+    fn test_tiles_around() {
+        // A single tile is surrounded by eight, starting from the corner with the lowest row and column
+        let ring = tiles_around(&(3..4), &(5..6), 1);
+        assert_eq!(vec![(2, 4), (2, 5), (2, 6), (3, 4), (3, 6), (4, 4), (4, 5), (4, 6)], ring);
+
+        let ring = tiles_around(&(0..2), &(0..2), 2);
+        assert_eq!(20, ring.len());
+        assert!(ring.contains(&(-2, -2)) && ring.contains(&(3, 3)) && ring.contains(&(-2, 1)));
+        assert!(!ring.contains(&(-1, -1)) && !ring.contains(&(0, 0)) && !ring.contains(&(2, 2)));
+    }
+
+    #[test]
+    fn test_resource_storage() {
+        let mut villager = dat::Unit::default();
+        villager.resource_storage.push(dat::ResourceCost::new(dat::ResourceType::Unknown(4), -1.0));
+        villager.resource_storage.push(dat::ResourceCost::new(dat::ResourceType::Unknown(11), 1.0));
+        let mut disabled = dat::ResourceCost::new(dat::ResourceType::Unknown(4), -3.0);
+        disabled.enabled = false;
+        villager.resource_storage.push(disabled);
+
+        assert_eq!(Fixed::from(-1), resource_storage(&villager, dat::ResourceType::Unknown(4)));
+        assert_eq!(Fixed::from(1), resource_storage(&villager, dat::ResourceType::Unknown(11)));
+        assert_eq!(Fixed::from(0), resource_storage(&villager, dat::ResourceType::Food));
+    }
 }